@@ -1,6 +1,12 @@
 use clap::CommandFactory;
 use clap_complete::{generate_to, Shell};
-use std::{env, fs, io::Result};
+use std::{
+    env, fs,
+    io::Result,
+    path::Path,
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 include!("src/cli.rs");
 
@@ -18,7 +24,78 @@ fn generate_completions() -> Result<()> {
     Ok(())
 }
 
+/// Find the version of a dependency in Cargo.lock
+fn locked_version(name: &str) -> Option<String> {
+    let lock = fs::read_to_string("Cargo.lock").ok()?;
+    let mut lines = lock.lines();
+
+    lines.find(|l| *l == format!("name = \"{}\"", name))?;
+    lines
+        .next()?
+        .strip_prefix("version = \"")?
+        .strip_suffix('"')
+        .map(|v| v.to_owned())
+}
+
+/// Pass the information of the build to `sbf version --verbose`
+fn emit_build_info() {
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_owned())
+        .unwrap_or_else(|| "unknown".to_owned());
+
+    let mut features = env::vars()
+        .filter_map(|(k, _)| {
+            k.strip_prefix("CARGO_FEATURE_")
+                .map(|f| f.to_lowercase().replace('_', "-"))
+        })
+        .collect::<Vec<_>>();
+    features.sort();
+
+    // Respect reproducible builds
+    let date = env::var("SOURCE_DATE_EPOCH").unwrap_or_else(|_| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs())
+            .to_string()
+    });
+
+    // A new commit only changes the ref HEAD points to, or the packed refs
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    let head_ref = fs::read_to_string(".git/HEAD")
+        .ok()
+        .and_then(|head| Some(format!(".git/{}", head.strip_prefix("ref: ")?.trim())));
+    // A missing path would run the build script every time
+    for path in head_ref
+        .iter()
+        .map(|p| p.as_str())
+        .chain([".git/packed-refs"])
+    {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+    println!("cargo:rerun-if-changed=Cargo.lock");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    println!("cargo:rustc-env=SBF_BUILD_COMMIT={}", commit);
+    println!("cargo:rustc-env=SBF_BUILD_FEATURES={}", features.join(","));
+    println!(
+        "cargo:rustc-env=SBF_BUILD_LIBSDBOOTCONF={}",
+        locked_version("libsdbootconf").unwrap_or_else(|| "unknown".to_owned())
+    );
+    println!(
+        "cargo:rustc-env=SBF_BUILD_TARGET={}",
+        env::var("TARGET").unwrap_or_default()
+    );
+    println!("cargo:rustc-env=SBF_BUILD_DATE={}", date);
+}
+
 fn main() -> Result<()> {
+    emit_build_info();
+
     println!("cargo:rerun-if-env-changed=SBF_GEN_COMPLETIONS");
     if env::var("SBF_GEN_COMPLETIONS").is_ok() {
         generate_completions()?;
@@ -1,8 +1,11 @@
 conf_default = { $conf_path } is missing! Generating a template ...
 conf_old = Old configuration detected, updating ...
 edit_conf = You may need to edit { $conf_path } before continuing.
+conf_not_written = Could not write { $conf_path } ({ $reason }), the changes apply to this run only.
+save_conf = Save the configuration above as { $conf_path } and edit it before continuing.
 empty_list = Empty kernel list
-invalid_esp = Invalid ESP_MOUNTPOINT
+token_path_space = { $path } contains whitespace, which boot entries cannot refer to; check VMLINUX, INITRD and the kernel names
+bootctl_timeout = bootctl { $verb } did not finish in { $seconds } seconds and was stopped. Check that the ESP is mounted and responsive: { $esp } (BOOTCTL_TIMEOUT sets the limit)
 invalid_index = Invalid kernel index
 no_kernel = No kernel found
 invalid_kernel_filename = Invalid kernel filename
@@ -13,6 +16,8 @@ info_path_not_exist =
 err_path_not_exist = { $path } not found
 skip_incomplete_kernel = Skipping incomplete kernel { $kernel } ...
 skip_unidentified_kernel = Skipping unidentified kernel { $kernel } ...
+skip_duplicate_kernel = Skipping kernel { $kernel }, which duplicates { $original } ...
+skip_never_touch_kernel = Skipping kernel { $kernel }, which matches { $pattern } in NEVER_TOUCH ...
 no_space = No space left on device
 edit_bootarg = Please use your favorite text editor to edit `BOOTARG=` entry in { $config }
 invalid_dirname = Invalid directory name:
@@ -44,6 +49,8 @@ current_root = Detected current root partition: { $root }
 note_list_available = "*" denotes the installed kernel(s)
 note_list_installed = "*" denotes the default kernel
 ask_overwrite = { $entry } already exists. Overwrite?
+ask_overwrite_modified = { $entry } was edited with entry edit. Overwrite it?
+keep_modified_entry = Keeping the edited entry { $entry } ...
 ask_set_default = Set { $kernel } as the default boot entry?
 select_install = Kernel(s) to install as boot entry(s)
 select_remove = Kernel(s) to remove from the boot menu
@@ -59,3 +66,252 @@ ask_empty_bootarg = Automatically generate the boot arguments?
 ask_current_bootarg = Use the boot arguments above as the systemd-boot defaults?
 ask_current_root = Use `root={ $root } rw` as the default systemd-boot boot arguments?
 input_timeout = Boot menu timeout (seconds)
+diagnose_boot_failure = Analyzing systemd-boot logs of the previous boot ...
+no_boot_failure = No systemd-boot errors found in the previous boot.
+diagnose_file_found = Entry { $entry } references { $path }, which exists on the ESP
+diagnose_file_missing = Entry { $entry } references { $path }, which is missing from the ESP
+diagnose_no_entry = No boot entry references the files in this message
+no_default = No default boot entry is configured
+dangling_default = The default boot entry { $entry } does not exist
+kernel_changed = Kernel { $kernel } has changed since last install:
+suggest_reinstall = Run `sbf install-kernel { $kernel } --force` to update it.
+no_kernel_changed = All installed kernels are up to date.
+loader_conf_parse_error = Failed to parse { $path }: { $error }, run `sbf doctor` to check the boot setup
+loader_conf_parse_error_line = Failed to parse { $path } at line { $line }: { $error }, run `sbf doctor` to check the boot setup
+degraded_mode = Continuing in read-only mode, please fix the file above before making changes.
+total_size = Total: { $size }
+select_clear_default = (clear default)
+clear_default = Clearing default boot entry ...
+fallback_default = Without a default, systemd-boot boots the first entry in its menu, which is sorted by version with the newest first unless the entries have a sort-key: { $entry }
+efivar_default_set = The EFI variable LoaderEntryDefault still selects { $entry } and takes precedence over loader.conf, run `bootctl set-default ""` to clear it
+template_no_version = Warning: "{ $template }" does not contain {"{"}VERSION{"}"}, all kernels will share the same file
+not_a_kernel = This file does not look like a kernel image
+manifest_corrupt = { $path } is corrupt and is treated as empty: { $error }
+entry_unparsable = Failed to parse the entry, skipping it: { $error }
+empty_source = { $path } is empty, refusing to install it
+bad_initrd = { $path } does not look like an initramfs image, refusing to install it. Please regenerate the initramfs, or use --skip-sanity-checks to install it anyway
+entry_friend = friend
+entry_hidden = hidden
+entry_foreign = foreign
+entry_last_booted = last booted
+entry_missing = missing
+note_list_entries = "*" denotes the default entry, "!" denotes the broken entry
+auto_timeout = Setting boot menu timeout to { $timeout } seconds ...
+no_file_to_sign = No files to sign.
+sign_dry_run = Would sign { $path }
+sign_success = Signed { $path }
+sign_failed = Failed to sign { $path }: { $error }
+sign_summary_failed = Failed to sign { $failed } of { $total } files.
+reboot_required = Reboot required to use { $version }
+no_reboot_required = No reboot required.
+reboot_stamp_failed = Failed to update the reboot stamp { $path }: { $error }
+no_pacnew = { $path } not found, nothing to merge.
+ask_merge_pacnew = How to handle the new configuration file?
+pacnew_merge = Merge: add the new options, keep the current values
+pacnew_replace = Replace the current configuration
+pacnew_keep = Keep the current configuration
+pacnew_merged = Configuration updated, { $path } removed.
+invalid_conf = Invalid configuration: { $reason }
+default_profile_dropped = the entries of DEFAULT_PROFILE { $profile } are dropped by { $option }
+reason_incomplete = incomplete
+reason_unidentified = unidentified
+reason_duplicate = duplicate of { $kernel }
+reason_unmanaged = unmanaged
+reason_ostree = ostree deployment, set MANAGE_OSTREE to manage it
+reason_never_touch = matches { $pattern } in NEVER_TOUCH
+convert_uki = Converting kernel { $kernel } to a unified kernel image ...
+uki_installed = Unified kernel image written to { $path }
+err_no_efi_stub = EFI stub { $path } not found, please install systemd-boot
+err_invalid_efi_stub = EFI stub { $path } is not a valid PE image
+err_entry_no_linux = Boot entry { $entry } does not specify a kernel image
+doctor_esp_mounted = ESP is mounted at { $path }
+doctor_esp_not_mounted = { $path } is not a mount point, is the ESP mounted?
+doctor_esp_partition_ok = { $path } is on the EFI system partition the firmware boots
+doctor_esp_partition_unknown = The partition of { $path } cannot be identified, skipped
+esp_wrong_type = { $path } is on { $device }, which is not an EFI system partition (type { $type_guid })
+esp_not_booted = { $path } is on { $device }, but the firmware boots from { $booted }, the kernels installed there may never boot
+doctor_friend_initialized = systemd-boot-friend is initialized
+doctor_default_resolves = The default entry resolves to { $entry }
+doctor_running_kernel_installed = The running kernel { $kernel } is installed
+doctor_running_kernel_missing = The running kernel { $kernel } has no boot entry
+install_splash = Installing splash image { $path } ...
+remove_splash = Removing splash image { $path } ...
+doctor_splash_installed = The splash image is installed to { $path }
+doctor_splash_outdated = The splash image at { $path } is missing or outdated, please run update
+doctor_splash_not_configured = No splash image is configured, skipped
+retain_kernel = Keeping kernel { $kernel } ({ $reason })
+default_flavor_alias = Default moved: flavor alias { $old }→{ $new }
+keep_obsolete = Keeping obsolete kernel { $kernel } (--install-new-only)
+exclude_kernel = Leaving out kernel { $kernel }, which matches nothing in MANAGE_ONLY
+update_stats = Installed { $installed } and removed { $removed } kernel(s), created { $entries_created } and removed { $entries_removed } entries, copied { $size } in { $seconds }s
+summary_kernel = Kernel
+summary_action = Action
+summary_files = Files changed
+summary_size = Size
+summary_install = install
+summary_refresh = refresh
+summary_remove = remove
+retain_within_keep = within keep
+retain_pinned = pinned
+retain_running = running
+retain_default = default
+err_timeout = Update timed out, { $completed } kernels installed, { $remaining } remaining
+topology_fw_bits = { $bits }-bit firmware
+topology_no_uefi = Not booted in UEFI mode
+retain_previous_default = previous default
+title_previous_default = (previous default)
+esp_disagree = The configured ESP { $conf } differs from the detected { $detected }
+err_esp_disagree = Please specify the ESP with --esp-path
+ask_esp = Which ESP to use?
+detected = Detected { $key }: { $value }
+detect_no_esp = Failed to detect the ESP, mount it or pass --esp-path
+detect_no_root = Failed to detect the root partition, set root= in BOOTARGS
+update_esp = Updating the ESP in the configuration to { $path } ...
+status_oneline = boot: { $default } default, { $count } kernels installed, ESP { $usage }% full, { $state }
+oneline_no_default = no
+oneline_up_to_date = up to date
+oneline_update_pending = update available
+note_remove_sources =
+    remove_sources is enabled: once copied to the ESP and verified, the kernel
+    images and initrds are removed from /boot. Only the copies in the ESP are
+    left, a damaged or full ESP may leave the system unbootable, and the files
+    can only be restored by reinstalling the kernel packages.
+ask_remove_sources = Remove the source files from /boot now and in future installations?
+notice_modules_installed =
+    ORPHAN_CHECK is enabled and the modules of the kernel are still installed.
+    Booting the kernel again needs its files in the ESP, remove the kernel
+    package first, or pass --force to remove-kernel.
+modules_still_installed = Not removing { $kernel }, its modules are still installed in { $path }
+remove_sources_unconfirmed = remove_sources needs to be confirmed in an interactive session first, keeping the source files
+remove_source = Removing { $path } ...
+err_copy_mismatch = The copy of { $path } in the ESP does not match the source
+verify_ok = { $path } is intact
+verify_mismatch = { $path } does not match the checksum
+verify_missing = { $path } is missing
+verify_unknown = { $path } has neither a source nor a recorded checksum
+err_remove_all_no_available = No kernel in /usr/lib/modules can be installed again, refusing to remove all the kernels
+ask_remove_all = Remove all { $count } installed kernel(s)? The system will not boot until a kernel is installed again
+warn_removed_all =
+    Warning: all the kernels have been removed, the system is UNBOOTABLE now.
+    Run `sbf install-kernel` or `sbf update` before rebooting.
+doctor_no_unexpected_dirs = No unexpected directory in the ESP
+doctor_unexpected_dirs = Unexpected directory: { $paths }, run `sbf prune --include-dirs` to remove
+prune_file = Removing { $path } ...
+prune_dir = Removing directory { $path } ...
+prune_skip_dir = Skipping unexpected directory { $path }, use --include-dirs to remove
+ask_prune_dir = Remove the unexpected directory { $path } and everything in it?
+prune_would_remove = Would remove { $path }
+ask_prune_files = Remove the { $count } files above, which belong to no installed kernel?
+last_updated = Last updated: { $time }
+import_conf = Importing { $path } as { $conf_path } ...
+ask_sbm_replace = { $key } is "{ $current }" here but "{ $new }" in systemd-boot-manager, use the latter?
+ask_sbm_default_latest = systemd-boot-manager boots the latest kernel by default, always set the installed kernel as default?
+sbm_unmapped = { $key }="{ $value }" of systemd-boot-manager has no counterpart, skipping ...
+sbm_unchanged = Nothing to import from { $path }.
+ask_sbm_write = Write the configuration to { $conf_path }?
+sbm_migrated = Imported { $path } into { $conf_path }, the existing boot entries are left as they are.
+editor_failed = { $editor } exited with { $status }
+ask_edit_again = The entry has errors. Edit it again?
+entry_edit_discarded = The changes to { $entry } are discarded.
+entry_unchanged = { $entry } is unchanged.
+entry_edited = { $entry } is edited, update will ask before overwriting it.
+unknown_placeholder = Unknown placeholder {"{"}{ $placeholder }{"}"}, available: { $available }
+unclosed_placeholder = Unclosed placeholder in { $template }
+notice_uninstall =
+    systemd-boot-friend will now remove all the kernels and boot entries it manages
+    from the ESP. Unless another boot manager or bootloader takes over, your system
+    will no longer boot.
+ask_uninstall = Proceed with removing everything systemd-boot-friend put in the ESP?
+uninstalled = Removed systemd-boot-friend from the ESP, you may now uninstall the package.
+invalid_alias = Invalid alias "{ $alias }", only letters, digits and "-_.+" are allowed
+alias_taken = The alias { $alias } is already given to kernel { $kernel }
+alias_multiple = --version-alias can only be given to a single kernel
+history_install = Installed { $kernel }
+history_remove = Removed { $kernel }
+history_entries = Wrote the entries of { $kernel }: { $entries }
+history_default = Default entry: { $old } -> { $new }
+history_empty = No history recorded
+journal_failed = Failed to record the change in the journal: { $error }
+perm_not_owned_by_root = not owned by root
+perm_not_readable = not readable by the owner
+perm_not_traversable = directory not traversable by the owner
+perm_world_writable = writable by everyone
+perm_skip_fat = The ESP is { $fs }, which has no Unix permissions and is accessible by all users, skipping
+perm_ok = The permissions of the files in the ESP are fine
+snapshots_no_policy = max_snapshot_entries is not configured, no snapshot entry is pruned
+snapshots_nothing_to_prune = No snapshot entry to prune
+snapshots_would_remove = Would remove { $entry }
+snapshots_remove = Removing { $entry } ...
+default_ok = The default boot entry { $entry } exists, nothing to repair
+repaired_default = Changed the default boot entry from { $old } to { $new }
+duplicate_profiles = Profiles { $profiles } have identical options, set ALLOW_DUPLICATE_PROFILES if they are intended aliases
+doctor_no_duplicate_profiles = No profiles with identical options
+hint_editor_chars = Hint: the file contains characters usually introduced by Windows editors (CRLF line endings, non-breaking or zero-width spaces, byte order mark), please check the file in a plain text editor
+conf_crlf = Converted the CRLF line endings in { $key } to LF
+openrc_script = Wrote the OpenRC script to { $path }, enable it with `rc-update add systemd-boot-friend default`
+sbctl_no_pkcs11 = sbctl can not sign with the keys on PKCS#11 tokens, please install pesign or use --tool pesign
+invalid_pkcs11_uri = PKCS11_URI has no { $attr } attribute
+no_pkcs11_uri = PKCS11_URI is not configured
+token_missing = The PKCS#11 token { $token } is not found
+token_present = Found the PKCS#11 token { $token }
+token_key_accessible = The signing key is accessible
+token_key_inaccessible = The signing key is not found or not accessible
+kernel_not_installed = { $kernel } is not installed
+compare_identical = The boot entries of { $a } and { $b } are identical
+ucode_missing = Microcode image { $ucode } not found in the source directory
+ucode_reason_default = the default
+ucode_reason_override = matching { $pattern } in UCODE_OVERRIDES
+print_entry_ucode = Microcode: { $ucode } ({ $reason })
+print_entry_ucode_absent = Microcode: none, { $ucode } ({ $reason }) is not in the ESP
+print_entry_no_ucode = Microcode: none, the unified kernel image is booted as a whole
+kernel_info_version = Version:
+kernel_info_build = Build metadata:
+kernel_info_sources = Source files:
+kernel_info_esp_files = Files in the ESP:
+kernel_info_not_installed = none, not installed
+kernel_info_entries = Boot entries:
+kernel_info_no_entries = none
+kernel_info_default = Default: { $value ->
+    [true] yes
+   *[false] no
+}
+kernel_info_running = Running: { $value ->
+    [true] yes
+   *[false] no
+}
+validate_ok = { $path } is a valid boot entry
+validate_no_linux = The entry specifies no kernel image with `linux` or `efi`
+validate_no_title = The entry has no `title`, the boot menu shows the file name instead
+validate_unbalanced_quotes = The quotes in `options` are unbalanced
+validate_empty_options = The `options` of the entry are empty
+invalid_conf_key = Unknown option { $key }, the options are: { $keys }
+invalid_conf_key_type = { $key } is not a table
+conf_set = Set { $key } to { $new }, was { $old }
+conf_unset = Removed { $key }, was { $old }
+conf_not_set = not set
+list_filtered = Showing { $shown } of { $total } kernels
+migrate_esp_dry_run = Dry run, nothing is changed
+migrate_esp_bootctl = Installing systemd-boot to { $path } ...
+migrate_esp_bootloader_copied = bootctl failed ({ $error }), copied systemd-boot from the old ESP instead
+migrate_esp_kernel = Would install { $kernel } to { $path }
+migrate_esp_no_source = { $kernel } is not in { $src }, it is not moved
+ask_migrate_esp_no_source = { $count } kernels are not in SRC_PATH and would not be moved, continue?
+migrate_esp_loader_conf = Copying { $src } to { $dest } ...
+migrate_esp_done = Moved to { $path }, the old ESP { $old } is left as it is
+ask_migrate_esp_config = Use { $path } as ESP_MOUNTPOINT from now on?
+note_migrate_esp_config =
+    The configuration still points to the old ESP. Set ESP_MOUNTPOINT to
+    { $path } to manage the new one.
+err_migrate_esp_target = { $path } is not a directory, format and mount the new ESP first
+err_migrate_esp_same = { $path } is already the ESP
+err_migrate_esp_no_source = { $kernels } not in { $src }, install them there or use --force to leave them behind
+entries_generated = Wrote the entries of { $count } installed kernels
+plan_unreadable = Cannot read { $path }: { $error }
+plan_version = unsupported plan version { $version }
+plan_changed = the kernels to be installed or removed differ
+plan_empty = Nothing to be done
+plan_default = { $kernel } would be set as default
+plan_stale = The system has changed since the plan was made ({ $reason }), make the plan again
+plan_incomplete = The plan was made without reading the whole ESP and cannot be checked, make it again as a user who can read the ESP
+ask_apply_plan = Apply the plan?
+plan_not_applied = The plan is not applied
@@ -1,8 +1,11 @@
 conf_default = { $conf_path } 不存在！正在生成配置样例...
 conf_old = 检测到旧的配置文件，正在更新...
 edit_conf = 在继续操作前，您可能需要修改 { $conf_path }。
+conf_not_written = 无法写入 { $conf_path }（{ $reason }），更改仅在本次运行中生效。
+save_conf = 在继续操作前，请将上述配置保存为 { $conf_path } 并进行修改。
 empty_list = 内核列表为空
-invalid_esp = ESP_MOUNTPOINT 不正确
+token_path_space = { $path } 含有空白字符，启动项无法引用该路径；请检查 VMLINUX、INITRD 和内核名称
+bootctl_timeout = bootctl { $verb } 在 { $seconds } 秒内未完成，已被终止。请检查 ESP 是否已挂载且可正常访问：{ $esp }（可通过 BOOTCTL_TIMEOUT 调整时限）
 invalid_index = 内核序号不正确
 no_kernel = 找不到内核
 invalid_kernel_filename = 内核文件名不正确
@@ -12,6 +15,8 @@ info_path_not_exist =
 err_path_not_exist = { $path } 不存在
 skip_incomplete_kernel = 已跳过不完整的内核 { $kernel } ...
 skip_unidentified_kernel = 已跳过不明内核 { $kernel } ...
+skip_duplicate_kernel = 已跳过内核 { $kernel }，其与 { $original } 重复 ...
+skip_never_touch_kernel = 已跳过内核 { $kernel }，其匹配 NEVER_TOUCH 中的 { $pattern } ...
 no_space = 设备上没有空间
 edit_bootarg = 请使用任意文本编辑器编辑 { $config } 中的 `BOOTARG=` 条目
 invalid_dirname = 目录名不正确：
@@ -41,6 +46,8 @@ current_root = 检测到了当前的根目录分区： { $root }
 note_list_available = "*" 表示已登记的内核
 note_list_installed = "*" 表示默认内核
 ask_overwrite = { $entry } 已存在。是否覆盖该文件？
+ask_overwrite_modified = { $entry } 已通过 entry edit 修改。是否覆盖该文件？
+keep_modified_entry = 正在保留修改过的启动项 { $entry } ...
 ask_set_default = 是否将 { $kernel } 设为默认启动项？
 select_install = 要登记启动项的内核
 select_remove = 要从启动菜单移除的内核
@@ -55,3 +62,248 @@ ask_empty_bootarg = 是否自动生成启动参数？
 ask_current_bootarg = 是否将上述启动参数设为 systemd-boot 默认启动参数？
 ask_current_root = 是否将 `root={ $root } rw` 设为 systemd-boot 默认启动参数？
 input_timeout = 启动菜单显示时长（秒）
+diagnose_boot_failure = 正在分析上次启动的 systemd-boot 日志...
+no_boot_failure = 上次启动的日志中未发现 systemd-boot 错误。
+diagnose_file_found = 启动项 { $entry } 引用了 { $path }，该文件存在于 ESP 中
+diagnose_file_missing = 启动项 { $entry } 引用了 { $path }，但该文件在 ESP 中不存在
+diagnose_no_entry = 没有启动项引用此消息中的文件
+no_default = 未设置默认启动项
+dangling_default = 默认启动项 { $entry } 不存在
+kernel_changed = 内核 { $kernel } 自上次登记后已被修改：
+suggest_reinstall = 请运行 `sbf install-kernel { $kernel } --force` 以更新该内核。
+no_kernel_changed = 所有已登记的内核均为最新。
+loader_conf_parse_error = 无法解析 { $path }：{ $error }，请运行 `sbf doctor` 检查启动配置
+loader_conf_parse_error_line = 无法解析 { $path } 第 { $line } 行：{ $error }，请运行 `sbf doctor` 检查启动配置
+degraded_mode = 将以只读模式继续运行，请在进行修改前修复上述文件。
+total_size = 总计：{ $size }
+select_clear_default = （清除默认启动项）
+clear_default = 正在清除默认启动项...
+fallback_default = 未设置默认启动项时，systemd-boot 启动菜单中的第一项；除非启动项设置了 sort-key，菜单按版本从新到旧排序：{ $entry }
+efivar_default_set = EFI 变量 LoaderEntryDefault 仍指向 { $entry }，其优先级高于 loader.conf，可运行 `bootctl set-default ""` 清除
+template_no_version = 警告："{ $template }" 中不包含 {"{"}VERSION{"}"}，所有内核将共用同一个文件
+not_a_kernel = 该文件似乎不是内核镜像
+manifest_corrupt = { $path } 已损坏，将视为空清单：{ $error }
+entry_unparsable = 无法解析该启动项，已跳过：{ $error }
+empty_source = { $path } 是空文件，拒绝安装
+bad_initrd = { $path } 似乎不是 initramfs 镜像，拒绝安装。请重新生成 initramfs，或使用 --skip-sanity-checks 强制安装
+entry_friend = friend
+entry_hidden = 隐藏
+entry_foreign = 外部
+entry_last_booted = 上次启动
+entry_missing = 不存在
+note_list_entries = "*" 表示默认启动项，"!" 表示损坏的启动项
+auto_timeout = 正在将启动菜单显示时长设为 { $timeout } 秒...
+no_file_to_sign = 没有需要签名的文件。
+sign_dry_run = 将签名 { $path }
+sign_success = 已签名 { $path }
+sign_failed = 签名 { $path } 失败：{ $error }
+sign_summary_failed = { $total } 个文件中有 { $failed } 个签名失败。
+reboot_required = 需要重启以使用 { $version }
+no_reboot_required = 无需重启。
+reboot_stamp_failed = 无法更新重启标记 { $path }：{ $error }
+no_pacnew = 未找到 { $path }，无需合并。
+ask_merge_pacnew = 如何处理新的配置文件？
+pacnew_merge = 合并：添加新选项，保留当前值
+pacnew_replace = 替换当前配置
+pacnew_keep = 保留当前配置
+pacnew_merged = 配置已更新，已删除 { $path }。
+invalid_conf = 配置无效：{ $reason }
+default_profile_dropped = DEFAULT_PROFILE { $profile } 的启动项会被 { $option } 排除
+reason_incomplete = 不完整
+reason_unidentified = 不明
+reason_duplicate = 与 { $kernel } 重复
+reason_unmanaged = 不受管理
+reason_ostree = ostree 部署，设置 MANAGE_OSTREE 以管理该内核
+reason_never_touch = 匹配 NEVER_TOUCH 中的 { $pattern }
+convert_uki = 正在将内核 { $kernel } 转换为统一内核镜像 ...
+uki_installed = 统一内核镜像已写入 { $path }
+err_no_efi_stub = 未找到 EFI stub { $path }，请安装 systemd-boot
+err_invalid_efi_stub = EFI stub { $path } 不是有效的 PE 映像
+err_entry_no_linux = 启动项 { $entry } 未指定内核镜像
+doctor_esp_mounted = ESP 已挂载于 { $path }
+doctor_esp_not_mounted = { $path } 不是挂载点，ESP 是否已挂载？
+doctor_esp_partition_ok = { $path } 位于固件所启动的 EFI 系统分区上
+doctor_esp_partition_unknown = 无法识别 { $path } 所在的分区，已跳过
+esp_wrong_type = { $path } 位于 { $device }，但该分区不是 EFI 系统分区（类型 { $type_guid }）
+esp_not_booted = { $path } 位于 { $device }，但固件从 { $booted } 启动，安装在此的内核可能永远不会被启动
+doctor_friend_initialized = systemd-boot-friend 已初始化
+doctor_default_resolves = 默认启动项为 { $entry }
+doctor_running_kernel_installed = 正在运行的内核 { $kernel } 已安装
+doctor_running_kernel_missing = 正在运行的内核 { $kernel } 没有启动项
+install_splash = 正在安装启动画面图片 { $path } ...
+remove_splash = 正在删除启动画面图片 { $path } ...
+doctor_splash_installed = 启动画面图片已安装至 { $path }
+doctor_splash_outdated = { $path } 处的启动画面图片缺失或已过时，请运行 update
+doctor_splash_not_configured = 未配置启动画面图片，已跳过
+retain_kernel = 保留内核 { $kernel }（{ $reason }）
+default_flavor_alias = 默认启动项已跟随改名的内核变体：{ $old }→{ $new }
+keep_obsolete = 保留过时内核 { $kernel }（--install-new-only）
+exclude_kernel = 不处理内核 { $kernel }，其不匹配 MANAGE_ONLY 中的任何模式
+update_stats = 安装了 { $installed } 个、移除了 { $removed } 个内核，创建了 { $entries_created } 个、移除了 { $entries_removed } 个启动项，复制了 { $size }，耗时 { $seconds } 秒
+summary_kernel = 内核
+summary_action = 操作
+summary_files = 变更文件数
+summary_size = 大小
+summary_install = 安装
+summary_refresh = 更新
+summary_remove = 移除
+retain_within_keep = 在保留数量内
+retain_pinned = 已固定
+retain_running = 正在运行
+retain_default = 默认
+err_timeout = 更新超时，已安装 { $completed } 个内核，剩余 { $remaining } 个
+topology_fw_bits = { $bits } 位固件
+topology_no_uefi = 未以 UEFI 模式启动
+retain_previous_default = 上一个默认
+title_previous_default = (上一个默认启动项)
+esp_disagree = 配置中的 ESP { $conf } 与检测到的 { $detected } 不一致
+err_esp_disagree = 请使用 --esp-path 指定 ESP
+ask_esp = 使用哪个 ESP？
+detected = 检测到 { $key }：{ $value }
+detect_no_esp = 无法检测到 ESP，请挂载 ESP 或指定 --esp-path
+detect_no_root = 无法检测到根分区，请在 BOOTARGS 中设置 root=
+update_esp = 正在将配置中的 ESP 更新为 { $path } ...
+status_oneline = 启动：默认 { $default }，已安装 { $count } 个内核，ESP 已用 { $usage }%，{ $state }
+oneline_no_default = 无
+oneline_up_to_date = 已是最新
+oneline_update_pending = 有可用更新
+note_remove_sources =
+    已启用 remove_sources：内核镜像和 initrd 复制到 ESP 并校验后将从 /boot 删除。
+    届时仅 ESP 中存有副本，ESP 损坏或空间已满可能导致系统无法启动，
+    且只能通过重新安装内核软件包恢复这些文件。
+ask_remove_sources = 现在及以后安装时从 /boot 删除源文件吗？
+notice_modules_installed =
+    已启用 ORPHAN_CHECK，且该内核的模块仍已安装。再次启动该内核需要 ESP 中的文件，
+    请先卸载内核软件包，或为 remove-kernel 指定 --force。
+modules_still_installed = 未删除 { $kernel }，其模块仍安装在 { $path }
+remove_sources_unconfirmed = remove_sources 需先在交互式会话中确认，保留源文件
+remove_source = 正在删除 { $path } ...
+err_copy_mismatch = ESP 中 { $path } 的副本与源文件不一致
+verify_ok = { $path } 完好
+verify_mismatch = { $path } 与校验和不符
+verify_missing = { $path } 不存在
+verify_unknown = { $path } 既无源文件也无记录的校验和
+err_remove_all_no_available = /usr/lib/modules 中没有可重新安装的内核，拒绝移除所有内核
+ask_remove_all = 要移除全部 { $count } 个已安装的内核吗？重新安装内核前系统将无法启动
+warn_removed_all =
+    警告：所有内核均已移除，系统当前【无法启动】。
+    请在重启前执行 `sbf install-kernel` 或 `sbf update`。
+doctor_no_unexpected_dirs = ESP 中没有意外的目录
+doctor_unexpected_dirs = 意外的目录：{ $paths }，执行 `sbf prune --include-dirs` 以删除
+prune_file = 正在删除 { $path } ...
+prune_dir = 正在删除目录 { $path } ...
+prune_skip_dir = 跳过意外的目录 { $path }，使用 --include-dirs 以删除
+ask_prune_dir = 删除意外的目录 { $path } 及其中的所有内容吗？
+prune_would_remove = 将移除 { $path }
+ask_prune_files = 删除以上 { $count } 个不属于任何已安装内核的文件吗？
+last_updated = 最后更新：{ $time }
+import_conf = 正在将 { $path } 导入为 { $conf_path } ...
+ask_sbm_replace = { $key } 当前为 "{ $current }"，而 systemd-boot-manager 中为 "{ $new }"，是否使用后者？
+ask_sbm_default_latest = systemd-boot-manager 默认启动最新内核，是否总是将新安装的内核设为默认？
+sbm_unmapped = systemd-boot-manager 的 { $key }="{ $value }" 没有对应的选项，已跳过 ...
+sbm_unchanged = { $path } 中没有需要导入的内容。
+ask_sbm_write = 是否将配置写入 { $conf_path }？
+sbm_migrated = 已将 { $path } 导入 { $conf_path }，现有启动项保持不变。
+editor_failed = { $editor } 以 { $status } 退出
+ask_edit_again = 启动项存在错误。是否重新编辑？
+entry_edit_discarded = 已放弃对 { $entry } 的修改。
+entry_unchanged = { $entry } 未作修改。
+entry_edited = 已修改 { $entry }，update 在覆盖前将进行询问。
+unknown_placeholder = 未知的占位符 {"{"}{ $placeholder }{"}"}，可用的有：{ $available }
+unclosed_placeholder = { $template } 中的占位符未闭合
+notice_uninstall =
+    systemd-boot-friend 即将从 ESP 中移除其管理的所有内核及启动项。除非有其他启动管理器或
+    启动引导器接管，您的系统将无法启动。
+ask_uninstall = 是否继续移除 systemd-boot-friend 放入 ESP 的所有内容？
+uninstalled = 已从 ESP 中移除 systemd-boot-friend，现在可以卸载该软件包。
+invalid_alias = 无效的别名 "{ $alias }"，仅允许使用字母、数字及 "-_.+"
+alias_taken = 别名 { $alias } 已被内核 { $kernel } 使用
+alias_multiple = --version-alias 只能用于单个内核
+history_install = 已安装 { $kernel }
+history_remove = 已移除 { $kernel }
+history_entries = 已写入 { $kernel } 的启动项：{ $entries }
+history_default = 默认启动项：{ $old } -> { $new }
+history_empty = 没有历史记录
+journal_failed = 无法在日志中记录更改：{ $error }
+perm_not_owned_by_root = 所有者不是 root
+perm_not_readable = 所有者不可读
+perm_not_traversable = 所有者无法进入目录
+perm_world_writable = 所有人均可写入
+perm_skip_fat = ESP 的文件系统为 { $fs }，不支持 Unix 权限且所有用户均可访问，跳过检查
+perm_ok = ESP 中文件的权限正常
+snapshots_no_policy = 未配置 max_snapshot_entries，不清理快照启动项
+snapshots_nothing_to_prune = 没有需要清理的快照启动项
+snapshots_would_remove = 将移除 { $entry }
+snapshots_remove = 正在移除 { $entry } ...
+default_ok = 默认启动项 { $entry } 存在，无需修复
+repaired_default = 已将默认启动项从 { $old } 改为 { $new }
+duplicate_profiles = 配置 { $profiles } 的启动参数相同，如有意设置别名，请启用 ALLOW_DUPLICATE_PROFILES
+doctor_no_duplicate_profiles = 没有启动参数相同的配置
+hint_editor_chars = 提示：文件中包含通常由 Windows 编辑器引入的字符（CRLF 换行符、不换行空格或零宽空格、字节顺序标记），请使用纯文本编辑器检查该文件
+conf_crlf = 已将 { $key } 中的 CRLF 换行符转换为 LF
+openrc_script = 已将 OpenRC 脚本写入 { $path }，可使用 `rc-update add systemd-boot-friend default` 启用
+sbctl_no_pkcs11 = sbctl 无法使用 PKCS#11 令牌中的密钥签名，请安装 pesign 或使用 --tool pesign
+invalid_pkcs11_uri = PKCS11_URI 中缺少 { $attr } 属性
+no_pkcs11_uri = 未配置 PKCS11_URI
+token_missing = 未找到 PKCS#11 令牌 { $token }
+token_present = 已找到 PKCS#11 令牌 { $token }
+token_key_accessible = 签名密钥可以访问
+token_key_inaccessible = 未找到签名密钥或无法访问
+kernel_not_installed = 未安装 { $kernel }
+compare_identical = { $a } 与 { $b } 的启动项完全相同
+ucode_missing = 源目录中未找到微码镜像 { $ucode }
+ucode_reason_default = 默认
+ucode_reason_override = 匹配 UCODE_OVERRIDES 中的 { $pattern }
+print_entry_ucode = 微码：{ $ucode }（{ $reason }）
+print_entry_ucode_absent = 微码：无，{ $ucode }（{ $reason }）不在 ESP 中
+print_entry_no_ucode = 微码：无，统一内核镜像作为整体启动
+kernel_info_version = 版本：
+kernel_info_build = 构建信息：
+kernel_info_sources = 源文件：
+kernel_info_esp_files = ESP 中的文件：
+kernel_info_not_installed = 无，未安装
+kernel_info_entries = 启动项：
+kernel_info_no_entries = 无
+kernel_info_default = 默认：{ $value ->
+    [true] 是
+   *[false] 否
+}
+kernel_info_running = 正在运行：{ $value ->
+    [true] 是
+   *[false] 否
+}
+validate_ok = { $path } 是有效的启动项
+validate_no_linux = 启动项未通过 `linux` 或 `efi` 指定内核镜像
+validate_no_title = 启动项没有 `title`，启动菜单将显示文件名
+validate_unbalanced_quotes = `options` 中的引号不成对
+validate_empty_options = 启动项的 `options` 为空
+invalid_conf_key = 未知选项 { $key }，可用选项为：{ $keys }
+invalid_conf_key_type = { $key } 不是表
+conf_set = 已将 { $key } 设为 { $new }，原值为 { $old }
+conf_unset = 已移除 { $key }，原值为 { $old }
+conf_not_set = 未设置
+list_filtered = 显示 { $total } 个内核中的 { $shown } 个
+migrate_esp_dry_run = 试运行，不做任何更改
+migrate_esp_bootctl = 正在将 systemd-boot 安装到 { $path } ...
+migrate_esp_bootloader_copied = bootctl 执行失败（{ $error }），已改为从旧 ESP 复制 systemd-boot
+migrate_esp_kernel = 将安装 { $kernel } 到 { $path }
+migrate_esp_no_source = { $kernel } 不在 { $src } 中，不予迁移
+ask_migrate_esp_no_source = 有 { $count } 个内核不在 SRC_PATH 中，将不予迁移，是否继续？
+migrate_esp_loader_conf = 正在复制 { $src } 到 { $dest } ...
+migrate_esp_done = 已迁移到 { $path }，旧 ESP { $old } 保持不变
+ask_migrate_esp_config = 今后使用 { $path } 作为 ESP_MOUNTPOINT 吗？
+note_migrate_esp_config =
+    配置文件仍指向旧 ESP。请将 ESP_MOUNTPOINT 设置为 { $path } 以管理新 ESP。
+err_migrate_esp_target = { $path } 不是目录，请先格式化并挂载新 ESP
+err_migrate_esp_same = { $path } 已是当前 ESP
+err_migrate_esp_no_source = { $kernels } 不在 { $src } 中，请将其安装到该目录，或使用 --force 不迁移这些内核
+entries_generated = 已写入 { $count } 个已安装内核的启动项
+plan_unreadable = 无法读取 { $path }：{ $error }
+plan_version = 不支持的计划版本 { $version }
+plan_changed = 待安装或移除的内核有所不同
+plan_empty = 无需进行任何操作
+plan_default = 将设置 { $kernel } 为默认启动项
+plan_stale = 系统在生成计划后已发生变化（{ $reason }），请重新生成计划
+plan_incomplete = 该计划生成时未能读取整个 ESP，无法检查，请以可读取 ESP 的用户重新生成计划
+ask_apply_plan = 是否执行该计划？
+plan_not_applied = 未执行该计划
@@ -1,8 +1,11 @@
 conf_default = { $conf_path } 不存在！正在產生模板...
 conf_old = 偵測到舊的設定檔，正在更新...
 edit_conf = 在繼續操作前，您可能需要修改 { $conf_path }。
+conf_not_written = 無法寫入 { $conf_path }（{ $reason }），變更僅在本次執行中生效。
+save_conf = 在繼續操作前，請將上述設定儲存為 { $conf_path } 並進行修改。
 empty_list = 核心列表為空
-invalid_esp = ESP_MOUNTPOINT 不正確
+token_path_space = { $path } 含有空白字元，開機選項無法參照該路徑；請檢查 VMLINUX、INITRD 和核心名稱
+bootctl_timeout = bootctl { $verb } 在 { $seconds } 秒內未完成，已被終止。請檢查 ESP 是否已掛載且可正常存取：{ $esp }（可透過 BOOTCTL_TIMEOUT 調整時限）
 invalid_index = 核心編號不正確
 no_kernel = 找不到核心
 invalid_kernel_filename = 核心檔案名稱不正確
@@ -12,6 +15,8 @@ info_path_not_exist =
 err_path_not_exist = { $path } 不存在
 skip_incomplete_kernel = 已跳過不完整的核心 { $kernel } ...
 skip_unidentified_kernel = 已跳過不明核心 { $kernel } ...
+skip_duplicate_kernel = 已跳過核心 { $kernel }，其與 { $original } 重複 ...
+skip_never_touch_kernel = 已跳過核心 { $kernel }，其符合 NEVER_TOUCH 中的 { $pattern } ...
 no_space = 裝置上已無多餘空間
 edit_bootarg = 請使用任意文字編輯器編輯 { $config } 中的 `BOOTARG=` 項目
 invalid_dirname = 目錄名稱不正確：
@@ -42,6 +47,8 @@ current_root = 偵測到了目前的根目錄分割區：{ $root }
 note_list_available = "*" 表示已登記的核心
 note_list_installed = "*" 表示預設核心
 ask_overwrite = { $entry } 已存在。是否覆寫該檔案？
+ask_overwrite_modified = { $entry } 已透過 entry edit 修改。是否覆寫該檔案？
+keep_modified_entry = 正在保留修改過的開機選項 { $entry } ...
 ask_set_default = 是否將 { $kernel } 設為預設開機選項？
 select_install = 要登記開機選項的核心
 select_remove = 要從開機選單移除的核心
@@ -56,3 +63,248 @@ ask_empty_bootarg = 是否自動產生開機引數？
 ask_current_bootarg = 是否將上述開機引數設為 systemd-boot 預設開機引數？
 ask_current_root = 是否將 `root={ $root } rw` 設為 systemd-boot 預設開機引數？
 input_timeout = 開機選單顯示時長（秒）
+diagnose_boot_failure = 正在分析上次開機的 systemd-boot 日誌...
+no_boot_failure = 上次開機的日誌中未發現 systemd-boot 錯誤。
+diagnose_file_found = 開機選項 { $entry } 參照了 { $path }，該檔案存在於 ESP 中
+diagnose_file_missing = 開機選項 { $entry } 參照了 { $path }，但該檔案不存在於 ESP 中
+diagnose_no_entry = 沒有開機選項參照此訊息中的檔案
+no_default = 未設定預設開機選項
+dangling_default = 預設開機選項 { $entry } 不存在
+kernel_changed = 核心 { $kernel } 自上次登記後已被修改：
+suggest_reinstall = 請執行 `sbf install-kernel { $kernel } --force` 以更新該核心。
+no_kernel_changed = 所有已登記的核心均為最新。
+loader_conf_parse_error = 無法解析 { $path }：{ $error }，請執行 `sbf doctor` 檢查開機設定
+loader_conf_parse_error_line = 無法解析 { $path } 第 { $line } 行：{ $error }，請執行 `sbf doctor` 檢查開機設定
+degraded_mode = 將以唯讀模式繼續執行，請在進行修改前修復上述檔案。
+total_size = 總計：{ $size }
+select_clear_default = （清除預設開機選項）
+clear_default = 正在清除預設開機選項...
+fallback_default = 未設定預設開機選項時，systemd-boot 啟動選單中的第一項；除非開機選項設定了 sort-key，選單按版本由新到舊排序：{ $entry }
+efivar_default_set = EFI 變數 LoaderEntryDefault 仍指向 { $entry }，其優先順序高於 loader.conf，可執行 `bootctl set-default ""` 清除
+template_no_version = 警告："{ $template }" 中不包含 {"{"}VERSION{"}"}，所有核心將共用同一個檔案
+not_a_kernel = 該檔案似乎不是核心映像檔
+manifest_corrupt = { $path } 已損毀，將視為空清單：{ $error }
+entry_unparsable = 無法解析該開機選項，已略過：{ $error }
+empty_source = { $path } 是空檔案，拒絕安裝
+bad_initrd = { $path } 似乎不是 initramfs 映像檔，拒絕安裝。請重新產生 initramfs，或使用 --skip-sanity-checks 強制安裝
+entry_friend = friend
+entry_hidden = 隱藏
+entry_foreign = 外部
+entry_last_booted = 上次開機
+entry_missing = 不存在
+note_list_entries = "*" 表示預設開機選項，"!" 表示損壞的開機選項
+auto_timeout = 正在將開機選單顯示時長設為 { $timeout } 秒...
+no_file_to_sign = 沒有需要簽署的檔案。
+sign_dry_run = 將簽署 { $path }
+sign_success = 已簽署 { $path }
+sign_failed = 簽署 { $path } 失敗：{ $error }
+sign_summary_failed = { $total } 個檔案中有 { $failed } 個簽署失敗。
+reboot_required = 需要重新開機以使用 { $version }
+no_reboot_required = 無需重新開機。
+reboot_stamp_failed = 無法更新重新開機標記 { $path }：{ $error }
+no_pacnew = 找不到 { $path }，無需合併。
+ask_merge_pacnew = 如何處理新的設定檔？
+pacnew_merge = 合併：加入新選項，保留目前的值
+pacnew_replace = 取代目前的設定
+pacnew_keep = 保留目前的設定
+pacnew_merged = 設定已更新，已刪除 { $path }。
+invalid_conf = 設定無效：{ $reason }
+default_profile_dropped = DEFAULT_PROFILE { $profile } 的開機選項會被 { $option } 排除
+reason_incomplete = 不完整
+reason_unidentified = 不明
+reason_duplicate = 與 { $kernel } 重複
+reason_unmanaged = 不受管理
+reason_ostree = ostree 部署，設定 MANAGE_OSTREE 以管理該核心
+reason_never_touch = 符合 NEVER_TOUCH 中的 { $pattern }
+convert_uki = 正在將核心 { $kernel } 轉換為統一核心映像檔 ...
+uki_installed = 統一核心映像檔已寫入 { $path }
+err_no_efi_stub = 找不到 EFI stub { $path }，請安裝 systemd-boot
+err_invalid_efi_stub = EFI stub { $path } 不是有效的 PE 映像檔
+err_entry_no_linux = 開機選項 { $entry } 未指定核心映像檔
+doctor_esp_mounted = ESP 已掛載於 { $path }
+doctor_esp_not_mounted = { $path } 不是掛載點，ESP 是否已掛載？
+doctor_esp_partition_ok = { $path } 位於韌體所開機的 EFI 系統分割區上
+doctor_esp_partition_unknown = 無法識別 { $path } 所在的分割區，已略過
+esp_wrong_type = { $path } 位於 { $device }，但該分割區不是 EFI 系統分割區（類型 { $type_guid }）
+esp_not_booted = { $path } 位於 { $device }，但韌體從 { $booted } 開機，安裝在此的核心可能永遠不會被開機
+doctor_friend_initialized = systemd-boot-friend 已初始化
+doctor_default_resolves = 預設開機選項為 { $entry }
+doctor_running_kernel_installed = 正在執行的核心 { $kernel } 已安裝
+doctor_running_kernel_missing = 正在執行的核心 { $kernel } 沒有開機選項
+install_splash = 正在安裝開機畫面圖片 { $path } ...
+remove_splash = 正在移除開機畫面圖片 { $path } ...
+doctor_splash_installed = 開機畫面圖片已安裝至 { $path }
+doctor_splash_outdated = { $path } 處的開機畫面圖片遺失或已過時，請執行 update
+doctor_splash_not_configured = 未設定開機畫面圖片，已略過
+retain_kernel = 保留核心 { $kernel }（{ $reason }）
+default_flavor_alias = 預設開機選項已跟隨改名的核心變體：{ $old }→{ $new }
+keep_obsolete = 保留過時核心 { $kernel }（--install-new-only）
+exclude_kernel = 不處理核心 { $kernel }，其不符合 MANAGE_ONLY 中的任何模式
+update_stats = 安裝了 { $installed } 個、移除了 { $removed } 個核心，建立了 { $entries_created } 個、移除了 { $entries_removed } 個開機選項，複製了 { $size }，耗時 { $seconds } 秒
+summary_kernel = 核心
+summary_action = 操作
+summary_files = 變更檔案數
+summary_size = 大小
+summary_install = 安裝
+summary_refresh = 更新
+summary_remove = 移除
+retain_within_keep = 在保留數量內
+retain_pinned = 已釘選
+retain_running = 正在執行
+retain_default = 預設
+err_timeout = 更新逾時，已安裝 { $completed } 個核心，剩餘 { $remaining } 個
+topology_fw_bits = { $bits } 位元韌體
+topology_no_uefi = 未以 UEFI 模式開機
+retain_previous_default = 上一個預設
+title_previous_default = (上一個預設開機選項)
+esp_disagree = 設定中的 ESP { $conf } 與偵測到的 { $detected } 不一致
+err_esp_disagree = 請使用 --esp-path 指定 ESP
+ask_esp = 使用哪個 ESP？
+detected = 偵測到 { $key }：{ $value }
+detect_no_esp = 無法偵測到 ESP，請掛載 ESP 或指定 --esp-path
+detect_no_root = 無法偵測到根分割區，請在 BOOTARGS 中設定 root=
+update_esp = 正在將設定中的 ESP 更新為 { $path } ...
+status_oneline = 開機：預設 { $default }，已安裝 { $count } 個核心，ESP 已用 { $usage }%，{ $state }
+oneline_no_default = 無
+oneline_up_to_date = 已是最新
+oneline_update_pending = 有可用更新
+note_remove_sources =
+    已啟用 remove_sources：核心映像檔和 initrd 複製到 ESP 並驗證後將從 /boot 刪除。
+    屆時僅 ESP 中存有副本，ESP 損壞或空間已滿可能導致系統無法開機，
+    且只能透過重新安裝核心套件復原這些檔案。
+ask_remove_sources = 現在及以後安裝時從 /boot 刪除來源檔案嗎？
+notice_modules_installed =
+    已啟用 ORPHAN_CHECK，且該核心的模組仍已安裝。再次開機該核心需要 ESP 中的檔案，
+    請先移除核心套件，或為 remove-kernel 指定 --force。
+modules_still_installed = 未刪除 { $kernel }，其模組仍安裝在 { $path }
+remove_sources_unconfirmed = remove_sources 需先在互動式工作階段中確認，保留來源檔案
+remove_source = 正在刪除 { $path } ...
+err_copy_mismatch = ESP 中 { $path } 的副本與來源檔案不一致
+verify_ok = { $path } 完好
+verify_mismatch = { $path } 與校驗和不符
+verify_missing = { $path } 不存在
+verify_unknown = { $path } 既無來源檔案也無記錄的校驗和
+err_remove_all_no_available = /usr/lib/modules 中沒有可重新安裝的核心，拒絕移除所有核心
+ask_remove_all = 要移除全部 { $count } 個已安裝的核心嗎？重新安裝核心前系統將無法開機
+warn_removed_all =
+    警告：所有核心均已移除，系統目前【無法開機】。
+    請在重新開機前執行 `sbf install-kernel` 或 `sbf update`。
+doctor_no_unexpected_dirs = ESP 中沒有非預期的目錄
+doctor_unexpected_dirs = 非預期的目錄：{ $paths }，執行 `sbf prune --include-dirs` 以刪除
+prune_file = 正在刪除 { $path } ...
+prune_dir = 正在刪除目錄 { $path } ...
+prune_skip_dir = 略過非預期的目錄 { $path }，使用 --include-dirs 以刪除
+ask_prune_dir = 刪除非預期的目錄 { $path } 及其中的所有內容嗎？
+prune_would_remove = 將移除 { $path }
+ask_prune_files = 刪除以上 { $count } 個不屬於任何已安裝核心的檔案嗎？
+last_updated = 最後更新：{ $time }
+import_conf = 正在將 { $path } 匯入為 { $conf_path } ...
+ask_sbm_replace = { $key } 目前為 "{ $current }"，而 systemd-boot-manager 中為 "{ $new }"，是否使用後者？
+ask_sbm_default_latest = systemd-boot-manager 預設啟動最新核心，是否總是將新安裝的核心設為預設？
+sbm_unmapped = systemd-boot-manager 的 { $key }="{ $value }" 沒有對應的選項，已跳過 ...
+sbm_unchanged = { $path } 中沒有需要匯入的內容。
+ask_sbm_write = 是否將設定檔寫入 { $conf_path }？
+sbm_migrated = 已將 { $path } 匯入 { $conf_path }，現有開機選項保持不變。
+editor_failed = { $editor } 以 { $status } 結束
+ask_edit_again = 開機選項存在錯誤。是否重新編輯？
+entry_edit_discarded = 已捨棄對 { $entry } 的修改。
+entry_unchanged = { $entry } 未作修改。
+entry_edited = 已修改 { $entry }，update 在覆寫前將進行詢問。
+unknown_placeholder = 未知的預留位置 {"{"}{ $placeholder }{"}"}，可用的有：{ $available }
+unclosed_placeholder = { $template } 中的預留位置未閉合
+notice_uninstall =
+    systemd-boot-friend 即將從 ESP 中移除其管理的所有核心及開機選項。除非有其他開機管理程式
+    或開機載入器接手，您的系統將無法開機。
+ask_uninstall = 是否繼續移除 systemd-boot-friend 放入 ESP 的所有內容？
+uninstalled = 已從 ESP 中移除 systemd-boot-friend，現在可以解除安裝該套件。
+invalid_alias = 無效的別名 "{ $alias }"，僅允許使用字母、數字及 "-_.+"
+alias_taken = 別名 { $alias } 已被核心 { $kernel } 使用
+alias_multiple = --version-alias 只能用於單一核心
+history_install = 已安裝 { $kernel }
+history_remove = 已移除 { $kernel }
+history_entries = 已寫入 { $kernel } 的開機選項：{ $entries }
+history_default = 預設開機選項：{ $old } -> { $new }
+history_empty = 沒有歷史記錄
+journal_failed = 無法在日誌中記錄變更：{ $error }
+perm_not_owned_by_root = 擁有者不是 root
+perm_not_readable = 擁有者無法讀取
+perm_not_traversable = 擁有者無法進入目錄
+perm_world_writable = 所有人皆可寫入
+perm_skip_fat = ESP 的檔案系統為 { $fs }，不支援 Unix 權限且所有使用者皆可存取，略過檢查
+perm_ok = ESP 中檔案的權限正常
+snapshots_no_policy = 未設定 max_snapshot_entries，不清理快照開機選項
+snapshots_nothing_to_prune = 沒有需要清理的快照開機選項
+snapshots_would_remove = 將移除 { $entry }
+snapshots_remove = 正在移除 { $entry } ...
+default_ok = 預設開機選項 { $entry } 存在，無需修復
+repaired_default = 已將預設開機選項從 { $old } 改為 { $new }
+duplicate_profiles = 設定檔 { $profiles } 的開機參數相同，如有意設定別名，請啟用 ALLOW_DUPLICATE_PROFILES
+doctor_no_duplicate_profiles = 沒有開機參數相同的設定檔
+hint_editor_chars = 提示：檔案中包含通常由 Windows 編輯器引入的字元（CRLF 換行符號、不換行空格或零寬空格、位元組順序記號），請使用純文字編輯器檢查該檔案
+conf_crlf = 已將 { $key } 中的 CRLF 換行符號轉換為 LF
+openrc_script = 已將 OpenRC 指令稿寫入 { $path }，可使用 `rc-update add systemd-boot-friend default` 啟用
+sbctl_no_pkcs11 = sbctl 無法使用 PKCS#11 權杖中的金鑰簽署，請安裝 pesign 或使用 --tool pesign
+invalid_pkcs11_uri = PKCS11_URI 中缺少 { $attr } 屬性
+no_pkcs11_uri = 未設定 PKCS11_URI
+token_missing = 找不到 PKCS#11 權杖 { $token }
+token_present = 已找到 PKCS#11 權杖 { $token }
+token_key_accessible = 簽署金鑰可以存取
+token_key_inaccessible = 找不到簽署金鑰或無法存取
+kernel_not_installed = 未安裝 { $kernel }
+compare_identical = { $a } 與 { $b } 的開機選項完全相同
+ucode_missing = 來源目錄中找不到微碼映像 { $ucode }
+ucode_reason_default = 預設
+ucode_reason_override = 符合 UCODE_OVERRIDES 中的 { $pattern }
+print_entry_ucode = 微碼：{ $ucode }（{ $reason }）
+print_entry_ucode_absent = 微碼：無，{ $ucode }（{ $reason }）不在 ESP 中
+print_entry_no_ucode = 微碼：無，統一核心映像作為整體開機
+kernel_info_version = 版本：
+kernel_info_build = 建置資訊：
+kernel_info_sources = 來源檔案：
+kernel_info_esp_files = ESP 中的檔案：
+kernel_info_not_installed = 無，未安裝
+kernel_info_entries = 開機選項：
+kernel_info_no_entries = 無
+kernel_info_default = 預設：{ $value ->
+    [true] 是
+   *[false] 否
+}
+kernel_info_running = 正在執行：{ $value ->
+    [true] 是
+   *[false] 否
+}
+validate_ok = { $path } 是有效的開機選項
+validate_no_linux = 開機選項未透過 `linux` 或 `efi` 指定核心映像
+validate_no_title = 開機選項沒有 `title`，開機選單將顯示檔案名稱
+validate_unbalanced_quotes = `options` 中的引號不成對
+validate_empty_options = 開機選項的 `options` 為空
+invalid_conf_key = 未知選項 { $key }，可用選項為：{ $keys }
+invalid_conf_key_type = { $key } 不是表
+conf_set = 已將 { $key } 設為 { $new }，原值為 { $old }
+conf_unset = 已移除 { $key }，原值為 { $old }
+conf_not_set = 未設定
+list_filtered = 顯示 { $total } 個核心中的 { $shown } 個
+migrate_esp_dry_run = 試執行，不做任何變更
+migrate_esp_bootctl = 正在將 systemd-boot 安裝到 { $path } ...
+migrate_esp_bootloader_copied = bootctl 執行失敗（{ $error }），已改為從舊 ESP 複製 systemd-boot
+migrate_esp_kernel = 將安裝 { $kernel } 到 { $path }
+migrate_esp_no_source = { $kernel } 不在 { $src } 中，不予遷移
+ask_migrate_esp_no_source = 有 { $count } 個核心不在 SRC_PATH 中，將不予遷移，是否繼續？
+migrate_esp_loader_conf = 正在複製 { $src } 到 { $dest } ...
+migrate_esp_done = 已遷移到 { $path }，舊 ESP { $old } 保持不變
+ask_migrate_esp_config = 今後使用 { $path } 作為 ESP_MOUNTPOINT 嗎？
+note_migrate_esp_config =
+    設定檔仍指向舊 ESP。請將 ESP_MOUNTPOINT 設定為 { $path } 以管理新 ESP。
+err_migrate_esp_target = { $path } 不是目錄，請先格式化並掛載新 ESP
+err_migrate_esp_same = { $path } 已是目前的 ESP
+err_migrate_esp_no_source = { $kernels } 不在 { $src } 中，請將其安裝到該目錄，或使用 --force 不遷移這些核心
+entries_generated = 已寫入 { $count } 個已安裝核心的開機選項
+plan_unreadable = 無法讀取 { $path }：{ $error }
+plan_version = 不支援的計畫版本 { $version }
+plan_changed = 待安裝或移除的核心有所不同
+plan_empty = 無需進行任何操作
+plan_default = 將設定 { $kernel } 為預設開機選項
+plan_stale = 系統在產生計畫後已發生變化（{ $reason }），請重新產生計畫
+plan_incomplete = 該計畫產生時未能讀取整個 ESP，無法檢查，請以可讀取 ESP 的使用者重新產生計畫
+ask_apply_plan = 是否執行此計畫？
+plan_not_applied = 未執行此計畫
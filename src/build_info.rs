@@ -0,0 +1,23 @@
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::util::format_utc;
+
+/// Print the version, with the information of the build if `verbose`
+pub fn print_version(verbose: bool) {
+    println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+
+    if !verbose {
+        return;
+    }
+
+    let date = env!("SBF_BUILD_DATE")
+        .parse()
+        .map(|secs| format_utc(UNIX_EPOCH + Duration::from_secs(secs)))
+        .unwrap_or_else(|_| env!("SBF_BUILD_DATE").to_owned());
+
+    println!("commit: {}", env!("SBF_BUILD_COMMIT"));
+    println!("features: {}", env!("SBF_BUILD_FEATURES"));
+    println!("libsdbootconf: {}", env!("SBF_BUILD_LIBSDBOOTCONF"));
+    println!("target: {}", env!("SBF_BUILD_TARGET"));
+    println!("built: {}", date);
+}
@@ -1,47 +1,468 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
 
 #[derive(Parser, Debug)]
 #[command(about, author, version, arg_required_else_help(true))]
 pub struct Opts {
+    /// Write machine-readable progress events to the file descriptor
+    #[arg(long, global = true, value_name = "FD")]
+    pub status_fd: Option<i32>,
+    /// Install the kernel images and initramfs without checking them first
+    #[arg(long, global = true)]
+    pub skip_sanity_checks: bool,
+    /// Scan the kernels again every time they are listed in the session
+    #[arg(long, global = true)]
+    pub no_cache: bool,
+    /// Never write the migrated or generated configuration back to the
+    /// file, as if /etc were read-only
+    #[arg(long, global = true)]
+    pub no_config_write: bool,
     #[command(subcommand)]
     pub subcommands: Option<SubCommands>,
 }
 
+/// Filters of the kernels listed or offered for selection, applied after
+/// sorting
+#[derive(Args, Debug, Clone, Default)]
+pub struct KernelFilter {
+    /// Only show the N newest kernels
+    #[arg(long, value_name = "N")]
+    pub newest: Option<usize>,
+    /// Only show the kernels of the version or newer
+    #[arg(long, value_name = "VERSION")]
+    pub since: Option<String>,
+}
+
 #[derive(Subcommand, Debug)]
 pub enum SubCommands {
     /// Initialize systemd-boot-friend
     #[command(display_order = 1)]
-    Init,
+    Init {
+        /// Use the path as the ESP, resolving the disagreement of the
+        /// configuration and bootctl
+        #[arg(long, value_name = "PATH")]
+        esp_path: Option<PathBuf>,
+        /// Install the configuration file first instead of the template
+        #[arg(long, value_name = "FILE")]
+        import_config: Option<PathBuf>,
+        /// Initialize and install the kernels without asking
+        #[arg(long, short = 'y')]
+        assume_yes: bool,
+        /// Detect the ESP, the distribution, the root partition, the kernel
+        /// files and the microcode, and initialize without asking
+        #[arg(long, conflicts_with = "import_config")]
+        detect_all: bool,
+    },
     /// Install all kernels and update boot entries
     #[command(display_order = 2)]
-    Update,
+    Update {
+        /// Print the before and after state in JSON format
+        #[arg(long)]
+        report_json: bool,
+        /// Set the boot menu timeout according to the number of entries
+        #[arg(long)]
+        auto_timeout: bool,
+        /// Stop installing kernels after the given seconds
+        #[arg(long, value_name = "SECONDS")]
+        timeout_seconds: Option<u64>,
+        /// Only manage the kernels of the flavors matching the glob, overriding MANAGE_FLAVORS
+        #[arg(long, value_name = "FLAVOR")]
+        flavor: Vec<String>,
+        /// Install new kernels without removing the obsolete ones
+        #[arg(long)]
+        install_new_only: bool,
+        /// Print the statistics of the update
+        #[arg(long, short)]
+        verbose: bool,
+    },
     /// Install the kernels specified
     #[command(display_order = 3)]
     InstallKernel {
+        #[arg(conflicts_with = "from")]
         targets: Vec<String>,
+        /// Install the kernel image from the path instead of /boot
+        #[arg(long, value_name = "PATH", requires = "version")]
+        from: Option<PathBuf>,
+        /// Version of the kernel image given by --from
+        #[arg(long, requires = "from")]
+        version: Option<String>,
+        /// Initrd of the kernel image given by --from
+        #[arg(long, value_name = "PATH", requires = "from")]
+        initrd: Option<PathBuf>,
+        /// Copy the kernel files from the directory instead of /boot
+        #[arg(long, value_name = "PATH", conflicts_with = "from")]
+        kernel_path: Option<PathBuf>,
         /// Force overwrite the entry config or not
         #[arg(long, short)]
         force: bool,
+        /// Set the installed kernel as default without asking
+        #[arg(long, conflicts_with = "no_set_default")]
+        set_default: bool,
+        /// Do not set the installed kernel as default
+        #[arg(long)]
+        no_set_default: bool,
+        /// Only copy the kernel files to the ESP without writing boot entries
+        #[arg(long, conflicts_with_all = ["entry_only", "set_default"])]
+        copy_only: bool,
+        /// Only write the boot entries for the kernel files already in the ESP
+        #[arg(long)]
+        entry_only: bool,
+        /// Name the entries of the kernel after the alias, which is also
+        /// shown in their titles instead of the version
+        #[arg(long, value_name = "ALIAS", conflicts_with = "copy_only")]
+        version_alias: Option<String>,
+        #[command(flatten)]
+        filter: KernelFilter,
     },
     /// Remove the kernels specified
     #[command(display_order = 4)]
-    RemoveKernel { targets: Vec<String> },
+    RemoveKernel {
+        #[arg(conflicts_with = "all")]
+        targets: Vec<String>,
+        /// Remove all the installed kernels
+        #[arg(long)]
+        all: bool,
+        /// Do not ask for confirmation, only effective with --i-know-what-i-am-doing
+        #[arg(long, short = 'y', requires = "all")]
+        assume_yes: bool,
+        /// Allow --assume-yes to skip the confirmation of --all
+        #[arg(long, requires = "assume_yes")]
+        i_know_what_i_am_doing: bool,
+        /// Remove the kernels even if their modules are still installed,
+        /// overriding ORPHAN_CHECK
+        #[arg(long)]
+        force: bool,
+        #[command(flatten)]
+        filter: KernelFilter,
+    },
     /// Select kernels to install or remove
     #[command(display_order = 5)]
-    Select,
+    Select {
+        /// Only manage the kernels of the flavors matching the glob, overriding MANAGE_FLAVORS
+        #[arg(long, value_name = "FLAVOR")]
+        flavor: Vec<String>,
+        #[command(flatten)]
+        filter: KernelFilter,
+    },
     /// List all available kernels
     #[command(display_order = 6)]
-    ListAvailable,
+    ListAvailable {
+        /// Also show the skipped kernels and why
+        #[arg(long)]
+        all: bool,
+        /// Print stable tab-separated fields for scripts
+        #[arg(long, conflicts_with = "all")]
+        porcelain: bool,
+        /// Print every kernel with the template, e.g. '{version}\t{installed}'
+        #[arg(long, value_name = "FORMAT", conflicts_with_all = ["all", "porcelain"])]
+        format: Option<String>,
+        /// Only manage the kernels of the flavors matching the glob, overriding MANAGE_FLAVORS
+        #[arg(long, value_name = "FLAVOR")]
+        flavor: Vec<String>,
+        #[command(flatten)]
+        filter: KernelFilter,
+    },
     /// List all installed kernels
     #[command(display_order = 7)]
-    ListInstalled,
-    /// Configure systemd-boot
+    ListInstalled {
+        /// Show the size of the kernel files in the ESP
+        #[arg(long)]
+        show_size: bool,
+        /// Also show the installed kernels friend leaves alone and why
+        #[arg(long)]
+        all: bool,
+        /// Exit with code 1 if no default entry is configured or it does not exist
+        #[arg(long)]
+        check_default: bool,
+        /// Print stable tab-separated fields for scripts
+        #[arg(long, conflicts_with_all = ["show_size", "all"])]
+        porcelain: bool,
+        /// Print every kernel with the template, e.g. '{version}\t{default}'
+        #[arg(
+            long,
+            value_name = "FORMAT",
+            conflicts_with_all = ["show_size", "porcelain", "all"]
+        )]
+        format: Option<String>,
+        /// Only manage the kernels of the flavors matching the glob, overriding MANAGE_FLAVORS
+        #[arg(long, value_name = "FLAVOR")]
+        flavor: Vec<String>,
+        #[command(flatten)]
+        filter: KernelFilter,
+    },
+    /// Configure systemd-boot, or access the options non-interactively
     #[command(display_order = 8)]
-    Config,
+    Config {
+        #[command(subcommand)]
+        command: Option<ConfigCommands>,
+    },
     /// Set the default kernel
     #[command(display_order = 9)]
-    SetDefault { target: Option<String> },
+    SetDefault {
+        /// Kernel to set as default, or "none" to clear the default
+        target: Option<String>,
+        /// Clear the default entry
+        #[arg(long, conflicts_with = "target")]
+        clear: bool,
+    },
     /// Set the boot menu timeout
     #[command(display_order = 10)]
     SetTimeout { timeout: Option<u32> },
+    /// Set the boot menu timeout according to the number of entries
+    #[command(display_order = 11)]
+    AutoConfigureTimeout,
+    /// Analyze systemd-boot logs of the previous boot for failures
+    #[command(display_order = 12)]
+    DiagnoseBootFailure,
+    /// Print the current default boot entry
+    #[command(display_order = 13)]
+    Default {
+        /// Print in JSON format
+        #[arg(long)]
+        json: bool,
+    },
+    /// Detect kernel file changes since last install
+    #[command(display_order = 14)]
+    TrackChanges,
+    /// List all boot entries, including those not managed by friend
+    #[command(display_order = 15)]
+    ListEntries {
+        /// Print in JSON format
+        #[arg(long)]
+        json: bool,
+        /// Print stable tab-separated fields for scripts
+        #[arg(long, conflicts_with = "json")]
+        porcelain: bool,
+    },
+    /// Sign all installed kernel images and the systemd-boot EFI binaries
+    #[command(display_order = 16)]
+    SignAll {
+        /// Signing tool to use, sbctl by default, or pesign if PKCS11_URI is set
+        #[arg(long, value_enum)]
+        tool: Option<SigningTool>,
+        /// Show the files to be signed without signing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Check if a reboot is required to use the default kernel, exit with 0 if so
+    #[command(display_order = 17)]
+    NeedsReboot,
+    /// Merge the .pacnew configuration file left by pacman
+    #[command(display_order = 18)]
+    MergePacnew,
+    /// Repack an installed kernel and its initrd into a unified kernel image
+    #[command(display_order = 19)]
+    ConvertToUki {
+        kernel: String,
+        /// Write the image to the path instead of EFI/Linux on the ESP
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Show the boot chain from the firmware to the default kernel
+    #[command(display_order = 21)]
+    ShowTopology,
+    /// Verify the kernel files in the ESP against the sources or checksums
+    #[command(display_order = 22)]
+    Verify,
+    /// Remove the files in the ESP which belong to no installed kernel
+    #[command(display_order = 23)]
+    Prune {
+        /// Also remove the unexpected directories, after confirmation
+        #[arg(long)]
+        include_dirs: bool,
+        /// Show the files and directories to be removed without removing them
+        #[arg(long)]
+        dry_run: bool,
+        /// Remove without asking
+        #[arg(long, short = 'y', conflicts_with = "dry_run")]
+        yes: bool,
+    },
+    /// Show the history of the changes made to the ESP
+    #[command(display_order = 25)]
+    ShowHistory {
+        /// Only show the changes of the default entry
+        #[arg(long)]
+        default_changes_only: bool,
+    },
+    /// Check the ownership and permissions of the files in the ESP
+    #[command(display_order = 26)]
+    CheckPermissions,
+    /// Manage the boot entries of filesystem snapshots
+    #[command(display_order = 27)]
+    Snapshots {
+        #[command(subcommand)]
+        command: SnapshotsCommands,
+    },
+    /// Set the newest installed kernel as default if the default entry does not exist
+    #[command(display_order = 28)]
+    RepairDefault,
+    /// Write an OpenRC service installing the kernels when they change
+    #[command(display_order = 29)]
+    GenerateOpenrcScript {
+        /// Write the script to the path instead of /etc/init.d/systemd-boot-friend
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Check that the PKCS#11 token is present and the signing key is accessible
+    #[command(display_order = 30)]
+    CheckToken,
+    /// Show the differences between the boot entries of two installed kernels
+    #[command(display_order = 31)]
+    Compare { kernel_a: String, kernel_b: String },
+    /// Set the newest installed kernel as default if no existing default entry is configured
+    #[command(display_order = 32)]
+    EnsureDefault,
+    /// Print the boot entries of an installed kernel and the microcode image they load
+    #[command(display_order = 33)]
+    PrintEntry { kernel: String },
+    /// Show the version fields, files, entries and state of a kernel
+    #[command(display_order = 34)]
+    KernelInfo { version: String },
+    /// Check an entry file for problems before rebooting, exit with 1 on
+    /// warnings and 2 on errors
+    #[command(display_order = 35)]
+    ValidateEntry { path: PathBuf },
+    /// Edit or revert the boot entries of a kernel
+    #[command(display_order = 37)]
+    Entry {
+        #[command(subcommand)]
+        command: EntryCommands,
+    },
+    /// Remove the kernels, entries and files of friend from the ESP
+    #[command(display_order = 38)]
+    Uninstall {
+        /// Also remove systemd-boot with bootctl
+        #[arg(long)]
+        remove_bootloader: bool,
+    },
+    /// Move to a new ESP, installing systemd-boot and the kernels there
+    #[command(display_order = 39)]
+    MigrateEsp {
+        /// Mount point of the new, formatted ESP
+        new_mountpoint: PathBuf,
+        /// Mount point of the old ESP, ESP_MOUNTPOINT by default
+        #[arg(long, value_name = "PATH")]
+        from: Option<PathBuf>,
+        /// Print what would be done without changing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Leave the kernels that are not in SRC_PATH behind without asking
+        #[arg(long, short)]
+        force: bool,
+    },
+    /// Write the boot entries of all the installed kernels again, without
+    /// copying the kernel files
+    #[command(display_order = 40)]
+    GenerateEntriesOnly {
+        /// Overwrite the existing entries
+        #[arg(long, short)]
+        force: bool,
+    },
+    /// Print what update would do without changing anything, runs without
+    /// root
+    #[command(display_order = 41)]
+    Plan {
+        /// Print the plan in JSON format for apply-plan
+        #[arg(long)]
+        json: bool,
+    },
+    /// Apply a plan printed by plan --json, exit with 3 if the system
+    /// changed since then, or with 4 if the plan is incomplete
+    #[command(display_order = 42)]
+    ApplyPlan {
+        /// The file of the plan
+        file: PathBuf,
+        /// Apply the plan without asking
+        #[arg(long, short = 'y')]
+        yes: bool,
+    },
+    /// Import the configuration of systemd-boot-manager
+    #[command(
+        name = "migrate-from-systemd-boot-manager",
+        visible_alias = "migrate-from-sbm",
+        display_order = 36
+    )]
+    MigrateFromSbm {
+        /// Path of the configuration, /etc/systemd-boot-manager.conf by default
+        sbm_config: Option<PathBuf>,
+    },
+    /// Print the version
+    #[command(display_order = 24)]
+    Version {
+        /// Also print the commit, features, dependencies and target of the build
+        #[arg(long, short)]
+        verbose: bool,
+    },
+    /// Check the health of the boot setup
+    #[command(display_order = 20, visible_alias = "doctor")]
+    Status {
+        /// Print in JSON format
+        #[arg(long)]
+        json: bool,
+        /// Print a one-line summary, for MOTD and login scripts
+        #[arg(long, conflicts_with = "json")]
+        oneline: bool,
+        /// Refresh the status every given seconds until interrupted
+        #[arg(
+            long,
+            value_name = "INTERVAL_SECONDS",
+            conflicts_with = "oneline",
+            value_parser = clap::value_parser!(u64).range(1..)
+        )]
+        watch: Option<u64>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SnapshotsCommands {
+    /// Remove the snapshot entries outside the retention limits
+    Prune {
+        /// Show the entries to be removed without removing them
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum EntryCommands {
+    /// Open an entry of an installed kernel in $EDITOR, update asks before
+    /// overwriting it afterwards
+    Edit {
+        kernel: String,
+        /// Boot argument profile of the entry, DEFAULT_PROFILE by default
+        #[arg(long, short)]
+        profile: Option<String>,
+        /// Generate the entry from the configuration again and forget the edit
+        #[arg(long)]
+        revert: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommands {
+    /// Print the value of an option in the configuration file, exit with 1
+    /// if it is not set
+    Get {
+        /// Option name, maps are accessed by dotted paths, e.g. bootargs.default
+        key: String,
+    },
+    /// Set an option in the configuration file
+    Set {
+        /// Option name, maps are accessed by dotted paths, e.g. bootargs.default
+        key: String,
+        /// Value written as in the configuration file, or a plain string
+        value: String,
+    },
+    /// Remove an option from the configuration file
+    Unset {
+        /// Option name, maps are accessed by dotted paths, e.g. bootargs.default
+        key: String,
+    },
+}
+
+/// Tools for signing EFI binaries for Secure Boot
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum SigningTool {
+    Sbctl,
+    Pesign,
 }
@@ -0,0 +1,75 @@
+use anyhow::{anyhow, Result};
+use similar::TextDiff;
+use std::collections::BTreeSet;
+
+use crate::{
+    config::Config,
+    fl,
+    kernel::{find_kernel, read_entries, KernelOps},
+    println_with_prefix, println_with_prefix_and_fl,
+    util::print_colored_diff,
+};
+
+/// Print the entry only present for one of the kernels in full
+fn print_whole(file: &str, content: &str, added: bool) {
+    let (old, new, prefix) = if added {
+        ("/dev/null", file, '+')
+    } else {
+        (file, "/dev/null", '-')
+    };
+    let mut diff = format!("--- {}\n+++ {}\n", old, new);
+
+    for line in content.lines() {
+        diff.push(prefix);
+        diff.push_str(line);
+        diff.push('\n');
+    }
+
+    print_colored_diff(&diff);
+}
+
+/// Show the differences between the boot entries of two installed kernels
+pub fn compare<K: KernelOps>(
+    config: &Config,
+    installed_kernels: &[K],
+    kernel_a: &str,
+    kernel_b: &str,
+) -> Result<()> {
+    let [a, b] = [kernel_a, kernel_b].map(|name| {
+        find_kernel(installed_kernels, name)
+            .ok_or_else(|| anyhow!(fl!("kernel_not_installed", kernel = name)))
+    });
+    let (entries_a, entries_b) = (read_entries(config, a?)?, read_entries(config, b?)?);
+    let mut identical = true;
+
+    for profile in entries_a
+        .keys()
+        .chain(entries_b.keys())
+        .collect::<BTreeSet<_>>()
+    {
+        match (entries_a.get(profile), entries_b.get(profile)) {
+            (Some((file_a, a)), Some((file_b, b))) => {
+                if a != b {
+                    let diff = TextDiff::from_lines(a, b);
+                    print_colored_diff(&diff.unified_diff().header(file_a, file_b).to_string());
+                    identical = false;
+                }
+            }
+            (Some((file, content)), None) => {
+                print_whole(file, content, false);
+                identical = false;
+            }
+            (None, Some((file, content))) => {
+                print_whole(file, content, true);
+                identical = false;
+            }
+            (None, None) => unreachable!(),
+        }
+    }
+
+    if identical {
+        println_with_prefix_and_fl!("compare_identical", a = kernel_a, b = kernel_b);
+    }
+
+    Ok(())
+}
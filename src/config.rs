@@ -1,12 +1,192 @@
-use anyhow::{anyhow, Result};
-use serde::{Deserialize, Serialize};
-use std::{cell::RefCell, collections::HashMap, fs, path::PathBuf, rc::Rc};
+use anyhow::{anyhow, bail, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
 
-use crate::{fl, println_with_prefix, println_with_prefix_and_fl};
+use crate::{
+    default_entry::match_entry, fl, println_with_prefix, println_with_prefix_and_fl,
+    version::VersionDisplayFormat, MODULES_PATH, SRC_PATH,
+};
 
-const CONF_PATH: &str = "/etc/systemd-boot-friend.conf";
+pub(crate) const CONF_PATH: &str = "/etc/systemd-boot-friend.conf";
+const CONF_D_PATH: &str = "/etc/systemd-boot-friend.d/";
 const MOUNTS: &str = "/proc/mounts";
+/// Prefix of the environment variables overriding the configuration
+const ENV_PREFIX: &str = "SBF_";
+
+thread_local! {
+    static WRITE_BACK_ENABLED: Cell<bool> = const { Cell::new(true) };
+    static CONF_PATH_OVERRIDE: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+/// Use another configuration file than `CONF_PATH` in this session
+pub fn set_conf_path(path: PathBuf) {
+    CONF_PATH_OVERRIDE.with(|p| *p.borrow_mut() = Some(path));
+}
+
+/// The configuration file of this session
+pub(crate) fn conf_path() -> PathBuf {
+    CONF_PATH_OVERRIDE.with(|p| {
+        p.borrow()
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(CONF_PATH))
+    })
+}
+
+/// Allow friend to write the migrated or generated configuration back to
+/// the file in this session, it is kept in memory otherwise
+pub fn set_config_write(enabled: bool) {
+    WRITE_BACK_ENABLED.with(|c| c.set(enabled));
+}
+
+/// Options which are tables, not overridden by the environment variables
+const TABLE_KEYS: &[&str] = &[
+    "flavor_aliases",
+    "bootargs",
+    "profiles",
+    "skip_profiles",
+    "ucode_overrides",
+];
+/// Options which are lists, comma-separated in the environment variables
+const LIST_KEYS: &[&str] = &["pinned", "manage_flavors", "manage_only", "never_touch"];
+/// Width of the comment lines in the generated configuration file
+const CONF_HEADER_WIDTH: usize = 80;
+const CONF_HEADER_NOTE: &str = "\
+#
+# DISTRO and the title_suffix of the profiles may contain {PKGBASE} and
+# {BUILD_ID}, read from the pkgbase and build-id files in the module
+# directory of each kernel, and empty if missing.
+
+";
 // const CMDLINE: &str = "/proc/cmdline";
+/// Options accessible by `config get/set/unset`, the maps are accessed by
+/// dotted paths, e.g. `bootargs.default`
+const CONF_KEYS: &[&str] = &[
+    "vmlinux",
+    "initrd",
+    "distro",
+    "esp_mountpoint",
+    "keep",
+    "pinned",
+    "manage_flavors",
+    "default_follows_flavor",
+    "flavor_aliases",
+    "manage_only",
+    "never_touch",
+    "ask_default_on_install",
+    "installed_detection_method",
+    "version_format",
+    "splash",
+    "splash_dest",
+    "timestamp_based_entries",
+    "remove_sources",
+    "snapshot_entry_prefix",
+    "max_snapshot_entries",
+    "snapshot_min_age_days",
+    "allow_duplicate_profiles",
+    "pkcs11_uri",
+    "manage_ostree",
+    "scan_uki",
+    "embed_hashes",
+    "orphan_check",
+    "bootctl_timeout",
+    "default_profile",
+    "bootargs",
+    "profiles",
+    "skip_profiles",
+    "ucode_overrides",
+];
+
+/// Whether to set the kernel as default after installation
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "AskDefaultValue", into = "AskDefaultValue")]
+pub enum AskDefault {
+    /// Ask the user
+    #[default]
+    Ask,
+    /// Leave the current default untouched
+    Skip,
+    /// Set the default without asking
+    AlwaysYes,
+}
+
+/// Raw representation of `ask_default_on_install`, either a boolean or "always-yes"
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum AskDefaultValue {
+    Bool(bool),
+    String(String),
+}
+
+impl TryFrom<AskDefaultValue> for AskDefault {
+    type Error = String;
+
+    fn try_from(value: AskDefaultValue) -> Result<Self, Self::Error> {
+        match value {
+            AskDefaultValue::Bool(true) => Ok(AskDefault::Ask),
+            AskDefaultValue::Bool(false) => Ok(AskDefault::Skip),
+            AskDefaultValue::String(s) if s == "always-yes" => Ok(AskDefault::AlwaysYes),
+            AskDefaultValue::String(s) => Err(format!("invalid value \"{}\"", s)),
+        }
+    }
+}
+
+impl From<AskDefault> for AskDefaultValue {
+    fn from(value: AskDefault) -> Self {
+        match value {
+            AskDefault::Ask => AskDefaultValue::Bool(true),
+            AskDefault::Skip => AskDefaultValue::Bool(false),
+            AskDefault::AlwaysYes => AskDefaultValue::String("always-yes".to_owned()),
+        }
+    }
+}
+
+/// How to detect the installed kernels
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InstalledDetectionMethod {
+    /// Match the kernel filenames in the ESP against the template
+    #[default]
+    Files,
+    /// Parse the boot entries managed by friend
+    Entries,
+}
+
+/// Boot menu options of a boot argument profile
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    /// Replaces the default "(profile)" decoration of the entry titles
+    ///
+    /// `{PKGBASE}` and `{BUILD_ID}` are replaced by the contents of the
+    /// `pkgbase` and `build-id` files in the module directory of the kernel,
+    /// as in `DISTRO`
+    #[serde(default)]
+    pub title_suffix: Option<String>,
+    /// Hide the entries from the boot menu
+    ///
+    /// The entry files are written with a leading dot in their names, which
+    /// systemd-boot skips altogether: the entries cannot be set as default
+    /// or booted once either, so the default profile cannot be hidden
+    #[serde(default)]
+    pub hidden: bool,
+}
+
+/// Per-kernel overrides, read from `<version>.toml` in the override directory
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KernelOverride {
+    #[serde(alias = "DISTRO")]
+    pub distro: Option<String>,
+    /// Merged into the bootargs of the main configuration, by profile
+    #[serde(alias = "BOOTARGS", default)]
+    pub bootargs: HashMap<String, String>,
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
@@ -20,10 +200,151 @@ pub struct Config {
     pub esp_mountpoint: Rc<PathBuf>,
     #[serde(alias = "KEEP")]
     pub keep: Option<usize>,
+    /// Kernels never pruned by update, not counted against `keep`
+    #[serde(alias = "PINNED", default)]
+    pub pinned: Vec<String>,
+    /// Only manage the kernels of the flavors, e.g. "aosc-main", or those
+    /// matching the globs, e.g. "aosc-*", all kernels are managed if empty
+    #[serde(alias = "MANAGE_FLAVORS", default)]
+    pub manage_flavors: Vec<String>,
+    /// Let the default entry follow the newest kernel of its own flavor
+    /// instead of the newest kernel
+    #[serde(alias = "DEFAULT_FOLLOWS_FLAVOR", default)]
+    pub default_follows_flavor: bool,
+    /// Flavors mapped to the ones they were renamed to, e.g.
+    /// `"aosc-main" = "aosc"`, so that the default entry follows the
+    /// kernels of the renamed flavor with `default_follows_flavor`
+    #[serde(alias = "FLAVOR_ALIASES", default)]
+    pub flavor_aliases: Rc<HashMap<String, String>>,
+    /// Globs of the kernel versions update installs, e.g. "6.6.*", the
+    /// others can still be installed with install-kernel
+    #[serde(alias = "MANAGE_ONLY", default)]
+    pub manage_only: Vec<String>,
+    /// Globs of the kernel versions friend leaves alone, e.g. "*-rt*",
+    /// even if it installed them before
+    #[serde(alias = "NEVER_TOUCH", default)]
+    pub never_touch: Vec<String>,
+    #[serde(alias = "ASK_DEFAULT_ON_INSTALL", default)]
+    pub ask_default_on_install: AskDefault,
+    #[serde(alias = "INSTALLED_DETECTION_METHOD", default)]
+    pub installed_detection_method: InstalledDetectionMethod,
+    /// How the kernel version is displayed in the entry titles
+    #[serde(alias = "VERSION_FORMAT", default)]
+    pub version_format: VersionDisplayFormat,
+    /// Image copied to the ESP for custom systemd-boot builds
+    #[serde(alias = "SPLASH", default)]
+    pub splash: Option<PathBuf>,
+    /// Where the splash image is copied to, relative to the ESP, the root
+    /// of the ESP by default
+    #[serde(alias = "SPLASH_DEST", default)]
+    pub splash_dest: Option<PathBuf>,
+    /// Name the entries `<version>-<timestamp>-<profile>` after the
+    /// modification time of the kernel image, so that they are ordered by
+    /// the installation time
+    #[serde(alias = "TIMESTAMP_BASED_ENTRIES", default)]
+    pub timestamp_based_entries: bool,
+    /// Remove the kernel images and initrds from /boot once they are
+    /// copied to the ESP and verified
+    #[serde(alias = "REMOVE_SOURCES", default)]
+    pub remove_sources: bool,
+    /// Prefix of the boot entries generated for filesystem snapshots
+    #[serde(
+        alias = "SNAPSHOT_ENTRY_PREFIX",
+        default = "default_snapshot_entry_prefix"
+    )]
+    pub snapshot_entry_prefix: String,
+    /// Keep only the newest snapshot entries when pruning
+    #[serde(alias = "MAX_SNAPSHOT_ENTRIES", default)]
+    pub max_snapshot_entries: Option<usize>,
+    /// Never prune the snapshot entries younger than the given days
+    #[serde(alias = "SNAPSHOT_MIN_AGE_DAYS", default)]
+    pub snapshot_min_age_days: Option<u64>,
+    /// Do not warn about the profiles with identical options, for those
+    /// who want aliases
+    #[serde(alias = "ALLOW_DUPLICATE_PROFILES", default)]
+    pub allow_duplicate_profiles: bool,
+    /// PKCS#11 URI of the signing key on a hardware token, e.g.
+    /// "pkcs11:token=YubiKey;object=db", used by sign-all instead of the
+    /// file-based keys
+    #[serde(alias = "PKCS11_URI", default)]
+    pub pkcs11_uri: Option<String>,
+    /// Manage the kernels of ostree deployments, whose files are usually
+    /// installed to the ESP by ostree itself
+    #[serde(alias = "MANAGE_OSTREE", default)]
+    pub manage_ostree: bool,
+    /// Also manage the unified kernel images in EFI/Linux of the ESP where
+    /// they are, systemd-boot lists them without entries
+    #[serde(alias = "SCAN_UKI", default)]
+    pub scan_uki: bool,
+    /// Append the checksums of the kernel files to the entries as comments,
+    /// for verify to fall back on without the manifest
+    #[serde(alias = "EMBED_HASHES", default)]
+    pub embed_hashes: bool,
+    /// Refuse to remove a kernel from the ESP while its modules are still
+    /// installed, unless `remove-kernel --force` is given
+    #[serde(alias = "ORPHAN_CHECK", default)]
+    pub orphan_check: bool,
+    /// Seconds to wait for bootctl before killing it, 0 to wait forever
+    #[serde(alias = "BOOTCTL_TIMEOUT", default = "default_bootctl_timeout")]
+    pub bootctl_timeout: u64,
+    /// The profile whose entry is set as the default one, e.g. "debug"
+    #[serde(alias = "DEFAULT_PROFILE", default = "default_default_profile")]
+    pub default_profile: String,
     #[serde(alias = "BOOTARG")]
     bootarg: Option<String>, // for compatibility
     #[serde(alias = "BOOTARGS", default)]
     pub bootargs: Rc<RefCell<HashMap<String, String>>>,
+    #[serde(alias = "PROFILES", default)]
+    pub profiles: Rc<HashMap<String, Profile>>,
+    /// Profiles mapped to the glob patterns of the kernels they generate no
+    /// entries for, e.g. `debug = ["5.*"]`
+    #[serde(alias = "SKIP_PROFILES", default)]
+    pub skip_profiles: Rc<HashMap<String, Vec<String>>>,
+    /// Glob patterns of the kernels mapped to the microcode images in the
+    /// source directory their entries load instead of intel-ucode.img,
+    /// e.g. `"6.8.*" = "intel-ucode-20231114.img"`
+    #[serde(alias = "UCODE_OVERRIDES", default)]
+    pub ucode_overrides: Rc<HashMap<String, String>>,
+    /// Where the kernel images are installed by the package manager
+    #[serde(skip, default = "default_src_path")]
+    pub src_path: Rc<PathBuf>,
+    /// Where the kernel modules are installed
+    #[serde(skip, default = "default_modules_path")]
+    pub modules_path: PathBuf,
+    /// Where the per-kernel overrides are placed
+    #[serde(skip, default = "default_conf_d_path")]
+    pub conf_d_path: PathBuf,
+    /// Install the source files without checking them first
+    #[serde(skip)]
+    pub skip_sanity_checks: bool,
+    /// Remove the kernels even if their modules are still installed,
+    /// overriding `orphan_check`
+    #[serde(skip)]
+    pub force_remove: bool,
+}
+
+fn default_src_path() -> Rc<PathBuf> {
+    Rc::new(PathBuf::from(SRC_PATH))
+}
+
+fn default_snapshot_entry_prefix() -> String {
+    "snapshot-".to_owned()
+}
+
+fn default_bootctl_timeout() -> u64 {
+    30
+}
+
+fn default_default_profile() -> String {
+    "default".to_owned()
+}
+
+fn default_modules_path() -> PathBuf {
+    PathBuf::from(MODULES_PATH)
+}
+
+fn default_conf_d_path() -> PathBuf {
+    PathBuf::from(CONF_D_PATH)
 }
 
 impl Default for Config {
@@ -34,15 +355,74 @@ impl Default for Config {
             distro: Rc::new("Linux".to_owned()),
             esp_mountpoint: Rc::new(PathBuf::from("/efi")),
             keep: None,
+            pinned: Vec::new(),
+            manage_flavors: Vec::new(),
+            default_follows_flavor: false,
+            flavor_aliases: Rc::new(HashMap::new()),
+            manage_only: Vec::new(),
+            never_touch: Vec::new(),
+            ask_default_on_install: AskDefault::Ask,
+            installed_detection_method: InstalledDetectionMethod::Files,
+            version_format: VersionDisplayFormat::Compact,
+            splash: None,
+            splash_dest: None,
+            timestamp_based_entries: false,
+            remove_sources: false,
+            snapshot_entry_prefix: default_snapshot_entry_prefix(),
+            max_snapshot_entries: None,
+            snapshot_min_age_days: None,
+            allow_duplicate_profiles: false,
+            pkcs11_uri: None,
+            manage_ostree: false,
+            scan_uki: false,
+            embed_hashes: false,
+            orphan_check: false,
+            bootctl_timeout: default_bootctl_timeout(),
+            default_profile: default_default_profile(),
             bootarg: None,
             bootargs: Rc::new(RefCell::new(HashMap::from([(
                 "default".to_owned(),
                 String::new(),
             )]))),
+            profiles: Rc::new(HashMap::new()),
+            skip_profiles: Rc::new(HashMap::new()),
+            ucode_overrides: Rc::new(HashMap::new()),
+            src_path: default_src_path(),
+            modules_path: default_modules_path(),
+            conf_d_path: default_conf_d_path(),
+            skip_sanity_checks: false,
+            force_remove: false,
         }
     }
 }
 
+/// Parse the value as it would be written in the configuration file
+fn parse_value(value: &str) -> Option<toml::Value> {
+    toml::from_str::<toml::Table>(&format!("v = {}", value))
+        .ok()
+        .and_then(|mut t| t.remove("v"))
+}
+
+/// Read an option from the environment variable, parsed as a TOML value
+/// and falling back to a plain string
+fn env_value<T: DeserializeOwned>(env: &HashMap<String, String>, name: &str) -> Result<Option<T>> {
+    let name = format!("{}{}", ENV_PREFIX, name);
+    let value = match env.get(&name) {
+        Some(v) => v.to_owned(),
+        None => return Ok(None),
+    };
+
+    let parsed = parse_value(&value).and_then(|v| v.try_into().ok());
+
+    match parsed {
+        Some(v) => Ok(Some(v)),
+        None => toml::Value::String(value)
+            .try_into()
+            .map(Some)
+            .map_err(|e| anyhow!("{}: {}", name, e)),
+    }
+}
+
 /// Detect current root partition, used for generating kernel cmdline
 fn detect_root_partition() -> Result<String> {
     let mounts = fs::read_to_string(MOUNTS)?;
@@ -87,19 +467,399 @@ fn fill_necessary_bootarg(bootarg: &str) -> Result<String> {
     Ok(filled_bootarg)
 }
 
+/// Byte order mark left by Windows editors
+const BOM: char = '\u{feff}';
+
+/// Describe a parse failure with the line and column, hinting about the
+/// characters introduced by the editors if any
+fn parse_error(content: &str, path: &Path, e: &toml::de::Error) -> anyhow::Error {
+    let mut location = path.display().to_string();
+
+    if let Some(span) = e.span() {
+        let before = &content[..span.start];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().map_or(0, |l| l.chars().count()) + 1;
+        location.push_str(&format!(":{}:{}", line, column));
+    }
+
+    let mut message = format!("{}: {}", location, e.message().trim_end());
+
+    if content.contains(['\r', '\u{a0}', '\u{200b}', BOM]) {
+        message.push('\n');
+        message.push_str(&fl!("hint_editor_chars"));
+    }
+
+    anyhow!(message)
+}
+
+/// Convert the CRLF line endings in the value to LF
+fn normalize_crlf(key: &str, value: &mut String) {
+    if value.contains('\r') {
+        println_with_prefix_and_fl!("conf_crlf", key = key);
+        *value = value.replace("\r\n", "\n").replace('\r', "\n");
+    }
+}
+
 impl Config {
+    /// Parse the configuration, tolerating the byte order mark and the CRLF
+    /// line endings left by Windows editors
+    fn parse(content: &str, path: &Path) -> Result<Self> {
+        let content = content.strip_prefix(BOM).unwrap_or(content);
+        let mut config: Config =
+            toml::from_str(content).map_err(|e| parse_error(content, path, &e))?;
+
+        // The values ending up in the boot entries
+        normalize_crlf("DISTRO", Rc::make_mut(&mut config.distro));
+
+        if let Some(b) = config.bootarg.as_mut() {
+            normalize_crlf("BOOTARG", b);
+        }
+
+        for (profile, bootarg) in config.bootargs.borrow_mut().iter_mut() {
+            normalize_crlf(&format!("BOOTARGS.{}", profile), bootarg);
+        }
+
+        for (name, profile) in Rc::make_mut(&mut config.profiles).iter_mut() {
+            if let Some(s) = profile.title_suffix.as_mut() {
+                normalize_crlf(&format!("PROFILES.{}.title_suffix", name), s);
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Install the given file as the configuration file after validating it
+    pub fn import(path: &Path) -> Result<()> {
+        let content = fs::read_to_string(path).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+
+        Config::parse(&content, path)?.validate()?;
+
+        let conf_path = conf_path();
+        println_with_prefix_and_fl!(
+            "import_conf",
+            path = path.to_string_lossy(),
+            conf_path = conf_path.to_string_lossy()
+        );
+        fs::write(conf_path, content)?;
+
+        Ok(())
+    }
+
+    /// Serialize the current state as the configuration file
+    pub(crate) fn contents(&self) -> Result<String> {
+        Ok(conf_header() + &toml::to_string_pretty(self)?)
+    }
+
     /// Write the current state to the configuration file
-    fn write(&self) -> Result<()> {
-        fs::create_dir_all(PathBuf::from(CONF_PATH).parent().unwrap())?;
-        fs::write(CONF_PATH, toml::to_string_pretty(self)?)?;
+    pub fn write(&self) -> Result<()> {
+        let conf_path = conf_path();
+
+        if let Some(parent) = conf_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(conf_path, self.contents()?)?;
+        Ok(())
+    }
+
+    /// Write the current state to the configuration file unless /etc is
+    /// read-only or `--no-config-write` is given, return whether it is written
+    pub fn write_back(&self) -> Result<bool> {
+        let reason = if WRITE_BACK_ENABLED.with(|c| c.get()) {
+            match self.write() {
+                Ok(()) => return Ok(true),
+                Err(e) if unwritable(&e) => e.to_string(),
+                Err(e) => return Err(e),
+            }
+        } else {
+            "--no-config-write".to_owned()
+        };
+
+        println_with_prefix_and_fl!(
+            "conf_not_written",
+            conf_path = conf_path().to_string_lossy().into_owned(),
+            reason = reason
+        );
+
+        Ok(false)
+    }
+
+    /// Apply what is not written back to the configuration file: the
+    /// environment variables and the necessary boot arguments
+    pub(crate) fn finish(mut self) -> Result<Self> {
+        self.apply_env(&std::env::vars().collect())?;
+        self.check_default_profile()?;
+
+        for (_, bootarg) in self.bootargs.borrow_mut().iter_mut() {
+            fill_necessary_bootarg(bootarg)?.trim().clone_into(bootarg);
+        }
+
+        Ok(self)
+    }
+
+    /// Read the configuration file as it is, without the environment
+    /// variables and the filled boot arguments
+    pub(crate) fn read_file() -> Result<Self> {
+        let conf_path = conf_path();
+
+        match fs::read_to_string(&conf_path) {
+            Ok(f) => Config::parse(&f, &conf_path),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(anyhow!("{}: {}", conf_path.display(), e)),
+        }
+    }
+
+    /// Build the configuration from the environment variables only
+    pub fn from_env() -> Result<Self> {
+        let mut config = Config::default();
+        config.apply_env(&std::env::vars().collect())?;
+
+        Ok(config)
+    }
+
+    /// Override the options with the environment variables
+    fn apply_env(&mut self, env: &HashMap<String, String>) -> Result<()> {
+        if let Some(v) = env_value(env, "VMLINUX")? {
+            self.vmlinux = v;
+        }
+        if let Some(v) = env_value(env, "INITRD")? {
+            self.initrd = v;
+        }
+        if let Some(v) = env_value::<String>(env, "DISTRO")? {
+            self.distro = Rc::new(v);
+        }
+        if let Some(v) = env_value::<PathBuf>(env, "ESP_MOUNTPOINT")? {
+            self.esp_mountpoint = Rc::new(v);
+        }
+        if let Some(v) = env_value(env, "KEEP")? {
+            self.keep = Some(v);
+        }
+        if let Some(v) = env_value::<String>(env, "PINNED")? {
+            self.pinned = v
+                .split(',')
+                .map(|s| s.trim().to_owned())
+                .filter(|s| !s.is_empty())
+                .collect();
+        }
+        if let Some(v) = env_value::<String>(env, "MANAGE_FLAVORS")? {
+            self.manage_flavors = v
+                .split(',')
+                .map(|s| s.trim().to_owned())
+                .filter(|s| !s.is_empty())
+                .collect();
+        }
+        if let Some(v) = env_value::<String>(env, "MANAGE_ONLY")? {
+            self.manage_only = v
+                .split(',')
+                .map(|s| s.trim().to_owned())
+                .filter(|s| !s.is_empty())
+                .collect();
+        }
+        if let Some(v) = env_value::<String>(env, "NEVER_TOUCH")? {
+            self.never_touch = v
+                .split(',')
+                .map(|s| s.trim().to_owned())
+                .filter(|s| !s.is_empty())
+                .collect();
+        }
+        if let Some(v) = env_value(env, "DEFAULT_FOLLOWS_FLAVOR")? {
+            self.default_follows_flavor = v;
+        }
+        if let Some(v) = env_value(env, "ASK_DEFAULT_ON_INSTALL")? {
+            self.ask_default_on_install = v;
+        }
+        if let Some(v) = env_value(env, "INSTALLED_DETECTION_METHOD")? {
+            self.installed_detection_method = v;
+        }
+        if let Some(v) = env_value(env, "VERSION_FORMAT")? {
+            self.version_format = v;
+        }
+        if let Some(v) = env_value(env, "SPLASH")? {
+            self.splash = Some(v);
+        }
+        if let Some(v) = env_value(env, "SPLASH_DEST")? {
+            self.splash_dest = Some(v);
+        }
+        if let Some(v) = env_value(env, "TIMESTAMP_BASED_ENTRIES")? {
+            self.timestamp_based_entries = v;
+        }
+        if let Some(v) = env_value(env, "REMOVE_SOURCES")? {
+            self.remove_sources = v;
+        }
+        if let Some(v) = env_value(env, "SNAPSHOT_ENTRY_PREFIX")? {
+            self.snapshot_entry_prefix = v;
+        }
+        if let Some(v) = env_value(env, "MAX_SNAPSHOT_ENTRIES")? {
+            self.max_snapshot_entries = Some(v);
+        }
+        if let Some(v) = env_value(env, "SNAPSHOT_MIN_AGE_DAYS")? {
+            self.snapshot_min_age_days = Some(v);
+        }
+        if let Some(v) = env_value(env, "ALLOW_DUPLICATE_PROFILES")? {
+            self.allow_duplicate_profiles = v;
+        }
+        if let Some(v) = env_value(env, "PKCS11_URI")? {
+            self.pkcs11_uri = Some(v);
+        }
+        if let Some(v) = env_value(env, "MANAGE_OSTREE")? {
+            self.manage_ostree = v;
+        }
+        if let Some(v) = env_value(env, "SCAN_UKI")? {
+            self.scan_uki = v;
+        }
+        if let Some(v) = env_value(env, "EMBED_HASHES")? {
+            self.embed_hashes = v;
+        }
+        if let Some(v) = env_value(env, "ORPHAN_CHECK")? {
+            self.orphan_check = v;
+        }
+        if let Some(v) = env_value(env, "BOOTCTL_TIMEOUT")? {
+            self.bootctl_timeout = v;
+        }
+        if let Some(v) = env_value(env, "DEFAULT_PROFILE")? {
+            self.default_profile = v;
+        }
+
+        // SBF_BOOTARGS_<PROFILE>, profiles are lowercase in the configuration
+        let bootargs_prefix = format!("{}BOOTARGS_", ENV_PREFIX);
+        for (name, value) in env {
+            if let Some(profile) = name.strip_prefix(&bootargs_prefix) {
+                self.bootargs
+                    .borrow_mut()
+                    .insert(profile.to_lowercase(), value.to_owned());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check if the kernel of the localversion, e.g. "-aosc-main", is managed
+    pub fn manages(&self, localversion: &str) -> bool {
+        let flavor = localversion.trim_start_matches('-');
+
+        self.manage_flavors.is_empty()
+            || self
+                .manage_flavors
+                .iter()
+                .any(|f| match_entry(f, [flavor]).is_ok_and(|m| m.is_some()))
+    }
+
+    /// The flavor a flavor was renamed to, following `flavor_aliases`
+    pub fn flavor_series<'a>(&'a self, mut flavor: &'a str) -> &'a str {
+        // The aliases may be chained, but must not loop forever
+        for _ in 0..self.flavor_aliases.len() {
+            match self.flavor_aliases.get(flavor) {
+                Some(renamed) if renamed != flavor => flavor = renamed,
+                _ => break,
+            }
+        }
+
+        flavor
+    }
+
+    /// Check if update installs the kernel of the version, according to
+    /// `manage_only`
+    pub fn manage_only_allows(&self, version: &str) -> bool {
+        self.manage_only.is_empty()
+            || self
+                .manage_only
+                .iter()
+                .any(|p| match_entry(p, [version]).is_ok_and(|m| m.is_some()))
+    }
+
+    /// Find the glob in `never_touch` matching the kernel version, if any
+    pub fn never_touch_match(&self, version: &str) -> Option<&str> {
+        self.never_touch
+            .iter()
+            .find(|p| match_entry(p, [version]).is_ok_and(|m| m.is_some()))
+            .map(|p| p.as_str())
+    }
+
+    /// Read the overrides of the given kernel, if any
+    pub fn kernel_override(&self, kernel_name: &str) -> Result<Option<KernelOverride>> {
+        let path = self.conf_d_path.join(format!("{}.toml", kernel_name));
+
+        let content = match fs::read_to_string(&path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let content = content.strip_prefix(BOM).unwrap_or(&content);
+        let mut kernel_override: KernelOverride =
+            toml::from_str(content).map_err(|e| parse_error(content, &path, &e))?;
+
+        for bootarg in kernel_override.bootargs.values_mut() {
+            fill_necessary_bootarg(bootarg)?.trim().clone_into(bootarg);
+        }
+
+        Ok(Some(kernel_override))
+    }
+
+    /// Check the options that would make friend misbehave
+    pub fn validate(&self) -> Result<()> {
+        for template in [&self.vmlinux, &self.initrd] {
+            if template.is_empty() {
+                bail!(fl!("invalid_conf", reason = "VMLINUX/INITRD"));
+            }
+        }
+
+        if !self.esp_mountpoint.is_absolute() {
+            bail!(fl!("invalid_conf", reason = "ESP_MOUNTPOINT"));
+        }
+
+        if self.keep == Some(0) {
+            bail!(fl!("invalid_conf", reason = "KEEP"));
+        }
+
+        if !self.bootargs.borrow().contains_key(&self.default_profile) {
+            bail!(fl!("invalid_conf", reason = "DEFAULT_PROFILE"));
+        }
+
+        self.check_default_profile()
+    }
+
+    /// Refuse a default profile whose entries are not written, or not
+    /// bootable, for some kernels
+    fn check_default_profile(&self) -> Result<()> {
+        // Some kernels would have no entry to be set as default
+        let dropped_by = if self
+            .skip_profiles
+            .get(&self.default_profile)
+            .is_some_and(|patterns| !patterns.is_empty())
+        {
+            Some("SKIP_PROFILES")
+        } else if self
+            .profiles
+            .get(&self.default_profile)
+            .is_some_and(|p| p.hidden)
+        {
+            Some("PROFILES.hidden")
+        } else {
+            None
+        };
+
+        if let Some(option) = dropped_by {
+            bail!(fl!(
+                "invalid_conf",
+                reason = fl!(
+                    "default_profile_dropped",
+                    profile = self.default_profile.as_str(),
+                    option = option
+                )
+            ));
+        }
+
         Ok(())
     }
 
     /// Read the configuration file
     pub fn read() -> Result<Self> {
-        match fs::read_to_string(CONF_PATH) {
+        let conf_path = conf_path();
+        let conf_path_str = conf_path.to_string_lossy();
+
+        match fs::read_to_string(&conf_path) {
             Ok(f) => {
-                let mut config: Config = toml::from_str(&f)?;
+                let mut config = Config::parse(&f, &conf_path)?;
 
                 // Migrate from old configuration
                 let old_conf = "{VERSION}-{LOCALVERSION}";
@@ -109,14 +869,24 @@ impl Config {
                     println_with_prefix_and_fl!("conf_old");
                     config.vmlinux = config.vmlinux.replace(old_conf, new_conf);
                     config.initrd = config.initrd.replace(old_conf, new_conf);
-                    config.write()?;
+                    config.write_back()?;
+                }
+
+                // Without {VERSION}, all kernels would share the same file
+                for template in [&config.vmlinux, &config.initrd] {
+                    if !template.contains("{VERSION}") {
+                        println_with_prefix_and_fl!(
+                            "template_no_version",
+                            template = template.as_str()
+                        );
+                    }
                 }
 
                 // For compatibility
                 if let Some(b) = config.bootarg {
                     config.bootargs.borrow_mut().insert("default".to_owned(), b);
                     config.bootarg = None;
-                    config.write()?;
+                    config.write_back()?;
                 }
 
                 if config.bootargs.borrow().is_empty()
@@ -126,9 +896,16 @@ impl Config {
                         .bootargs
                         .borrow_mut()
                         .insert("default".to_owned(), String::new());
-                    config.write()?;
+                    config.write_back()?;
                 }
 
+                config.finish()
+            }
+            // Containers may be configured with environment variables only
+            Err(_) if std::env::vars().any(|(k, _)| is_option_env(&k)) => {
+                let config = Config::from_env()?;
+                config.check_default_profile()?;
+
                 for (_, bootarg) in config.bootargs.borrow_mut().iter_mut() {
                     fill_necessary_bootarg(bootarg)?.trim().clone_into(bootarg);
                 }
@@ -136,9 +913,19 @@ impl Config {
                 Ok(config)
             }
             Err(_) => {
-                println_with_prefix_and_fl!("conf_default", conf_path = CONF_PATH);
-                Config::default().write()?;
-                Err(anyhow!(fl!("edit_conf", conf_path = CONF_PATH)))
+                println_with_prefix_and_fl!("conf_default", conf_path = conf_path_str.as_ref());
+                let config = Config::default();
+
+                // Leave it to the user to save the template
+                if !config.write_back()? {
+                    print!("{}", config.contents()?);
+                    bail!(fl!("save_conf", conf_path = conf_path_str.as_ref()));
+                }
+
+                Err(anyhow!(fl!(
+                    "edit_conf",
+                    conf_path = conf_path_str.as_ref()
+                )))
             }
         }
     }
@@ -200,3 +987,444 @@ impl Config {
     //     Ok(())
     // }
 }
+
+/// Prepended to the generated configuration file, listing the environment
+/// variables of the options in `CONF_KEYS`
+fn conf_header() -> String {
+    let env_name = |k: &str| format!("{}{}", ENV_PREFIX, k.to_uppercase());
+    let lists = CONF_KEYS
+        .iter()
+        .filter(|k| LIST_KEYS.contains(k))
+        .map(|k| env_name(k))
+        .collect::<Vec<_>>();
+    let others = CONF_KEYS
+        .iter()
+        .filter(|k| !LIST_KEYS.contains(k) && !TABLE_KEYS.contains(k))
+        .map(|k| env_name(k))
+        .collect::<Vec<_>>();
+
+    let text = format!(
+        "Every option but the tables can be overridden by an environment variable \
+         named after it, with the {} prefix: {}, and {} as comma-separated lists. \
+         Boot arguments are set by profile, e.g. {}BOOTARGS_DEFAULT.",
+        ENV_PREFIX,
+        others.join(", "),
+        lists.join(", "),
+        ENV_PREFIX
+    );
+
+    let mut header = String::new();
+    let mut line = String::from("#");
+
+    for word in text.split(' ') {
+        if line.len() + 1 + word.len() > CONF_HEADER_WIDTH {
+            header += &line;
+            header.push('\n');
+            line = String::from("#");
+        }
+        line.push(' ');
+        line += word;
+    }
+    header += &line;
+    header.push('\n');
+
+    header + CONF_HEADER_NOTE
+}
+
+/// Whether the environment variable overrides an option, unlike e.g. the
+/// SBF_BUILD_* variables of cargo
+fn is_option_env(name: &str) -> bool {
+    name.strip_prefix(ENV_PREFIX).is_some_and(|name| {
+        name.starts_with("BOOTARGS_")
+            || CONF_KEYS
+                .iter()
+                .any(|k| !TABLE_KEYS.contains(k) && k.to_uppercase() == name)
+    })
+}
+
+/// Whether the configuration file failed to be written because /etc is
+/// read-only, which is not an error
+fn unwritable(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<std::io::Error>().is_some_and(|e| {
+        matches!(
+            e.kind(),
+            ErrorKind::PermissionDenied | ErrorKind::ReadOnlyFilesystem
+        )
+    })
+}
+
+/// Split the dotted path of an option, checking it against the known options
+fn split_key(key: &str) -> Result<Vec<String>> {
+    let mut path = key.split('.').map(|k| k.to_owned()).collect::<Vec<_>>();
+    path[0] = path[0].to_lowercase();
+
+    if !CONF_KEYS.contains(&path[0].as_str()) || path.iter().any(|k| k.is_empty()) {
+        bail!(fl!(
+            "invalid_conf_key",
+            key = key,
+            keys = CONF_KEYS.join(", ")
+        ));
+    }
+
+    Ok(path)
+}
+
+/// Format the value of an option, strings are printed as they are
+fn display_value(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(s) => s.to_owned(),
+        v => v.to_string(),
+    }
+}
+
+/// Read the configuration file as a table of the canonical option names
+fn read_table() -> Result<toml::Table> {
+    match toml::Value::try_from(Config::read_file()?)? {
+        toml::Value::Table(t) => Ok(t),
+        _ => unreachable!(),
+    }
+}
+
+/// Find the option in the table by its path
+fn lookup<'a>(table: &'a toml::Table, path: &[String]) -> Option<&'a toml::Value> {
+    let (last, parents) = path.split_last()?;
+    let mut table = table;
+
+    for k in parents {
+        table = table.get(k)?.as_table()?;
+    }
+
+    table.get(last)
+}
+
+/// Set the option in the table by its path, creating the missing tables,
+/// return the old value
+fn insert(
+    table: &mut toml::Table,
+    path: &[String],
+    value: toml::Value,
+) -> Result<Option<toml::Value>> {
+    let (last, parents) = path.split_last().unwrap();
+    let mut table = table;
+
+    for k in parents {
+        table = table
+            .entry(k.to_owned())
+            .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+            .as_table_mut()
+            .ok_or_else(|| anyhow!(fl!("invalid_conf_key_type", key = path.join("."))))?;
+    }
+
+    Ok(table.insert(last.to_owned(), value))
+}
+
+/// Remove the option from the table by its path, return the old value
+fn remove(table: &mut toml::Table, path: &[String]) -> Option<toml::Value> {
+    let (last, parents) = path.split_last()?;
+    let mut table = table;
+
+    for k in parents {
+        table = table.get_mut(k)?.as_table_mut()?;
+    }
+
+    table.remove(last)
+}
+
+/// Build the configuration from the table, checking it with the same
+/// rules as the configuration file
+fn from_table(table: toml::Table, key: &str) -> Result<Config> {
+    let config: Config = toml::Value::Table(table)
+        .try_into()
+        .map_err(|e: toml::de::Error| anyhow!("{}: {}", key, e.message().trim_end()))?;
+    config.validate()?;
+
+    Ok(config)
+}
+
+/// Print the value of the option in the configuration file, return 1 if
+/// it is not set
+pub fn get_option(key: &str) -> Result<i32> {
+    let path = split_key(key)?;
+    let table = read_table()?;
+
+    match lookup(&table, &path) {
+        Some(v) => {
+            println!("{}", display_value(v));
+            Ok(0)
+        }
+        None => Ok(1),
+    }
+}
+
+/// Set the option in the configuration file, the value is parsed as it
+/// would be written in the file, falling back to a plain string
+pub fn set_option(key: &str, value: &str) -> Result<()> {
+    let path = split_key(key)?;
+    let table = read_table()?;
+    let mut error = None;
+
+    for v in parse_value(value)
+        .into_iter()
+        .chain([toml::Value::String(value.to_owned())])
+    {
+        let mut table = table.clone();
+        let old = insert(&mut table, &path, v.clone())?;
+
+        match from_table(table, key) {
+            Ok(config) => {
+                config.write()?;
+                println_with_prefix_and_fl!(
+                    "conf_set",
+                    key = key,
+                    old = old
+                        .as_ref()
+                        .map_or_else(|| fl!("conf_not_set"), display_value),
+                    new = display_value(&v)
+                );
+                return Ok(());
+            }
+            Err(e) => {
+                error.get_or_insert(e);
+            }
+        }
+    }
+
+    Err(error.unwrap())
+}
+
+/// Remove the option from the configuration file, falling back to its
+/// default value
+pub fn unset_option(key: &str) -> Result<()> {
+    let path = split_key(key)?;
+    let mut table = read_table()?;
+
+    if let Some(old) = remove(&mut table, &path) {
+        from_table(table, key)?.write()?;
+        println_with_prefix_and_fl!("conf_unset", key = key, old = display_value(&old));
+    }
+
+    Ok(())
+}
+
+/// Group the profiles whose options are identical, ignoring the
+/// whitespace, only the groups of two or more profiles are returned
+pub fn duplicate_profiles(bootargs: &HashMap<String, String>) -> Vec<Vec<String>> {
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for (profile, options) in bootargs {
+        groups
+            .entry(options.split_whitespace().collect::<Vec<_>>().join(" "))
+            .or_default()
+            .push(profile.to_owned());
+    }
+
+    let mut groups = groups
+        .into_values()
+        .filter(|g| g.len() > 1)
+        .map(|mut g| {
+            g.sort();
+            g
+        })
+        .collect::<Vec<_>>();
+    groups.sort();
+
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_env() {
+        let env = [
+            ("SBF_KEEP", "2"),
+            ("SBF_DISTRO", "AOSC OS"),
+            ("SBF_ASK_DEFAULT_ON_INSTALL", "always-yes"),
+            ("SBF_PINNED", "6.8.0-aosc-main, 6.6.8-aosc-main"),
+            ("SBF_BOOTARGS_DEBUG", "root=/dev/sda1 rw debug"),
+        ]
+        .map(|(k, v)| (k.to_owned(), v.to_owned()))
+        .into();
+
+        let mut config = Config::default();
+        config.apply_env(&env).unwrap();
+
+        assert_eq!(config.keep, Some(2));
+        assert_eq!(config.distro.as_str(), "AOSC OS");
+        assert_eq!(config.ask_default_on_install, AskDefault::AlwaysYes);
+        assert_eq!(config.pinned, ["6.8.0-aosc-main", "6.6.8-aosc-main"]);
+        assert_eq!(
+            config.bootargs.borrow().get("debug").map(|s| s.as_str()),
+            Some("root=/dev/sda1 rw debug")
+        );
+        assert_eq!(config.vmlinux, "vmlinuz-{VERSION}");
+    }
+
+    const OLD_CONF: &str = "\
+DISTRO = \"AOSC OS\"
+ESP_MOUNTPOINT = \"/efi\"
+VMLINUX = \"vmlinuz-{VERSION}-{LOCALVERSION}\"
+INITRD = \"initramfs-{VERSION}-{LOCALVERSION}.img\"
+
+[BOOTARGS]
+default = \"root=/dev/sda1 rw\"
+";
+
+    #[test]
+    fn test_read_migrate() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("systemd-boot-friend.conf");
+        set_conf_path(path.clone());
+
+        // Migrated in memory only
+        fs::write(&path, OLD_CONF).unwrap();
+        set_config_write(false);
+        assert_eq!(Config::read().unwrap().vmlinux, "vmlinuz-{VERSION}");
+        assert_eq!(fs::read_to_string(&path).unwrap(), OLD_CONF);
+
+        set_config_write(true);
+        assert_eq!(Config::read().unwrap().vmlinux, "vmlinuz-{VERSION}");
+        assert_eq!(Config::read_file().unwrap().vmlinux, "vmlinuz-{VERSION}");
+        assert!(fs::read_to_string(&path).unwrap().starts_with("# "));
+    }
+
+    #[test]
+    fn test_read_first_run() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("etc/systemd-boot-friend.conf");
+        set_conf_path(path.clone());
+
+        // The template is printed instead
+        set_config_write(false);
+        let err = Config::read().unwrap_err().to_string();
+        assert!(err.contains(&*path.to_string_lossy()));
+        assert!(!path.exists());
+
+        set_config_write(true);
+        assert!(Config::read().is_err());
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            Config::default().contents().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_unwritable() {
+        for (kind, expected) in [
+            (ErrorKind::ReadOnlyFilesystem, true),
+            (ErrorKind::PermissionDenied, true),
+            (ErrorKind::NotFound, false),
+        ] {
+            assert_eq!(unwritable(&std::io::Error::from(kind).into()), expected);
+        }
+        assert!(!unwritable(&anyhow!("invalid")));
+    }
+
+    #[test]
+    fn test_is_option_env() {
+        assert!(is_option_env("SBF_KEEP"));
+        assert!(is_option_env("SBF_BOOTARGS_DEBUG"));
+        assert!(!is_option_env("SBF_BUILD_COMMIT"));
+        assert!(!is_option_env("SBF_PROFILES"));
+        assert!(!is_option_env("KEEP"));
+    }
+
+    #[test]
+    fn test_validate_default_profile() {
+        let mut config = Config::default();
+        config
+            .bootargs
+            .borrow_mut()
+            .insert("default".to_owned(), "root=/dev/sda1 rw".to_owned());
+        assert!(config.validate().is_ok());
+
+        config.skip_profiles = Rc::new(HashMap::from([(
+            "default".to_owned(),
+            vec!["5.*".to_owned()],
+        )]));
+        assert!(config.validate().is_err());
+
+        config.skip_profiles = Rc::new(HashMap::new());
+        config.profiles = Rc::new(HashMap::from([(
+            "default".to_owned(),
+            Profile {
+                hidden: true,
+                ..Default::default()
+            },
+        )]));
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_conf_header() {
+        let header = conf_header();
+        let names = header
+            .split_whitespace()
+            .map(|w| w.trim_end_matches([',', '.']))
+            .collect::<Vec<_>>();
+
+        for key in CONF_KEYS.iter().filter(|k| !TABLE_KEYS.contains(k)) {
+            assert!(names.contains(&format!("SBF_{}", key.to_uppercase()).as_str()));
+        }
+        assert!(!names.contains(&"SBF_PROFILES"));
+        assert!(header.lines().all(|l| l.len() <= CONF_HEADER_WIDTH));
+        // Still a comment in front of the options
+        assert!(toml::from_str::<toml::Table>(&header).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_bom_crlf() {
+        let path = Path::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/config-bom-crlf.conf"
+        ));
+        let content = fs::read_to_string(path).unwrap();
+        let config = Config::parse(&content, path).unwrap();
+
+        assert_eq!(config.distro.as_str(), "AOSC OS");
+        assert!(!config.bootargs.borrow()["default"].contains('\r'));
+
+        // The location is reported
+        let err = Config::parse(&content.replace("[BOOTARGS]", "[BOOTARGS"), path)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("config-bom-crlf.conf:6:"));
+    }
+
+    #[test]
+    fn test_duplicate_profiles() {
+        let bootargs = HashMap::from([
+            ("default".to_owned(), "root=/dev/sda1 rw".to_owned()),
+            ("copy".to_owned(), "root=/dev/sda1  rw".to_owned()),
+            ("debug".to_owned(), "root=/dev/sda1 rw debug".to_owned()),
+        ]);
+
+        assert_eq!(
+            duplicate_profiles(&bootargs),
+            vec![vec!["copy".to_owned(), "default".to_owned()]]
+        );
+    }
+
+    #[test]
+    fn test_set_by_path() {
+        let mut table = match toml::Value::try_from(Config::default()).unwrap() {
+            toml::Value::Table(t) => t,
+            _ => unreachable!(),
+        };
+        let path = split_key("bootargs.debug").unwrap();
+
+        assert!(insert(&mut table, &path, "root=/dev/sda1 rw debug".into())
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            lookup(&table, &path).and_then(|v| v.as_str()),
+            Some("root=/dev/sda1 rw debug")
+        );
+        assert!(from_table(table.clone(), "bootargs.debug").is_ok());
+
+        insert(&mut table, &split_key("KEEP").unwrap(), "three".into()).unwrap();
+        assert!(from_table(table, "keep").is_err());
+        assert!(split_key("no_such_option").is_err());
+    }
+}
@@ -0,0 +1,359 @@
+use anyhow::Result;
+use libsdbootconf::SystemdBootConf;
+use regex::Regex;
+use serde::Serialize;
+use std::{cell::RefCell, cmp::Ordering, fs, rc::Rc};
+
+use crate::{
+    config::Config,
+    fl, journal,
+    kernel::{list_entry_ids, Kernel, HIDDEN_ENTRY_PREFIX},
+    println_with_prefix, println_with_prefix_and_fl,
+};
+
+const EFIVARS_PATH: &str = "/sys/firmware/efi/efivars/";
+const LOADER_GUID: &str = "4a67b082-0a4c-41cf-b6c7-440b29bb8c4f";
+
+/// Exit code when no default entry is configured
+pub const EXIT_NO_DEFAULT: i32 = 1;
+/// Exit code when the configured default entry does not exist
+pub const EXIT_DANGLING_DEFAULT: i32 = 2;
+
+/// The resolved default boot entry
+#[derive(Debug, Serialize)]
+struct DefaultEntry {
+    entry: String,
+    source: &'static str,
+    kernel: Option<String>,
+    profile: Option<String>,
+}
+
+/// Read a UTF-16 string from a systemd-boot EFI variable
+pub fn read_loader_efivar(name: &str) -> Option<String> {
+    let data = fs::read(format!("{}{}-{}", EFIVARS_PATH, name, LOADER_GUID)).ok()?;
+    // The first 4 bytes are the variable attributes
+    let utf16 = data
+        .get(4..)?
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .take_while(|c| *c != 0)
+        .collect::<Vec<_>>();
+
+    String::from_utf16(&utf16).ok().filter(|s| !s.is_empty())
+}
+
+/// Compare the entry ids the way systemd-boot orders its menu when the
+/// entries have no sort-key: the runs of digits are compared by value
+fn version_cmp(a: &str, b: &str) -> Ordering {
+    let re = Regex::new(r"\d+|\D+").unwrap();
+    let (mut a, mut b) = (re.find_iter(a), re.find_iter(b));
+
+    loop {
+        let ord = match (a.next(), b.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => match (x.as_str().parse::<u128>(), y.as_str().parse::<u128>()) {
+                (Ok(x), Ok(y)) => x.cmp(&y),
+                _ => x.as_str().cmp(y.as_str()),
+            },
+        };
+
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+}
+
+/// Get the entry systemd-boot boots without a default: the first one in
+/// its menu, i.e. the newest version, hidden entries are not listed
+pub fn fallback_entry<'a, I>(ids: I) -> Option<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    ids.into_iter()
+        .filter(|id| !id.starts_with(HIDDEN_ENTRY_PREFIX))
+        .max_by(|a, b| version_cmp(a, b))
+}
+
+/// Find the entry id matching the `default` pattern of systemd-boot,
+/// which may contain glob patterns. The newest match wins.
+pub fn match_entry<'a, I>(pattern: &str, ids: I) -> Result<Option<&'a str>>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let pattern = pattern.strip_suffix(".conf").unwrap_or(pattern);
+    let mut re = String::from("^");
+
+    for c in pattern.chars() {
+        match c {
+            '*' => re.push_str(".*"),
+            '?' => re.push('.'),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    re.push('$');
+
+    let re = Regex::new(&re)?;
+
+    Ok(ids
+        .into_iter()
+        .filter(|id| re.is_match(id))
+        .max_by(|a, b| version_cmp(a, b)))
+}
+
+/// Get the `default` pattern in effect and where it comes from, the EFI
+/// variables take precedence over loader.conf
+pub fn default_pattern(sbconf: &SystemdBootConf) -> Option<(String, &'static str)> {
+    read_loader_efivar("LoaderEntryOneShot")
+        .map(|p| (p, "oneshot"))
+        .or_else(|| read_loader_efivar("LoaderEntryDefault").map(|p| (p, "efivar")))
+        .or_else(|| {
+            sbconf
+                .config
+                .default
+                .as_ref()
+                .map(|p| (p.to_owned(), "loader.conf"))
+        })
+}
+
+/// Print the current default boot entry, return the exit code
+pub fn print_default<K: Kernel>(
+    installed_kernels: &[K],
+    config: &Config,
+    sbconf: Rc<RefCell<SystemdBootConf>>,
+    json: bool,
+) -> Result<i32> {
+    let (pattern, source) = match default_pattern(&sbconf.borrow()) {
+        Some(p) => p,
+        None => {
+            println_with_prefix_and_fl!("no_default");
+            return Ok(EXIT_NO_DEFAULT);
+        }
+    };
+
+    let ids = list_entry_ids(&config.esp_mountpoint)?;
+    let id = match match_entry(&pattern, ids.iter().map(|id| id.as_str()))? {
+        Some(id) => id,
+        None => {
+            println_with_prefix_and_fl!("dangling_default", entry = pattern);
+            return Ok(EXIT_DANGLING_DEFAULT);
+        }
+    };
+
+    let mut default = DefaultEntry {
+        entry: format!("{}.conf", id),
+        source,
+        kernel: None,
+        profile: None,
+    };
+
+    // Look up the kernel and profile if the entry is managed by friend
+    'outer: for kernel in installed_kernels {
+        for profile in config.bootargs.borrow().keys() {
+            if kernel.entry_id(profile) == id {
+                default.kernel = Some(kernel.to_string());
+                default.profile = Some(profile.to_owned());
+                break 'outer;
+            }
+        }
+    }
+
+    if json {
+        println!("{}", serde_json::to_string(&default)?);
+    } else {
+        println!(
+            "{}\t{}\t{}",
+            default.entry,
+            default.kernel.as_deref().unwrap_or("-"),
+            default.profile.as_deref().unwrap_or("-")
+        );
+    }
+
+    Ok(0)
+}
+
+/// The default entry configured in loader.conf
+enum DefaultState {
+    Missing,
+    /// Matches no entry in the ESP
+    Dangling(String),
+    Resolved(String),
+}
+
+fn default_state(config: &Config, sbconf: &SystemdBootConf) -> Result<DefaultState> {
+    let pattern = match sbconf.config.default.clone() {
+        Some(p) => p,
+        None => return Ok(DefaultState::Missing),
+    };
+
+    let ids = list_entry_ids(&config.esp_mountpoint)?;
+
+    Ok(
+        match match_entry(&pattern, ids.iter().map(|id| id.as_str()))? {
+            Some(_) => DefaultState::Resolved(pattern),
+            None => DefaultState::Dangling(pattern),
+        },
+    )
+}
+
+/// Set the newest installed kernel as default, return it if any
+fn default_to_newest<K: Kernel>(installed_kernels: &[K], announce: bool) -> Result<Option<&K>> {
+    // The installed kernels are sorted newest first
+    match installed_kernels.first() {
+        Some(k) => {
+            k.set_default(announce)?;
+            Ok(Some(k))
+        }
+        None => {
+            println_with_prefix_and_fl!("empty_list");
+            Ok(None)
+        }
+    }
+}
+
+/// Check that loader.conf has a default entry and that the entry exists,
+/// return the exit code
+pub fn check_default(config: &Config, sbconf: &SystemdBootConf) -> Result<i32> {
+    match default_state(config, sbconf)? {
+        DefaultState::Missing => {
+            println_with_prefix_and_fl!("no_default");
+            Ok(EXIT_NO_DEFAULT)
+        }
+        DefaultState::Dangling(pattern) => {
+            println_with_prefix_and_fl!("dangling_default", entry = pattern);
+            Ok(EXIT_NO_DEFAULT)
+        }
+        DefaultState::Resolved(_) => Ok(0),
+    }
+}
+
+/// Point a dangling default entry in loader.conf at the newest installed
+/// kernel
+pub fn repair_default<K: Kernel>(
+    config: &Config,
+    installed_kernels: &[K],
+    sbconf: Rc<RefCell<SystemdBootConf>>,
+) -> Result<()> {
+    // Bound first, as set_default() writes to loader.conf
+    let state = default_state(config, &sbconf.borrow())?;
+
+    match state {
+        DefaultState::Missing => println_with_prefix_and_fl!("no_default"),
+        DefaultState::Resolved(pattern) => {
+            println_with_prefix_and_fl!("default_ok", entry = pattern)
+        }
+        DefaultState::Dangling(pattern) => {
+            println_with_prefix_and_fl!("dangling_default", entry = pattern.as_str());
+
+            if let Some(kernel) = default_to_newest(installed_kernels, false)? {
+                let new = format!("{}.conf", kernel.entry_id(&config.default_profile));
+                println_with_prefix_and_fl!("repaired_default", old = pattern, new = new);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Set the newest installed kernel as default if no default entry is
+/// configured or the configured one does not exist, do nothing otherwise
+pub fn ensure_default<K: Kernel>(
+    config: &Config,
+    installed_kernels: &[K],
+    sbconf: Rc<RefCell<SystemdBootConf>>,
+) -> Result<()> {
+    // Bound first, as set_default() writes to loader.conf
+    let state = default_state(config, &sbconf.borrow())?;
+
+    match state {
+        DefaultState::Resolved(pattern) => {
+            println_with_prefix_and_fl!("default_ok", entry = pattern);
+        }
+        DefaultState::Missing | DefaultState::Dangling(_) => {
+            default_to_newest(installed_kernels, true)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Clear the default boot entry, print the entry systemd-boot will fall back to
+pub fn clear_default(config: &Config, sbconf: Rc<RefCell<SystemdBootConf>>) -> Result<()> {
+    println_with_prefix_and_fl!("clear_default");
+    let old = sbconf.borrow_mut().config.default.take();
+    sbconf.borrow().write_config()?;
+
+    if old.is_some() {
+        journal::record(
+            &config.esp_mountpoint,
+            journal::Event::DefaultChanged { old, new: None },
+        );
+    }
+
+    // Set by `bootctl set-default` or the menu, loader.conf is ignored then
+    if let Some(efivar) = read_loader_efivar("LoaderEntryDefault") {
+        println_with_prefix_and_fl!("efivar_default_set", entry = efivar);
+    }
+
+    let ids = list_entry_ids(&config.esp_mountpoint)?;
+
+    match fallback_entry(ids.iter().map(|id| id.as_str())) {
+        Some(id) => {
+            println_with_prefix_and_fl!("fallback_default", entry = format!("{}.conf", id))
+        }
+        None => println_with_prefix_and_fl!("empty_list"),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_match_entry() {
+        let ids = [
+            "5.10.0-aosc-default",
+            "5.15.0-aosc-default",
+            "5.15.0-aosc-rescue",
+        ];
+
+        assert_eq!(
+            match_entry("5.10.0-aosc-default.conf", ids).unwrap(),
+            Some("5.10.0-aosc-default")
+        );
+        assert_eq!(
+            match_entry("*-default", ids).unwrap(),
+            Some("5.15.0-aosc-default")
+        );
+        assert_eq!(
+            match_entry("5.1?.0-aosc-r*", ids).unwrap(),
+            Some("5.15.0-aosc-rescue")
+        );
+        assert_eq!(match_entry("6.*", ids).unwrap(), None);
+        assert_eq!(
+            match_entry("*-default", ["6.9.0-aosc-default", "6.10.0-aosc-default"]).unwrap(),
+            Some("6.10.0-aosc-default")
+        );
+    }
+
+    #[test]
+    fn test_fallback_entry() {
+        assert_eq!(
+            fallback_entry([
+                "6.9.0-aosc-default",
+                "6.10.0-aosc-default",
+                "6.10.0-aosc-debug"
+            ]),
+            Some("6.10.0-aosc-default")
+        );
+        assert_eq!(
+            fallback_entry([".6.11.0-aosc-default", "6.9.0-aosc-default"]),
+            Some("6.9.0-aosc-default")
+        );
+        assert_eq!(fallback_entry([]), None);
+    }
+}
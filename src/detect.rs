@@ -0,0 +1,238 @@
+use anyhow::{bail, Result};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    rc::Rc,
+};
+
+use crate::{
+    config::Config, esp_partition::mounted_partition, fl, println_with_prefix,
+    println_with_prefix_and_fl,
+};
+
+const OS_RELEASE: &str = "/etc/os-release";
+const MOUNTINFO: &str = "/proc/self/mountinfo";
+const VMLINUX_PREFIXES: &[&str] = &["vmlinuz", "vmlinux", "bzImage", "Image", "kernel"];
+const INITRD_PREFIXES: &[&str] = &["initramfs", "initrd"];
+const UCODES: &[&str] = &["intel-ucode.img", "amd-ucode.img"];
+
+/// Get the ESP path bootctl would install to
+pub fn bootctl_esp_path() -> Option<PathBuf> {
+    let output = Command::new("bootctl")
+        .arg("--print-esp-path")
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+
+    output
+        .status
+        .success()
+        .then(|| PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()))
+}
+
+/// Get the mount points of the mounted vfat partitions
+pub fn vfat_mount_points() -> Vec<PathBuf> {
+    fs::read_to_string("/proc/mounts")
+        .map(|mounts| {
+            mounts
+                .lines()
+                .filter_map(|l| {
+                    let mut parts = l.split_whitespace().skip(1);
+                    let mount = parts.next()?;
+                    (parts.next()? == "vfat").then(|| PathBuf::from(mount))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Get the name of the distribution from os-release
+fn os_release_name(content: &str) -> Option<String> {
+    let field = |key: &str| {
+        content.lines().find_map(|l| {
+            let value = l.strip_prefix(key)?.strip_prefix('=')?;
+            let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+            (!value.is_empty()).then(|| value.to_owned())
+        })
+    };
+
+    field("PRETTY_NAME").or_else(|| field("NAME"))
+}
+
+/// Get the subvolume of the root filesystem if it is btrfs
+fn root_subvolume() -> Option<String> {
+    let mountinfo = fs::read_to_string(MOUNTINFO).ok()?;
+
+    mountinfo.lines().rev().find_map(|l| {
+        let (mount, source) = l.split_once(" - ")?;
+        let mut fields = mount.split_whitespace();
+        let root = fields.nth(3)?;
+
+        if fields.next()? != "/" || source.split_whitespace().next()? != "btrfs" {
+            return None;
+        }
+
+        let subvol = root.trim_start_matches('/');
+        (!subvol.is_empty()).then(|| subvol.to_owned())
+    })
+}
+
+/// Derive the templates of the kernel images and initrds from the files
+/// named after the versions of the installed modules
+fn kernel_templates(files: &[String], versions: &[String]) -> (Option<String>, Option<String>) {
+    let find = |prefixes: &[&str]| {
+        versions.iter().find_map(|v| {
+            files
+                .iter()
+                .filter(|f| prefixes.iter().any(|p| f.starts_with(p)))
+                .find(|f| f.contains(v.as_str()))
+                .map(|f| f.replacen(v.as_str(), "{VERSION}", 1))
+        })
+    };
+
+    (find(VMLINUX_PREFIXES), find(INITRD_PREFIXES))
+}
+
+/// List the names of the entries in the directory
+fn file_names(path: &Path) -> Vec<String> {
+    let mut names = fs::read_dir(path)
+        .map(|d| {
+            d.filter_map(|e| e.ok()?.file_name().into_string().ok())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    names.sort();
+
+    names
+}
+
+/// Fill the configuration with everything that can be told about the
+/// system, without asking. The options already set are kept, except the
+/// ones left at their defaults.
+pub fn detect_all(esp_path: Option<PathBuf>) -> Result<Config> {
+    let mut config = Config::read_file()?;
+    let default = Config::default();
+
+    // ESP
+    let esp = esp_path
+        .or_else(|| {
+            (*config.esp_mountpoint != *default.esp_mountpoint)
+                .then(|| config.esp_mountpoint.to_path_buf())
+        })
+        .or_else(bootctl_esp_path)
+        .or_else(|| vfat_mount_points().into_iter().next());
+    let Some(esp) = esp else {
+        bail!(fl!("detect_no_esp"));
+    };
+    println_with_prefix_and_fl!(
+        "detected",
+        key = "ESP_MOUNTPOINT",
+        value = esp.to_string_lossy()
+    );
+    config.esp_mountpoint = Rc::new(esp);
+
+    // Distribution
+    if config.distro == default.distro {
+        if let Some(name) = fs::read_to_string(OS_RELEASE)
+            .ok()
+            .and_then(|c| os_release_name(&c))
+        {
+            println_with_prefix_and_fl!("detected", key = "DISTRO", value = name.as_str());
+            config.distro = Rc::new(name);
+        }
+    }
+
+    // Kernel images and initrds
+    let files = file_names(&config.src_path);
+    let versions = file_names(&config.modules_path);
+    let (vmlinux, initrd) = kernel_templates(&files, &versions);
+
+    for (key, template, detected) in [
+        ("VMLINUX", &mut config.vmlinux, vmlinux),
+        ("INITRD", &mut config.initrd, initrd),
+    ] {
+        if let Some(detected) = detected {
+            println_with_prefix_and_fl!("detected", key = key, value = detected.as_str());
+            *template = detected;
+        }
+    }
+
+    // Microcode, the Intel one is loaded by default
+    let ucodes = UCODES
+        .iter()
+        .filter(|u| config.src_path.join(u).exists())
+        .collect::<Vec<_>>();
+
+    if config.ucode_overrides.is_empty() && ucodes == [&"amd-ucode.img"] {
+        println_with_prefix_and_fl!("detected", key = "UCODE_OVERRIDES", value = "amd-ucode.img");
+        config.ucode_overrides = Rc::new(HashMap::from([(
+            "*".to_owned(),
+            "amd-ucode.img".to_owned(),
+        )]));
+    }
+
+    // Root partition and subvolume
+    let mut bootargs = config.bootargs.borrow_mut();
+    let bootarg = bootargs.entry("default".to_owned()).or_default();
+
+    if !bootarg.split_whitespace().any(|p| p.starts_with("root=")) {
+        let mut detected = match mounted_partition(Path::new("/")) {
+            Some(p) => match p.part_uuid {
+                Some(uuid) => format!("root=PARTUUID={} rw", uuid),
+                None => format!("root={} rw", p.device),
+            },
+            None => bail!(fl!("detect_no_root")),
+        };
+
+        if let Some(subvol) = root_subvolume() {
+            detected.push_str(&format!(" rootflags=subvol={}", subvol));
+        }
+
+        println_with_prefix_and_fl!(
+            "detected",
+            key = "BOOTARGS.default",
+            value = detected.as_str()
+        );
+        *bootarg = format!("{} {}", detected, bootarg).trim().to_owned();
+    }
+
+    drop(bootargs);
+    config.validate()?;
+
+    // The same configuration as every other subcommand runs on
+    if config.write_back()? {
+        Config::read()
+    } else {
+        config.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kernel_templates() {
+        let files = [
+            "initrd.img-6.8.0-1-amd64",
+            "System.map-6.8.0-1-amd64",
+            "vmlinuz-6.8.0-1-amd64",
+            "grub",
+        ]
+        .map(String::from);
+
+        assert_eq!(
+            kernel_templates(&files, &["6.8.0-1-amd64".to_owned()]),
+            (
+                Some("vmlinuz-{VERSION}".to_owned()),
+                Some("initrd.img-{VERSION}".to_owned())
+            )
+        );
+        assert_eq!(
+            os_release_name("NAME=\"AOSC OS\"\nPRETTY_NAME=\"AOSC OS (12.0.0)\"\n"),
+            Some("AOSC OS (12.0.0)".to_owned())
+        );
+    }
+}
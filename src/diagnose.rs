@@ -0,0 +1,113 @@
+use anyhow::{bail, Result};
+use console::style;
+use libsdbootconf::entry::Token;
+use std::{
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::{
+    config::Config, fl, kernel::load_entries, println_with_prefix, println_with_prefix_and_fl,
+};
+
+const ERROR_KEYWORDS: &[&str] = &["error", "fail", "not found", "invalid", "unable"];
+
+/// Check if a log line looks like an error message
+fn is_error_line(line: &str) -> bool {
+    let line = line.to_lowercase();
+
+    ERROR_KEYWORDS.iter().any(|k| line.contains(k))
+}
+
+/// Normalize EFI-style paths (`\EFI\foo`) to slash-separated, lowercase relative paths,
+/// as FAT is case-insensitive
+fn normalize_path(path: &str) -> String {
+    path.replace('\\', "/")
+        .trim_start_matches('/')
+        .to_lowercase()
+}
+
+/// Check if a log line mentions the given path
+fn mentions_path(line: &str, path: &Path) -> bool {
+    let path = normalize_path(&path.to_string_lossy());
+
+    !path.is_empty() && line.replace('\\', "/").to_lowercase().contains(&path)
+}
+
+/// Analyze the systemd-boot logs of the previous boot and cross-reference
+/// the error messages against the installed entries
+pub fn diagnose_boot_failure(config: &Config) -> Result<()> {
+    println_with_prefix_and_fl!("diagnose_boot_failure");
+
+    let child_output = Command::new("journalctl")
+        .args(["-b", "-1", "-t", "systemd-boot", "--no-pager", "-o", "cat"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?
+        .wait_with_output()?;
+
+    if !child_output.status.success() {
+        bail!(String::from_utf8(child_output.stderr)?);
+    }
+
+    let log = String::from_utf8_lossy(&child_output.stdout);
+    let errors = log.lines().filter(|l| is_error_line(l)).collect::<Vec<_>>();
+
+    if errors.is_empty() {
+        println_with_prefix_and_fl!("no_boot_failure");
+        return Ok(());
+    }
+
+    // Cross-referencing needs the knowledge of all entries
+    let entries = load_entries(&config.esp_mountpoint, |_| true)?;
+
+    for line in errors {
+        println!("{}", style(line).bold());
+
+        let mut referenced = false;
+
+        for entry in entries.iter() {
+            for token in entry.tokens.iter() {
+                let path = match token {
+                    Token::Linux(p) | Token::Initrd(p) | Token::Efi(p) => p,
+                    _ => continue,
+                };
+
+                if !mentions_path(line, path) {
+                    continue;
+                }
+
+                referenced = true;
+
+                let entry_file = format!("{}.conf", entry.id);
+                let path_str = path.to_string_lossy();
+                let esp_path = config
+                    .esp_mountpoint
+                    .join(path.strip_prefix("/").unwrap_or(path));
+
+                if esp_path.exists() {
+                    println!(
+                        "  {}",
+                        fl!("diagnose_file_found", entry = entry_file, path = path_str)
+                    );
+                } else {
+                    println!(
+                        "  {}",
+                        style(fl!(
+                            "diagnose_file_missing",
+                            entry = entry_file,
+                            path = path_str
+                        ))
+                        .red()
+                    );
+                }
+            }
+        }
+
+        if !referenced {
+            println!("  {}", fl!("diagnose_no_entry"));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,535 @@
+use anyhow::Result;
+use console::{style, StyledObject, Term};
+use libsdbootconf::SystemdBootConf;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::{
+    cell::RefCell,
+    fs,
+    path::Path,
+    process::Command,
+    rc::Rc,
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    config::{duplicate_profiles, Config},
+    default_entry::{default_pattern, match_entry},
+    esp_partition::EspPartitions,
+    fl,
+    kernel::{list_entry_ids, Kernel},
+    kernel_manager::KernelManager,
+    prune::unexpected_dirs,
+    reboot::running_kernel,
+    splash::{splash_dest, splash_up_to_date},
+    util::{format_utc, load_loader_conf},
+    REL_DEST_PATH,
+};
+
+const MOUNTS: &str = "/proc/mounts";
+
+/// Stable identifiers of the checks, used by the JSON output
+pub const ESP_MOUNTED: &str = "esp_mounted";
+pub const ESP_PARTITION: &str = "esp_partition";
+pub const FRIEND_INITIALIZED: &str = "friend_initialized";
+pub const LOADER_CONF_DEFAULT_RESOLVES: &str = "loader_conf_default_resolves";
+pub const RUNNING_KERNEL_HAS_ENTRY: &str = "running_kernel_has_entry";
+pub const SPLASH_INSTALLED: &str = "splash_installed";
+pub const UNEXPECTED_DIRECTORIES: &str = "unexpected_directories";
+pub const DUPLICATE_PROFILES: &str = "duplicate_profiles";
+
+/// All the checks in the order they are run
+pub const CHECK_IDS: &[&str] = &[
+    ESP_MOUNTED,
+    ESP_PARTITION,
+    FRIEND_INITIALIZED,
+    LOADER_CONF_DEFAULT_RESOLVES,
+    RUNNING_KERNEL_HAS_ENTRY,
+    SPLASH_INSTALLED,
+    UNEXPECTED_DIRECTORIES,
+    DUPLICATE_PROFILES,
+];
+
+/// Severity of a check result, the worst one decides the exit code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Not applicable to this system
+    Skipped,
+    Ok,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Severity::Skipped | Severity::Ok => 0,
+            Severity::Warning => 1,
+            Severity::Error => 2,
+        }
+    }
+
+    /// The mark printed before a result of the severity
+    pub fn mark(self) -> StyledObject<&'static str> {
+        match self {
+            Severity::Skipped => style("[SKIP]").dim(),
+            Severity::Ok => style("[ OK ]").green(),
+            Severity::Warning => style("[WARN]").yellow(),
+            Severity::Error => style("[FAIL]").red(),
+        }
+    }
+}
+
+/// The result of a health check
+#[derive(Debug, Serialize)]
+pub struct Check {
+    pub id: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    pub data: Value,
+}
+
+impl Check {
+    fn new(id: &'static str, severity: Severity, message: String, data: Value) -> Self {
+        Self {
+            id,
+            severity,
+            message,
+            data,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    status: Severity,
+    checks: &'a [Check],
+}
+
+/// Check if the path is a mount point
+fn is_mount_point(path: &Path) -> bool {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+
+    fs::read_to_string(MOUNTS).is_ok_and(|mounts| {
+        mounts
+            .lines()
+            .filter_map(|l| l.split_whitespace().nth(1))
+            .any(|m| Path::new(m) == path)
+    })
+}
+
+fn check_esp_mounted(config: &Config) -> Check {
+    let path = config.esp_mountpoint.to_string_lossy();
+    let data = json!({ "path": path });
+
+    if is_mount_point(&config.esp_mountpoint) {
+        Check::new(
+            ESP_MOUNTED,
+            Severity::Ok,
+            fl!("doctor_esp_mounted", path = path.as_ref()),
+            data,
+        )
+    } else {
+        Check::new(
+            ESP_MOUNTED,
+            Severity::Error,
+            fl!("doctor_esp_not_mounted", path = path.as_ref()),
+            data,
+        )
+    }
+}
+
+/// Skipped when the partition backing the ESP cannot be identified
+fn check_esp_partition(config: &Config) -> Check {
+    let partitions = EspPartitions::detect(&config.esp_mountpoint);
+    let data = json!(partitions);
+
+    if !partitions.is_known() {
+        return Check::new(
+            ESP_PARTITION,
+            Severity::Skipped,
+            fl!(
+                "doctor_esp_partition_unknown",
+                path = config.esp_mountpoint.to_string_lossy()
+            ),
+            data,
+        );
+    }
+
+    let warnings = partitions.warnings(&config.esp_mountpoint);
+
+    if warnings.is_empty() {
+        Check::new(
+            ESP_PARTITION,
+            Severity::Ok,
+            fl!(
+                "doctor_esp_partition_ok",
+                path = config.esp_mountpoint.to_string_lossy()
+            ),
+            data,
+        )
+    } else {
+        Check::new(ESP_PARTITION, Severity::Warning, warnings.join("\n"), data)
+    }
+}
+
+fn check_friend_initialized(config: &Config) -> Check {
+    let path = config.esp_mountpoint.join(REL_DEST_PATH);
+    let data = json!({ "path": path });
+
+    if path.is_dir() {
+        Check::new(
+            FRIEND_INITIALIZED,
+            Severity::Ok,
+            fl!("doctor_friend_initialized"),
+            data,
+        )
+    } else {
+        Check::new(
+            FRIEND_INITIALIZED,
+            Severity::Error,
+            fl!("err_path_not_exist", path = path.to_string_lossy()),
+            data,
+        )
+    }
+}
+
+fn check_loader_conf_default(config: &Config, sbconf: &SystemdBootConf) -> Check {
+    let (pattern, source) = match default_pattern(sbconf) {
+        Some(p) => p,
+        None => {
+            return Check::new(
+                LOADER_CONF_DEFAULT_RESOLVES,
+                Severity::Warning,
+                fl!("no_default"),
+                Value::Null,
+            )
+        }
+    };
+
+    let ids = list_entry_ids(&config.esp_mountpoint).unwrap_or_default();
+
+    match match_entry(&pattern, ids.iter().map(|id| id.as_str())) {
+        Ok(Some(id)) => Check::new(
+            LOADER_CONF_DEFAULT_RESOLVES,
+            Severity::Ok,
+            fl!("doctor_default_resolves", entry = format!("{}.conf", id)),
+            json!({ "pattern": pattern, "source": source, "entry": format!("{}.conf", id) }),
+        ),
+        _ => Check::new(
+            LOADER_CONF_DEFAULT_RESOLVES,
+            Severity::Error,
+            fl!("dangling_default", entry = pattern.as_str()),
+            json!({ "pattern": pattern, "source": source, "entry": null }),
+        ),
+    }
+}
+
+fn check_running_kernel<K: Kernel>(installed_kernels: &[K]) -> Check {
+    let running = match running_kernel() {
+        Ok(r) => r,
+        Err(e) => {
+            return Check::new(
+                RUNNING_KERNEL_HAS_ENTRY,
+                Severity::Warning,
+                e.to_string(),
+                Value::Null,
+            )
+        }
+    };
+    let data = json!({ "kernel": running });
+
+    if installed_kernels.iter().any(|k| k.name() == running) {
+        Check::new(
+            RUNNING_KERNEL_HAS_ENTRY,
+            Severity::Ok,
+            fl!("doctor_running_kernel_installed", kernel = running.as_str()),
+            data,
+        )
+    } else {
+        Check::new(
+            RUNNING_KERNEL_HAS_ENTRY,
+            Severity::Warning,
+            fl!("doctor_running_kernel_missing", kernel = running.as_str()),
+            data,
+        )
+    }
+}
+
+/// Only checked when a splash image is configured
+fn check_splash(config: &Config) -> Check {
+    let (Some(up_to_date), Some(dest)) = (splash_up_to_date(config), splash_dest(config)) else {
+        return Check::new(
+            SPLASH_INSTALLED,
+            Severity::Skipped,
+            fl!("doctor_splash_not_configured"),
+            Value::Null,
+        );
+    };
+    let path = config.esp_mountpoint.join(dest);
+    let data = json!({ "path": path });
+
+    if up_to_date {
+        Check::new(
+            SPLASH_INSTALLED,
+            Severity::Ok,
+            fl!("doctor_splash_installed", path = path.to_string_lossy()),
+            data,
+        )
+    } else {
+        Check::new(
+            SPLASH_INSTALLED,
+            Severity::Warning,
+            fl!("doctor_splash_outdated", path = path.to_string_lossy()),
+            data,
+        )
+    }
+}
+
+fn check_unexpected_dirs(config: &Config) -> Check {
+    let dirs = unexpected_dirs(&config.esp_mountpoint).unwrap_or_default();
+    let data = json!({ "paths": dirs });
+
+    if dirs.is_empty() {
+        Check::new(
+            UNEXPECTED_DIRECTORIES,
+            Severity::Ok,
+            fl!("doctor_no_unexpected_dirs"),
+            data,
+        )
+    } else {
+        Check::new(
+            UNEXPECTED_DIRECTORIES,
+            Severity::Warning,
+            fl!(
+                "doctor_unexpected_dirs",
+                paths = dirs
+                    .iter()
+                    .map(|d| d.to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            data,
+        )
+    }
+}
+
+fn check_duplicate_profiles(config: &Config) -> Check {
+    let groups = duplicate_profiles(&config.bootargs.borrow());
+    let data = json!({ "groups": groups });
+
+    if groups.is_empty() || config.allow_duplicate_profiles {
+        Check::new(
+            DUPLICATE_PROFILES,
+            Severity::Ok,
+            fl!("doctor_no_duplicate_profiles"),
+            data,
+        )
+    } else {
+        Check::new(
+            DUPLICATE_PROFILES,
+            Severity::Warning,
+            fl!(
+                "duplicate_profiles",
+                profiles = groups
+                    .iter()
+                    .map(|g| g.join(", "))
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            ),
+            data,
+        )
+    }
+}
+
+/// Run all the health checks
+pub fn run_checks<K: Kernel>(
+    config: &Config,
+    sbconf: Rc<RefCell<SystemdBootConf>>,
+    installed_kernels: &[K],
+) -> Vec<Check> {
+    vec![
+        check_esp_mounted(config),
+        check_esp_partition(config),
+        check_friend_initialized(config),
+        check_loader_conf_default(config, &sbconf.borrow()),
+        check_running_kernel(installed_kernels),
+        check_splash(config),
+        check_unexpected_dirs(config),
+        check_duplicate_profiles(config),
+    ]
+}
+
+/// Get the usage of the filesystem in percent
+fn disk_usage(path: &Path) -> Option<u8> {
+    let output = Command::new("df")
+        .arg("--output=pcent")
+        .arg(path)
+        .output()
+        .ok()?;
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .last()?
+        .trim()
+        .trim_end_matches('%')
+        .parse()
+        .ok()
+}
+
+/// Print a one-line summary for MOTD and login scripts
+pub fn oneline<K: Kernel>(
+    config: &Config,
+    kernel_manager: &KernelManager<K>,
+    installed_kernels: &[K],
+) -> Result<()> {
+    let mut default = None;
+
+    for k in installed_kernels.iter() {
+        if k.is_default()? {
+            default = Some(k.to_string());
+            break;
+        }
+    }
+
+    let state = if kernel_manager.update_pending(config)? {
+        fl!("oneline_update_pending")
+    } else {
+        fl!("oneline_up_to_date")
+    };
+
+    println!(
+        "{}",
+        fl!(
+            "status_oneline",
+            default = default.unwrap_or_else(|| fl!("oneline_no_default")),
+            count = installed_kernels.len(),
+            usage = disk_usage(&config.esp_mountpoint)
+                .map_or_else(|| "?".to_owned(), |u| u.to_string()),
+            state = state
+        )
+    );
+
+    Ok(())
+}
+
+/// Print the results of the health checks, return the exit code
+pub fn doctor<K: Kernel>(
+    config: &Config,
+    sbconf: Rc<RefCell<SystemdBootConf>>,
+    installed_kernels: &[K],
+    json: bool,
+) -> Result<i32> {
+    let checks = run_checks(config, sbconf, installed_kernels);
+    let status = checks
+        .iter()
+        .map(|c| c.severity)
+        .max()
+        .unwrap_or(Severity::Ok);
+
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string(&Report {
+                status,
+                checks: &checks
+            })?
+        );
+    } else {
+        for check in checks.iter() {
+            println!("{} {}", check.severity.mark(), check.message);
+        }
+    }
+
+    Ok(status.exit_code())
+}
+
+/// Clear the terminal and run the health checks again every `interval`,
+/// until interrupted
+pub fn watch<K: Kernel>(
+    config: &Config,
+    sbconf: Rc<RefCell<SystemdBootConf>>,
+    interval: Duration,
+    json: bool,
+) -> Result<()> {
+    let loader_conf_path = config.esp_mountpoint.join("loader/loader.conf");
+    let term = Term::stdout();
+
+    loop {
+        // Pick up the changes made by the kernel installation in progress
+        sbconf.borrow_mut().config = load_loader_conf(&loader_conf_path).unwrap_or_default();
+        K::invalidate_cache();
+        let installed_kernels = K::list_installed(config, sbconf.clone())?;
+
+        term.clear_screen()?;
+        doctor(config, sbconf.clone(), &installed_kernels, json)?;
+        println!();
+        println!(
+            "{}",
+            fl!("last_updated", time = format_utc(SystemTime::now()))
+        );
+
+        thread::sleep(interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::kernel::generic_kernel::GenericKernel;
+
+    #[test]
+    fn test_check_ids() {
+        let esp = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.esp_mountpoint = Rc::new(esp.path().to_owned());
+        let sbconf = Rc::new(RefCell::new(SystemdBootConf::new(
+            esp.path().join("loader/"),
+            libsdbootconf::Config::default(),
+            Vec::new(),
+        )));
+        let checks = run_checks::<GenericKernel>(&config, sbconf, &[]);
+
+        // These are part of the JSON output, renaming them breaks the consumers
+        assert_eq!(
+            checks.iter().map(|c| c.id).collect::<Vec<_>>(),
+            [
+                "esp_mounted",
+                "esp_partition",
+                "friend_initialized",
+                "loader_conf_default_resolves",
+                "running_kernel_has_entry",
+                "splash_installed",
+                "unexpected_directories",
+                "duplicate_profiles",
+            ]
+        );
+        assert_eq!(checks.iter().map(|c| c.id).collect::<Vec<_>>(), CHECK_IDS);
+    }
+
+    #[test]
+    fn test_severity_exit_code() {
+        assert_eq!(Severity::Skipped.exit_code(), 0);
+        assert_eq!(Severity::Ok.exit_code(), 0);
+        assert_eq!(Severity::Warning.exit_code(), 1);
+        assert_eq!(Severity::Error.exit_code(), 2);
+        assert_eq!(
+            [
+                Severity::Warning,
+                Severity::Error,
+                Severity::Ok,
+                Severity::Skipped
+            ]
+            .into_iter()
+            .max(),
+            Some(Severity::Error)
+        );
+        assert_eq!(
+            [Severity::Skipped, Severity::Ok].into_iter().max(),
+            Some(Severity::Ok)
+        );
+    }
+}
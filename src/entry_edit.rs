@@ -0,0 +1,116 @@
+use anyhow::{anyhow, bail, Result};
+use std::{env, fs, path::Path, process::Command};
+
+use crate::{
+    config::Config,
+    doctor::Severity,
+    fl,
+    kernel::{find_kernel, KernelOps, REL_ENTRY_PATH, TMP_ENTRY_EXTENSION},
+    manifest::Manifest,
+    println_with_prefix, println_with_prefix_and_fl,
+    prompt::{ColorfulTheme, Confirm},
+    validate_entry::entry_issues,
+};
+
+/// Open the file in $VISUAL or $EDITOR, vi by default
+fn run_editor(path: &Path) -> Result<()> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_owned());
+    let mut args = editor.split_whitespace();
+    let program = args.next().unwrap_or("vi");
+
+    let status = Command::new(program).args(args).arg(path).status()?;
+
+    if !status.success() {
+        bail!(fl!(
+            "editor_failed",
+            editor = editor,
+            status = status.to_string()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Edit an entry of an installed kernel in place, it is marked as locally
+/// modified so that update asks before overwriting it. With `revert`, the
+/// entry is generated again from the configuration.
+pub fn edit_entry<K: KernelOps>(
+    config: &Config,
+    installed_kernels: &[K],
+    name: &str,
+    profile: &str,
+    revert: bool,
+) -> Result<()> {
+    let kernel = find_kernel(installed_kernels, name)
+        .ok_or_else(|| anyhow!(fl!("kernel_not_installed", kernel = name)))?;
+    let id = kernel.entry_id(profile);
+    let entries_path = config.esp_mountpoint.join(REL_ENTRY_PATH);
+    let path = entries_path.join(format!("{}.conf", id));
+    let mut manifest = Manifest::read(&config.esp_mountpoint)?;
+
+    if revert {
+        manifest.modified_entries.retain(|e| *e != id);
+        manifest.write(&config.esp_mountpoint)?;
+        kernel.make_config(true)?;
+
+        return Ok(());
+    }
+
+    let original = fs::read_to_string(&path).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+
+    // Edit a copy, so that the entry is only replaced by a valid one
+    let tmp_path = entries_path.join(format!("{}.{}", id, TMP_ENTRY_EXTENSION));
+    fs::write(&tmp_path, &original)?;
+
+    loop {
+        if let Err(e) = run_editor(&tmp_path) {
+            fs::remove_file(&tmp_path).ok();
+            return Err(e);
+        }
+
+        if fs::read_to_string(&tmp_path)? == original {
+            fs::remove_file(&tmp_path)?;
+            println_with_prefix_and_fl!("entry_unchanged", entry = path.to_string_lossy());
+
+            return Ok(());
+        }
+
+        let issues = entry_issues(config, &tmp_path);
+
+        for (severity, message) in issues.iter() {
+            println!("{} {}", severity.mark(), message);
+        }
+
+        if issues.iter().all(|(s, _)| *s < Severity::Error) {
+            break;
+        }
+
+        // Nobody can fix the entry without a terminal
+        let again = console::user_attended()
+            && Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(fl!("ask_edit_again"))
+                .default(true)
+                .interact()
+                .inspect_err(|_| {
+                    fs::remove_file(&tmp_path).ok();
+                })?;
+
+        if !again {
+            fs::remove_file(&tmp_path)?;
+            bail!(fl!("entry_edit_discarded", entry = path.to_string_lossy()));
+        }
+    }
+
+    fs::rename(&tmp_path, &path)?;
+
+    if !manifest.modified_entries.contains(&id) {
+        manifest.modified_entries.push(id);
+        manifest.write(&config.esp_mountpoint)?;
+    }
+
+    println_with_prefix_and_fl!("entry_edited", entry = path.to_string_lossy());
+
+    Ok(())
+}
@@ -0,0 +1,217 @@
+use anyhow::{anyhow, Result};
+use console::style;
+use libsdbootconf::{
+    entry::{Entry, Token},
+    SystemdBootConf,
+};
+use serde::Serialize;
+use std::{cell::RefCell, path::Path, rc::Rc};
+
+use crate::{
+    config::Config,
+    default_entry::{default_pattern, match_entry, read_loader_efivar},
+    fl,
+    kernel::{
+        find_kernel, generic_kernel::GenericKernel, list_entry_ids, read_entries,
+        HIDDEN_ENTRY_PREFIX, REL_ENTRY_PATH,
+    },
+    println_with_fl,
+    util::{wrap_entry_line, wrap_options, wrap_width},
+    REL_DEST_PATH,
+};
+
+/// Information of a boot entry, managed by friend or not
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct EntryInfo {
+    pub entry: String,
+    pub title: Option<String>,
+    pub target: Option<String>,
+    pub target_exists: Option<bool>,
+    pub options: Option<String>,
+    pub managed: bool,
+    pub hidden: bool,
+    pub default: bool,
+    pub last_booted: bool,
+    pub error: Option<String>,
+}
+
+impl EntryInfo {
+    /// Collect the information from a parsed entry
+    fn fill(&mut self, entry: &Entry, esp_mountpoint: &Path) {
+        for token in entry.tokens.iter() {
+            match token {
+                Token::Title(t) => self.title = Some(t.to_owned()),
+                Token::Options(o) => self.options = Some(o.to_owned()),
+                Token::Linux(p) | Token::Efi(p) => {
+                    let rel_path = p.strip_prefix("/").unwrap_or(p);
+
+                    self.target = Some(p.to_string_lossy().into_owned());
+                    self.target_exists = Some(esp_mountpoint.join(rel_path).exists());
+                    self.managed = rel_path.starts_with(REL_DEST_PATH);
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+/// Collect the information of all the boot entries, sorted by the ids
+pub(crate) fn collect_entries(
+    config: &Config,
+    sbconf: &Rc<RefCell<SystemdBootConf>>,
+) -> Result<Vec<EntryInfo>> {
+    let mut ids = list_entry_ids(&config.esp_mountpoint)?;
+    ids.sort();
+
+    let default = match default_pattern(&sbconf.borrow()) {
+        Some((pattern, _)) => match_entry(&pattern, ids.iter().map(|id| id.as_str()))?,
+        None => None,
+    };
+    let last_booted = read_loader_efivar("LoaderEntrySelected");
+    let entries_path = config.esp_mountpoint.join(REL_ENTRY_PATH);
+
+    let infos = ids
+        .iter()
+        .map(|id| {
+            let mut info = EntryInfo {
+                entry: format!("{}.conf", id),
+                hidden: id.starts_with(HIDDEN_ENTRY_PREFIX),
+                default: default == Some(id.as_str()),
+                last_booted: last_booted
+                    .as_deref()
+                    .map(|e| e.strip_suffix(".conf").unwrap_or(e))
+                    == Some(id.as_str()),
+                ..Default::default()
+            };
+
+            // Broken entries are listed with the error instead of being omitted
+            match Entry::load(entries_path.join(&info.entry)) {
+                Ok(entry) => info.fill(&entry, &config.esp_mountpoint),
+                Err(e) => info.error = Some(e.to_string()),
+            }
+
+            info
+        })
+        .collect();
+
+    Ok(infos)
+}
+
+/// Print all the boot entries, including those not managed by friend
+pub fn list_entries(
+    config: &Config,
+    sbconf: Rc<RefCell<SystemdBootConf>>,
+    json: bool,
+) -> Result<()> {
+    let infos = collect_entries(config, &sbconf)?;
+
+    if json {
+        println!("{}", serde_json::to_string(&infos)?);
+        return Ok(());
+    }
+
+    for info in infos.iter() {
+        if info.error.is_some() {
+            print!("{} ", style("[!]").red());
+        } else if info.default {
+            print!("{} ", style("[*]").green());
+        } else {
+            print!("[ ] ");
+        }
+
+        let mut tags = vec![if info.managed {
+            fl!("entry_friend")
+        } else {
+            fl!("entry_foreign")
+        }];
+
+        if info.hidden {
+            tags.push(fl!("entry_hidden"));
+        }
+
+        if info.last_booted {
+            tags.push(fl!("entry_last_booted"));
+        }
+
+        println!("{} ({})", info.entry, tags.join(", "));
+
+        if let Some(title) = &info.title {
+            println!("    title: {}", title);
+        }
+
+        if let Some(target) = &info.target {
+            if info.target_exists == Some(false) {
+                println!(
+                    "    target: {} ({})",
+                    target,
+                    style(fl!("entry_missing")).red()
+                );
+            } else {
+                println!("    target: {}", target);
+            }
+        }
+
+        if let Some(options) = &info.options {
+            for line in wrap_options("    options: ", options, wrap_width()) {
+                println!("{}", line);
+            }
+        }
+
+        if let Some(error) = &info.error {
+            println!("    error: {}", style(error).red());
+        }
+    }
+
+    if !infos.is_empty() {
+        println!();
+        println_with_fl!("note_list_entries");
+    }
+
+    Ok(())
+}
+
+/// Print the entry files of an installed kernel, along with the microcode
+/// image they load and why
+pub fn print_entry(config: &Config, installed_kernels: &[GenericKernel], name: &str) -> Result<()> {
+    let kernel = find_kernel(installed_kernels, name)
+        .ok_or_else(|| anyhow!(fl!("kernel_not_installed", kernel = name)))?;
+
+    for (file, content) in read_entries(config, kernel)?.into_values() {
+        println!("{}", style(format!("# {}", file)).bold());
+        for line in content.trim_end().lines() {
+            for wrapped in wrap_entry_line(line, wrap_width()) {
+                println!("{}", wrapped);
+            }
+        }
+        println!();
+    }
+
+    match kernel.ucode() {
+        Some((ucode, pattern)) => {
+            let reason = match pattern {
+                Some(pattern) => fl!("ucode_reason_override", pattern = pattern),
+                None => fl!("ucode_reason_default"),
+            };
+
+            if config
+                .esp_mountpoint
+                .join(REL_DEST_PATH)
+                .join(ucode)
+                .exists()
+            {
+                println!(
+                    "{}",
+                    fl!("print_entry_ucode", ucode = ucode, reason = reason)
+                );
+            } else {
+                println!(
+                    "{}",
+                    fl!("print_entry_ucode_absent", ucode = ucode, reason = reason)
+                );
+            }
+        }
+        None => println!("{}", fl!("print_entry_no_ucode")),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,27 @@
+use std::fmt;
+
+use crate::fl;
+
+/// Errors callers may want to handle, e.g. by downcasting the `anyhow::Error`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SbfError {
+    /// The time limit of update expired before all kernels were installed
+    Timeout { completed: usize, remaining: usize },
+}
+
+impl fmt::Display for SbfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SbfError::Timeout {
+                completed,
+                remaining,
+            } => write!(
+                f,
+                "{}",
+                fl!("err_timeout", completed = completed, remaining = remaining)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SbfError {}
@@ -0,0 +1,251 @@
+use serde::Serialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{default_entry::read_loader_efivar, fl};
+
+const MOUNTINFO: &str = "/proc/self/mountinfo";
+const UDEV_DATA_PATH: &str = "/run/udev/data/";
+const BY_PARTUUID_PATH: &str = "/dev/disk/by-partuuid/";
+const EFIVARS_PATH: &str = "/sys/firmware/efi/efivars/";
+const EFI_GLOBAL_GUID: &str = "8be4df61-93ca-11d2-aa0d-00e098032b8c";
+/// GPT partition type of EFI system partitions
+pub const ESP_TYPE_GUID: &str = "c12a7328-f81f-11d2-ba4b-00a0c93ec93b";
+
+/// A partition backing a mount point, the GPT details are only known if
+/// udev recorded them
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Partition {
+    pub device: String,
+    pub part_uuid: Option<String>,
+    pub type_guid: Option<String>,
+}
+
+/// The partition mounted at the ESP mount point and the one the firmware
+/// booted from, as far as they can be told
+#[derive(Debug, Serialize)]
+pub struct EspPartitions {
+    pub esp: Option<Partition>,
+    pub booted_part_uuid: Option<String>,
+    pub booted_device: Option<PathBuf>,
+}
+
+impl EspPartitions {
+    pub fn detect(esp_mountpoint: &Path) -> Self {
+        let booted_part_uuid = booted_part_uuid();
+        let booted_device = booted_part_uuid
+            .as_ref()
+            .and_then(|u| fs::canonicalize(Path::new(BY_PARTUUID_PATH).join(u)).ok());
+
+        Self {
+            esp: mounted_partition(esp_mountpoint),
+            booted_part_uuid,
+            booted_device,
+        }
+    }
+
+    /// Check if anything could be told about the partitions at all
+    pub fn is_known(&self) -> bool {
+        self.esp
+            .as_ref()
+            .is_some_and(|p| p.type_guid.is_some() || p.part_uuid.is_some())
+    }
+
+    /// Describe the signs that the ESP mount point is not the partition
+    /// the firmware boots
+    pub fn warnings(&self, esp_mountpoint: &Path) -> Vec<String> {
+        let Some(esp) = &self.esp else {
+            return Vec::new();
+        };
+        let path = esp_mountpoint.to_string_lossy();
+        let mut warnings = Vec::new();
+
+        if let Some(type_guid) = esp.type_guid.as_deref() {
+            if type_guid != ESP_TYPE_GUID {
+                warnings.push(fl!(
+                    "esp_wrong_type",
+                    path = path.as_ref(),
+                    device = esp.device.as_str(),
+                    type_guid = type_guid
+                ));
+            }
+        }
+
+        if let (Some(esp_uuid), Some(booted_uuid)) = (&esp.part_uuid, &self.booted_part_uuid) {
+            if esp_uuid != booted_uuid {
+                let booted = match &self.booted_device {
+                    Some(d) => format!("{} ({})", d.display(), booted_uuid),
+                    None => booted_uuid.to_owned(),
+                };
+
+                warnings.push(fl!(
+                    "esp_not_booted",
+                    path = path.as_ref(),
+                    device = format!("{} ({})", esp.device, esp_uuid),
+                    booted = booted
+                ));
+            }
+        }
+
+        warnings
+    }
+}
+
+/// Find the partition mounted at the path in mountinfo, along with its GPT
+/// details from the udev database
+pub(crate) fn mounted_partition(path: &Path) -> Option<Partition> {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+    let mountinfo = fs::read_to_string(MOUNTINFO).ok()?;
+
+    // The last mount on the path hides the earlier ones
+    let (dev, device) = mountinfo.lines().rev().find_map(|l| {
+        let (mount, source) = l.split_once(" - ")?;
+        let mut fields = mount.split_whitespace();
+        let dev = fields.nth(2)?;
+        let mount_point = fields.nth(1)?.replace("\\040", " ");
+
+        if Path::new(&mount_point) != path {
+            return None;
+        }
+
+        Some((dev.to_owned(), source.split_whitespace().nth(1)?.to_owned()))
+    })?;
+
+    let udev = fs::read_to_string(format!("{}b{}", UDEV_DATA_PATH, dev)).unwrap_or_default();
+    let property = |key: &str| {
+        udev.lines()
+            .find_map(|l| l.strip_prefix("E:")?.strip_prefix(key)?.strip_prefix('='))
+            .map(|v| v.to_lowercase())
+    };
+
+    Some(Partition {
+        part_uuid: property("ID_PART_ENTRY_UUID").or_else(|| part_uuid_by_link(&device)),
+        type_guid: property("ID_PART_ENTRY_TYPE"),
+        device,
+    })
+}
+
+/// Find the PARTUUID of the device among the udev symlinks
+fn part_uuid_by_link(device: &str) -> Option<String> {
+    let device = fs::canonicalize(device).ok()?;
+
+    fs::read_dir(BY_PARTUUID_PATH)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .find(|e| fs::canonicalize(e.path()).is_ok_and(|p| p == device))
+        .map(|e| e.file_name().to_string_lossy().to_lowercase())
+}
+
+/// The PARTUUID of the partition the firmware booted from, according to its
+/// current Boot#### entry, or to systemd-boot itself
+fn booted_part_uuid() -> Option<String> {
+    boot_current_part_uuid()
+        .or_else(|| read_loader_efivar("LoaderDevicePartUUID").map(|u| u.to_lowercase()))
+}
+
+fn read_global_efivar(name: &str) -> Option<Vec<u8>> {
+    let data = fs::read(format!("{}{}-{}", EFIVARS_PATH, name, EFI_GLOBAL_GUID)).ok()?;
+
+    // The first 4 bytes are the variable attributes
+    data.get(4..).map(|d| d.to_vec())
+}
+
+fn boot_current_part_uuid() -> Option<String> {
+    let current = read_global_efivar("BootCurrent")?;
+    let current = u16::from_le_bytes([*current.first()?, *current.get(1)?]);
+
+    load_option_part_uuid(&read_global_efivar(&format!("Boot{:04X}", current))?)
+}
+
+/// Find the GPT partition signature among the device paths of an
+/// EFI_LOAD_OPTION
+fn load_option_part_uuid(option: &[u8]) -> Option<String> {
+    let path_list_len = u16::from_le_bytes([*option.get(4)?, *option.get(5)?]) as usize;
+
+    // Skip the attributes, the length and the NUL-terminated UTF-16
+    // description
+    let mut start = 6;
+    while option.get(start..start + 2)? != [0, 0] {
+        start += 2;
+    }
+    start += 2;
+
+    let mut paths = option.get(start..start + path_list_len)?;
+    while paths.len() >= 4 {
+        let (kind, subtype) = (paths[0], paths[1]);
+        let len = u16::from_le_bytes([paths[2], paths[3]]) as usize;
+
+        if len < 4 || len > paths.len() {
+            return None;
+        }
+
+        // A hard drive media path with a GPT partition signature
+        if kind == 0x04 && subtype == 0x01 && len >= 42 && paths[41] == 0x02 {
+            return Some(format_guid(&paths[24..40]));
+        }
+
+        // The end of the device path
+        if kind == 0x7f && subtype == 0xff {
+            break;
+        }
+
+        paths = &paths[len..];
+    }
+
+    None
+}
+
+/// Format a GUID stored in the mixed-endian EFI layout
+fn format_guid(b: &[u8]) -> String {
+    format!(
+        "{:08x}-{:04x}-{:04x}-{}-{}",
+        u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        u16::from_le_bytes([b[4], b[5]]),
+        u16::from_le_bytes([b[6], b[7]]),
+        b[8..10]
+            .iter()
+            .map(|x| format!("{:02x}", x))
+            .collect::<String>(),
+        b[10..16]
+            .iter()
+            .map(|x| format!("{:02x}", x))
+            .collect::<String>()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_option_part_uuid() {
+        let mut node = vec![0x04, 0x01, 42, 0];
+        node.extend(1u32.to_le_bytes());
+        node.extend(2048u64.to_le_bytes());
+        node.extend(1048576u64.to_le_bytes());
+        // c12a7328-f81f-11d2-ba4b-00a0c93ec93b in the EFI layout
+        node.extend([
+            0x28, 0x73, 0x2a, 0xc1, 0x1f, 0xf8, 0xd2, 0x11, 0xba, 0x4b, 0x00, 0xa0, 0xc9, 0x3e,
+            0xc9, 0x3b,
+        ]);
+        node.extend([0x02, 0x02]);
+        node.extend([0x7f, 0xff, 4, 0]);
+
+        let mut option = 1u32.to_le_bytes().to_vec();
+        option.extend((node.len() as u16).to_le_bytes());
+        option.extend(
+            "Linux Boot Manager\0"
+                .encode_utf16()
+                .flat_map(|c| c.to_le_bytes()),
+        );
+        option.extend(node);
+
+        assert_eq!(
+            load_option_part_uuid(&option).as_deref(),
+            Some(ESP_TYPE_GUID)
+        );
+        assert_eq!(load_option_part_uuid(&option[..20]), None);
+    }
+}
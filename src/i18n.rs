@@ -1,34 +1,60 @@
 // From AOSC-Dev/atm
 
+#[cfg(feature = "i18n")]
 use anyhow::Result;
+#[cfg(feature = "i18n")]
 use i18n_embed::{
     fluent::{fluent_language_loader, FluentLanguageLoader},
     DesktopLanguageRequester, LanguageLoader,
 };
 use lazy_static::lazy_static;
+#[cfg(feature = "i18n")]
 use rust_embed::RustEmbed;
+#[cfg(not(feature = "i18n"))]
+use std::collections::HashMap;
+#[cfg(feature = "i18n")]
 use unic_langid::LanguageIdentifier;
 
+#[cfg(feature = "i18n")]
 #[macro_export]
 macro_rules! fl {
     ($message_id:literal) => {{
         i18n_embed_fl::fl!($crate::I18N_LOADER, $message_id)
     }};
 
-    ($message_id:literal, $($args:expr),*) => {{
-        i18n_embed_fl::fl!($crate::I18N_LOADER, $message_id, $($args), *)
+    ($message_id:literal, $($key:ident = $value:expr),*) => {{
+        i18n_embed_fl::fl!($crate::I18N_LOADER, $message_id, $($key = $value), *)
     }};
 }
 
+/// The English messages, formatted without the fluent machinery
+#[cfg(not(feature = "i18n"))]
+#[macro_export]
+macro_rules! fl {
+    ($message_id:literal) => {{
+        $crate::i18n::message($message_id, &[])
+    }};
+
+    ($message_id:literal, $($key:ident = $value:expr),*) => {{
+        $crate::i18n::message(
+            $message_id,
+            &[$((stringify!($key), $value.to_string())), *],
+        )
+    }};
+}
+
+#[cfg(feature = "i18n")]
 lazy_static! {
     pub static ref I18N_LOADER: FluentLanguageLoader =
         load_i18n().expect("Unable to load i18n strings.");
 }
 
+#[cfg(feature = "i18n")]
 #[derive(RustEmbed)]
 #[folder = "i18n"]
 struct Localizations;
 
+#[cfg(feature = "i18n")]
 fn load_i18n() -> Result<FluentLanguageLoader> {
     let language_loader: FluentLanguageLoader = fluent_language_loader!();
     let requested_languages = DesktopLanguageRequester::requested_languages();
@@ -41,3 +67,147 @@ fn load_i18n() -> Result<FluentLanguageLoader> {
 
     Ok(language_loader)
 }
+
+#[cfg(not(feature = "i18n"))]
+const MESSAGES_EN: &str = include_str!("../i18n/en-US/systemd_boot_friend_rs.ftl");
+
+#[cfg(not(feature = "i18n"))]
+lazy_static! {
+    static ref MESSAGES: HashMap<&'static str, String> = parse_messages(MESSAGES_EN);
+}
+
+/// Read the messages of the catalog, the lines of a multiline message are
+/// joined without their indentation
+#[cfg(not(feature = "i18n"))]
+fn parse_messages(catalog: &str) -> HashMap<&str, String> {
+    let mut messages = HashMap::new();
+    let mut current: Option<(&str, String)> = None;
+
+    for line in catalog.lines() {
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+
+        if !line.starts_with(char::is_whitespace) && !line.starts_with('}') {
+            if let Some((id, value)) = line.split_once('=') {
+                messages.extend(current.take());
+                current = Some((id.trim(), value.trim().to_owned()));
+                continue;
+            }
+        }
+
+        if let Some((_, value)) = current.as_mut() {
+            if !value.is_empty() {
+                value.push('\n');
+            }
+            value.push_str(line.trim());
+        }
+    }
+
+    messages.extend(current);
+
+    messages
+}
+
+/// Expand the placeables of a message: variables and the selectors on them
+#[cfg(not(feature = "i18n"))]
+fn format_message(message: &str, args: &[(&str, String)]) -> String {
+    let arg = |name: &str| {
+        let name = name.trim().trim_start_matches('$');
+        args.iter()
+            .find(|(k, _)| *k == name)
+            .map_or_else(|| format!("{{${}}}", name), |(_, v)| v.to_owned())
+    };
+    let mut formatted = String::new();
+    let mut rest = message;
+
+    while let Some(start) = rest.find('{') {
+        // Find the matching brace, the variants may contain placeables
+        let mut depth = 0;
+        let Some(end) = rest[start..].char_indices().find_map(|(i, c)| {
+            match c {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                _ => (),
+            }
+            (depth == 0).then_some(start + i)
+        }) else {
+            break;
+        };
+        formatted.push_str(&rest[..start]);
+        let placeable = rest[start + 1..end].trim();
+
+        match placeable.split_once("->") {
+            Some((selector, variants)) => {
+                let value = arg(selector);
+                let mut default = "";
+                let mut chosen = None;
+
+                for variant in variants.lines().map(str::trim) {
+                    let is_default = variant.starts_with('*');
+                    let Some((key, text)) = variant
+                        .trim_start_matches('*')
+                        .strip_prefix('[')
+                        .and_then(|v| v.split_once(']'))
+                    else {
+                        continue;
+                    };
+
+                    if is_default {
+                        default = text.trim();
+                    }
+                    if key.trim() == value {
+                        chosen = Some(text.trim());
+                    }
+                }
+
+                formatted.push_str(&format_message(chosen.unwrap_or(default), args));
+            }
+            None => match placeable.strip_prefix('"') {
+                Some(literal) => formatted.push_str(literal.trim_end_matches('"')),
+                None => formatted.push_str(&arg(placeable)),
+            },
+        }
+
+        rest = &rest[end + 1..];
+    }
+
+    formatted.push_str(rest);
+
+    formatted
+}
+
+/// Look up and format an English message, as `fl!` does without i18n
+#[cfg(not(feature = "i18n"))]
+pub fn message(id: &str, args: &[(&str, String)]) -> String {
+    match MESSAGES.get(id) {
+        Some(m) => format_message(m, args),
+        None => id.to_owned(),
+    }
+}
+
+#[cfg(all(test, not(feature = "i18n")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_message() {
+        let messages = parse_messages(
+            "# comment\nplain = Hello { $name }\nblock =\n    first\n    second\nyes = Default: { $value ->\n    [true] yes\n   *[false] no\n}\n",
+        );
+
+        assert_eq!(
+            format_message(&messages["plain"], &[("name", "ESP".to_owned())]),
+            "Hello ESP"
+        );
+        assert_eq!(messages["block"], "first\nsecond");
+        assert_eq!(
+            format_message(&messages["yes"], &[("value", "true".to_owned())]),
+            "Default: yes"
+        );
+        assert_eq!(
+            format_message(&messages["yes"], &[("value", "maybe".to_owned())]),
+            "Default: no"
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::Write,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{fl, println_with_prefix, println_with_prefix_and_fl, util::format_utc, REL_DEST_PATH};
+
+pub(crate) const JOURNAL: &str = "journal.jsonl";
+
+/// Changes made to the ESP by friend
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    Install {
+        kernel: String,
+    },
+    Remove {
+        kernel: String,
+    },
+    /// The entries of a kernel were written as a set
+    EntriesWritten {
+        kernel: String,
+        entries: Vec<String>,
+    },
+    /// The default entry in loader.conf changed
+    DefaultChanged {
+        old: Option<String>,
+        new: Option<String>,
+    },
+}
+
+impl Event {
+    fn describe(&self) -> String {
+        match self {
+            Event::Install { kernel } => fl!("history_install", kernel = kernel.as_str()),
+            Event::Remove { kernel } => fl!("history_remove", kernel = kernel.as_str()),
+            Event::EntriesWritten { kernel, entries } => fl!(
+                "history_entries",
+                kernel = kernel.as_str(),
+                entries = entries.join(", ")
+            ),
+            Event::DefaultChanged { old, new } => fl!(
+                "history_default",
+                old = old.as_deref().unwrap_or("-"),
+                new = new.as_deref().unwrap_or("-")
+            ),
+        }
+    }
+}
+
+/// An event with the time it happened, in seconds since the epoch
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Record {
+    pub time: u64,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+/// Append an event to the journal in the ESP. The journal is only a record
+/// of the changes, a failure is reported and the change goes on.
+pub fn record(esp_mountpoint: &Path, event: Event) {
+    if let Err(e) = append(esp_mountpoint, event) {
+        println_with_prefix_and_fl!("journal_failed", error = e.to_string());
+    }
+}
+
+fn append(esp_mountpoint: &Path, event: Event) -> Result<()> {
+    let record = Record {
+        time: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs()),
+        event,
+    };
+    let mut file = fs::File::options()
+        .create(true)
+        .append(true)
+        .open(esp_mountpoint.join(REL_DEST_PATH).join(JOURNAL))?;
+
+    writeln!(file, "{}", serde_json::to_string(&record)?)?;
+
+    Ok(())
+}
+
+/// Read the journal, skipping the lines which can not be parsed
+pub fn read(esp_mountpoint: &Path) -> Result<Vec<Record>> {
+    let content = match fs::read_to_string(esp_mountpoint.join(REL_DEST_PATH).join(JOURNAL)) {
+        Ok(c) => c,
+        Err(_) => return Ok(Vec::new()),
+    };
+
+    Ok(content
+        .lines()
+        .filter_map(|l| serde_json::from_str(l).ok())
+        .collect())
+}
+
+/// Print the journal, oldest first
+pub fn show_history(esp_mountpoint: &Path, default_changes_only: bool) -> Result<()> {
+    let records = read(esp_mountpoint)?
+        .into_iter()
+        .filter(|r| !default_changes_only || matches!(r.event, Event::DefaultChanged { .. }))
+        .collect::<Vec<_>>();
+
+    if records.is_empty() {
+        println!("{}", fl!("history_empty"));
+    }
+
+    for r in records {
+        println!(
+            "{}  {}",
+            format_utc(UNIX_EPOCH + Duration::from_secs(r.time)),
+            r.event.describe()
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_format() {
+        let record = Record {
+            time: 1700000000,
+            event: Event::DefaultChanged {
+                old: Some("6.8.0-default.conf".to_owned()),
+                new: None,
+            },
+        };
+
+        assert_eq!(
+            serde_json::to_string(&record).unwrap(),
+            r#"{"time":1700000000,"event":"default_changed","old":"6.8.0-default.conf","new":null}"#
+        );
+    }
+}
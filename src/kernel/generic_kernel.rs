@@ -1,32 +1,81 @@
 use anyhow::{anyhow, bail, Result};
-use dialoguer::{theme::ColorfulTheme, Confirm};
 use libsdbootconf::{
     entry::{EntryBuilder, Token},
     SystemdBootConf,
 };
 use regex::Regex;
-use std::{cell::RefCell, cmp::Ordering, collections::HashMap, fmt, fs, path::PathBuf, rc::Rc};
+use std::{
+    cell::{Cell, RefCell},
+    cmp::Ordering,
+    collections::{BTreeSet, HashMap},
+    fmt::{self, Write},
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    rc::Rc,
+    thread::LocalKey,
+    time::UNIX_EPOCH,
+};
 
-use super::{file_copy, Kernel, REL_ENTRY_PATH};
+use super::{
+    fat_mtime, file_copy, is_ostree_dir, load_entries, mtime_eq, same_path, sha256_file,
+    write_entry_set, Kernel, KernelOps, KernelSummary, EMBEDDED_HASH_KEYS, HIDDEN_ENTRY_PREFIX,
+    REL_ENTRY_PATH, REL_UKI_PATH,
+};
 use crate::{
-    fl, print_block_with_fl, println_with_prefix, println_with_prefix_and_fl,
-    version::{generic_version::GenericVersion, Version},
-    Config, REL_DEST_PATH, SRC_PATH,
+    config::{duplicate_profiles, InstalledDetectionMethod, Profile},
+    default_entry::match_entry,
+    fl, journal,
+    manifest::{read_aliases, write_aliases, Manifest},
+    print_block_with_fl, println_with_prefix, println_with_prefix_and_fl,
+    prompt::{ColorfulTheme, Confirm},
+    status::{self, Event},
+    version::{generic_version::GenericVersion, Version, VersionDisplayFormat},
+    Config, REL_DEST_PATH,
 };
 
-const MODULES_PATH: &str = "/usr/lib/modules/";
 const UCODE: &str = "intel-ucode.img";
 
 /// A kernel struct for parsing kernel filenames
 #[derive(Debug, Clone)]
 pub struct GenericKernel {
     version: GenericVersion,
+    version_format: VersionDisplayFormat,
     vmlinux: String,
     initrd: String,
     distro: Rc<String>,
     esp_mountpoint: Rc<PathBuf>,
+    src_path: Rc<PathBuf>,
+    skip_sanity_checks: bool,
+    remove_sources: bool,
+    allow_duplicate_profiles: bool,
+    embed_hashes: bool,
     entry: String,
+    /// Replaces the kernel name in the entry ids and the version in the
+    /// titles, see `--version-alias`
+    alias: Option<String>,
+    /// Modification time of the kernel image, part of the entry ids if
+    /// `timestamp_based_entries` is enabled
+    timestamp: Option<u64>,
+    /// Build metadata shipped in the module directory, empty if missing,
+    /// available to the titles as `{PKGBASE}` and `{BUILD_ID}`
+    pkgbase: String,
+    build_id: String,
+    /// The module directory that must be gone before the kernel is removed,
+    /// if `ORPHAN_CHECK` is enabled
+    orphan_check: Option<PathBuf>,
+    /// The profile whose entry is the default one
+    default_profile: String,
     bootargs: Rc<RefCell<HashMap<String, String>>>,
+    profiles: Rc<HashMap<String, Profile>>,
+    skip_profiles: Rc<HashMap<String, Vec<String>>>,
+    /// A unified kernel image from EFI/Linux, booted as a whole without
+    /// an initrd
+    uki: bool,
+    /// The microcode image loaded by the entries, and the pattern in
+    /// `ucode_overrides` selecting it if not the default one
+    ucode: String,
+    ucode_pattern: Option<String>,
     sbconf: Rc<RefCell<SystemdBootConf>>,
 }
 
@@ -59,40 +108,253 @@ impl fmt::Display for GenericKernel {
     }
 }
 
+/// Construct a regex matching the filenames generated by the template,
+/// capturing the version
+fn template_regex(template: &str) -> Result<Regex> {
+    let pattern = template
+        .split("{VERSION}")
+        .map(regex::escape)
+        .collect::<Vec<_>>()
+        .join(r"(?P<version>.+)");
+
+    Ok(Regex::new(&format!("^{}$", pattern))?)
+}
+
+/// Check the magic numbers of EFI stub (MZ) and x86 bzImage (HdrS)
+fn looks_like_kernel(path: &Path) -> Result<bool> {
+    let mut header = Vec::new();
+    fs::File::open(path)?.take(0x206).read_to_end(&mut header)?;
+
+    Ok(header.starts_with(b"MZ") || header.get(0x202..0x206) == Some(b"HdrS"))
+}
+
+/// Magic numbers of the compression formats and cpio archives an
+/// initramfs may begin with
+const INITRD_MAGICS: &[&[u8]] = &[
+    b"\x1f\x8b",         // gzip
+    b"\x28\xb5\x2f\xfd", // zstd
+    b"\xfd7zXZ\x00",     // xz
+    b"\x5d\x00\x00",     // lzma
+    b"BZh",              // bzip2
+    b"\x02\x21\x4c\x18", // lz4
+    b"\x89LZO",          // lzo
+    b"070701",           // cpio (newc), including early cpio
+    b"070702",           // cpio (crc)
+    b"070707",           // cpio (odc)
+];
+
+/// Check if the header begins with a known initramfs magic number
+fn looks_like_initrd(header: &[u8]) -> bool {
+    INITRD_MAGICS.iter().any(|m| header.starts_with(m))
+}
+
+/// Refuse to install empty files and initramfs images without a
+/// recognizable magic number, only the first few bytes are read
+fn sanity_check(path: &Path, is_initrd: bool) -> Result<()> {
+    if fs::metadata(path)?.len() == 0 {
+        bail!(fl!("empty_source", path = path.to_string_lossy()));
+    }
+
+    if is_initrd {
+        let mut header = Vec::new();
+        fs::File::open(path)?.take(6).read_to_end(&mut header)?;
+
+        if !looks_like_initrd(&header) {
+            bail!(fl!("bad_initrd", path = path.to_string_lossy()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Why a directory in the modules path is not listed as a kernel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipReason {
+    /// Some of the module files are missing
+    Incomplete,
+    /// The version can not be parsed
+    Unidentified,
+    /// Parsed to the same version as the given kernel
+    Duplicate(String),
+    /// The flavor is not in `manage_flavors`
+    Unmanaged,
+    /// Belongs to an ostree deployment and `manage_ostree` is not set
+    Ostree,
+    /// Matches the glob in `never_touch`
+    NeverTouch(String),
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::Incomplete => write!(f, "{}", fl!("reason_incomplete")),
+            SkipReason::Unidentified => write!(f, "{}", fl!("reason_unidentified")),
+            SkipReason::Duplicate(k) => write!(f, "{}", fl!("reason_duplicate", kernel = k)),
+            SkipReason::Unmanaged => write!(f, "{}", fl!("reason_unmanaged")),
+            SkipReason::Ostree => write!(f, "{}", fl!("reason_ostree")),
+            SkipReason::NeverTouch(p) => write!(f, "{}", fl!("reason_never_touch", pattern = p)),
+        }
+    }
+}
+
+/// A directory in the modules path skipped when listing kernels
+#[derive(Debug, Clone)]
+pub struct SkippedKernel {
+    pub dirname: String,
+    pub reason: SkipReason,
+}
+
+/// Read the first line of the first existing metadata file in the module
+/// directory, e.g. `pkgbase` on Arch Linux
+fn read_metadata(modules_dir: &Path, names: &[&str]) -> String {
+    names
+        .iter()
+        .find_map(|n| fs::read_to_string(modules_dir.join(n)).ok())
+        .and_then(|c| c.lines().next().map(|l| l.trim().to_owned()))
+        .unwrap_or_default()
+}
+
+/// Get the timestamp naming the entries of the kernel: the one recorded
+/// when they were written, so that touching the source does not rename
+/// them, or the modification time of the kernel image in the source
+/// directory, or of the installed copy if the source is gone. Rounded to
+/// the FAT granularity so that both agree.
+fn image_timestamp(config: &Config, entry: &str, vmlinux: &str) -> Option<u64> {
+    let recorded = Manifest::read(&config.esp_mountpoint)
+        .ok()
+        .and_then(|m| m.timestamps.get(entry).copied());
+
+    if recorded.is_some() {
+        return recorded;
+    }
+
+    let src = config.src_path.join(vmlinux);
+    let path = if src.exists() {
+        src
+    } else {
+        config.esp_mountpoint.join(REL_DEST_PATH).join(vmlinux)
+    };
+    let mtime = fs::metadata(path).and_then(|m| m.modified()).ok()?;
+
+    fat_mtime(mtime)
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .map(|d| d.as_secs())
+}
+
+/// Sort the installed kernels from newer to older, by the installation
+/// time if the entries are timestamp based
+fn sort_installed(config: &Config, kernels: &mut [GenericKernel]) {
+    if config.timestamp_based_entries {
+        kernels.sort_by(|a, b| b.timestamp.cmp(&a.timestamp).then_with(|| b.cmp(a)));
+    } else {
+        kernels.sort_by(|a, b| b.cmp(a));
+    }
+}
+
+thread_local! {
+    static CACHE_ENABLED: Cell<bool> = const { Cell::new(false) };
+    static LIST_CACHE: KernelCache = const { RefCell::new(None) };
+    static INSTALLED_CACHE: KernelCache = const { RefCell::new(None) };
+    static WARNED_DUPLICATES: RefCell<BTreeSet<Vec<String>>> = const { RefCell::new(BTreeSet::new()) };
+}
+
+type KernelCache = RefCell<Option<Vec<GenericKernel>>>;
+
+/// Reuse the lists of kernels for the rest of the session, which has a
+/// single configuration. They are forgotten once any kernel is installed
+/// or removed.
+pub fn set_list_cache(enabled: bool) {
+    CACHE_ENABLED.with(|c| c.set(enabled));
+    invalidate_list_cache();
+}
+
+/// Forget the cached lists of kernels, e.g. when the files may have been
+/// changed by another process
+pub fn invalidate_list_cache() {
+    LIST_CACHE.with(|c| c.take());
+    INSTALLED_CACHE.with(|c| c.take());
+}
+
+fn cached<F>(cache: &'static LocalKey<KernelCache>, scan: F) -> Result<Vec<GenericKernel>>
+where
+    F: FnOnce() -> Result<Vec<GenericKernel>>,
+{
+    if !CACHE_ENABLED.with(|c| c.get()) {
+        return scan();
+    }
+
+    if let Some(kernels) = cache.with(|c| c.borrow().clone()) {
+        return Ok(kernels);
+    }
+
+    let kernels = scan()?;
+    cache.with(|c| *c.borrow_mut() = Some(kernels.clone()));
+
+    Ok(kernels)
+}
+
+/// Select the microcode image of the kernel, the longest pattern in
+/// `ucode_overrides` matching it wins
+fn select_ucode(overrides: &HashMap<String, String>, entry: &str) -> (String, Option<String>) {
+    overrides
+        .iter()
+        .filter(|(p, _)| match_entry(p, [entry]).is_ok_and(|m| m.is_some()))
+        .max_by(|(a, _), (b, _)| a.len().cmp(&b.len()).then_with(|| b.cmp(a)))
+        .map_or_else(
+            || (UCODE.to_owned(), None),
+            |(p, u)| (u.to_owned(), Some(p.to_owned())),
+        )
+}
+
+/// Check if any entry still loads the microcode image
+fn ucode_referenced(esp_mountpoint: &Path, ucode: &str) -> Result<bool> {
+    let target = Path::new(REL_DEST_PATH).join(ucode);
+
+    for entry in fs::read_dir(esp_mountpoint.join(REL_ENTRY_PATH))? {
+        let content = fs::read_to_string(entry?.path()).unwrap_or_default();
+
+        if content.lines().any(|l| {
+            l.trim()
+                .strip_prefix("initrd")
+                .is_some_and(|p| Path::new(p.trim().trim_start_matches('/')) == target)
+        }) {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
 #[inline]
 fn warn<O: fmt::Display, M: fmt::Display>(object: O, message: M) {
     eprintln!("Warning: {}: {}", object, message);
 }
 
-impl Kernel for GenericKernel {
-    /// Parse a kernel filename
-    fn parse(
-        config: &Config,
-        kernel_name: &str,
-        sbconf: Rc<RefCell<SystemdBootConf>>,
-    ) -> Result<Self> {
-        let version = GenericVersion::parse(kernel_name)?;
-        let vmlinux = config.vmlinux.replace("{VERSION}", kernel_name);
-        let initrd = config.initrd.replace("{VERSION}", kernel_name);
-        let entry = kernel_name.to_owned();
+impl KernelOps for GenericKernel {
+    /// Install a specific kernel to the esp using the given kernel filename,
+    /// from the given source directory instead of the configured one if any
+    fn install(&self, src_path: Option<&Path>) -> Result<()> {
+        if let Some(copies) = self.install_copies(src_path)? {
+            for (src, dest) in copies {
+                file_copy(src, dest)?;
+            }
+        }
 
-        Ok(Self {
-            version,
-            vmlinux,
-            initrd,
-            distro: config.distro.clone(),
-            esp_mountpoint: config.esp_mountpoint.clone(),
-            entry,
-            bootargs: config.bootargs.clone(),
-            sbconf,
-        })
+        self.finish_install(src_path)
     }
 
-    /// Install a specific kernel to the esp using the given kernel filename
-    fn install(&self) -> Result<()> {
+    fn install_copies(&self, src_path: Option<&Path>) -> Result<Option<Vec<(PathBuf, PathBuf)>>> {
+        invalidate_list_cache();
+
+        // A unified kernel image is booted where it is
+        if self.uki {
+            return Ok(Some(Vec::new()));
+        }
+
         // if the path does not exist, ask the user for initializing friend
         let dest_path = self.esp_mountpoint.join(REL_DEST_PATH);
-        let src_path = PathBuf::from(SRC_PATH);
+        let src_path = src_path.unwrap_or(&self.src_path);
 
         if !dest_path.exists() {
             print_block_with_fl!("info_path_not_exist");
@@ -102,68 +364,259 @@ impl Kernel for GenericKernel {
             ));
         }
 
+        let initrd_paths = [src_path.join(&self.initrd), src_path.join(&self.ucode)];
+
+        // The image removed by `remove_sources` has nothing to be copied
+        // as long as its copy in the ESP is intact
+        let vmlinux_removed = self.source_removed(
+            &src_path.join(&self.vmlinux),
+            &dest_path.join(&self.vmlinux),
+        )?;
+
+        // Check the source files before touching the ESP
+        if !self.skip_sanity_checks {
+            if !vmlinux_removed {
+                sanity_check(&src_path.join(&self.vmlinux), false)?;
+            }
+
+            for path in initrd_paths.iter() {
+                if path.exists() {
+                    sanity_check(path, true)?;
+                }
+            }
+        }
+
         // generate the path to the source files
         println_with_prefix_and_fl!("install", kernel = self.to_string());
 
         // Copy the source files to the `install_path` using specific
         // filename format, remove the version parts of the files
-        file_copy(src_path.join(&self.vmlinux), dest_path.join(&self.vmlinux))?;
+        let mut copies = Vec::new();
+
+        if !vmlinux_removed {
+            copies.push((src_path.join(&self.vmlinux), dest_path.join(&self.vmlinux)));
+        }
 
-        let initrd_path = src_path.join(&self.initrd);
+        let [initrd_path, ucode_path] = initrd_paths;
 
         if initrd_path.exists() {
-            file_copy(src_path.join(&self.initrd), dest_path.join(&self.initrd))?;
+            copies.push((initrd_path, dest_path.join(&self.initrd)));
         }
 
         // copy Intel ucode if exists
-        let ucode_path = src_path.join(UCODE);
-        let ucode_dest_path = dest_path.join(UCODE);
-
         if ucode_path.exists() {
             println_with_prefix_and_fl!("install_ucode");
-            file_copy(ucode_path, ucode_dest_path)?;
-        } else {
-            fs::remove_file(ucode_dest_path).ok();
+            copies.push((ucode_path, dest_path.join(&self.ucode)));
+        }
+
+        Ok(Some(copies))
+    }
+
+    fn finish_install(&self, src_path: Option<&Path>) -> Result<()> {
+        // Only the files in the configured source directory are removed
+        let remove_sources = self.remove_sources && src_path.is_none();
+        let src_path = src_path.unwrap_or(&self.src_path);
+
+        if !self.uki && !src_path.join(&self.ucode).exists() {
+            if self.ucode_pattern.is_some() {
+                // The image may still be loaded by the other kernels
+                warn(
+                    self.to_string(),
+                    fl!("ucode_missing", ucode = self.ucode.as_str()),
+                );
+            } else {
+                fs::remove_file(self.esp_mountpoint.join(REL_DEST_PATH).join(&self.ucode)).ok();
+            }
+        }
+
+        if remove_sources {
+            self.remove_sources()?;
+        }
+
+        journal::record(
+            &self.esp_mountpoint,
+            journal::Event::Install {
+                kernel: self.to_string(),
+            },
+        );
+        status::emit(Event::KernelInstalled {
+            kernel: self.to_string(),
+        });
+
+        Ok(())
+    }
+
+    /// Install a kernel image and an optional initrd from arbitrary paths
+    fn install_from(&self, image: &Path, initrd: Option<&Path>) -> Result<()> {
+        invalidate_list_cache();
+
+        let dest_path = self.esp_mountpoint.join(REL_DEST_PATH);
+
+        if !dest_path.exists() {
+            print_block_with_fl!("info_path_not_exist");
+            bail!(fl!(
+                "err_path_not_exist",
+                path = dest_path.to_string_lossy()
+            ));
+        }
+
+        for path in std::iter::once(image).chain(initrd) {
+            if !path.is_file() {
+                bail!(fl!("err_path_not_exist", path = path.to_string_lossy()));
+            }
+        }
+
+        if !looks_like_kernel(image)? {
+            warn(image.display(), fl!("not_a_kernel"));
+        }
+
+        if !self.skip_sanity_checks {
+            sanity_check(image, false)?;
+
+            if let Some(initrd) = initrd {
+                sanity_check(initrd, true)?;
+            }
+        }
+
+        println_with_prefix_and_fl!("install", kernel = self.to_string());
+        file_copy(image, dest_path.join(&self.vmlinux))?;
+
+        if let Some(initrd) = initrd {
+            file_copy(initrd, dest_path.join(&self.initrd))?;
         }
 
+        journal::record(
+            &self.esp_mountpoint,
+            journal::Event::Install {
+                kernel: self.to_string(),
+            },
+        );
+        status::emit(Event::KernelInstalled {
+            kernel: self.to_string(),
+        });
+
         Ok(())
     }
 
     // Try to remove a kernel
     fn remove(&self) -> Result<()> {
-        let kernel_path = self.esp_mountpoint.join(REL_DEST_PATH);
+        if let Some(modules) = &self.orphan_check {
+            if modules.exists() {
+                print_block_with_fl!("notice_modules_installed");
+                bail!(fl!(
+                    "modules_still_installed",
+                    kernel = self.to_string(),
+                    path = modules.to_string_lossy()
+                ));
+            }
+        }
+
+        invalidate_list_cache();
+
+        let kernel_path = self.image_dir();
 
         println_with_prefix_and_fl!("remove_kernel", kernel = self.to_string());
-        let vmlinux = kernel_path.join(&self.vmlinux);
-        let initrd = kernel_path.join(&self.initrd);
+        for file in self.files() {
+            let path = kernel_path.join(file);
 
-        fs::remove_file(&vmlinux)
-            .map_err(|x| warn(vmlinux.display(), x))
-            .ok();
-        fs::remove_file(&initrd)
-            .map_err(|x| warn(initrd.display(), x))
-            .ok();
+            fs::remove_file(&path)
+                .map_err(|x| warn(path.display(), x))
+                .ok();
+        }
 
-        println_with_prefix_and_fl!("remove_entry", kernel = self.to_string());
-        for profile in self.bootargs.borrow().keys() {
-            let entry = self.esp_mountpoint.join(format!(
-                "loader/entries/{}-{}.conf",
-                self.entry,
-                profile.replace(' ', "_")
-            ));
+        // The unified kernel images have no entries
+        let profiles = if self.uki {
+            Vec::new()
+        } else {
+            self.bootargs.borrow().keys().cloned().collect()
+        };
+
+        if !profiles.is_empty() {
+            println_with_prefix_and_fl!("remove_entry", kernel = self.to_string());
+        }
+
+        for profile in profiles {
+            let entry = self
+                .esp_mountpoint
+                .join(format!("loader/entries/{}.conf", self.entry_id(&profile)));
+
+            // Skipped profiles usually have no entries
+            if self.skips_profile(&profile) && !entry.exists() {
+                continue;
+            }
 
             fs::remove_file(&entry)
                 .map_err(|x| warn(entry.display(), x))
                 .ok();
         }
 
+        // The microcode images selected by overrides may be shared, the
+        // default one is always kept
+        if !self.uki
+            && self.ucode_pattern.is_some()
+            && !ucode_referenced(&self.esp_mountpoint, &self.ucode)?
+        {
+            fs::remove_file(kernel_path.join(&self.ucode)).ok();
+        }
+
         self.remove_default()?;
 
+        // The entries written again on reinstall are not edited ones
+        let ids = self
+            .bootargs
+            .borrow()
+            .keys()
+            .map(|profile| self.entry_id(profile))
+            .collect::<Vec<_>>();
+
+        // Forget the kernel if it was installed from an arbitrary path
+        let mut manifest = Manifest::read(&self.esp_mountpoint)?;
+        let hashes = manifest.hashes.len();
+        let modified = manifest.modified_entries.len();
+
+        manifest.hashes.remove(&self.vmlinux);
+        manifest.hashes.remove(&self.initrd);
+        manifest.modified_entries.retain(|id| !ids.contains(id));
+        let timestamp = manifest.timestamps.remove(&self.entry);
+
+        if manifest.out_of_tree.iter().any(|k| k == &self.entry)
+            || manifest.hashes.len() != hashes
+            || manifest.modified_entries.len() != modified
+            || timestamp.is_some()
+        {
+            manifest.out_of_tree.retain(|k| k != &self.entry);
+            manifest.write(&self.esp_mountpoint)?;
+        }
+
+        if self.alias.is_some() {
+            let mut aliases = read_aliases(&self.esp_mountpoint)?;
+            aliases.remove(&self.entry);
+            write_aliases(&self.esp_mountpoint, &aliases)?;
+        }
+
+        journal::record(
+            &self.esp_mountpoint,
+            journal::Event::Remove {
+                kernel: self.to_string(),
+            },
+        );
+        status::emit(Event::KernelRemoved {
+            kernel: self.to_string(),
+        });
+
         Ok(())
     }
 
     /// Create a systemd-boot entry config
     fn make_config(&self, force_write: bool) -> Result<()> {
+        invalidate_list_cache();
+
+        // systemd-boot lists the unified kernel images in EFI/Linux by
+        // itself, an entry would only duplicate them
+        if self.uki {
+            return Ok(());
+        }
+
         // if the path does not exist, ask the user for initializing friend
         let entries_path = self.esp_mountpoint.join(REL_ENTRY_PATH);
 
@@ -200,48 +653,183 @@ impl Kernel for GenericKernel {
         let dest_path = self.esp_mountpoint.join(REL_DEST_PATH);
         let rel_dest_path = PathBuf::from(REL_DEST_PATH);
         let mut entries = Vec::new();
+        let mut manifest = Manifest::read(&self.esp_mountpoint)?;
+        let previous_default = manifest
+            .previous_default
+            .as_ref()
+            .is_some_and(|k| *k == self.entry);
+
+        if !self.allow_duplicate_profiles {
+            for group in self.duplicate_profiles() {
+                // Once in the session, not for every kernel sharing the profiles
+                if WARNED_DUPLICATES.with(|w| w.borrow_mut().insert(group.clone())) {
+                    warn(
+                        self.to_string(),
+                        fl!("duplicate_profiles", profiles = group.join(", ")),
+                    );
+                }
+            }
+        }
 
         for (profile, bootarg) in self.bootargs.borrow().iter() {
-            let mut entry =
-                EntryBuilder::new(format!("{}-{}", self.entry, profile.replace(' ', "_")))
-                    .title(format!("{} ({}) ({})", self.distro, self, profile))
-                    .linux(rel_dest_path.join(&self.vmlinux))
-                    .build();
-
-            dest_path
-                .join(UCODE)
-                .exists()
-                .then(|| entry.tokens.push(Token::Initrd(rel_dest_path.join(UCODE))));
+            if self.skips_profile(profile) {
+                continue;
+            }
+
+            let mut suffix = self
+                .profiles
+                .get(profile)
+                .and_then(|p| p.title_suffix.clone())
+                .unwrap_or_else(|| format!("({})", profile));
+
+            if previous_default {
+                suffix.push(' ');
+                suffix.push_str(&fl!("title_previous_default"));
+            }
+
+            let version = match &self.alias {
+                Some(a) => a.to_owned(),
+                None => self.version.display_as(self.version_format).to_string(),
+            };
+            let title = format!("{} ({}) {}", self.distro, version, suffix)
+                .replace("{PKGBASE}", &self.pkgbase)
+                .replace("{BUILD_ID}", &self.build_id);
+            // A missing value should not leave a gap in the title
+            let mut entry = EntryBuilder::new(self.entry_id(profile))
+                .title(title.split_whitespace().collect::<Vec<_>>().join(" "))
+                .build();
+
+            entry
+                .tokens
+                .push(Token::Linux(rel_dest_path.join(&self.vmlinux)));
+            dest_path.join(&self.ucode).exists().then(|| {
+                entry
+                    .tokens
+                    .push(Token::Initrd(rel_dest_path.join(&self.ucode)))
+            });
             dest_path.join(&self.initrd).exists().then(|| {
                 entry
                     .tokens
                     .push(Token::Initrd(rel_dest_path.join(&self.initrd)))
             });
+
+            // systemd-boot splits the lines at whitespace, the paths cannot
+            // be quoted
+            for token in entry.tokens.iter() {
+                if let Token::Linux(p) | Token::Initrd(p) = token {
+                    if p.to_string_lossy().contains(char::is_whitespace) {
+                        bail!(fl!("token_path_space", path = p.to_string_lossy()));
+                    }
+                }
+            }
+
             entry.tokens.push(Token::Options(bootarg.to_owned()));
             entries.push(entry);
         }
 
+        // The entries edited with `entry edit` are kept unless the user
+        // agrees to overwrite them
+        let modified = entries
+            .iter()
+            .filter(|e| {
+                manifest.modified_entries.contains(&e.id)
+                    && entries_path.join(format!("{}.conf", e.id)).exists()
+            })
+            .map(|e| e.id.clone())
+            .collect::<Vec<_>>();
+
+        for id in modified {
+            let entry = entries_path.join(format!("{}.conf", id));
+            let overwrite = console::user_attended()
+                && Confirm::with_theme(&ColorfulTheme::default())
+                    .with_prompt(fl!(
+                        "ask_overwrite_modified",
+                        entry = entry.to_string_lossy()
+                    ))
+                    .default(false)
+                    .interact()?;
+
+            if overwrite {
+                manifest.modified_entries.retain(|e| *e != id);
+                manifest.write(&self.esp_mountpoint)?;
+            } else {
+                println_with_prefix_and_fl!("keep_modified_entry", entry = entry.to_string_lossy());
+                entries.retain(|e| e.id != id);
+            }
+        }
+
+        let comments = self
+            .embed_hashes
+            .then(|| self.hash_comments(&dest_path))
+            .transpose()?;
+
+        write_entry_set(&entries_path, &entries, |e, path| {
+            e.write(path)?;
+
+            // Appended after the builder output, which has no comments
+            if let Some(comments) = &comments {
+                let mut content = fs::read_to_string(path)?;
+
+                if !content.is_empty() && !content.ends_with('\n') {
+                    content.push('\n');
+                }
+                content.push_str(comments);
+                fs::write(path, content)?;
+            }
+
+            Ok(())
+        })?;
+        journal::record(
+            &self.esp_mountpoint,
+            journal::Event::EntriesWritten {
+                kernel: self.to_string(),
+                entries: entries.iter().map(|e| format!("{}.conf", e.id)).collect(),
+            },
+        );
         self.sbconf.borrow_mut().entries = entries;
-        self.sbconf.borrow().write_entries()?;
+
+        // The entries keep their names until the kernel is removed
+        if let Some(timestamp) = self.timestamp {
+            let mut manifest = Manifest::read(&self.esp_mountpoint)?;
+
+            if manifest.timestamps.insert(self.entry.clone(), timestamp) != Some(timestamp) {
+                manifest.write(&self.esp_mountpoint)?;
+            }
+        }
 
         Ok(())
     }
 
     // Set default entry
-    fn set_default(&self) -> Result<()> {
-        println_with_prefix_and_fl!("set_default", kernel = self.to_string());
-        self.sbconf.borrow_mut().config.default = Some(self.entry.to_owned() + "-default.conf");
+    fn set_default(&self, announce: bool) -> Result<()> {
+        if announce {
+            println_with_prefix_and_fl!("set_default", kernel = self.to_string());
+        }
+
+        let new = Some(self.default_entry_file());
+        let old = std::mem::replace(&mut self.sbconf.borrow_mut().config.default, new.clone());
         self.sbconf.borrow().write_config()?;
 
+        if old != new {
+            journal::record(
+                &self.esp_mountpoint,
+                journal::Event::DefaultChanged { old, new },
+            );
+        }
+
         Ok(())
     }
 
     // Remove default entry
     fn remove_default(&self) -> Result<()> {
-        if self.sbconf.borrow().config.default == Some(self.entry.to_owned() + "-default.conf") {
+        if self.sbconf.borrow().config.default == Some(self.default_entry_file()) {
             println_with_prefix_and_fl!("remove_default", kernel = self.to_string());
-            self.sbconf.borrow_mut().config.default = None;
+            let old = self.sbconf.borrow_mut().config.default.take();
             self.sbconf.borrow().write_config()?;
+            journal::record(
+                &self.esp_mountpoint,
+                journal::Event::DefaultChanged { old, new: None },
+            );
         }
 
         Ok(())
@@ -253,7 +841,7 @@ impl Kernel for GenericKernel {
             .with_prompt(fl!("ask_set_default", kernel = self.to_string()))
             .default(false)
             .interact()?
-            .then(|| self.set_default())
+            .then(|| self.set_default(true))
             .transpose()?;
 
         Ok(())
@@ -262,6 +850,10 @@ impl Kernel for GenericKernel {
     /// Check if the kernel is the default kernel
     #[inline]
     fn is_default(&self) -> Result<bool> {
+        if self.uki {
+            return Ok(self.sbconf.borrow().config.default.as_ref() == Some(&self.vmlinux));
+        }
+
         let entry = &self
             .sbconf
             .borrow()
@@ -283,57 +875,324 @@ impl Kernel for GenericKernel {
 
     #[inline]
     fn install_and_make_config(&self, force_write: bool) -> Result<()> {
-        self.install()?;
+        self.install(None)?;
         self.make_config(force_write)?;
 
         Ok(())
     }
 
+    /// The kernel name, as in /usr/lib/modules
+    #[inline]
+    fn name(&self) -> &str {
+        &self.entry
+    }
+
+    #[inline]
+    fn flavor(&self) -> &str {
+        self.version.localversion.trim_start_matches('-')
+    }
+
+    /// Generate the entry id of the given boot argument profile
+    #[inline]
+    fn entry_id(&self, profile: &str) -> String {
+        let prefix = if self.profiles.get(profile).is_some_and(|p| p.hidden) {
+            HIDDEN_ENTRY_PREFIX
+        } else {
+            ""
+        };
+
+        let name = self.alias.as_deref().unwrap_or(&self.entry);
+
+        match self.timestamp {
+            Some(t) => format!("{}{}-{}-{}", prefix, name, t, profile.replace(' ', "_")),
+            None => format!("{}{}-{}", prefix, name, profile.replace(' ', "_")),
+        }
+    }
+
+    /// Find the source files which are newer than the installed ones in the ESP
+    fn changed_files(&self) -> Result<Vec<PathBuf>> {
+        // A unified kernel image is its own source
+        if self.uki {
+            return Ok(Vec::new());
+        }
+
+        let src_path = &self.src_path;
+        let dest_path = self.esp_mountpoint.join(REL_DEST_PATH);
+        let manifest = Manifest::read(&self.esp_mountpoint)?;
+        let mut changed = Vec::new();
+
+        for file in self.files() {
+            let src = src_path.join(file);
+            let dest = dest_path.join(file);
+
+            if !src.exists() {
+                continue;
+            }
+
+            if !dest.exists() {
+                changed.push(src);
+                continue;
+            }
+
+            let (src_mtime, dest_mtime) = (
+                fs::metadata(&src)?.modified()?,
+                fs::metadata(&dest)?.modified()?,
+            );
+
+            if src_mtime <= dest_mtime || mtime_eq(src_mtime, dest_mtime) {
+                continue;
+            }
+
+            // A source removed by `remove_sources` and recreated by reinstalling
+            // the package is only changed if the contents differ
+            match manifest.hashes.get(file) {
+                Some(hash) if &sha256_file(&src)? == hash => (),
+                _ => changed.push(src),
+            }
+        }
+
+        Ok(changed)
+    }
+
+    /// List the files of the kernel installed in the ESP
+    fn esp_files(&self) -> Vec<PathBuf> {
+        let dest_path = self.image_dir();
+
+        self.files()
+            .into_iter()
+            .chain((!self.uki).then_some(self.ucode.as_str()))
+            .map(|f| dest_path.join(f))
+            .filter(|p| p.exists())
+            .collect()
+    }
+
+    /// List the source files of the kernel image and initrd, along with
+    /// where they are installed in the ESP
+    fn source_files(&self) -> Vec<(PathBuf, PathBuf)> {
+        // A unified kernel image is its own source
+        if self.uki {
+            return Vec::new();
+        }
+
+        let dest_path = self.esp_mountpoint.join(REL_DEST_PATH);
+
+        self.files()
+            .into_iter()
+            .map(|f| (self.src_path.join(f), dest_path.join(f)))
+            .collect()
+    }
+
+    /// Assemble the state of the kernel
+    fn summary(&self) -> Result<KernelSummary> {
+        let entries_path = self.esp_mountpoint.join(REL_ENTRY_PATH);
+        let mut entry_files = self
+            .bootargs
+            .borrow()
+            .keys()
+            .map(|p| format!("{}.conf", self.entry_id(p)))
+            .filter(|e| entries_path.join(e).exists())
+            .collect::<Vec<_>>();
+        entry_files.sort();
+
+        Ok(KernelSummary {
+            version: self.version.to_string(),
+            vmlinux: self.vmlinux.clone(),
+            initrd: self.initrd.clone(),
+            entry_files,
+            is_installed: self.image_dir().join(&self.vmlinux).exists(),
+            is_default: self.is_default()?,
+            pkgbase: self.pkgbase.clone(),
+            build_id: self.build_id.clone(),
+        })
+    }
+}
+
+impl Kernel for GenericKernel {
+    /// Parse a kernel filename
+    fn parse(
+        config: &Config,
+        kernel_name: &str,
+        sbconf: Rc<RefCell<SystemdBootConf>>,
+    ) -> Result<Self> {
+        // Unified kernel images are named after their entries, the image
+        // is the complete entry
+        let (kernel_name, uki) = match kernel_name.strip_suffix(".efi") {
+            Some(stem) => (stem, true),
+            None => (kernel_name, false),
+        };
+        let version = GenericVersion::parse(kernel_name)?;
+        let (vmlinux, initrd, src_path) = if uki {
+            (
+                format!("{}.efi", kernel_name),
+                String::new(),
+                Rc::new(config.esp_mountpoint.join(REL_UKI_PATH)),
+            )
+        } else {
+            (
+                config.vmlinux.replace("{VERSION}", kernel_name),
+                config.initrd.replace("{VERSION}", kernel_name),
+                config.src_path.clone(),
+            )
+        };
+        let entry = kernel_name.to_owned();
+        let (ucode, ucode_pattern) = select_ucode(&config.ucode_overrides, &entry);
+
+        // Apply the per-kernel overrides on top of the main configuration
+        let mut distro = config.distro.clone();
+        let mut bootargs = config.bootargs.clone();
+
+        if let Some(o) = config.kernel_override(kernel_name)? {
+            if let Some(d) = o.distro {
+                distro = Rc::new(d);
+            }
+
+            if !o.bootargs.is_empty() {
+                let mut merged = config.bootargs.borrow().clone();
+                merged.extend(o.bootargs);
+                bootargs = Rc::new(RefCell::new(merged));
+            }
+        }
+
+        let modules_dir = config.modules_path.join(&entry);
+        let pkgbase = read_metadata(&modules_dir, &["pkgbase"]);
+        let build_id = read_metadata(&modules_dir, &["build-id", "build_id"]);
+
+        let timestamp = if config.timestamp_based_entries {
+            image_timestamp(config, &entry, &vmlinux)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            version,
+            version_format: config.version_format,
+            vmlinux,
+            initrd,
+            distro,
+            esp_mountpoint: config.esp_mountpoint.clone(),
+            src_path,
+            skip_sanity_checks: config.skip_sanity_checks,
+            // The images in EFI/Linux are not the package manager's
+            remove_sources: config.remove_sources && !uki,
+            allow_duplicate_profiles: config.allow_duplicate_profiles,
+            embed_hashes: config.embed_hashes,
+            alias: read_aliases(&config.esp_mountpoint)?.remove(&entry),
+            entry,
+            timestamp,
+            pkgbase,
+            build_id,
+            orphan_check: (config.orphan_check && !config.force_remove).then_some(modules_dir),
+            default_profile: config.default_profile.clone(),
+            bootargs,
+            profiles: config.profiles.clone(),
+            skip_profiles: config.skip_profiles.clone(),
+            uki,
+            ucode,
+            ucode_pattern,
+            sbconf,
+        })
+    }
+
     /// Generate a sorted vector of kernel filenames
     fn list(config: &Config, sbconf: Rc<RefCell<SystemdBootConf>>) -> Result<Vec<Self>> {
-        // read /usr/lib/modules to get kernel filenames
-        let mut kernels = Vec::new();
+        cached(&LIST_CACHE, || Self::scan_available(config, sbconf))
+    }
+
+    /// Generate installed kernel list
+    fn list_installed(config: &Config, sbconf: Rc<RefCell<SystemdBootConf>>) -> Result<Vec<Self>> {
+        cached(&INSTALLED_CACHE, || Self::scan_installed(config, sbconf))
+    }
 
-        for f in fs::read_dir(MODULES_PATH)? {
-            let dirname = f?
-                .file_name()
-                .into_string()
-                .map_err(|s| anyhow!("{} {:?}", fl!("invalid_dirname"), s))?;
-            let dirpath = PathBuf::from(MODULES_PATH).join(&dirname);
+    #[inline]
+    fn invalidate_cache() {
+        invalidate_list_cache();
+    }
+}
 
-            if dirpath.join("modules.dep").exists()
-                && dirpath.join("modules.order").exists()
-                && dirpath.join("modules.builtin").exists()
-            {
-                match Self::parse(config, &dirname, sbconf.clone()) {
-                    Ok(k) => kernels.push(k),
-                    Err(_) => {
-                        println_with_prefix_and_fl!("skip_unidentified_kernel", kernel = dirname);
-                    }
+impl GenericKernel {
+    /// Scan the module directories for the available kernels, report the
+    /// skipped ones
+    fn scan_available(config: &Config, sbconf: Rc<RefCell<SystemdBootConf>>) -> Result<Vec<Self>> {
+        let (kernels, skipped) = Self::scan_modules(config, sbconf)?;
+
+        for SkippedKernel { dirname, reason } in skipped.iter() {
+            match reason {
+                SkipReason::Incomplete => {
+                    println_with_prefix_and_fl!("skip_incomplete_kernel", kernel = dirname)
                 }
-            } else {
-                println_with_prefix_and_fl!("skip_incomplete_kernel", kernel = dirname);
+                SkipReason::Unidentified => {
+                    println_with_prefix_and_fl!("skip_unidentified_kernel", kernel = dirname)
+                }
+                SkipReason::Duplicate(k) => println_with_prefix_and_fl!(
+                    "skip_duplicate_kernel",
+                    kernel = dirname,
+                    original = k
+                ),
+                SkipReason::NeverTouch(p) => println_with_prefix_and_fl!(
+                    "skip_never_touch_kernel",
+                    kernel = dirname,
+                    pattern = p
+                ),
+                // Left alone on purpose
+                SkipReason::Unmanaged | SkipReason::Ostree => (),
             }
         }
 
-        // Sort the vector, thus the kernels are
-        // arranged with versions from newer to older
-        kernels.sort_by(|a, b| b.cmp(a));
-
         Ok(kernels)
     }
 
-    /// Generate installed kernel list
-    fn list_installed(config: &Config, sbconf: Rc<RefCell<SystemdBootConf>>) -> Result<Vec<Self>> {
+    /// Scan the ESP or the entries for the installed kernels
+    fn scan_installed(config: &Config, sbconf: Rc<RefCell<SystemdBootConf>>) -> Result<Vec<Self>> {
+        let mut installed_kernels = Self::scan_esp(config, sbconf)?;
+
+        // The kernels of other flavors, ostree deployments and those in
+        // `never_touch` are never touched
+        installed_kernels.retain(|k| k.is_managed(config));
+        sort_installed(config, &mut installed_kernels);
+
+        Ok(installed_kernels)
+    }
+
+    /// List the kernels in the ESP which friend leaves alone, with the
+    /// reasons
+    pub fn list_unmanaged(
+        config: &Config,
+        sbconf: Rc<RefCell<SystemdBootConf>>,
+    ) -> Result<Vec<(Self, SkipReason)>> {
+        let mut kernels = Self::scan_esp(config, sbconf)?;
+        sort_installed(config, &mut kernels);
+
+        Ok(kernels
+            .into_iter()
+            .filter_map(|k| {
+                let reason = k.unmanaged_reason(config)?;
+                Some((k, reason))
+            })
+            .collect())
+    }
+
+    /// Find the kernels in the ESP or the entries, managed or not
+    fn scan_esp(config: &Config, sbconf: Rc<RefCell<SystemdBootConf>>) -> Result<Vec<Self>> {
+        if config.installed_detection_method == InstalledDetectionMethod::Entries {
+            return Self::scan_entries(config, sbconf);
+        }
+
         let mut installed_kernels = Vec::new();
 
         // Construct regex for the template
-        let re = Regex::new(&config.vmlinux.replace("{VERSION}", r"(?P<version>.+)"))?;
+        let re = template_regex(&config.vmlinux)?;
 
         // Regex match group
         if let Ok(d) = fs::read_dir(config.esp_mountpoint.join(REL_DEST_PATH)) {
             for x in d {
-                let filename = &x?
+                let x = x?;
+
+                // Directories are never created by friend, e.g. left by backup tools
+                if x.file_type()?.is_dir() {
+                    continue;
+                }
+
+                let filename = &x
                     .file_name()
                     .into_string()
                     .map_err(|_| anyhow!(fl!("invalid_kernel_filename")))?;
@@ -349,10 +1208,497 @@ impl Kernel for GenericKernel {
             }
         }
 
+        // The unified kernel images are installed where they are found
+        installed_kernels.extend(Self::scan_uki(config, sbconf)?.0);
+
+        Ok(installed_kernels)
+    }
+
+    /// Where the files of the kernel are in the ESP, EFI/Linux for the
+    /// unified kernel images
+    fn image_dir(&self) -> PathBuf {
+        if self.uki {
+            self.src_path.to_path_buf()
+        } else {
+            self.esp_mountpoint.join(REL_DEST_PATH)
+        }
+    }
+
+    /// The file set as default in loader.conf to boot the kernel, the image
+    /// itself for the unified kernel images
+    fn default_entry_file(&self) -> String {
+        if self.uki {
+            self.vmlinux.clone()
+        } else {
+            self.entry_id(&self.default_profile) + ".conf"
+        }
+    }
+
+    /// The files of the kernel, relative to the source and destination
+    /// directories
+    fn files(&self) -> Vec<&str> {
+        if self.uki {
+            vec![&self.vmlinux]
+        } else {
+            vec![&self.vmlinux, &self.initrd]
+        }
+    }
+
+    /// The parsed version of the kernel
+    pub fn version(&self) -> &GenericVersion {
+        &self.version
+    }
+
+    /// The microcode image loaded by the entries, along with the pattern in
+    /// `ucode_overrides` selecting it, or None for unified kernel images
+    pub fn ucode(&self) -> Option<(&str, Option<&str>)> {
+        (!self.uki).then_some((self.ucode.as_str(), self.ucode_pattern.as_deref()))
+    }
+
+    /// The comments embedding the checksums of the kernel files in the ESP
+    /// into its entries, see `embed_hashes`
+    fn hash_comments(&self, dest_path: &Path) -> Result<String> {
+        let mut comments = String::new();
+
+        for (key, file) in EMBEDDED_HASH_KEYS.into_iter().zip(self.files()) {
+            let path = dest_path.join(file);
+
+            if path.exists() {
+                writeln!(comments, "# sha256 {}={}", key, sha256_file(&path)?)?;
+            }
+        }
+        // Without a timestamp, so that an unchanged entry is not rewritten
+        writeln!(
+            comments,
+            "# generated-by systemd-boot-friend {}",
+            env!("CARGO_PKG_VERSION")
+        )?;
+
+        Ok(comments)
+    }
+
+    /// Give the kernel a stable name for its entries and their titles,
+    /// nothing is changed in the ESP until `save_alias`
+    pub fn with_alias(&self, alias: &str) -> Result<Self> {
+        if alias.is_empty()
+            || !alias
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_.+".contains(c))
+        {
+            bail!(fl!("invalid_alias", alias = alias));
+        }
+
+        let aliases = read_aliases(&self.esp_mountpoint)?;
+
+        if let Some((kernel, _)) = aliases
+            .iter()
+            .find(|(k, a)| *a == alias && **k != self.entry)
+        {
+            bail!(fl!("alias_taken", alias = alias, kernel = kernel.as_str()));
+        }
+
+        Ok(Self {
+            alias: Some(alias.to_owned()),
+            ..self.clone()
+        })
+    }
+
+    /// Remember the alias once the kernel is installed under it, the entries
+    /// named after `previous` are removed and the default entry follows
+    pub fn save_alias(&self, previous: &Self) -> Result<()> {
+        let alias = match &self.alias {
+            Some(alias) => alias,
+            None => return Ok(()),
+        };
+
+        invalidate_list_cache();
+
+        let entries_path = self.esp_mountpoint.join(REL_ENTRY_PATH);
+        let default = self.sbconf.borrow().config.default.clone();
+
+        for profile in self.bootargs.borrow().keys() {
+            let old = format!("{}.conf", previous.entry_id(profile));
+            let new = format!("{}.conf", self.entry_id(profile));
+
+            if old == new {
+                continue;
+            }
+
+            if default.as_ref() == Some(&old) {
+                self.sbconf.borrow_mut().config.default = Some(new.clone());
+                self.sbconf.borrow().write_config()?;
+                journal::record(
+                    &self.esp_mountpoint,
+                    journal::Event::DefaultChanged {
+                        old: Some(old.clone()),
+                        new: Some(new),
+                    },
+                );
+            }
+
+            let entry = entries_path.join(old);
+
+            if entry.exists() {
+                fs::remove_file(entry)?;
+            }
+        }
+
+        let mut aliases = read_aliases(&self.esp_mountpoint)?;
+        aliases.insert(self.entry.clone(), alias.to_owned());
+        write_aliases(&self.esp_mountpoint, &aliases)?;
+
+        Ok(())
+    }
+
+    /// Check if the kernel is managed by friend, according to its flavor,
+    /// `never_touch` and whether it belongs to an ostree deployment
+    fn is_managed(&self, config: &Config) -> bool {
+        self.unmanaged_reason(config).is_none()
+    }
+
+    /// Tell why friend leaves the kernel alone, if it does
+    fn unmanaged_reason(&self, config: &Config) -> Option<SkipReason> {
+        if !config.manages(&self.version.localversion) {
+            return Some(SkipReason::Unmanaged);
+        }
+
+        if let Some(pattern) = config.never_touch_match(&self.entry) {
+            return Some(SkipReason::NeverTouch(pattern.to_owned()));
+        }
+
+        (!config.manage_ostree && is_ostree_dir(&config.modules_path.join(&self.entry)))
+            .then_some(SkipReason::Ostree)
+    }
+
+    /// Group the profiles generating identical entries for the kernel, with
+    /// its overrides and `skip_profiles` applied
+    pub fn duplicate_profiles(&self) -> Vec<Vec<String>> {
+        let bootargs = self
+            .bootargs
+            .borrow()
+            .iter()
+            .filter(|(profile, _)| !self.skips_profile(profile))
+            .map(|(profile, options)| (profile.to_owned(), options.to_owned()))
+            .collect();
+
+        duplicate_profiles(&bootargs)
+    }
+
+    /// Check if the profile generates no entry for the kernel according to
+    /// `skip_profiles`
+    fn skips_profile(&self, profile: &str) -> bool {
+        self.skip_profiles.get(profile).is_some_and(|patterns| {
+            patterns
+                .iter()
+                .any(|p| match_entry(p, [self.entry.as_str()]).is_ok_and(|m| m.is_some()))
+        })
+    }
+
+    /// Check if the source was removed by `remove_sources` and the copy in
+    /// the ESP still matches the checksum recorded then
+    fn source_removed(&self, src: &Path, dest: &Path) -> Result<bool> {
+        if src.exists() || !dest.exists() {
+            return Ok(false);
+        }
+
+        let manifest = Manifest::read(&self.esp_mountpoint)?;
+
+        Ok(match dest.file_name().and_then(|n| n.to_str()) {
+            Some(name) => manifest.hashes.get(name) == Some(&sha256_file(dest)?),
+            None => false,
+        })
+    }
+
+    /// Remove the kernel image and initrd from the source directory once
+    /// the copies in the ESP are verified, record the checksums instead
+    fn remove_sources(&self) -> Result<()> {
+        let mut manifest = Manifest::read(&self.esp_mountpoint)?;
+
+        if !manifest.remove_sources_confirmed {
+            // Never remove the files without the user knowing the risks
+            if !console::user_attended() {
+                println_with_prefix_and_fl!("remove_sources_unconfirmed");
+                return Ok(());
+            }
+
+            print_block_with_fl!("note_remove_sources");
+
+            if !Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(fl!("ask_remove_sources"))
+                .default(false)
+                .interact()?
+            {
+                return Ok(());
+            }
+
+            manifest.remove_sources_confirmed = true;
+        }
+
+        let mut verified = Vec::new();
+
+        for (src, dest) in self.source_files() {
+            // The source is the only copy if it is also the destination
+            if !src.exists() || same_path(&src, &dest) {
+                continue;
+            }
+
+            let hash = sha256_file(&dest)?;
+
+            if sha256_file(&src)? != hash {
+                bail!(fl!("err_copy_mismatch", path = src.to_string_lossy()));
+            }
+
+            verified.push((src, dest, hash));
+        }
+
+        for (src, dest, hash) in verified {
+            if let Some(name) = dest.file_name().and_then(|n| n.to_str()) {
+                manifest.hashes.insert(name.to_owned(), hash);
+            }
+
+            println_with_prefix_and_fl!("remove_source", path = src.to_string_lossy());
+            fs::remove_file(&src)?;
+        }
+
+        manifest.write(&self.esp_mountpoint)?;
+
+        Ok(())
+    }
+
+    /// Read the modules path to get the available kernels, along with the
+    /// directories skipped and why
+    pub fn scan_modules(
+        config: &Config,
+        sbconf: Rc<RefCell<SystemdBootConf>>,
+    ) -> Result<(Vec<Self>, Vec<SkippedKernel>)> {
+        let mut dirnames = Vec::new();
+
+        for f in fs::read_dir(&config.modules_path)? {
+            dirnames.push(
+                f?.file_name()
+                    .into_string()
+                    .map_err(|s| anyhow!("{} {:?}", fl!("invalid_dirname"), s))?,
+            );
+        }
+
+        // Keep the result deterministic regardless of the directory order
+        dirnames.sort();
+
+        let mut kernels: Vec<Self> = Vec::new();
+        let mut skipped = Vec::new();
+
+        for dirname in dirnames {
+            let dirpath = config.modules_path.join(&dirname);
+
+            if !(dirpath.join("modules.dep").exists()
+                && dirpath.join("modules.order").exists()
+                && dirpath.join("modules.builtin").exists())
+            {
+                skipped.push(SkippedKernel {
+                    dirname,
+                    reason: SkipReason::Incomplete,
+                });
+                continue;
+            }
+
+            // The files of the ostree deployments are managed by ostree
+            if !config.manage_ostree && is_ostree_dir(&dirpath) {
+                skipped.push(SkippedKernel {
+                    dirname,
+                    reason: SkipReason::Ostree,
+                });
+                continue;
+            }
+
+            let kernel = match Self::parse(config, &dirname, sbconf.clone()) {
+                Ok(k) => k,
+                Err(_) => {
+                    skipped.push(SkippedKernel {
+                        dirname,
+                        reason: SkipReason::Unidentified,
+                    });
+                    continue;
+                }
+            };
+
+            if !config.manages(&kernel.version.localversion) {
+                skipped.push(SkippedKernel {
+                    dirname,
+                    reason: SkipReason::Unmanaged,
+                });
+                continue;
+            }
+
+            if let Some(p) = config.never_touch_match(&kernel.entry) {
+                skipped.push(SkippedKernel {
+                    dirname,
+                    reason: SkipReason::NeverTouch(p.to_owned()),
+                });
+                continue;
+            }
+
+            // Different directories may parse to the same version, prefer
+            // the one named exactly after the version
+            match kernels.iter_mut().find(|k| k.version == kernel.version) {
+                Some(k) if dirname == kernel.version.to_string() => {
+                    skipped.push(SkippedKernel {
+                        dirname: k.entry.clone(),
+                        reason: SkipReason::Duplicate(dirname),
+                    });
+                    *k = kernel;
+                }
+                Some(k) => skipped.push(SkippedKernel {
+                    dirname,
+                    reason: SkipReason::Duplicate(k.entry.clone()),
+                }),
+                None => kernels.push(kernel),
+            }
+        }
+
+        let (ukis, skipped_ukis) = Self::scan_uki(config, sbconf)?;
+        kernels.extend(ukis);
+        skipped.extend(skipped_ukis);
+
         // Sort the vector, thus the kernels are
         // arranged with versions from newer to older
-        installed_kernels.sort_by(|a, b| b.cmp(a));
+        kernels.sort_by(|a, b| b.cmp(a));
+
+        Ok((kernels, skipped))
+    }
+
+    /// Scan EFI/Linux in the ESP for the unified kernel images, if
+    /// `scan_uki` is set. They are both available and installed, and the
+    /// images made by convert-to-uki are left out.
+    fn scan_uki(
+        config: &Config,
+        sbconf: Rc<RefCell<SystemdBootConf>>,
+    ) -> Result<(Vec<Self>, Vec<SkippedKernel>)> {
+        let mut kernels = Vec::new();
+        let mut skipped = Vec::new();
+
+        let d = match fs::read_dir(config.esp_mountpoint.join(REL_UKI_PATH)) {
+            Ok(d) if config.scan_uki => d,
+            _ => return Ok((kernels, skipped)),
+        };
+        let converted = Manifest::read(&config.esp_mountpoint)?.converted_ukis;
+        let mut filenames = d
+            .filter_map(|e| e.ok()?.file_name().into_string().ok())
+            .filter(|f| f.ends_with(".efi") && !converted.contains(f))
+            .collect::<Vec<_>>();
+        filenames.sort();
+
+        for filename in filenames {
+            match Self::parse(config, &filename, sbconf.clone()) {
+                Ok(k) if !config.manages(&k.version.localversion) => skipped.push(SkippedKernel {
+                    dirname: filename,
+                    reason: SkipReason::Unmanaged,
+                }),
+                Ok(k) => match config.never_touch_match(&k.entry) {
+                    Some(p) => skipped.push(SkippedKernel {
+                        dirname: filename,
+                        reason: SkipReason::NeverTouch(p.to_owned()),
+                    }),
+                    None => kernels.push(k),
+                },
+                Err(_) => skipped.push(SkippedKernel {
+                    dirname: filename,
+                    reason: SkipReason::Unidentified,
+                }),
+            }
+        }
+
+        Ok((kernels, skipped))
+    }
+
+    /// Generate installed kernel list from the boot entries managed by friend
+    pub fn list_installed_from_entries(
+        config: &Config,
+        sbconf: Rc<RefCell<SystemdBootConf>>,
+    ) -> Result<Vec<Self>> {
+        let mut installed_kernels = Self::scan_entries(config, sbconf)?;
+
+        // The kernels of other flavors, ostree deployments and those in
+        // `never_touch` are never touched
+        installed_kernels.retain(|k| k.is_managed(config));
+        sort_installed(config, &mut installed_kernels);
 
         Ok(installed_kernels)
     }
+
+    /// Find the kernels referred to by the entries, managed or not
+    fn scan_entries(config: &Config, sbconf: Rc<RefCell<SystemdBootConf>>) -> Result<Vec<Self>> {
+        let mut installed_kernels: Vec<Self> = Vec::new();
+
+        // Construct regex for the template
+        let re = template_regex(&config.vmlinux)?;
+
+        for entry in load_entries(&config.esp_mountpoint, |_| true)? {
+            for token in entry.tokens.iter() {
+                let path = match token {
+                    Token::Linux(p) => p.strip_prefix("/").unwrap_or(p),
+                    _ => continue,
+                };
+
+                // Skip entries not managed by friend
+                if !path.starts_with(REL_DEST_PATH) {
+                    continue;
+                }
+
+                let filename = path
+                    .file_name()
+                    .and_then(|f| f.to_str())
+                    .ok_or_else(|| anyhow!(fl!("invalid_kernel_filename")))?;
+
+                if let Some(c) = re.captures(filename) {
+                    let version = c
+                        .name("version")
+                        .ok_or_else(|| anyhow!(fl!("invalid_kernel_filename")))?
+                        .as_str();
+                    let kernel = Self::parse(config, version, sbconf.clone())?;
+
+                    // A kernel may have multiple entries for different profiles
+                    if !installed_kernels.contains(&kernel) {
+                        installed_kernels.push(kernel);
+                    }
+                }
+            }
+        }
+
+        // The unified kernel images have no entries
+        installed_kernels.extend(Self::scan_uki(config, sbconf)?.0);
+
+        Ok(installed_kernels)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template_regex() {
+        let re = template_regex("vmlinuz-{VERSION}").unwrap();
+        assert_eq!(
+            &re.captures("vmlinuz-6.8.0-aosc-main").unwrap()["version"],
+            "6.8.0-aosc-main"
+        );
+        assert!(re.captures("old-vmlinuz-6.8.0").is_none());
+
+        let re = template_regex("vmlinuz+(x86)[{VERSION}].efi").unwrap();
+        assert_eq!(
+            &re.captures("vmlinuz+(x86)[6.8.0].efi").unwrap()["version"],
+            "6.8.0"
+        );
+        assert!(re.captures("vmlinuzz(x86)[6.8.0].efi").is_none());
+        assert!(re.captures("vmlinuz+(x86)[6.8.0]xefi").is_none());
+    }
+
+    #[test]
+    fn test_looks_like_initrd() {
+        assert!(looks_like_initrd(b"\x1f\x8b\x08\x00\x00\x00"));
+        assert!(looks_like_initrd(b"\x28\xb5\x2f\xfd\x00\x58"));
+        assert!(looks_like_initrd(b"070701"));
+        assert!(!looks_like_initrd(b"<html>"));
+        assert!(!looks_like_initrd(b""));
+    }
 }
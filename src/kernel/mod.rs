@@ -1,28 +1,215 @@
 use anyhow::Result;
-use libsdbootconf::SystemdBootConf;
+use libsdbootconf::{entry::Entry, SystemdBootConf};
 use same_file::is_same_file;
-use std::{cell::RefCell, fmt::Display, fs, path::Path, rc::Rc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt::Display,
+    fs,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
-use crate::config::Config;
+use crate::{
+    config::Config,
+    fl,
+    status::{self, Event},
+    REL_DEST_PATH,
+};
 
-const REL_ENTRY_PATH: &str = "loader/entries/";
+pub const REL_ENTRY_PATH: &str = "loader/entries/";
+/// Where systemd-boot discovers unified kernel images automatically
+pub const REL_UKI_PATH: &str = "EFI/Linux/";
+/// Left in the module directories of ostree deployments
+const OSTREE_COMMIT_MARKER: &str = ".ostree-commit";
+/// Length of the checksums suffixed to the kernel images by ostree
+const OSTREE_CHECKSUM_LEN: usize = 64;
+/// Extension of the entry files being written, not loaded as entries
+pub(crate) const TMP_ENTRY_EXTENSION: &str = "conf.sbf-tmp";
+/// Prefix of the entry files of hidden profiles
+pub const HIDDEN_ENTRY_PREFIX: &str = ".";
+/// Keys of the checksums embedded into the entries, in the order of the
+/// kernel image and initrd in `source_files`
+pub const EMBEDDED_HASH_KEYS: [&str; 2] = ["vmlinuz", "initrd"];
+/// FAT stores modification times at a 2-second granularity
+const FAT_MTIME_GRANULARITY: u64 = 2;
+/// Size of the chunks the kernel files are copied in
+const COPY_BUFFER_SIZE: usize = 4 << 20;
 
-pub trait Kernel: Display + Clone + PartialEq {
-    fn parse(
-        config: &Config,
-        kernel_name: &str,
-        sbconf: Rc<RefCell<SystemdBootConf>>,
-    ) -> Result<Self>;
-    fn install(&self) -> Result<()>;
+/// The state of a kernel, for the JSON output and the integrations
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KernelSummary {
+    pub version: String,
+    pub vmlinux: String,
+    pub initrd: String,
+    /// The entry files of the kernel existing in the ESP
+    pub entry_files: Vec<String>,
+    pub is_installed: bool,
+    pub is_default: bool,
+    /// Contents of `pkgbase` and `build-id` in the module directory, empty
+    /// if missing
+    pub pkgbase: String,
+    pub build_id: String,
+}
+
+/// The operations on a single kernel, object-safe so that kernels of
+/// different types can be managed together as `Rc<dyn KernelOps>`
+pub trait KernelOps: Display {
+    fn install(&self, src_path: Option<&Path>) -> Result<()>;
+    /// Check the source files and list the copies `install` would make, as
+    /// (source, destination), so that the copies of several kernels can be
+    /// ordered together. None if the kernel can only be installed at once.
+    fn install_copies(&self, _src_path: Option<&Path>) -> Result<Option<Vec<(PathBuf, PathBuf)>>> {
+        Ok(None)
+    }
+    /// Complete the installation once the copies listed by
+    /// `install_copies` are made
+    fn finish_install(&self, _src_path: Option<&Path>) -> Result<()> {
+        Ok(())
+    }
+    fn install_from(&self, image: &Path, initrd: Option<&Path>) -> Result<()>;
     fn remove(&self) -> Result<()>;
     fn make_config(&self, force_write: bool) -> Result<()>;
-    fn set_default(&self) -> Result<()>;
+    fn set_default(&self, announce: bool) -> Result<()>;
     fn remove_default(&self) -> Result<()>;
     fn ask_set_default(&self) -> Result<()>;
     fn is_default(&self) -> Result<bool>;
     fn install_and_make_config(&self, force_write: bool) -> Result<()>;
+    fn name(&self) -> &str;
+    /// The flavor of the kernel, e.g. "aosc-main", empty if it has none
+    fn flavor(&self) -> &str {
+        ""
+    }
+    fn entry_id(&self, profile: &str) -> String;
+    fn changed_files(&self) -> Result<Vec<PathBuf>>;
+    fn esp_files(&self) -> Vec<PathBuf>;
+    fn source_files(&self) -> Vec<(PathBuf, PathBuf)>;
+    fn summary(&self) -> Result<KernelSummary>;
+}
+
+/// The discovery of the kernels of a type
+pub trait Kernel: KernelOps + Clone + PartialEq {
+    fn parse(
+        config: &Config,
+        kernel_name: &str,
+        sbconf: Rc<RefCell<SystemdBootConf>>,
+    ) -> Result<Self>;
     fn list(config: &Config, sbconf: Rc<RefCell<SystemdBootConf>>) -> Result<Vec<Self>>;
     fn list_installed(config: &Config, sbconf: Rc<RefCell<SystemdBootConf>>) -> Result<Vec<Self>>;
+    /// Forget the lists of kernels cached in the session, e.g. when the
+    /// files may have been changed by another process
+    fn invalidate_cache() {}
+}
+
+/// A kernel of any type
+pub type DynKernel = Rc<dyn KernelOps>;
+
+/// Kernels of different types are the same if they install the same files
+impl PartialEq for dyn KernelOps + '_ {
+    fn eq(&self, other: &Self) -> bool {
+        self.name() == other.name() && self.source_files() == other.source_files()
+    }
+}
+
+impl<T: KernelOps + ?Sized> KernelOps for Rc<T> {
+    fn install(&self, src_path: Option<&Path>) -> Result<()> {
+        (**self).install(src_path)
+    }
+
+    fn install_copies(&self, src_path: Option<&Path>) -> Result<Option<Vec<(PathBuf, PathBuf)>>> {
+        (**self).install_copies(src_path)
+    }
+
+    fn finish_install(&self, src_path: Option<&Path>) -> Result<()> {
+        (**self).finish_install(src_path)
+    }
+
+    fn install_from(&self, image: &Path, initrd: Option<&Path>) -> Result<()> {
+        (**self).install_from(image, initrd)
+    }
+
+    fn remove(&self) -> Result<()> {
+        (**self).remove()
+    }
+
+    fn make_config(&self, force_write: bool) -> Result<()> {
+        (**self).make_config(force_write)
+    }
+
+    fn set_default(&self, announce: bool) -> Result<()> {
+        (**self).set_default(announce)
+    }
+
+    fn remove_default(&self) -> Result<()> {
+        (**self).remove_default()
+    }
+
+    fn ask_set_default(&self) -> Result<()> {
+        (**self).ask_set_default()
+    }
+
+    fn is_default(&self) -> Result<bool> {
+        (**self).is_default()
+    }
+
+    fn install_and_make_config(&self, force_write: bool) -> Result<()> {
+        (**self).install_and_make_config(force_write)
+    }
+
+    fn name(&self) -> &str {
+        (**self).name()
+    }
+
+    fn flavor(&self) -> &str {
+        (**self).flavor()
+    }
+
+    fn entry_id(&self, profile: &str) -> String {
+        (**self).entry_id(profile)
+    }
+
+    fn changed_files(&self) -> Result<Vec<PathBuf>> {
+        (**self).changed_files()
+    }
+
+    fn esp_files(&self) -> Vec<PathBuf> {
+        (**self).esp_files()
+    }
+
+    fn source_files(&self) -> Vec<(PathBuf, PathBuf)> {
+        (**self).source_files()
+    }
+
+    fn summary(&self) -> Result<KernelSummary> {
+        (**self).summary()
+    }
+}
+
+/// Find a kernel by its name or version string
+pub fn find_kernel<'a, K: KernelOps>(kernels: &'a [K], version: &str) -> Option<&'a K> {
+    kernels
+        .iter()
+        .find(|k| k.name() == version)
+        .or_else(|| kernels.iter().find(|k| k.to_string() == version))
+}
+
+/// Keep the kernels of the version of `since` or newer, and then the
+/// `newest` ones of them, the kernels are sorted newest first
+pub fn filter_kernels<K: Clone + PartialOrd>(
+    kernels: &[K],
+    newest: Option<usize>,
+    since: Option<&K>,
+) -> Vec<K> {
+    kernels
+        .iter()
+        .filter(|k| since.is_none_or(|s| *k >= s))
+        .take(newest.unwrap_or(usize::MAX))
+        .cloned()
+        .collect()
 }
 
 pub fn file_copy<P, Q>(src: P, dest: Q) -> Result<()>
@@ -30,13 +217,475 @@ where
     P: AsRef<Path>,
     Q: AsRef<Path>,
 {
-    // Only copy if the dest file is missing / different
-    if !dest.as_ref().exists() || !is_same_file(&src, &dest)? {
-        fs::copy(&src, &dest)?;
+    let (src, dest) = (src.as_ref(), dest.as_ref());
+
+    // When the ESP is mounted at /boot, the source and destination may be
+    // the same path, which must not be copied onto itself
+    if same_path(src, dest) {
+        return Ok(());
+    }
+
+    // A hard link of the source, replace it with a copy of its own
+    if dest.exists() && is_same_file(src, dest)? {
+        fs::remove_file(dest)?;
+    }
+
+    let src_meta = fs::metadata(src)?;
+
+    if dest.exists() {
+        let dest_meta = fs::metadata(dest)?;
+
+        if src_meta.len() == dest_meta.len()
+            && mtime_eq(src_meta.modified()?, dest_meta.modified()?)
+        {
+            return Ok(());
+        }
+
+        // The source may only have been touched, e.g. by reinstalling the
+        // package, so compare the contents before rewriting the ESP
+        if !same_content(src, dest)? {
+            copy(src, dest)?;
+        }
+    } else {
+        copy(src, dest)?;
     }
 
+    // Neither copying method carries the mtime over
+    fs::File::options()
+        .write(true)
+        .open(dest)?
+        .set_modified(fat_mtime(src_meta.modified()?))?;
+
     Ok(())
 }
 
+/// Check if the paths resolve to the same location
+pub fn same_path(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Round a timestamp down to what FAT is able to store
+pub(crate) fn fat_mtime(time: SystemTime) -> SystemTime {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| {
+        d.as_secs() / FAT_MTIME_GRANULARITY * FAT_MTIME_GRANULARITY
+    });
+
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+/// Compare the modification times at FAT granularity, i.e. timestamps
+/// within 2 seconds are considered equal
+pub fn mtime_eq(a: SystemTime, b: SystemTime) -> bool {
+    let diff = a.duration_since(b).unwrap_or_else(|e| e.duration());
+
+    diff <= Duration::from_secs(FAT_MTIME_GRANULARITY)
+}
+
+/// Calculate the SHA-256 checksum of a file in hex
+pub fn sha256_file<P: AsRef<Path>>(path: P) -> Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1 << 16];
+
+    loop {
+        let n = file.read(&mut buf)?;
+
+        if n == 0 {
+            break;
+        }
+
+        hasher.update(&buf[..n]);
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Parse the `# sha256 <key>=<hash>` comments embedded into an entry
+pub fn embedded_hashes(entry: &str) -> HashMap<String, String> {
+    entry
+        .lines()
+        .filter_map(|l| l.trim().strip_prefix("# sha256 "))
+        .filter_map(|l| l.split_once('='))
+        .map(|(k, v)| (k.trim().to_owned(), v.trim().to_owned()))
+        .collect()
+}
+
+/// Compare the contents of two files chunk by chunk
+fn same_content(a: &Path, b: &Path) -> Result<bool> {
+    let (mut a, mut b) = (fs::File::open(a)?, fs::File::open(b)?);
+
+    if a.metadata()?.len() != b.metadata()?.len() {
+        return Ok(false);
+    }
+
+    let (mut buf_a, mut buf_b) = (vec![0u8; 1 << 16], vec![0u8; 1 << 16]);
+
+    loop {
+        let n = a.read(&mut buf_a)?;
+
+        if n == 0 {
+            return Ok(true);
+        }
+
+        b.read_exact(&mut buf_b[..n])?;
+
+        if buf_a[..n] != buf_b[..n] {
+            return Ok(false);
+        }
+    }
+}
+
+/// Copy a file chunk by chunk, report the progress to the status file
+/// descriptor. The large chunks keep slow ESPs such as SD cards busy with
+/// sequential writes, which the small buffer of `fs::copy` does not.
+fn copy(src: &Path, dest: &Path) -> Result<()> {
+    let mut reader = fs::File::open(src)?;
+    let mut writer = fs::File::create(dest)?;
+    let total = reader.metadata()?.len();
+    let mut buf = vec![0u8; COPY_BUFFER_SIZE];
+    let mut copied = 0u64;
+    let mut last_percent = None;
+
+    loop {
+        let n = reader.read(&mut buf)?;
+
+        if n == 0 {
+            break;
+        }
+
+        writer.write_all(&buf[..n])?;
+        copied += n as u64;
+
+        let percent = (copied * 100).checked_div(total).unwrap_or(100) as u8;
+
+        if last_percent != Some(percent) {
+            status::emit(Event::Copy {
+                file: dest,
+                percent,
+            });
+            last_percent = Some(percent);
+        }
+    }
+
+    if last_percent != Some(100) {
+        status::emit(Event::Copy {
+            file: dest,
+            percent: 100,
+        });
+    }
+
+    fs::set_permissions(dest, reader.metadata()?.permissions())?;
+
+    Ok(())
+}
+
+/// List the ids of all the boot entries without parsing them
+pub fn list_entry_ids(esp_mountpoint: &Path) -> Result<Vec<String>> {
+    let mut ids = Vec::new();
+
+    for f in fs::read_dir(esp_mountpoint.join(REL_ENTRY_PATH))? {
+        let path = f?.path();
+
+        if path.extension().is_some_and(|e| e == "conf") {
+            if let Some(id) = path.file_stem().and_then(|s| s.to_str()) {
+                ids.push(id.to_owned());
+            }
+        }
+    }
+
+    Ok(ids)
+}
+
+/// Load the boot entries whose ids satisfy the filter, so that
+/// unrelated entries (e.g. snapshots) are not parsed. The entries that
+/// fail to parse, e.g. those written by other tools, are skipped
+pub fn load_entries<F>(esp_mountpoint: &Path, filter: F) -> Result<Vec<Entry>>
+where
+    F: Fn(&str) -> bool,
+{
+    let entries_path = esp_mountpoint.join(REL_ENTRY_PATH);
+    let mut entries = Vec::new();
+
+    for id in list_entry_ids(esp_mountpoint)? {
+        if filter(&id) {
+            let path = entries_path.join(id + ".conf");
+
+            match Entry::load(&path) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => eprintln!(
+                    "Warning: {}: {}",
+                    path.display(),
+                    fl!("entry_unparsable", error = e.to_string())
+                ),
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Write the entries of a kernel as a set, so that a failure leaves the
+/// previous entries untouched: all the entries are written to temporary
+/// files with `write` before any of them is renamed into place
+pub fn write_entry_set<F>(entries_path: &Path, entries: &[Entry], mut write: F) -> Result<()>
+where
+    F: FnMut(&Entry, &Path) -> Result<()>,
+{
+    let tmp_path = |e: &Entry| entries_path.join(format!("{}.{}", e.id, TMP_ENTRY_EXTENSION));
+    let remove_tmp = |entries: &[Entry]| {
+        for e in entries {
+            fs::remove_file(tmp_path(e)).ok();
+        }
+    };
+
+    for (n, entry) in entries.iter().enumerate() {
+        if let Err(e) = write(entry, &tmp_path(entry)) {
+            remove_tmp(&entries[..=n]);
+            return Err(e);
+        }
+    }
+
+    for (n, entry) in entries.iter().enumerate() {
+        let dest = entries_path.join(format!("{}.conf", entry.id));
+
+        if let Err(e) = fs::rename(tmp_path(entry), dest) {
+            remove_tmp(&entries[n..]);
+            return Err(e.into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Check if the module directory belongs to an ostree deployment, which
+/// has a commit marker or a kernel image with a checksum suffix
+pub fn is_ostree_dir(dirpath: &Path) -> bool {
+    if dirpath.join(OSTREE_COMMIT_MARKER).exists() {
+        return true;
+    }
+
+    fs::read_dir(dirpath).is_ok_and(|d| {
+        d.filter_map(|e| e.ok()?.file_name().into_string().ok())
+            .any(|f| {
+                f.strip_prefix("vmlinuz-").is_some_and(|c| {
+                    c.len() == OSTREE_CHECKSUM_LEN && c.chars().all(|c| c.is_ascii_hexdigit())
+                })
+            })
+    })
+}
+
+/// List the files in the ESP named after the ostree deployments, which are
+/// managed by ostree unless `manage_ostree` is set
+pub fn ostree_esp_files(config: &Config) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    if config.manage_ostree || !config.modules_path.exists() {
+        return Ok(files);
+    }
+
+    let dest_path = config.esp_mountpoint.join(REL_DEST_PATH);
+
+    for entry in fs::read_dir(&config.modules_path)? {
+        let entry = entry?;
+
+        if let Some(dirname) = entry.file_name().to_str() {
+            if is_ostree_dir(&entry.path()) {
+                for template in [&config.vmlinux, &config.initrd] {
+                    files.push(dest_path.join(template.replace("{VERSION}", dirname)));
+                }
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// The profiles which may have entries for the kernel, including those only
+/// in its override
+fn profiles(config: &Config, kernel: &str) -> Result<BTreeSet<String>> {
+    let mut profiles = config
+        .bootargs
+        .borrow()
+        .keys()
+        .cloned()
+        .collect::<BTreeSet<_>>();
+
+    if let Some(o) = config.kernel_override(kernel)? {
+        profiles.extend(o.bootargs.into_keys());
+    }
+
+    Ok(profiles)
+}
+
+/// Read the entry files of the kernel, by profile
+pub(crate) fn read_entries<K: KernelOps>(
+    config: &Config,
+    kernel: &K,
+) -> Result<BTreeMap<String, (String, String)>> {
+    let entries_path = config.esp_mountpoint.join(REL_ENTRY_PATH);
+    let mut entries = BTreeMap::new();
+
+    for profile in profiles(config, kernel.name())? {
+        let file = format!("{}.conf", kernel.entry_id(&profile));
+
+        if let Ok(content) = fs::read_to_string(entries_path.join(&file)) {
+            entries.insert(profile, (file, content));
+        }
+    }
+
+    Ok(entries)
+}
+
 #[cfg(feature = "generic")]
 pub mod generic_kernel;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use filetime::{set_file_mtime, FileTime};
+
+    #[test]
+    fn test_filter_kernels() {
+        let kernels = [9, 8, 7, 6, 5];
+
+        assert_eq!(filter_kernels(&kernels, Some(2), None), [9, 8]);
+        assert_eq!(filter_kernels(&kernels, None, Some(&7)), [9, 8, 7]);
+        assert_eq!(filter_kernels(&kernels, Some(5), Some(&8)), [9, 8]);
+    }
+
+    #[test]
+    fn test_write_entry_set_failure() {
+        let esp = tempfile::tempdir().unwrap();
+        let entries_path = esp.path().join(REL_ENTRY_PATH);
+        fs::create_dir_all(&entries_path).unwrap();
+        fs::write(entries_path.join("6.7.1-default.conf"), "old").unwrap();
+
+        let entries = ["6.7.1-default", "6.7.1-fallback"]
+            .map(|id| Entry::new(id, Vec::new()))
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        // Fail between the writes of the two entries
+        let mut writes = 0;
+        let result = write_entry_set(&entries_path, &entries, |_, path| {
+            writes += 1;
+            fs::write(path, "new")?;
+
+            if writes == 2 {
+                anyhow::bail!("injected failure");
+            }
+
+            Ok(())
+        });
+
+        assert!(result.is_err());
+        assert_eq!(
+            fs::read_to_string(entries_path.join("6.7.1-default.conf")).unwrap(),
+            "old"
+        );
+        assert_eq!(list_entry_ids(esp.path()).unwrap(), ["6.7.1-default"]);
+        assert_eq!(fs::read_dir(&entries_path).unwrap().count(), 1);
+
+        write_entry_set(&entries_path, &entries, |_, path| {
+            Ok(fs::write(path, "new")?)
+        })
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(entries_path.join("6.7.1-fallback.conf")).unwrap(),
+            "new"
+        );
+        assert_eq!(fs::read_dir(&entries_path).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_load_entries_with_many_snapshots() {
+        let esp = tempfile::tempdir().unwrap();
+        let entries_path = esp.path().join(REL_ENTRY_PATH);
+
+        fs::create_dir_all(&entries_path).unwrap();
+
+        for i in 0..3000 {
+            fs::write(
+                entries_path.join(format!("snapshot-{}.conf", i)),
+                format!("title Snapshot {}\nlinux /vmlinuz\n", i),
+            )
+            .unwrap();
+        }
+
+        fs::write(
+            entries_path.join("5.10.0-aosc-main-default.conf"),
+            "title AOSC OS (5.10.0-aosc-main) (default)\nlinux /EFI/systemd-boot-friend/vmlinuz-5.10.0-aosc-main\n",
+        )
+        .unwrap();
+
+        assert_eq!(list_entry_ids(esp.path()).unwrap().len(), 3001);
+
+        // Each id is filtered once, before its entry would be parsed
+        let filtered = std::cell::Cell::new(0);
+        let entries = load_entries(esp.path(), |id| {
+            filtered.set(filtered.get() + 1);
+            id.starts_with("5.10.0-aosc-main-")
+        })
+        .unwrap();
+
+        assert_eq!(filtered.get(), 3001);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].id, "5.10.0-aosc-main-default");
+    }
+
+    #[test]
+    fn test_fat_mtime() {
+        let t = |secs, nanos| UNIX_EPOCH + Duration::new(secs, nanos);
+
+        assert_eq!(fat_mtime(t(1000, 0)), t(1000, 0));
+        assert_eq!(fat_mtime(t(1001, 999_999_999)), t(1000, 0));
+        assert!(mtime_eq(t(1001, 999_999_999), t(1000, 0)));
+        assert!(mtime_eq(t(1000, 0), t(1002, 0)));
+        assert!(!mtime_eq(t(1000, 0), t(1003, 0)));
+        // A DST change shifts the local time stored on FAT by an hour
+        assert!(!mtime_eq(t(1000, 0), t(4600, 0)));
+    }
+
+    #[test]
+    fn test_file_copy_mtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        let dest = dir.path().join("dest");
+
+        fs::write(&src, "new").unwrap();
+        set_file_mtime(&src, FileTime::from_unix_time(1001, 500_000_000)).unwrap();
+        file_copy(&src, &dest).unwrap();
+
+        assert_eq!(fs::read(&dest).unwrap(), b"new");
+        assert_eq!(
+            FileTime::from_last_modification_time(&fs::metadata(&dest).unwrap()),
+            FileTime::from_unix_time(1000, 0)
+        );
+
+        // Same mtime at FAT granularity but different size: copied
+        fs::write(&dest, "old!").unwrap();
+        set_file_mtime(&dest, FileTime::from_unix_time(1000, 0)).unwrap();
+        file_copy(&src, &dest).unwrap();
+
+        assert_eq!(fs::read(&dest).unwrap(), b"new");
+
+        // Touched source with the same content: only the mtime is updated
+        set_file_mtime(&src, FileTime::from_unix_time(5000, 0)).unwrap();
+        file_copy(&src, &dest).unwrap();
+
+        assert_eq!(
+            FileTime::from_last_modification_time(&fs::metadata(&dest).unwrap()),
+            FileTime::from_unix_time(5000, 0)
+        );
+
+        // Same mtime and size are trusted without reading the contents
+        fs::write(&dest, "old").unwrap();
+        set_file_mtime(&dest, FileTime::from_unix_time(5001, 0)).unwrap();
+        file_copy(&src, &dest).unwrap();
+
+        assert_eq!(fs::read(&dest).unwrap(), b"old");
+    }
+}
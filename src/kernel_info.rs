@@ -0,0 +1,102 @@
+use anyhow::Result;
+use console::style;
+use std::{fs, path::Path};
+
+use crate::{
+    config::Config,
+    fl,
+    kernel::{generic_kernel::GenericKernel, read_entries, KernelOps},
+    reboot::running_kernel,
+    util::{format_utc, human_size},
+};
+
+/// Print a file with its size and modification time
+fn print_file(path: &Path) {
+    match fs::metadata(path) {
+        Ok(m) => println!(
+            "    {} ({}, {})",
+            path.display(),
+            human_size(m.len()),
+            m.modified().map_or_else(|_| "-".to_owned(), format_utc)
+        ),
+        Err(_) => println!(
+            "    {} ({})",
+            path.display(),
+            style(fl!("entry_missing")).red()
+        ),
+    }
+}
+
+/// Show everything known about a kernel, whether available or installed
+pub fn kernel_info(config: &Config, kernel: &GenericKernel) -> Result<()> {
+    let version = kernel.version();
+    let optional = |v: Option<u64>| v.map_or_else(|| "-".to_owned(), |v| v.to_string());
+
+    println!("{}", style(kernel.to_string()).bold());
+
+    println!("  {}", fl!("kernel_info_version"));
+    for (field, value) in [
+        ("major", version.major.to_string()),
+        ("minor", version.minor.to_string()),
+        ("patch", version.patch.to_string()),
+        ("rc", optional(version.rc)),
+        ("rel", optional(version.rel)),
+        ("localversion", version.localversion.clone()),
+    ] {
+        println!("    {}: {}", field, value);
+    }
+
+    let summary = kernel.summary()?;
+    let metadata = |v: String| if v.is_empty() { "-".to_owned() } else { v };
+
+    println!("  {}", fl!("kernel_info_build"));
+    for (field, value) in [
+        ("pkgbase", metadata(summary.pkgbase)),
+        ("build-id", metadata(summary.build_id)),
+    ] {
+        println!("    {}: {}", field, value);
+    }
+
+    println!("  {}", fl!("kernel_info_sources"));
+    for (src, _) in kernel.source_files() {
+        print_file(&src);
+    }
+
+    println!("  {}", fl!("kernel_info_esp_files"));
+    let esp_files = kernel.esp_files();
+    if esp_files.is_empty() {
+        println!("    {}", fl!("kernel_info_not_installed"));
+    }
+    for path in esp_files {
+        print_file(&path);
+    }
+
+    println!("  {}", fl!("kernel_info_entries"));
+    let entries = read_entries(config, kernel)?;
+    if entries.is_empty() {
+        println!("    {}", fl!("kernel_info_no_entries"));
+    }
+    for (file, content) in entries.into_values() {
+        println!("    {}", file);
+        for line in content.lines().filter(|l| !l.trim().is_empty()) {
+            println!("      │ {}", line);
+        }
+    }
+
+    println!(
+        "  {}",
+        fl!(
+            "kernel_info_default",
+            value = kernel.is_default()?.to_string()
+        )
+    );
+    println!(
+        "  {}",
+        fl!(
+            "kernel_info_running",
+            value = (running_kernel().ok().as_deref() == Some(kernel.name())).to_string()
+        )
+    );
+
+    Ok(())
+}
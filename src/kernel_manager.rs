@@ -1,18 +1,532 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use console::style;
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Reverse,
+    collections::HashSet,
+    fmt, fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::{Duration, Instant},
+};
 
 use crate::{
-    fl, kernel::Kernel, print_block_with_fl, println_with_fl, println_with_prefix,
-    println_with_prefix_and_fl, Config,
+    config::AskDefault,
+    error::SbfError,
+    fl,
+    kernel::{file_copy, find_kernel, KernelOps},
+    macros::quiet,
+    manifest::Manifest,
+    print_block_with_fl, println_with_fl, println_with_prefix, println_with_prefix_and_fl,
+    prompt::{ColorfulTheme, Confirm},
+    reboot::{self, running_kernel},
+    splash::update_splash,
+    status::{self, Event},
+    util::{human_size, render_table, wrap_width},
+    version::{generic_version::GenericVersion, Version},
+    Config,
 };
 
+/// Before and after state of an update
+#[derive(Debug, Default, Serialize)]
+pub struct UpdateReport {
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+    pub installed: Vec<String>,
+    pub removed: Vec<String>,
+    pub retained: Vec<RetainedKernel>,
+    pub default: Option<String>,
+    pub stats: UpdateStats,
+}
+
+/// What applying a plan actually did
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct UpdateStats {
+    pub kernels_installed: u32,
+    pub kernels_removed: u32,
+    pub entries_created: u32,
+    pub entries_removed: u32,
+    pub bytes_copied: u64,
+    pub duration: Duration,
+}
+
+/// What update does to a kernel, for the summary table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SummaryAction {
+    Install,
+    Refresh,
+    Remove,
+}
+
+impl fmt::Display for SummaryAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SummaryAction::Install => write!(f, "{}", fl!("summary_install")),
+            SummaryAction::Refresh => write!(f, "{}", fl!("summary_refresh")),
+            SummaryAction::Remove => write!(f, "{}", fl!("summary_remove")),
+        }
+    }
+}
+
+/// A row of the summary table of update
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SummaryRow {
+    pub kernel: String,
+    pub action: SummaryAction,
+    pub files: usize,
+    pub size: u64,
+}
+
+/// Why a kernel is retained by `update`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RetainReason {
+    /// Among the newest kernels allowed by `keep`
+    WithinKeep,
+    /// Listed in `pinned`
+    Pinned,
+    /// The running kernel
+    Running,
+    /// The current default kernel
+    Default,
+    /// The default kernel before the last change of default, kept until
+    /// the new default boots
+    PreviousDefault,
+}
+
+impl fmt::Display for RetainReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RetainReason::WithinKeep => write!(f, "{}", fl!("retain_within_keep")),
+            RetainReason::Pinned => write!(f, "{}", fl!("retain_pinned")),
+            RetainReason::Running => write!(f, "{}", fl!("retain_running")),
+            RetainReason::Default => write!(f, "{}", fl!("retain_default")),
+            RetainReason::PreviousDefault => write!(f, "{}", fl!("retain_previous_default")),
+        }
+    }
+}
+
+/// Protections of a kernel against pruning
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct KernelFlags {
+    pub pinned: bool,
+    pub running: bool,
+    pub default: bool,
+    pub previous_default: bool,
+}
+
+impl KernelFlags {
+    /// The reason to always retain the kernel, if it is protected
+    pub fn protection(&self) -> Option<RetainReason> {
+        if self.pinned {
+            Some(RetainReason::Pinned)
+        } else if self.running {
+            Some(RetainReason::Running)
+        } else if self.default {
+            Some(RetainReason::Default)
+        } else if self.previous_default {
+            Some(RetainReason::PreviousDefault)
+        } else {
+            None
+        }
+    }
+}
+
+/// Decide which of the kernels, sorted from newer to older, to retain
+///
+/// Protected kernels are always retained and do not count against the
+/// `keep` budget, which applies to the newest unprotected kernels. Returns
+/// the reason for each retained kernel, `None` for those to be removed.
+pub fn select_retained<V>(
+    kernels: &[(V, KernelFlags)],
+    keep: Option<usize>,
+) -> Vec<Option<RetainReason>> {
+    let mut budget = keep.unwrap_or(usize::MAX);
+
+    kernels
+        .iter()
+        .map(|(_, flags)| {
+            flags.protection().or_else(|| {
+                budget.checked_sub(1).map(|b| {
+                    budget = b;
+                    RetainReason::WithinKeep
+                })
+            })
+        })
+        .collect()
+}
+
+/// A kernel retained by `update`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetainedKernel {
+    pub kernel: String,
+    pub reason: RetainReason,
+}
+
+/// Steps to run when installing a kernel
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum InstallSteps {
+    #[default]
+    All,
+    /// Only copy the files to the ESP, the entries are managed elsewhere
+    CopyOnly,
+    /// Only write the entries for the files already in the ESP
+    EntryOnly,
+}
+
+impl InstallSteps {
+    fn copy(self) -> bool {
+        self != Self::EntryOnly
+    }
+
+    fn entry(self) -> bool {
+        self != Self::CopyOnly
+    }
+}
+
+/// Kernels to be installed with the reasons to retain them, and the
+/// installed kernels to be removed
+type Selection<'a, K> = (Vec<(&'a K, RetainReason)>, Vec<&'a K>, Vec<&'a K>);
+
+/// The default entry to be set by a plan
+#[derive(Debug)]
+pub struct DefaultChange<'a, K> {
+    /// The current default, if any
+    pub old: Option<&'a K>,
+    pub new: &'a K,
+    /// Print the change
+    pub announce: bool,
+    /// The flavor of the old default and the one it was renamed to, if the
+    /// default follows the renamed flavor
+    pub flavor_alias: Option<(String, String)>,
+}
+
+/// Changes to the ESP, decided without side effects and applied later
+#[derive(Debug)]
+pub struct UpdatePlan<'a, K> {
+    /// Kernels to be copied to the ESP for the first time
+    pub installs: Vec<&'a K>,
+    /// Installed kernels to be copied again, the unchanged files are skipped
+    pub refreshes: Vec<&'a K>,
+    pub removals: Vec<&'a K>,
+    /// Kernels whose entries are written
+    pub entry_writes: Vec<&'a K>,
+    pub default_change: Option<DefaultChange<'a, K>>,
+    /// Kernels retained by update, with the reasons
+    pub retained: Vec<(&'a K, RetainReason)>,
+    /// Available kernels left out by update as `manage_only` does not
+    /// match them
+    pub excluded: Vec<&'a K>,
+    /// Kernels in the order they are installed
+    order: Vec<&'a K>,
+    /// The manifest to be written to the ESP, if it changes
+    manifest: Option<(Rc<PathBuf>, Manifest)>,
+}
+
+impl<'a, K: KernelOps + PartialEq> UpdatePlan<'a, K> {
+    fn new() -> Self {
+        Self {
+            installs: Vec::new(),
+            refreshes: Vec::new(),
+            removals: Vec::new(),
+            entry_writes: Vec::new(),
+            default_change: None,
+            retained: Vec::new(),
+            excluded: Vec::new(),
+            order: Vec::new(),
+            manifest: None,
+        }
+    }
+
+    /// Plan the installation of a single kernel
+    pub fn install(kernel: &'a K, steps: InstallSteps) -> Self {
+        let mut plan = Self::new();
+
+        if steps.copy() {
+            plan.installs.push(kernel);
+        }
+
+        if steps.entry() {
+            plan.entry_writes.push(kernel);
+        }
+
+        plan.order.push(kernel);
+
+        plan
+    }
+
+    /// Plan the removal of the kernels
+    pub fn remove(kernels: &'a [K]) -> Self {
+        let mut plan = Self::new();
+        plan.removals = kernels.iter().collect();
+
+        plan
+    }
+
+    /// Describe the plan as the report of update
+    pub fn report(&self, installed_kernels: &[K]) -> UpdateReport {
+        // Whatever is not removed stays in the ESP, even the kernels update
+        // leaves alone, e.g. those installed from arbitrary paths
+        let mut after = installed_kernels
+            .iter()
+            .filter(|k| !self.removals.contains(k))
+            .chain(
+                self.installs
+                    .iter()
+                    .copied()
+                    .filter(|k| !installed_kernels.contains(k)),
+            )
+            .collect::<Vec<_>>();
+        // Kernels of other types without a generic version go last
+        after.sort_by_cached_key(|k| Reverse(GenericVersion::parse(k.name()).ok()));
+
+        UpdateReport {
+            before: installed_kernels.iter().map(|k| k.to_string()).collect(),
+            after: after.iter().map(|k| k.to_string()).collect(),
+            installed: self.installs.iter().map(|k| k.to_string()).collect(),
+            removed: self.removals.iter().map(|k| k.to_string()).collect(),
+            retained: self
+                .retained
+                .iter()
+                .map(|(k, reason)| RetainedKernel {
+                    kernel: k.to_string(),
+                    reason: *reason,
+                })
+                .collect(),
+            default: None,
+            stats: UpdateStats::default(),
+        }
+    }
+
+    /// Describe the changes to the files of each kernel, newest first
+    pub fn summary(&self) -> Result<Vec<SummaryRow>> {
+        let mut rows = Vec::new();
+        let mut push = |k: &K, action, files: Vec<PathBuf>| {
+            // Kernels of other types without a generic version go last
+            let version = GenericVersion::parse(k.name()).ok();
+            let row = SummaryRow {
+                kernel: k.to_string(),
+                action,
+                files: files.len(),
+                size: files
+                    .iter()
+                    .map(|f| fs::metadata(f).map_or(0, |m| m.len()))
+                    .sum(),
+            };
+
+            rows.push((Reverse(version), row));
+        };
+
+        for k in self.installs.iter() {
+            push(k, SummaryAction::Install, k.changed_files()?);
+        }
+
+        for k in self.refreshes.iter() {
+            let files = k.changed_files()?;
+
+            if !files.is_empty() {
+                push(k, SummaryAction::Refresh, files);
+            }
+        }
+
+        for k in self.removals.iter() {
+            // The shared files like ucode are kept
+            let files = k
+                .source_files()
+                .into_iter()
+                .map(|(_, dest)| dest)
+                .filter(|dest| dest.exists())
+                .collect();
+
+            push(k, SummaryAction::Remove, files);
+        }
+
+        rows.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Ok(rows.into_iter().map(|(_, row)| row).collect())
+    }
+
+    /// Apply the plan, copying the kernels from `src_path` instead of the
+    /// configured directory if given, and stop installing kernels once the
+    /// deadline is reached
+    pub fn apply(
+        &self,
+        src_path: Option<&Path>,
+        force: bool,
+        deadline: Option<Instant>,
+    ) -> Result<UpdateStats> {
+        let start = Instant::now();
+        let mut stats = UpdateStats::default();
+
+        status::emit(Event::Phase { phase: "remove" });
+        for k in self.removals.iter() {
+            let entries = k.summary()?.entry_files.len();
+            k.remove()?;
+            stats.kernels_removed += 1;
+            stats.entries_removed += entries as u32;
+        }
+
+        if let Some((esp_mountpoint, manifest)) = self.manifest.as_ref() {
+            manifest.write(esp_mountpoint)?;
+        }
+
+        status::emit(Event::Phase { phase: "install" });
+
+        // Without a deadline, the copies of all the kernels are made at once,
+        // otherwise a kernel being copied could not always be completed
+        if deadline.is_none() {
+            let kernels = self
+                .order
+                .iter()
+                .filter(|k| self.installs.contains(k) || self.refreshes.contains(k))
+                .copied()
+                .collect::<Vec<_>>();
+
+            for k in kernels.iter() {
+                stats.bytes_copied += copied_bytes(*k, src_path)?;
+            }
+
+            install_together(&kernels, src_path)?;
+            stats.kernels_installed += self.installs.len() as u32;
+        }
+
+        for (n, k) in self.order.iter().enumerate() {
+            // A kernel being copied is always completed
+            if deadline.is_some_and(|d| Instant::now() >= d) {
+                return Err(SbfError::Timeout {
+                    completed: n,
+                    remaining: self.order.len() - n,
+                }
+                .into());
+            }
+
+            if deadline.is_some() && (self.installs.contains(k) || self.refreshes.contains(k)) {
+                stats.bytes_copied += copied_bytes(*k, src_path)?;
+                k.install(src_path)?;
+
+                if self.installs.contains(k) {
+                    stats.kernels_installed += 1;
+                }
+            }
+
+            if self.entry_writes.contains(k) {
+                k.make_config(force)?;
+                stats.entries_created += k.summary()?.entry_files.len() as u32;
+            }
+        }
+
+        status::emit(Event::Phase { phase: "default" });
+        if let Some(DefaultChange { new, announce, .. }) = self.default_change {
+            new.set_default(announce)?;
+            reboot::refresh_stamp(new.name());
+        }
+
+        stats.duration = start.elapsed();
+
+        Ok(stats)
+    }
+}
+
+/// Install the kernels with their copies ordered from the smallest file to
+/// the largest, instead of kernel by kernel, which interleaves small and
+/// large writes. Each kernel is completed as soon as its own files are copied.
+fn install_together<K: KernelOps>(kernels: &[&K], src_path: Option<&Path>) -> Result<()> {
+    // The copies, each with the kernels waiting for it
+    let mut copies: Vec<(PathBuf, PathBuf, Vec<usize>)> = Vec::new();
+    let mut pending = vec![0; kernels.len()];
+
+    for (i, k) in kernels.iter().enumerate() {
+        match k.install_copies(src_path)? {
+            Some(c) => {
+                for (src, dest) in c {
+                    // The microcode image may be shared by the kernels
+                    match copies.iter_mut().find(|(_, d, _)| *d == dest) {
+                        Some((_, _, owners)) => owners.push(i),
+                        None => copies.push((src, dest, vec![i])),
+                    }
+                    pending[i] += 1;
+                }
+
+                if pending[i] == 0 {
+                    k.finish_install(src_path)?;
+                }
+            }
+            None => k.install(src_path)?,
+        }
+    }
+
+    copies.sort_by_cached_key(|(src, _, _)| fs::metadata(src).map_or(0, |m| m.len()));
+
+    for (src, dest, owners) in copies {
+        file_copy(src, dest)?;
+
+        for i in owners {
+            pending[i] -= 1;
+
+            if pending[i] == 0 {
+                kernels[i].finish_install(src_path)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The size of the files to be copied by installing the kernel, from
+/// `src_path` instead of the configured directory if given
+fn copied_bytes<K: KernelOps>(kernel: &K, src_path: Option<&Path>) -> Result<u64> {
+    let mut bytes = 0;
+
+    for file in kernel.changed_files()? {
+        let file = match (src_path, file.file_name()) {
+            (Some(src_path), Some(name)) => src_path.join(name),
+            _ => file,
+        };
+
+        bytes += fs::metadata(file).map_or(0, |m| m.len());
+    }
+
+    Ok(bytes)
+}
+
+/// Print the summary table of update to stderr, along with the messages
+pub(crate) fn print_summary(rows: &[SummaryRow]) {
+    if rows.is_empty() || quiet() {
+        return;
+    }
+
+    let headers = [
+        fl!("summary_kernel"),
+        fl!("summary_action"),
+        fl!("summary_files"),
+        fl!("summary_size"),
+    ];
+    let rows = rows
+        .iter()
+        .map(|r| {
+            vec![
+                r.kernel.clone(),
+                r.action.to_string(),
+                r.files.to_string(),
+                human_size(r.size),
+            ]
+        })
+        .collect::<Vec<_>>();
+
+    eprintln!();
+    for line in render_table(&headers, &rows, wrap_width()) {
+        eprintln!("{}", line);
+    }
+    eprintln!();
+}
+
 /// Manage kernels
-pub struct KernelManager<'a, K: Kernel> {
+pub struct KernelManager<'a, K: KernelOps> {
     kernels: &'a [K],
     installed_kernels: &'a [K],
 }
 
-impl<'a, K: Kernel> KernelManager<'a, K> {
+impl<'a, K: KernelOps + PartialEq> KernelManager<'a, K> {
     /// Create a new Kernel Manager
     pub fn new(kernels: &'a [K], installed_kernels: &'a [K]) -> Self {
         Self {
@@ -21,55 +535,365 @@ impl<'a, K: Kernel> KernelManager<'a, K> {
         }
     }
 
-    /// Update systemd-boot kernels and entries
-    pub fn update(&self, config: &Config) -> Result<()> {
-        println_with_prefix_and_fl!("update");
-        print_block_with_fl!("note_copy_files");
+    /// Find a kernel by its name or version string, prefer the available
+    /// kernels to the installed ones
+    pub fn find_by_version_string(&self, version: &str) -> Option<&'a K> {
+        find_kernel(self.kernels, version).or_else(|| find_kernel(self.installed_kernels, version))
+    }
 
-        let keep = config
-            .keep
-            .unwrap_or(self.kernels.len())
-            .min(self.kernels.len());
+    /// Decide the kernels to be installed and removed by update
+    fn select(
+        &self,
+        config: &Config,
+        manifest: &Manifest,
+        running: Option<&str>,
+    ) -> Result<Selection<'a, K>> {
+        let flags = |k: &K| -> Result<KernelFlags> {
+            Ok(KernelFlags {
+                pinned: config.pinned.iter().any(|p| p == k.name()),
+                running: running == Some(k.name()),
+                default: k.is_default()?,
+                previous_default: manifest.previous_default.as_deref() == Some(k.name()),
+            })
+        };
 
-        let to_be_installed = &self.kernels[..keep];
+        let (allowed, excluded): (Vec<_>, Vec<_>) = self
+            .kernels
+            .iter()
+            .partition(|k| config.manage_only_allows(k.name()));
+        let candidates = allowed
+            .into_iter()
+            .map(|k| Ok((k, flags(k)?)))
+            .collect::<Result<Vec<_>>>()?;
+        let mut retained = candidates
+            .iter()
+            .zip(select_retained(&candidates, config.keep))
+            .filter_map(|((k, _), reason)| reason.map(|r| (*k, r)))
+            .collect::<Vec<_>>();
 
-        // Remove obsoleted kernels
-        self.installed_kernels.iter().try_for_each(|k| {
-            if !to_be_installed.contains(k) {
-                k.remove()
-            } else {
-                Ok(())
+        // Kernels installed from arbitrary paths are never pruned, neither
+        // are the protected ones without modules, or those installed on
+        // purpose out of `manage_only`
+        let mut to_be_removed = Vec::new();
+
+        for k in self.installed_kernels.iter() {
+            if retained.iter().any(|(r, _)| *r == k)
+                || manifest.out_of_tree.iter().any(|n| n == k.name())
+                || !config.manage_only_allows(k.name())
+            {
+                continue;
+            }
+
+            match flags(k)?.protection() {
+                Some(reason) if !self.kernels.contains(k) => retained.push((k, reason)),
+                _ => to_be_removed.push(k),
             }
-        })?;
+        }
+
+        Ok((retained, to_be_removed, excluded))
+    }
+
+    /// Decide the changes to be made by update, without touching the ESP
+    pub fn plan(&self, config: &Config, running: Option<&str>) -> Result<UpdatePlan<'a, K>> {
+        let mut manifest = Manifest::read(&config.esp_mountpoint)?;
+        let recorded_previous_default = manifest.previous_default.clone();
+        let old_default = self
+            .installed_kernels
+            .iter()
+            .map(|k| Ok((k, k.is_default()?)))
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .find_map(|(k, is_default)| is_default.then_some(k));
+
+        // The previous default is no longer needed once the current one boots
+        if old_default.is_some_and(|k| running == Some(k.name())) {
+            manifest.previous_default = None;
+        }
+
+        let (retained, removals, excluded) = self.select(config, &manifest, running)?;
+        // The protected kernels without modules are kept as they are
+        let order = retained
+            .iter()
+            .map(|(k, _)| *k)
+            .filter(|k| self.kernels.contains(k))
+            .collect::<Vec<_>>();
 
-        // Install all kernels
-        self.kernels
+        // The default follows the newest kernel, or with
+        // default_follows_flavor the newest kernel of its flavor or of the
+        // flavor it was renamed to
+        let mut candidates = self
+            .kernels
             .iter()
-            .take(keep)
-            .try_for_each(|k| k.install_and_make_config(true))?;
+            .filter(|k| config.manage_only_allows(k.name()));
+        let series = |k: &K| config.flavor_series(k.flavor()).to_owned();
+        let new_default = old_default
+            .filter(|_| config.default_follows_flavor)
+            .and_then(|old| candidates.clone().find(|k| series(k) == series(old)))
+            .or_else(|| candidates.next());
+        let flavor_alias = old_default
+            .zip(new_default)
+            .filter(|(old, new)| old.flavor() != new.flavor() && series(old) == series(new))
+            .map(|(old, new)| (old.flavor().to_owned(), new.flavor().to_owned()));
 
-        // Set the newest kernel as default entry
-        if keep > 0 {
-            if let Some(k) = self.kernels.first() {
-                k.set_default()?;
+        // Remember the old default if it is about to change, so that its
+        // entry is titled accordingly
+        if let (Some(old), Some(new)) = (old_default, new_default) {
+            if old != new {
+                manifest.previous_default = Some(old.name().to_owned());
             }
         }
 
-        Ok(())
+        let (refreshes, installs) = order
+            .iter()
+            .partition(|k| self.installed_kernels.contains(k));
+
+        Ok(UpdatePlan {
+            installs,
+            refreshes,
+            removals,
+            entry_writes: order.clone(),
+            default_change: new_default.map(|new| DefaultChange {
+                old: old_default,
+                new,
+                announce: config.ask_default_on_install != AskDefault::Skip,
+                flavor_alias,
+            }),
+            retained,
+            excluded,
+            order,
+            manifest: (manifest.previous_default != recorded_previous_default)
+                .then(|| (config.esp_mountpoint.clone(), manifest)),
+        })
     }
 
-    #[inline]
-    pub fn install(kernel: &K, force: bool) -> Result<()> {
+    /// Check if update would change anything, without touching the ESP
+    pub fn update_pending(&self, config: &Config) -> Result<bool> {
+        let plan = self.plan(config, running_kernel().ok().as_deref())?;
+
+        if !plan.removals.is_empty() || !plan.installs.is_empty() {
+            return Ok(true);
+        }
+
+        for k in plan.refreshes {
+            if !k.changed_files()?.is_empty() {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Update systemd-boot kernels and entries, stop installing kernels
+    /// once the timeout expires, keep the obsolete kernels if `install_new_only`
+    pub fn update(
+        &self,
+        config: &Config,
+        timeout: Option<Duration>,
+        install_new_only: bool,
+    ) -> Result<UpdateReport> {
+        let plan = self.plan(config, running_kernel().ok().as_deref())?;
+
+        self.execute(config, plan, timeout, install_new_only)
+    }
+
+    /// Apply a plan made by `plan` as update does
+    pub fn execute(
+        &self,
+        config: &Config,
+        mut plan: UpdatePlan<'a, K>,
+        timeout: Option<Duration>,
+        install_new_only: bool,
+    ) -> Result<UpdateReport> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+
+        println_with_prefix_and_fl!("update");
         print_block_with_fl!("note_copy_files");
 
-        kernel.install_and_make_config(force)?;
-        kernel.ask_set_default()?;
+        for k in plan.excluded.iter() {
+            println_with_prefix_and_fl!("exclude_kernel", kernel = k.to_string());
+        }
+
+        if install_new_only {
+            for k in plan.removals.drain(..) {
+                println_with_prefix_and_fl!("keep_obsolete", kernel = k.to_string());
+            }
+        }
+
+        let summary = plan.summary()?;
+        let stats = plan.apply(None, true, deadline)?;
+
+        // Reported as applied, the obsolete kernels kept above included
+        let mut report = plan.report(self.installed_kernels);
+        report.stats = stats;
+        report.default = plan.default_change.as_ref().map(|d| d.new.to_string());
+
+        update_splash(config)?;
+
+        for RetainedKernel { kernel, reason } in report.retained.iter() {
+            println_with_prefix_and_fl!(
+                "retain_kernel",
+                kernel = kernel.as_str(),
+                reason = reason.to_string()
+            );
+        }
+
+        if let Some((old, new)) = plan
+            .default_change
+            .as_ref()
+            .and_then(|d| d.flavor_alias.as_ref())
+        {
+            println_with_prefix_and_fl!(
+                "default_flavor_alias",
+                old = old.as_str(),
+                new = new.as_str()
+            );
+        }
+
+        print_summary(&summary);
+        status::emit(Event::Summary { report: &report });
+
+        Ok(report)
+    }
+
+    #[inline]
+    pub fn install(
+        kernel: &K,
+        src_path: Option<&Path>,
+        force: bool,
+        ask_default: AskDefault,
+        steps: InstallSteps,
+    ) -> Result<()> {
+        if steps.copy() {
+            print_block_with_fl!("note_copy_files");
+        }
+
+        UpdatePlan::install(kernel, steps).apply(src_path, force, None)?;
+
+        if steps.entry() {
+            Self::handle_default(kernel, ask_default)?;
+        }
+
+        Ok(())
+    }
+
+    /// Remove the kernels and their entries
+    pub fn remove(kernels: &[K]) -> Result<()> {
+        UpdatePlan::remove(kernels).apply(None, false, None)?;
 
         Ok(())
     }
 
-    /// Print all the available kernels
-    pub fn list_available(&self) {
+    /// Install a kernel image from an arbitrary path, which is kept
+    /// untouched by `update`
+    pub fn install_from(
+        config: &Config,
+        kernel: &K,
+        image: &Path,
+        initrd: Option<&Path>,
+        force: bool,
+        ask_default: AskDefault,
+        steps: InstallSteps,
+    ) -> Result<()> {
+        if steps.copy() {
+            print_block_with_fl!("note_copy_files");
+            kernel.install_from(image, initrd)?;
+        }
+
+        let mut manifest = Manifest::read(&config.esp_mountpoint)?;
+
+        if !manifest.out_of_tree.iter().any(|n| n == kernel.name()) {
+            manifest.out_of_tree.push(kernel.name().to_owned());
+            manifest.write(&config.esp_mountpoint)?;
+        }
+
+        if steps.entry() {
+            kernel.make_config(force)?;
+            Self::handle_default(kernel, ask_default)?;
+        }
+
+        Ok(())
+    }
+
+    /// Set the kernel as default according to the preference
+    fn handle_default(kernel: &K, ask_default: AskDefault) -> Result<()> {
+        match ask_default {
+            AskDefault::Ask => kernel.ask_set_default()?,
+            AskDefault::Skip => (),
+            AskDefault::AlwaysYes => kernel.set_default(true)?,
+        }
+
+        Ok(())
+    }
+
+    /// Report the installed kernels whose source files changed since last install
+    pub fn track_changes(&self) -> Result<()> {
+        let mut up_to_date = true;
+
+        for k in self.installed_kernels.iter() {
+            let changed = k.changed_files()?;
+
+            if changed.is_empty() {
+                continue;
+            }
+
+            up_to_date = false;
+            println_with_prefix_and_fl!("kernel_changed", kernel = k.to_string());
+            for f in changed.iter() {
+                println!("  {}", f.display());
+            }
+            println_with_fl!("suggest_reinstall", kernel = k.to_string());
+        }
+
+        if up_to_date {
+            println_with_prefix_and_fl!("no_kernel_changed");
+        }
+
+        Ok(())
+    }
+
+    /// Write the entries of every installed kernel again, e.g. after the
+    /// profiles or the distribution name are changed
+    pub fn generate_entries(&self, force: bool) -> Result<()> {
+        for k in self.installed_kernels.iter() {
+            k.make_config(force)?;
+        }
+
+        println_with_prefix_and_fl!("entries_generated", count = self.installed_kernels.len());
+
+        Ok(())
+    }
+
+    /// Remove all the installed kernels, the confirmation is only skipped if
+    /// `skip_confirm` is set
+    pub fn remove_all(&self, skip_confirm: bool) -> Result<()> {
+        if self.installed_kernels.is_empty() {
+            println_with_prefix_and_fl!("empty_list");
+            return Ok(());
+        }
+
+        // Leave a way back to a bootable system
+        if self.kernels.is_empty() {
+            bail!(fl!("err_remove_all_no_available"));
+        }
+
+        if !skip_confirm
+            && !Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(fl!("ask_remove_all", count = self.installed_kernels.len()))
+                .default(false)
+                .interact()?
+        {
+            return Ok(());
+        }
+
+        Self::remove(self.installed_kernels)?;
+        print_block_with_fl!("warn_removed_all");
+
+        Ok(())
+    }
+
+    /// Print all the available kernels, return the number of kernels listed
+    #[must_use = "the number of listed kernels should be checked"]
+    pub fn list_available(&self) -> Result<usize> {
         if !self.kernels.is_empty() {
             for k in self.kernels.iter() {
                 if self.installed_kernels.contains(k) {
@@ -82,23 +906,167 @@ impl<'a, K: Kernel> KernelManager<'a, K> {
             println!();
             println_with_fl!("note_list_available");
         }
+
+        Ok(self.kernels.len())
     }
 
-    /// Print all the installed kernels
-    pub fn list_installed(&self) -> Result<()> {
+    /// Print all the installed kernels, return the number of kernels listed
+    #[must_use = "the number of listed kernels should be checked"]
+    pub fn list_installed(&self, show_size: bool) -> Result<usize> {
         if !self.installed_kernels.is_empty() {
+            // Shared files like ucode are only counted once in the total
+            let mut all_files = HashSet::new();
+
             for k in self.installed_kernels.iter() {
                 if k.is_default()? {
                     print!("{} ", style("[*]").green());
                 } else {
                     print!("[ ] ");
                 }
-                println!("{}", k);
+
+                if show_size {
+                    let files = k.esp_files();
+                    let size = files
+                        .iter()
+                        .map(|f| Ok(fs::metadata(f)?.len()))
+                        .sum::<Result<u64>>()?;
+
+                    println!("{} ({})", k, human_size(size));
+                    all_files.extend(files);
+                } else {
+                    println!("{}", k);
+                }
+            }
+
+            if show_size {
+                let total = all_files
+                    .iter()
+                    .map(|f| Ok(fs::metadata(f)?.len()))
+                    .sum::<Result<u64>>()?;
+
+                println!();
+                println!("{}", fl!("total_size", size = human_size(total)));
             }
+
             println!();
             println_with_fl!("note_list_installed");
         }
 
-        Ok(())
+        Ok(self.installed_kernels.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NONE: KernelFlags = KernelFlags {
+        pinned: false,
+        running: false,
+        default: false,
+        previous_default: false,
+    };
+    const PINNED: KernelFlags = KernelFlags {
+        pinned: true,
+        ..NONE
+    };
+    const RUNNING: KernelFlags = KernelFlags {
+        running: true,
+        ..NONE
+    };
+    const DEFAULT: KernelFlags = KernelFlags {
+        default: true,
+        ..NONE
+    };
+
+    fn select(flags: &[KernelFlags], keep: Option<usize>) -> Vec<Option<RetainReason>> {
+        let kernels = flags
+            .iter()
+            .enumerate()
+            .map(|(i, f)| (i, *f))
+            .collect::<Vec<_>>();
+
+        select_retained(&kernels, keep)
+    }
+
+    #[test]
+    fn test_select_retained_unprotected() {
+        use RetainReason::*;
+
+        assert_eq!(select(&[NONE, NONE, NONE], None), [Some(WithinKeep); 3]);
+        assert_eq!(
+            select(&[NONE, NONE, NONE], Some(2)),
+            [Some(WithinKeep), Some(WithinKeep), None]
+        );
+        assert_eq!(select(&[NONE, NONE], Some(0)), [None, None]);
+        assert_eq!(select(&[NONE], Some(5)), [Some(WithinKeep)]);
+        assert!(select(&[], Some(1)).is_empty());
+    }
+
+    #[test]
+    fn test_select_retained_protected() {
+        use RetainReason::*;
+
+        // Protected kernels do not count against the budget
+        assert_eq!(
+            select(&[PINNED, NONE, RUNNING, NONE, DEFAULT, NONE], Some(1)),
+            [
+                Some(Pinned),
+                Some(WithinKeep),
+                Some(Running),
+                None,
+                Some(Default),
+                None
+            ]
+        );
+        assert_eq!(
+            select(&[NONE, NONE, PINNED], Some(0)),
+            [None, None, Some(Pinned)]
+        );
+        assert_eq!(
+            select(&[RUNNING, DEFAULT, NONE], Some(1)),
+            [Some(Running), Some(Default), Some(WithinKeep)]
+        );
+    }
+
+    #[test]
+    fn test_select_retained_reason_precedence() {
+        use RetainReason::*;
+
+        let all = KernelFlags {
+            pinned: true,
+            running: true,
+            default: true,
+            previous_default: true,
+        };
+        let running_default = KernelFlags {
+            running: true,
+            default: true,
+            ..NONE
+        };
+
+        let default_previous = KernelFlags {
+            default: true,
+            previous_default: true,
+            ..NONE
+        };
+        let previous = KernelFlags {
+            previous_default: true,
+            ..NONE
+        };
+
+        assert_eq!(
+            select(
+                &[all, running_default, default_previous, previous, NONE],
+                Some(1)
+            ),
+            [
+                Some(Pinned),
+                Some(Running),
+                Some(Default),
+                Some(PreviousDefault),
+                Some(WithinKeep)
+            ]
+        );
     }
 }
@@ -0,0 +1,73 @@
+//! Kernel version manager for systemd-boot
+//!
+//! # Features
+//!
+//! `generic`, the kernels and versions friend manages, is required. The
+//! optional features below are enabled by default, a minimal build for
+//! initramfs or recovery environments is made with `--no-default-features
+//! --features generic`.
+//!
+//! - `interactive`: the prompts of dialoguer. Without it, nothing is asked:
+//!   `init` and `apply-plan` run as with `--yes`, every other question takes
+//!   its default answer, e.g. entries are not overwritten and `remove-kernel
+//!   --all` still needs `--assume-yes --i-know-what-i-am-doing`, every
+//!   selection takes its default, and the commands choosing kernels from a
+//!   list should be given the kernels on the command line.
+//! - `i18n`: the translations and the locale detection. Without it, the
+//!   messages are in English, read from the English catalog built into the
+//!   binary.
+//!
+//! The commands and their output are the same otherwise. The console crate
+//! is kept in any case, for the terminal detection and the styling.
+
+#[cfg(not(feature = "generic"))]
+compile_error!("the generic feature is required, build with `--features generic`");
+
+pub mod build_info;
+pub mod cli;
+pub mod compare;
+pub mod config;
+pub mod default_entry;
+pub mod detect;
+pub mod diagnose;
+pub mod doctor;
+pub mod entry_edit;
+pub mod entry_list;
+pub mod error;
+pub mod esp_partition;
+pub mod i18n;
+pub mod journal;
+pub mod kernel;
+pub mod kernel_info;
+pub mod kernel_manager;
+pub mod macros;
+pub mod manifest;
+pub mod migrate_esp;
+pub mod openrc;
+pub mod pacnew;
+pub mod permissions;
+pub mod plan_file;
+pub mod porcelain;
+pub mod prompt;
+pub mod prune;
+pub mod reboot;
+pub mod sbm;
+pub mod sign;
+pub mod snapshots;
+pub mod splash;
+pub mod status;
+pub mod topology;
+pub mod uki;
+pub mod uninstall;
+pub mod util;
+pub mod validate_entry;
+pub mod verify;
+pub mod version;
+
+pub use config::Config;
+#[cfg(feature = "i18n")]
+pub use i18n::I18N_LOADER;
+
+pub const REL_DEST_PATH: &str = "EFI/systemd-boot-friend/";
+pub const SRC_PATH: &str = "/boot";
+pub const MODULES_PATH: &str = "/usr/lib/modules/";
@@ -1,30 +1,53 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Suppress the messages printed to stderr, for output used by scripts
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
 #[macro_export]
 macro_rules! println_with_prefix {
     ($($arg:tt)+) => {
-        eprint!("{}", console::style("[systemd-boot-friend] ").bold());
-        eprintln!($($arg)+);
+        if !$crate::macros::quiet() {
+            eprint!("{}", console::style("[systemd-boot-friend] ").bold());
+            eprintln!($($arg)+);
+        }
     };
 }
 
 #[macro_export]
 macro_rules! println_with_fl {
     ($message_id:literal) => {
-        eprintln!("{}", fl!($message_id))
+        if !$crate::macros::quiet() {
+            eprintln!("{}", fl!($message_id))
+        }
     };
 
-    ($message_id:literal, $($args:expr), *) => {
-        eprintln!("{}", fl!($message_id, $($args), *))
+    ($message_id:literal, $($key:ident = $value:expr), *) => {
+        if !$crate::macros::quiet() {
+            eprintln!("{}", fl!($message_id, $($key = $value), *))
+        }
     }
 }
 
 #[macro_export]
 macro_rules! print_block_with_fl {
     ($message_id:literal) => {
-        eprintln!("\n{}\n", fl!($message_id))
+        if !$crate::macros::quiet() {
+            eprintln!("\n{}\n", fl!($message_id))
+        }
     };
 
-    ($message_id:literal, $($args:expr), *) => {
-        eprintln!("\n{}\n", fl!($message_id, $($args), *))
+    ($message_id:literal, $($key:ident = $value:expr), *) => {
+        if !$crate::macros::quiet() {
+            eprintln!("\n{}\n", fl!($message_id, $($key = $value), *))
+        }
     }
 }
 
@@ -36,8 +59,8 @@ macro_rules! println_with_prefix_and_fl {
         }
     };
 
-    ($message_id:literal, $($args:expr), *) => {
-        for line in fl!($message_id, $($args), *).lines() {
+    ($message_id:literal, $($key:ident = $value:expr), *) => {
+        for line in fl!($message_id, $($key = $value), *).lines() {
             println_with_prefix!("{}", line);
         }
     };
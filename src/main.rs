@@ -1,64 +1,142 @@
-use anyhow::{anyhow, bail, Result};
+use anyhow::{bail, Result};
 use clap::Parser;
+use console::style;
 use core::default::Default;
-use dialoguer::{theme::ColorfulTheme, Confirm, Input};
 use libsdbootconf::SystemdBootConf;
-use std::{
-    cell::RefCell,
-    fs,
-    process::{Command, Stdio},
-    rc::Rc,
+use std::{cell::RefCell, fs, io, path::PathBuf, rc::Rc, time::Duration};
+
+use systemd_boot_friend_rs::{
+    build_info::print_version,
+    cli::{ConfigCommands, EntryCommands, KernelFilter, Opts, SnapshotsCommands, SubCommands},
+    compare::compare,
+    config::{get_option, set_config_write, set_option, unset_option, AskDefault, Config},
+    default_entry::{self, clear_default, ensure_default, print_default, repair_default},
+    detect::{bootctl_esp_path, detect_all, vfat_mount_points},
+    diagnose::diagnose_boot_failure,
+    doctor::{doctor, oneline, watch},
+    entry_edit::edit_entry,
+    entry_list::{list_entries, print_entry},
+    esp_partition::EspPartitions,
+    fl,
+    journal::show_history,
+    kernel::{
+        filter_kernels,
+        generic_kernel::{set_list_cache, GenericKernel, SkipReason},
+        list_entry_ids, Kernel, KernelOps,
+    },
+    kernel_info::kernel_info,
+    kernel_manager::{InstallSteps, KernelManager},
+    macros::set_quiet,
+    migrate_esp::migrate_esp,
+    openrc::generate_openrc_script,
+    pacnew::merge_pacnew,
+    permissions::check_permissions,
+    plan_file::{apply_plan, print_plan},
+    porcelain, print_block_with_fl, println_with_prefix, println_with_prefix_and_fl,
+    prompt::{ColorfulTheme, Confirm, Input, Select},
+    prune::{prune, PruneOptions},
+    reboot::needs_reboot,
+    sbm::migrate_from_sbm,
+    sign::{check_token, sign_all},
+    snapshots::prune_snapshots,
+    status,
+    topology::show_topology,
+    uki::convert_to_uki,
+    uninstall::{ask_uninstall, uninstall},
+    util::*,
+    validate_entry::validate_entry,
+    verify::verify,
+    REL_DEST_PATH,
 };
 
-mod cli;
-mod config;
-mod i18n;
-mod kernel;
-mod kernel_manager;
-mod macros;
-mod util;
-mod version;
-
-use cli::{Opts, SubCommands};
-use config::Config;
-use i18n::I18N_LOADER;
-use kernel::{generic_kernel::GenericKernel, Kernel};
-use kernel_manager::KernelManager;
-use util::*;
-
-const REL_DEST_PATH: &str = "EFI/systemd-boot-friend/";
-const SRC_PATH: &str = "/boot";
+/// Without the prompts, the commands with `--yes` run as if it were given
+const INTERACTIVE: bool = cfg!(feature = "interactive");
+
+/// Make sure the configuration agrees with bootctl and the mounted vfat
+/// partitions on the ESP, before any directories are created
+fn reconcile_esp(config: &mut Config, esp_path: Option<PathBuf>) -> Result<()> {
+    let chosen = match esp_path {
+        Some(p) => p,
+        None => {
+            let current = config.esp_mountpoint.as_ref().clone();
+            let mut candidates = bootctl_esp_path().into_iter().collect::<Vec<_>>();
+
+            // Fall back to the vfat partitions only if bootctl knows nothing
+            if candidates.is_empty() {
+                candidates = vfat_mount_points();
+            }
+
+            if candidates.is_empty() || candidates.contains(&current) {
+                return Ok(());
+            }
+
+            println_with_prefix_and_fl!(
+                "esp_disagree",
+                conf = current.to_string_lossy(),
+                detected = candidates
+                    .iter()
+                    .map(|p| p.to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+
+            if !console::user_attended() {
+                bail!(fl!("err_esp_disagree"));
+            }
+
+            candidates.insert(0, current);
+            let items = candidates
+                .iter()
+                .map(|p| p.to_string_lossy())
+                .collect::<Vec<_>>();
+            let n = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt(fl!("ask_esp"))
+                .items(&items)
+                .default(0)
+                .interact()?;
+
+            candidates.swap_remove(n)
+        }
+    };
+
+    if *config.esp_mountpoint != chosen {
+        println_with_prefix_and_fl!("update_esp", path = chosen.to_string_lossy());
+        config.esp_mountpoint = Rc::new(chosen);
+        config.write_back()?;
+    }
+
+    Ok(())
+}
+
+/// Warn prominently if the ESP mount point is not the partition the
+/// firmware boots
+fn warn_esp_partition(config: &Config) {
+    let partitions = EspPartitions::detect(&config.esp_mountpoint);
+
+    for warning in partitions.warnings(&config.esp_mountpoint) {
+        println_with_prefix!("{}", style(warning).red().bold());
+    }
+}
 
 /// Initialize the default environment for friend
-fn init(config: &Config) -> Result<()> {
+fn init(config: &mut Config, esp_path: Option<PathBuf>, assume_yes: bool) -> Result<()> {
     // use bootctl to install systemd-boot
     println_with_prefix_and_fl!("init");
     print_block_with_fl!("notice_init");
 
-    if !Confirm::with_theme(&ColorfulTheme::default())
-        .with_prompt(fl!("ask_init"))
-        .default(false)
-        .interact()?
+    if !assume_yes
+        && !Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(fl!("ask_init"))
+            .default(false)
+            .interact()?
     {
         return Ok(());
     }
 
-    let child_output = Command::new("bootctl")
-        .arg("install")
-        .arg(
-            "--esp=".to_owned()
-                + config
-                    .esp_mountpoint
-                    .to_str()
-                    .ok_or_else(|| anyhow!(fl!("invalid_esp")))?,
-        )
-        .stderr(Stdio::piped())
-        .spawn()?
-        .wait_with_output()?;
+    reconcile_esp(config, esp_path)?;
+    warn_esp_partition(config);
 
-    if !child_output.status.success() {
-        bail!(String::from_utf8(child_output.stderr)?);
-    }
+    run_bootctl("install", &config.esp_mountpoint, config.bootctl_timeout)?;
 
     let sbconf = Rc::new(RefCell::new(SystemdBootConf::new(
         config.esp_mountpoint.join("loader/"),
@@ -80,13 +158,17 @@ fn init(config: &Config) -> Result<()> {
     fs::create_dir_all(config.esp_mountpoint.join(REL_DEST_PATH))?;
 
     // Update systemd-boot kernels and entries
-    print_block_with_fl!("prompt_update", src_path = SRC_PATH);
-    if Confirm::with_theme(&ColorfulTheme::default())
-        .with_prompt(fl!("ask_update"))
-        .default(false)
-        .interact()?
+    print_block_with_fl!(
+        "prompt_update",
+        src_path = config.src_path.to_string_lossy()
+    );
+    if assume_yes
+        || Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(fl!("ask_update"))
+            .default(false)
+            .interact()?
     {
-        KernelManager::new(&kernels, &installed_kernels).update(config)?;
+        KernelManager::new(&kernels, &installed_kernels).update(config, None, false)?;
     } else {
         println_with_prefix_and_fl!("skip_update");
     }
@@ -108,51 +190,395 @@ fn ask_set_timeout(timeout: Option<u32>, sbconf: Rc<RefCell<SystemdBootConf>>) -
     Ok(())
 }
 
+/// Set the timeout of systemd-boot boot menu according to the number of entries
+fn auto_set_timeout(config: &Config, sbconf: Rc<RefCell<SystemdBootConf>>) -> Result<()> {
+    // Hide the menu when there is nothing to choose from
+    let timeout = match list_entry_ids(&config.esp_mountpoint)?.len() {
+        0 | 1 => 0,
+        2 => 3,
+        _ => 5,
+    };
+
+    println_with_prefix_and_fl!("auto_timeout", timeout = timeout);
+    sbconf.borrow_mut().config.timeout = Some(timeout);
+    sbconf.borrow().write_config()?;
+
+    Ok(())
+}
+
+/// Tell if no kernel is listed, or how many are hidden by the filters
+fn print_listed(shown: usize, total: usize) {
+    if shown == 0 {
+        println_with_prefix_and_fl!("no_kernel");
+    } else if shown < total {
+        println_with_prefix_and_fl!("list_filtered", shown = shown, total = total);
+    }
+}
+
 fn main() -> Result<()> {
     // CLI
     let matches: Opts = Opts::parse();
 
+    // Nothing but the summary line is printed
+    if let Some(SubCommands::Status { oneline: true, .. }) = &matches.subcommands {
+        set_quiet(true);
+    }
+
+    if let Some(SubCommands::Version { verbose }) = &matches.subcommands {
+        print_version(*verbose);
+        return Ok(());
+    }
+
+    // The script does not depend on the configuration
+    if let Some(SubCommands::GenerateOpenrcScript { output }) = &matches.subcommands {
+        return generate_openrc_script(output.clone());
+    }
+
+    if let Some(fd) = matches.status_fd {
+        status::init(fd)?;
+    }
+
+    // The current configuration may not be readable before merging
+    if let Some(SubCommands::MergePacnew) = &matches.subcommands {
+        return merge_pacnew();
+    }
+
+    if let Some(SubCommands::MigrateFromSbm { sbm_config }) = &matches.subcommands {
+        return migrate_from_sbm(sbm_config.as_deref());
+    }
+
+    // The configuration file is accessed as it is, without the environment
+    if let Some(SubCommands::Config {
+        command: Some(command),
+    }) = &matches.subcommands
+    {
+        match command {
+            ConfigCommands::Get { key } => {
+                let code = get_option(key)?;
+
+                if code != 0 {
+                    std::process::exit(code);
+                }
+            }
+            ConfigCommands::Set { key, value } => set_option(key, value)?,
+            ConfigCommands::Unset { key } => unset_option(key)?,
+        }
+
+        return Ok(());
+    }
+
+    if let Some(SubCommands::Init {
+        import_config: Some(path),
+        ..
+    }) = &matches.subcommands
+    {
+        Config::import(path)?;
+    }
+
+    // plan runs without root, nothing is written
+    set_config_write(
+        !matches.no_config_write && !matches!(matches.subcommands, Some(SubCommands::Plan { .. })),
+    );
+
     // Read config, create a default one if the file is missing
-    let config = Config::read()?;
+    let mut config = match &matches.subcommands {
+        Some(SubCommands::Init {
+            detect_all: true,
+            esp_path,
+            ..
+        }) => detect_all(esp_path.clone())?,
+        _ => Config::read()?,
+    };
+    config.skip_sanity_checks = matches.skip_sanity_checks;
+    set_list_cache(!matches.no_cache);
+    // update removes the kernels beyond KEEP by policy, not on request
+    config.force_remove = matches!(
+        matches.subcommands,
+        Some(
+            SubCommands::RemoveKernel { force: true, .. }
+                | SubCommands::Update { .. }
+                | SubCommands::ApplyPlan { .. }
+        )
+    );
+
+    // --flavor overrides MANAGE_FLAVORS
+    if let Some(
+        SubCommands::Update { flavor, .. }
+        | SubCommands::Select { flavor, .. }
+        | SubCommands::ListAvailable { flavor, .. }
+        | SubCommands::ListInstalled { flavor, .. },
+    ) = &matches.subcommands
+    {
+        if !flavor.is_empty() {
+            config.manage_flavors = flavor.clone();
+        }
+    }
 
     // Preprocess init subcommand
-    if let Some(SubCommands::Init) = &matches.subcommands {
-        init(&config)?;
+    if let Some(SubCommands::Init {
+        esp_path,
+        assume_yes,
+        detect_all,
+        ..
+    }) = &matches.subcommands
+    {
+        // The detected ESP is taken as given
+        let esp_path = if *detect_all {
+            Some(config.esp_mountpoint.to_path_buf())
+        } else {
+            esp_path.clone()
+        };
+        init(
+            &mut config,
+            esp_path,
+            *assume_yes || *detect_all || !INTERACTIVE,
+        )?;
+        return Ok(());
+    }
+
+    // Checking the stamp needs neither the ESP nor the kernel lists
+    if let Some(SubCommands::NeedsReboot) = &matches.subcommands {
+        std::process::exit(needs_reboot()?);
+    }
+
+    // The ESP in the configuration may be gone already
+    if let Some(SubCommands::MigrateEsp {
+        new_mountpoint,
+        from,
+        dry_run,
+        force,
+    }) = &matches.subcommands
+    {
+        migrate_esp::<GenericKernel>(
+            &mut config,
+            new_mountpoint,
+            from.as_deref(),
+            *dry_run,
+            *force,
+        )?;
         return Ok(());
     }
 
-    let sbconf = Rc::new(RefCell::new(
-        SystemdBootConf::load(config.esp_mountpoint.join("loader/"))
-            .map_err(|_| anyhow!(fl!("info_path_not_exist")))?,
-    ));
+    // Only load loader.conf here, entries are loaded on demand as the
+    // entries directory may contain lots of unrelated entries
+    let loader_path = config.esp_mountpoint.join("loader/");
+    let loader_conf_path = loader_path.join("loader.conf");
+
+    if !loader_conf_path.exists()
+        && !matches!(
+            matches.subcommands,
+            Some(SubCommands::Status { .. } | SubCommands::Plan { .. })
+        )
+    {
+        bail!(fl!("info_path_not_exist"));
+    }
+
+    let mut loader_conf_warnings = Vec::new();
+    let loader_conf = match load_loader_conf(&loader_conf_path) {
+        Ok(c) => c,
+        // Listing subcommands do not write loader.conf, continue with
+        // an empty configuration
+        Err(e)
+            if matches!(
+                matches.subcommands,
+                Some(
+                    SubCommands::ListAvailable { .. }
+                        | SubCommands::ListInstalled { .. }
+                        | SubCommands::TrackChanges
+                        | SubCommands::DiagnoseBootFailure
+                        | SubCommands::Status { .. }
+                        | SubCommands::ShowTopology
+                        | SubCommands::Verify
+                        | SubCommands::ShowHistory { .. }
+                        | SubCommands::CheckPermissions
+                        | SubCommands::CheckToken
+                        | SubCommands::Compare { .. }
+                        | SubCommands::PrintEntry { .. }
+                        | SubCommands::KernelInfo { .. }
+                        | SubCommands::ValidateEntry { .. }
+                        | SubCommands::Plan { .. }
+                )
+            ) =>
+        {
+            println_with_prefix!("{}", e);
+            println_with_prefix_and_fl!("degraded_mode");
+            loader_conf_warnings.push(e.to_string());
+            libsdbootconf::Config::default()
+        }
+        Err(e) => return Err(e),
+    };
+
+    let sbconf = Rc::new(RefCell::new(SystemdBootConf::new(
+        &loader_path,
+        loader_conf,
+        Vec::new(),
+    )));
     let installed_kernels = GenericKernel::list_installed(&config, sbconf.clone())?;
     let kernels = GenericKernel::list(&config, sbconf.clone())?;
 
     let kernel_manager = KernelManager::new(&kernels, &installed_kernels);
 
+    // The kernels listed or offered for selection
+    let filter = match &matches.subcommands {
+        Some(
+            SubCommands::InstallKernel { filter, .. }
+            | SubCommands::RemoveKernel { filter, .. }
+            | SubCommands::Select { filter, .. }
+            | SubCommands::ListAvailable { filter, .. }
+            | SubCommands::ListInstalled { filter, .. },
+        ) => filter.clone(),
+        _ => KernelFilter::default(),
+    };
+    let since = filter
+        .since
+        .as_deref()
+        .map(|v| GenericKernel::parse(&config, v, sbconf.clone()))
+        .transpose()?;
+    let shown_kernels = filter_kernels(&kernels, filter.newest, since.as_ref());
+    let shown_installed = filter_kernels(&installed_kernels, filter.newest, since.as_ref());
+
     // Switch table
     match matches.subcommands {
         Some(s) => match s {
-            SubCommands::Init => unreachable!(), // Handled above
-            SubCommands::Update => kernel_manager.update(&config)?,
-            SubCommands::InstallKernel { targets, force } => {
-                specify_or_multiselect(&kernels, &config, &targets, &fl!("select_install"), sbconf)?
-                    .iter()
-                    .try_for_each(|k| KernelManager::install(k, force))?
+            SubCommands::Init { .. } => unreachable!(), // Handled above
+            SubCommands::Update {
+                report_json,
+                auto_timeout,
+                timeout_seconds,
+                install_new_only,
+                verbose,
+                ..
+            } => {
+                warn_esp_partition(&config);
+
+                let report = kernel_manager.update(
+                    &config,
+                    timeout_seconds.map(Duration::from_secs),
+                    install_new_only,
+                )?;
+
+                if auto_timeout {
+                    auto_set_timeout(&config, sbconf)?;
+                }
+
+                if verbose {
+                    let stats = &report.stats;
+                    println_with_prefix_and_fl!(
+                        "update_stats",
+                        installed = stats.kernels_installed,
+                        removed = stats.kernels_removed,
+                        entries_created = stats.entries_created,
+                        entries_removed = stats.entries_removed,
+                        size = human_size(stats.bytes_copied),
+                        seconds = format!("{:.1}", stats.duration.as_secs_f64())
+                    );
+                }
+
+                if report_json {
+                    println!("{}", serde_json::to_string(&report)?);
+                }
             }
-            SubCommands::RemoveKernel { targets } => specify_or_multiselect(
-                &installed_kernels,
-                &config,
-                &targets,
-                &fl!("select_remove"),
-                sbconf,
-            )?
-            .iter()
-            .try_for_each(|k| k.remove())?,
-            SubCommands::Select => {
+            SubCommands::InstallKernel {
+                targets,
+                from,
+                version,
+                initrd,
+                kernel_path,
+                force,
+                set_default,
+                no_set_default,
+                copy_only,
+                entry_only,
+                version_alias,
+                ..
+            } => {
+                // Command line flags override the configuration
+                let ask_default = if set_default {
+                    AskDefault::AlwaysYes
+                } else if no_set_default {
+                    AskDefault::Skip
+                } else {
+                    config.ask_default_on_install
+                };
+                let steps = if copy_only {
+                    InstallSteps::CopyOnly
+                } else if entry_only {
+                    InstallSteps::EntryOnly
+                } else {
+                    InstallSteps::All
+                };
+
+                // The alias is saved only once the kernel is installed
+                let with_alias = |k: &GenericKernel| match &version_alias {
+                    Some(alias) => k.with_alias(alias),
+                    None => Ok(k.clone()),
+                };
+
+                match (from, version) {
+                    (Some(image), Some(version)) => {
+                        let kernel = GenericKernel::parse(&config, &version, sbconf)?;
+                        let aliased = with_alias(&kernel)?;
+
+                        KernelManager::install_from(
+                            &config,
+                            &aliased,
+                            &image,
+                            initrd.as_deref(),
+                            force,
+                            ask_default,
+                            steps,
+                        )?;
+                        aliased.save_alias(&kernel)?
+                    }
+                    _ => {
+                        let selected = specify_or_multiselect(
+                            &shown_kernels,
+                            &config,
+                            &targets,
+                            &fl!("select_install"),
+                            sbconf,
+                        )?;
+
+                        // An alias names a single kernel
+                        if version_alias.is_some() && selected.len() > 1 {
+                            bail!(fl!("alias_multiple"));
+                        }
+
+                        selected.iter().try_for_each(|k| {
+                            let aliased = with_alias(k)?;
+
+                            KernelManager::install(
+                                &aliased,
+                                kernel_path.as_deref(),
+                                force,
+                                ask_default,
+                                steps,
+                            )?;
+                            aliased.save_alias(k)
+                        })?
+                    }
+                }
+            }
+            SubCommands::RemoveKernel {
+                all: true,
+                assume_yes,
+                i_know_what_i_am_doing,
+                ..
+            } => kernel_manager.remove_all(assume_yes && i_know_what_i_am_doing)?,
+            SubCommands::RemoveKernel { targets, .. } => {
+                KernelManager::remove(&specify_or_multiselect(
+                    &shown_installed,
+                    &config,
+                    &targets,
+                    &fl!("select_remove"),
+                    sbconf,
+                )?)?
+            }
+            SubCommands::Select { .. } => {
                 let new_kernels =
-                    &multiselect_kernel(&kernels, &installed_kernels, &fl!("select"))?;
-                installed_kernels.iter().try_for_each(|k| {
+                    &multiselect_kernel(&shown_kernels, &installed_kernels, &fl!("select"))?;
+                // The kernels hidden by the filters are left untouched
+                shown_installed.iter().try_for_each(|k| {
                     if !new_kernels.contains(k) {
                         k.remove()
                     } else {
@@ -167,24 +593,263 @@ fn main() -> Result<()> {
                     }
                 })?;
             }
-            SubCommands::ListAvailable => kernel_manager.list_available(),
-            SubCommands::ListInstalled => kernel_manager.list_installed()?,
-            SubCommands::SetDefault { target } => {
-                specify_or_select(
+            SubCommands::ListAvailable {
+                format: Some(format),
+                ..
+            } => porcelain::list_formatted(
+                &mut io::stdout(),
+                &shown_kernels,
+                &installed_kernels,
+                &format,
+            )?,
+            SubCommands::ListAvailable {
+                porcelain: true, ..
+            } => porcelain::list_available(
+                &mut io::stdout(),
+                &shown_kernels,
+                &installed_kernels,
+                kernels.len(),
+            )?,
+            SubCommands::ListAvailable { all, .. } => {
+                let listed =
+                    KernelManager::new(&shown_kernels, &installed_kernels).list_available()?;
+                print_listed(listed, kernels.len());
+
+                if all {
+                    for skipped in GenericKernel::scan_modules(&config, sbconf)?.1 {
+                        let line = format!("[-] {} ({})", skipped.dirname, skipped.reason);
+
+                        if matches!(
+                            skipped.reason,
+                            SkipReason::Unmanaged | SkipReason::NeverTouch(_)
+                        ) {
+                            println!("{}", style(line).dim());
+                        } else {
+                            println!("{}", line);
+                        }
+                    }
+                }
+            }
+            SubCommands::ListInstalled {
+                show_size,
+                check_default,
+                porcelain,
+                format,
+                all,
+                ..
+            } => {
+                if let Some(format) = format {
+                    porcelain::list_formatted(
+                        &mut io::stdout(),
+                        &shown_installed,
+                        &installed_kernels,
+                        &format,
+                    )?;
+                } else if porcelain {
+                    porcelain::list_installed(
+                        &mut io::stdout(),
+                        &shown_installed,
+                        installed_kernels.len(),
+                    )?;
+                } else {
+                    let listed =
+                        KernelManager::new(&kernels, &shown_installed).list_installed(show_size)?;
+                    print_listed(listed, installed_kernels.len());
+
+                    if all {
+                        for (kernel, reason) in
+                            GenericKernel::list_unmanaged(&config, sbconf.clone())?
+                        {
+                            println!("{}", style(format!("[-] {} ({})", kernel, reason)).dim());
+                        }
+                    }
+                }
+
+                if check_default {
+                    let code = default_entry::check_default(&config, &sbconf.borrow())?;
+
+                    if code != 0 {
+                        std::process::exit(code);
+                    }
+                }
+            }
+            SubCommands::SetDefault { target, clear } => {
+                let kernel = if clear || target.as_deref() == Some("none") {
+                    None
+                } else if target.is_some() {
+                    Some(specify_or_select(
+                        &installed_kernels,
+                        &config,
+                        &target,
+                        &fl!("select_default"),
+                        sbconf.clone(),
+                    )?)
+                } else {
+                    select_kernel_or_none(
+                        &installed_kernels,
+                        &fl!("select_default"),
+                        &fl!("select_clear_default"),
+                    )?
+                };
+
+                match kernel {
+                    Some(k) => k.set_default(true)?,
+                    None => clear_default(&config, sbconf)?,
+                }
+            }
+            SubCommands::RepairDefault => repair_default(&config, &installed_kernels, sbconf)?,
+            SubCommands::EnsureDefault => ensure_default(&config, &installed_kernels, sbconf)?,
+            SubCommands::AutoConfigureTimeout => auto_set_timeout(&config, sbconf)?,
+            SubCommands::SetTimeout { timeout } => {
+                ask_set_timeout(timeout, sbconf)?;
+            }
+            SubCommands::Config { command: None } => {
+                select_kernel(&installed_kernels, &fl!("select_default"))?.set_default(true)?;
+                ask_set_timeout(None, sbconf)?;
+            }
+            SubCommands::ListEntries {
+                porcelain: true, ..
+            } => porcelain::list_entries(&mut io::stdout(), &config, &sbconf)?,
+            SubCommands::ListEntries { json, .. } => list_entries(&config, sbconf, json)?,
+            SubCommands::TrackChanges => kernel_manager.track_changes()?,
+            SubCommands::DiagnoseBootFailure => diagnose_boot_failure(&config)?,
+            SubCommands::SignAll { tool, dry_run } => sign_all(&config, tool, dry_run)?,
+            SubCommands::ConvertToUki { kernel, output } => convert_to_uki(
+                &config,
+                &specify_or_select(
                     &installed_kernels,
                     &config,
-                    &target,
+                    &Some(kernel),
                     &fl!("select_default"),
                     sbconf,
+                )?,
+                output,
+            )?,
+            SubCommands::NeedsReboot
+            | SubCommands::MergePacnew
+            | SubCommands::MigrateFromSbm { .. }
+            | SubCommands::MigrateEsp { .. }
+            | SubCommands::Config { command: Some(_) }
+            | SubCommands::Version { .. }
+            | SubCommands::GenerateOpenrcScript { .. } => {
+                unreachable!() // Handled above
+            }
+            SubCommands::ShowTopology => show_topology(&config, &sbconf.borrow())?,
+            SubCommands::ShowHistory {
+                default_changes_only,
+            } => show_history(&config.esp_mountpoint, default_changes_only)?,
+            SubCommands::Compare { kernel_a, kernel_b } => {
+                compare(&config, &installed_kernels, &kernel_a, &kernel_b)?
+            }
+            SubCommands::Entry {
+                command:
+                    EntryCommands::Edit {
+                        kernel,
+                        profile,
+                        revert,
+                    },
+            } => {
+                let profile = profile.as_deref().unwrap_or(&config.default_profile);
+                edit_entry(&config, &installed_kernels, &kernel, profile, revert)?
+            }
+            SubCommands::GenerateEntriesOnly { force } => kernel_manager.generate_entries(force)?,
+            SubCommands::Plan { json } => {
+                print_plan(&kernel_manager, &config, json, loader_conf_warnings)?
+            }
+            SubCommands::ApplyPlan { file, yes } => {
+                let code = apply_plan(&kernel_manager, &config, &file, yes || !INTERACTIVE)?;
+
+                if code != 0 {
+                    std::process::exit(code);
+                }
+            }
+            SubCommands::Uninstall { remove_bootloader } => {
+                uninstall(&config, sbconf, remove_bootloader, ask_uninstall)?
+            }
+            SubCommands::PrintEntry { kernel } => {
+                print_entry(&config, &installed_kernels, &kernel)?
+            }
+            SubCommands::KernelInfo { version } => {
+                let kernel = match kernel_manager.find_by_version_string(&version) {
+                    Some(k) => k.clone(),
+                    None => GenericKernel::parse(&config, &version, sbconf)?,
+                };
+
+                kernel_info(&config, &kernel)?
+            }
+            SubCommands::ValidateEntry { path } => {
+                let code = validate_entry(&config, &path)?;
+
+                if code != 0 {
+                    std::process::exit(code);
+                }
+            }
+            SubCommands::CheckToken => {
+                let code = check_token(&config)?;
+
+                if code != 0 {
+                    std::process::exit(code);
+                }
+            }
+            SubCommands::CheckPermissions => {
+                let code = check_permissions(&config)?;
+
+                if code != 0 {
+                    std::process::exit(code);
+                }
+            }
+            SubCommands::Snapshots {
+                command: SnapshotsCommands::Prune { dry_run },
+            } => prune_snapshots(&config, &sbconf.borrow(), dry_run)?,
+            SubCommands::Prune {
+                include_dirs,
+                dry_run,
+                yes,
+            } => {
+                let unmanaged = GenericKernel::list_unmanaged(&config, sbconf)?
+                    .into_iter()
+                    .map(|(k, _)| k)
+                    .collect::<Vec<_>>();
+
+                prune(
+                    &config,
+                    &installed_kernels,
+                    &unmanaged,
+                    PruneOptions {
+                        include_dirs,
+                        dry_run,
+                        assume_yes: yes,
+                    },
                 )?
-                .set_default()?;
             }
-            SubCommands::SetTimeout { timeout } => {
-                ask_set_timeout(timeout, sbconf)?;
+            SubCommands::Verify => {
+                let code = verify(&config, &installed_kernels)?;
+
+                if code != 0 {
+                    std::process::exit(code);
+                }
             }
-            SubCommands::Config => {
-                select_kernel(&installed_kernels, &fl!("select_default"))?.set_default()?;
-                ask_set_timeout(None, sbconf)?;
+            SubCommands::Status { oneline: true, .. } => {
+                oneline(&config, &kernel_manager, &installed_kernels)?
+            }
+            SubCommands::Status {
+                json,
+                watch: Some(interval),
+                ..
+            } => watch::<GenericKernel>(&config, sbconf, Duration::from_secs(interval), json)?,
+            SubCommands::Status { json, .. } => {
+                let code = doctor(&config, sbconf, &installed_kernels, json)?;
+
+                if code != 0 {
+                    std::process::exit(code);
+                }
+            }
+            SubCommands::Default { json } => {
+                let code = print_default(&installed_kernels, &config, sbconf, json)?;
+
+                if code != 0 {
+                    std::process::exit(code);
+                }
             }
         },
         None => unreachable!(),
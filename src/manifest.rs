@@ -0,0 +1,101 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{fl, kernel::REL_ENTRY_PATH, REL_DEST_PATH};
+
+pub(crate) const MANIFEST: &str = "manifest.json";
+/// Stable names of the kernels given by `--version-alias`, next to the
+/// entries named after them
+pub(crate) const ALIASES: &str = ".sbf-aliases.toml";
+
+/// Extra states of the kernels managed by friend, stored in the ESP
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Kernels installed from arbitrary paths, which have no modules directory
+    #[serde(default)]
+    pub out_of_tree: Vec<String>,
+    /// The splash image installed, relative to the ESP
+    #[serde(default)]
+    pub splash: Option<PathBuf>,
+    /// The default kernel before the last change of default
+    #[serde(default)]
+    pub previous_default: Option<String>,
+    /// SHA-256 checksums of the files whose sources were removed by
+    /// `remove_sources`, by the filenames in the ESP
+    #[serde(default)]
+    pub hashes: BTreeMap<String, String>,
+    /// Whether the user has accepted the risks of `remove_sources`
+    #[serde(default)]
+    pub remove_sources_confirmed: bool,
+    /// Entries edited with `entry edit`, by their ids, which are only
+    /// overwritten on confirmation
+    #[serde(default)]
+    pub modified_entries: Vec<String>,
+    /// The timestamps in the names of the entries, by the kernel names,
+    /// if `timestamp_based_entries` is enabled
+    #[serde(default)]
+    pub timestamps: BTreeMap<String, u64>,
+    /// The unified kernel images made by convert-to-uki in EFI/Linux, by
+    /// their filenames, which are not scanned as kernels by `scan_uki`
+    #[serde(default)]
+    pub converted_ukis: Vec<String>,
+}
+
+impl Manifest {
+    /// Read the manifest, an empty one is returned if the file is missing
+    /// or corrupt, so that the kernels can still be removed
+    pub fn read(esp_mountpoint: &Path) -> Result<Self> {
+        let path = esp_mountpoint.join(REL_DEST_PATH).join(MANIFEST);
+
+        match fs::read_to_string(&path) {
+            Ok(s) => Ok(serde_json::from_str(&s).unwrap_or_else(|e| {
+                eprintln!(
+                    "Warning: {}",
+                    fl!(
+                        "manifest_corrupt",
+                        path = path.to_string_lossy(),
+                        error = e.to_string()
+                    )
+                );
+                Self::default()
+            })),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Write the manifest to the ESP
+    pub fn write(&self, esp_mountpoint: &Path) -> Result<()> {
+        fs::write(
+            esp_mountpoint.join(REL_DEST_PATH).join(MANIFEST),
+            serde_json::to_string_pretty(self)?,
+        )?;
+
+        Ok(())
+    }
+}
+
+/// Read the aliases of the kernels, by the kernel names
+pub fn read_aliases(esp_mountpoint: &Path) -> Result<BTreeMap<String, String>> {
+    match fs::read_to_string(esp_mountpoint.join(REL_ENTRY_PATH).join(ALIASES)) {
+        Ok(s) => Ok(toml::from_str(&s)?),
+        Err(_) => Ok(BTreeMap::new()),
+    }
+}
+
+/// Write the aliases of the kernels, the file is removed once empty
+pub fn write_aliases(esp_mountpoint: &Path, aliases: &BTreeMap<String, String>) -> Result<()> {
+    let path = esp_mountpoint.join(REL_ENTRY_PATH).join(ALIASES);
+
+    if aliases.is_empty() {
+        fs::remove_file(path).ok();
+    } else {
+        fs::write(path, toml::to_string(aliases)?)?;
+    }
+
+    Ok(())
+}
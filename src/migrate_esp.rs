@@ -0,0 +1,253 @@
+use anyhow::{bail, Result};
+use libsdbootconf::SystemdBootConf;
+use std::{cell::RefCell, fs, path::Path, rc::Rc};
+
+use crate::{
+    config::Config,
+    fl,
+    kernel::{Kernel, REL_ENTRY_PATH},
+    manifest::{Manifest, ALIASES},
+    print_block_with_fl, println_with_prefix, println_with_prefix_and_fl,
+    prompt::{ColorfulTheme, Confirm},
+    util::run_bootctl,
+    REL_DEST_PATH,
+};
+
+/// The directories of systemd-boot, copied if bootctl cannot install it
+const BOOTLOADER_PATHS: &[&str] = &["EFI/systemd", "EFI/BOOT"];
+
+/// Copy a directory with everything in it
+fn copy_dir(src: &Path, dest: &Path) -> Result<()> {
+    fs::create_dir_all(dest)?;
+
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let path = entry.path();
+
+        if path.is_dir() {
+            copy_dir(&path, &dest.join(entry.file_name()))?;
+        } else {
+            fs::copy(&path, dest.join(entry.file_name()))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Set up systemd-boot on a new ESP, install the kernels of the old one
+/// from the source directory and carry loader.conf, the manifest and the
+/// splash image over. The old ESP is left as it is.
+///
+/// Kernels without a source are only left behind with `force` or on
+/// confirmation.
+pub fn populate_esp<K: Kernel>(
+    config: &mut Config,
+    target: &Path,
+    old: &Path,
+    dry_run: bool,
+    force: bool,
+) -> Result<()> {
+    let old = old.to_path_buf();
+
+    if !target.is_dir() {
+        bail!(fl!(
+            "err_migrate_esp_target",
+            path = target.to_string_lossy()
+        ));
+    }
+
+    if fs::canonicalize(target)? == fs::canonicalize(&old).unwrap_or_else(|_| old.clone()) {
+        bail!(fl!("err_migrate_esp_same", path = target.to_string_lossy()));
+    }
+
+    if dry_run {
+        println_with_prefix_and_fl!("migrate_esp_dry_run");
+    }
+
+    // The kernels installed in the old ESP are installed again
+    config.esp_mountpoint = Rc::new(old.clone());
+    K::invalidate_cache();
+    let old_sbconf = Rc::new(RefCell::new(SystemdBootConf::new(
+        old.join("loader/"),
+        libsdbootconf::Config::default(),
+        Vec::new(),
+    )));
+    let installed = K::list_installed(config, old_sbconf)?
+        .iter()
+        .map(|k| k.name().to_owned())
+        .collect::<Vec<_>>();
+    let old_manifest = Manifest::read(&old)?;
+
+    // Copies of the source directory, not of the old ESP, so that a damaged
+    // copy is not carried over
+    config.esp_mountpoint = Rc::new(target.to_path_buf());
+    K::invalidate_cache();
+    let sbconf = Rc::new(RefCell::new(SystemdBootConf::new(
+        target.join("loader/"),
+        libsdbootconf::Config::default(),
+        Vec::new(),
+    )));
+    let kernels = K::list(config, sbconf)?;
+    let missing = installed
+        .iter()
+        .filter(|name| !kernels.iter().any(|k| k.name() == name.as_str()))
+        .map(|name| name.as_str())
+        .collect::<Vec<_>>();
+
+    for name in missing.iter() {
+        println_with_prefix_and_fl!(
+            "migrate_esp_no_source",
+            kernel = name.to_string(),
+            src = config.src_path.to_string_lossy()
+        );
+    }
+
+    // Such as the kernels installed with `--from`
+    if !missing.is_empty() && !dry_run && !force && !ask_leave_behind(missing.len())? {
+        bail!(fl!(
+            "err_migrate_esp_no_source",
+            kernels = missing.join(", "),
+            src = config.src_path.to_string_lossy()
+        ));
+    }
+
+    println_with_prefix_and_fl!("migrate_esp_bootctl", path = target.to_string_lossy());
+
+    if !dry_run {
+        if let Err(e) = run_bootctl("install", target, config.bootctl_timeout) {
+            // Without bootctl, the bootloader of the old ESP is good enough
+            let copied = BOOTLOADER_PATHS
+                .iter()
+                .map(|p| (old.join(p), target.join(p)))
+                .filter(|(src, _)| src.is_dir())
+                .map(|(src, dest)| copy_dir(&src, &dest))
+                .collect::<Result<Vec<_>>>()?;
+
+            if copied.is_empty() {
+                return Err(e);
+            }
+
+            println_with_prefix_and_fl!("migrate_esp_bootloader_copied", error = e.to_string());
+        }
+
+        fs::create_dir_all(target.join(REL_DEST_PATH))?;
+        fs::create_dir_all(target.join(REL_ENTRY_PATH))?;
+
+        // The aliases name the entries of the kernels
+        let aliases = old.join(REL_ENTRY_PATH).join(ALIASES);
+        if aliases.exists() {
+            fs::copy(&aliases, target.join(REL_ENTRY_PATH).join(ALIASES))?;
+        }
+
+        // Before the kernels are installed, which update it
+        old_manifest.write(target)?;
+
+        if let Some(splash) = &old_manifest.splash {
+            let src = old.join(splash);
+            let dest = target.join(splash);
+            println_with_prefix_and_fl!("install_splash", path = src.to_string_lossy());
+
+            if let Some(parent) = dest.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(&src, dest)?;
+        }
+    }
+
+    for k in kernels
+        .iter()
+        .filter(|k| installed.iter().any(|name| name == k.name()))
+    {
+        if dry_run {
+            println_with_prefix_and_fl!(
+                "migrate_esp_kernel",
+                kernel = k.to_string(),
+                path = target.to_string_lossy()
+            );
+        } else {
+            k.install_and_make_config(true)?;
+        }
+    }
+
+    // The files of the kernels left behind are not in the new ESP
+    if !dry_run && !missing.is_empty() {
+        let mut manifest = Manifest::read(target)?;
+        let dest = target.join(REL_DEST_PATH);
+        manifest
+            .out_of_tree
+            .retain(|name| !missing.contains(&name.as_str()));
+        manifest.hashes.retain(|file, _| dest.join(file).exists());
+        manifest
+            .converted_ukis
+            .retain(|file| target.join("EFI/Linux").join(file).exists());
+        manifest.write(target)?;
+    }
+
+    // loader.conf as a whole, with the options friend does not know about
+    let loader_conf = old.join("loader/loader.conf");
+    if loader_conf.exists() {
+        let dest = target.join("loader/loader.conf");
+        println_with_prefix_and_fl!(
+            "migrate_esp_loader_conf",
+            src = loader_conf.to_string_lossy(),
+            dest = dest.to_string_lossy()
+        );
+
+        if !dry_run {
+            fs::copy(&loader_conf, &dest)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Ask whether to move to the new ESP without the kernels that have no
+/// source, never without a terminal
+fn ask_leave_behind(count: usize) -> Result<bool> {
+    Ok(console::user_attended()
+        && Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(fl!("ask_migrate_esp_no_source", count = count))
+            .default(false)
+            .interact()?)
+}
+
+/// Move to a new ESP, then point the configuration to it
+pub fn migrate_esp<K: Kernel>(
+    config: &mut Config,
+    target: &Path,
+    from: Option<&Path>,
+    dry_run: bool,
+    force: bool,
+) -> Result<()> {
+    let old = from.unwrap_or(&config.esp_mountpoint).to_path_buf();
+    populate_esp::<K>(config, target, &old, dry_run, force)?;
+
+    if dry_run {
+        return Ok(());
+    }
+
+    println_with_prefix_and_fl!(
+        "migrate_esp_done",
+        path = target.to_string_lossy(),
+        old = old.to_string_lossy()
+    );
+
+    // Only the mount point is changed in the configuration file
+    if console::user_attended()
+        && Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(fl!(
+                "ask_migrate_esp_config",
+                path = target.to_string_lossy()
+            ))
+            .default(true)
+            .interact()?
+    {
+        let mut file_config = Config::read_file()?;
+        file_config.esp_mountpoint = Rc::new(target.to_path_buf());
+        file_config.write_back()?;
+    } else {
+        print_block_with_fl!("note_migrate_esp_config", path = target.to_string_lossy());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,183 @@
+use anyhow::Result;
+use std::{
+    env, fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use crate::{fl, println_with_prefix, println_with_prefix_and_fl, MODULES_PATH};
+
+const OPENRC_SCRIPT_PATH: &str = "/etc/init.d/systemd-boot-friend";
+/// Touched by the service before each update
+const STAMP_PATH: &str = "/run/systemd-boot-friend.stamp";
+
+/// The events of the modules directory triggering an update
+const EVENTS: &str = "close_write,moved_to,delete";
+/// Seconds without any event before the package manager is taken as done
+const QUIET_SECONDS: u32 = 5;
+
+/// The loop run by the service, updating once the modules directory has
+/// been quiet for a while. The changes made while updating are caught by
+/// comparing with the stamp touched before the update.
+fn watch_loop(modules_path: &str, sbf: &Path, stamp: &str) -> String {
+    format!(
+        r#"touch '{stamp}'
+while :; do
+	# Changed while the last update was running
+	if [ -z "$(find '{modules_path}' -newer '{stamp}' | head -n 1)" ]; then
+		inotifywait -qq -r -e {EVENTS} '{modules_path}' || exit 1
+	fi
+	# Wait for the package manager to finish
+	while inotifywait -qq -r -t {QUIET_SECONDS} -e {EVENTS} '{modules_path}'; do :; done
+	touch '{stamp}'
+	SBF_ASK_DEFAULT_ON_INSTALL=false '{sbf}' update
+done"#,
+        sbf = sbf.display(),
+    )
+}
+
+/// Quote the script for a double-quoted argument
+fn double_quote(script: &str) -> String {
+    script
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('$', "\\$")
+        .replace('`', "\\`")
+}
+
+/// Generate the OpenRC init script running `sbf update` whenever the
+/// kernel modules directory changes
+fn openrc_script(sbf: &Path) -> String {
+    format!(
+        r#"#!/sbin/openrc-run
+# Generated by `sbf generate-openrc-script`
+
+description="Install the kernels to the ESP when they change"
+
+pidfile="/run/${{RC_SVCNAME}}.pid"
+
+depend() {{
+	need localmount
+	after bootmisc
+}}
+
+start_pre() {{
+	if ! command -v inotifywait > /dev/null; then
+		eerror "inotifywait is not found, please install inotify-tools"
+		return 1
+	fi
+}}
+
+start() {{
+	ebegin "Starting ${{RC_SVCNAME}}"
+	start-stop-daemon --start --background --make-pidfile \
+		--pidfile "${{pidfile}}" --exec /bin/sh -- -c \
+		"{watch_loop}"
+	eend $?
+}}
+
+stop() {{
+	ebegin "Stopping ${{RC_SVCNAME}}"
+	# Also stop the inotifywait waiting in the loop
+	[ -f "${{pidfile}}" ] && pkill -P "$(cat "${{pidfile}}")"
+	start-stop-daemon --stop --pidfile "${{pidfile}}"
+	eend $?
+}}
+"#,
+        watch_loop = double_quote(&watch_loop(MODULES_PATH, sbf, STAMP_PATH)),
+    )
+}
+
+/// Write the OpenRC init script to the path, /etc/init.d by default
+pub fn generate_openrc_script(output: Option<PathBuf>) -> Result<()> {
+    let output = output.unwrap_or_else(|| PathBuf::from(OPENRC_SCRIPT_PATH));
+    let sbf = env::current_exe().unwrap_or_else(|_| PathBuf::from("/usr/bin/sbf"));
+
+    fs::write(&output, openrc_script(&sbf))?;
+    fs::set_permissions(&output, fs::Permissions::from_mode(0o755))?;
+
+    println_with_prefix_and_fl!("openrc_script", path = output.to_string_lossy());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn test_openrc_script() {
+        let script = openrc_script(Path::new("/usr/bin/sbf"));
+
+        assert!(script.contains("SBF_ASK_DEFAULT_ON_INSTALL=false '/usr/bin/sbf' update"));
+        let status = Command::new("sh")
+            .args(["-n", "-c", &script])
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+
+    #[test]
+    fn test_watch_loop() {
+        let dir = tempfile::tempdir().unwrap();
+        let (bin, modules) = (dir.path().join("bin"), dir.path().join("modules"));
+        fs::create_dir_all(&bin).unwrap();
+        fs::create_dir_all(&modules).unwrap();
+
+        // An event, quiet, then the watch fails to end the loop
+        let inotifywait = bin.join("inotifywait");
+        fs::write(
+            &inotifywait,
+            format!(
+                r#"#!/bin/sh
+n=$(($(cat '{count}' 2>/dev/null || echo 0) + 1))
+echo "$n" > '{count}'
+case "$*" in *"-t "*) exit 2 ;; esac
+[ "$n" -eq 1 ]
+"#,
+                count = dir.path().join("count").display()
+            ),
+        )
+        .unwrap();
+        // A kernel is installed while the first update is running
+        let sbf = bin.join("sbf");
+        fs::write(
+            &sbf,
+            format!(
+                r#"#!/bin/sh
+echo "$SBF_ASK_DEFAULT_ON_INSTALL $1" >> '{log}'
+# Newer than the stamp on any filesystem
+sleep 1
+[ -e '{new}' ] || touch '{new}'
+"#,
+                log = dir.path().join("log").display(),
+                new = modules.join("6.9.0").display()
+            ),
+        )
+        .unwrap();
+        for f in [&inotifywait, &sbf] {
+            fs::set_permissions(f, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        let script = watch_loop(
+            &modules.to_string_lossy(),
+            &sbf,
+            &dir.path().join("stamp").to_string_lossy(),
+        );
+        let status = Command::new("sh")
+            .args(["-c", &script])
+            .env(
+                "PATH",
+                format!("{}:{}", bin.display(), env::var("PATH").unwrap()),
+            )
+            .status()
+            .unwrap();
+
+        assert_eq!(status.code(), Some(1));
+        assert_eq!(
+            fs::read_to_string(dir.path().join("log")).unwrap(),
+            "false update\nfalse update\n"
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use anyhow::Result;
+use similar::TextDiff;
+use std::fs;
+
+use crate::{
+    config::{conf_path, Config},
+    fl, println_with_prefix, println_with_prefix_and_fl,
+    prompt::{ColorfulTheme, Select},
+    util::print_colored_diff,
+};
+
+/// Print the differences between the current configuration and the new one
+fn print_diff(current: &str, pacnew: &str, pacnew_path: &str) {
+    let diff = TextDiff::from_lines(current, pacnew);
+
+    print_colored_diff(
+        &diff
+            .unified_diff()
+            .header(&conf_path().to_string_lossy(), pacnew_path)
+            .to_string(),
+    );
+}
+
+/// Add the options only present in the new configuration, keep the
+/// values of the current one
+fn merge(current: &str, pacnew: &str) -> Result<String> {
+    let mut merged: toml::Table = toml::from_str(current)?;
+    let pacnew: toml::Table = toml::from_str(pacnew)?;
+
+    for (key, value) in pacnew {
+        merged.entry(key).or_insert(value);
+    }
+
+    Ok(toml::to_string_pretty(&merged)?)
+}
+
+/// Merge or replace the configuration with the .pacnew file left by pacman
+pub fn merge_pacnew() -> Result<()> {
+    let conf_path = conf_path();
+    let pacnew_path = format!("{}.pacnew", conf_path.display());
+
+    let pacnew = match fs::read_to_string(&pacnew_path) {
+        Ok(s) => s,
+        Err(_) => {
+            println_with_prefix_and_fl!("no_pacnew", path = pacnew_path);
+            return Ok(());
+        }
+    };
+    let current = fs::read_to_string(&conf_path).unwrap_or_default();
+
+    print_diff(&current, &pacnew, &pacnew_path);
+    println!();
+
+    let items = [
+        fl!("pacnew_merge"),
+        fl!("pacnew_replace"),
+        fl!("pacnew_keep"),
+    ];
+    let result = match Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(fl!("ask_merge_pacnew"))
+        .items(&items)
+        .default(0)
+        .interact()?
+    {
+        0 => merge(&current, &pacnew)?,
+        1 => pacnew,
+        _ => current,
+    };
+
+    // Refuse to write a configuration friend can not read
+    toml::from_str::<Config>(&result)?.validate()?;
+
+    fs::write(conf_path, result)?;
+    fs::remove_file(&pacnew_path)?;
+    println_with_prefix_and_fl!("pacnew_merged", path = pacnew_path);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge() {
+        let current = "VMLINUX = \"vmlinuz-{VERSION}\"\nKEEP = 2\n";
+        let pacnew = "VMLINUX = \"vmlinux-{VERSION}\"\nKEEP = 3\nDISTRO = \"AOSC OS\"\n";
+        let merged: toml::Table = toml::from_str(&merge(current, pacnew).unwrap()).unwrap();
+
+        assert_eq!(merged["VMLINUX"].as_str(), Some("vmlinuz-{VERSION}"));
+        assert_eq!(merged["KEEP"].as_integer(), Some(2));
+        assert_eq!(merged["DISTRO"].as_str(), Some("AOSC OS"));
+    }
+}
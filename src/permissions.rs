@@ -0,0 +1,168 @@
+use anyhow::Result;
+use console::style;
+use std::{
+    fs,
+    os::unix::fs::{MetadataExt, PermissionsExt},
+    path::{Path, PathBuf},
+};
+
+use crate::{config::Config, fl, kernel::REL_ENTRY_PATH, REL_DEST_PATH};
+
+const MOUNTS: &str = "/proc/mounts";
+/// Filesystems without Unix permissions
+const NO_PERMISSION_FS: &[&str] = &["vfat", "msdos", "exfat"];
+
+/// A problem found with the permissions of a file or directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    NotOwnedByRoot,
+    NotReadable,
+    NotTraversable,
+    WorldWritable,
+}
+
+impl Problem {
+    fn describe(&self) -> String {
+        match self {
+            Problem::NotOwnedByRoot => fl!("perm_not_owned_by_root"),
+            Problem::NotReadable => fl!("perm_not_readable"),
+            Problem::NotTraversable => fl!("perm_not_traversable"),
+            Problem::WorldWritable => fl!("perm_world_writable"),
+        }
+    }
+}
+
+/// Get the filesystem type of the mount containing the path
+fn fs_type(path: &Path) -> Option<String> {
+    let path = fs::canonicalize(path).ok()?;
+    let mounts = fs::read_to_string(MOUNTS).ok()?;
+
+    mounts
+        .lines()
+        .filter_map(|l| {
+            let mut parts = l.split_whitespace().skip(1);
+            Some((PathBuf::from(parts.next()?), parts.next()?.to_owned()))
+        })
+        .filter(|(m, _)| path.starts_with(m))
+        .max_by_key(|(m, _)| m.components().count())
+        .map(|(_, t)| t)
+}
+
+/// Check the permissions of a file or directory
+fn check(path: &Path) -> Result<Vec<Problem>> {
+    let meta = fs::symlink_metadata(path)?;
+    let mode = meta.permissions().mode();
+    let mut problems = Vec::new();
+
+    if meta.uid() != 0 {
+        problems.push(Problem::NotOwnedByRoot);
+    }
+
+    if mode & 0o400 == 0 {
+        problems.push(Problem::NotReadable);
+    }
+
+    if meta.is_dir() && mode & 0o100 == 0 {
+        problems.push(Problem::NotTraversable);
+    }
+
+    if mode & 0o002 != 0 {
+        problems.push(Problem::WorldWritable);
+    }
+
+    Ok(problems)
+}
+
+/// Check the directory and everything in it
+pub fn check_tree(path: &Path) -> Result<Vec<(PathBuf, Vec<Problem>)>> {
+    let mut result = Vec::new();
+    let problems = check(path)?;
+
+    if !problems.is_empty() {
+        result.push((path.to_owned(), problems));
+    }
+
+    if path.is_dir() {
+        let mut children = fs::read_dir(path)?
+            .map(|e| Ok(e?.path()))
+            .collect::<Result<Vec<_>>>()?;
+        children.sort();
+
+        for child in children {
+            result.extend(check_tree(&child)?);
+        }
+    }
+
+    Ok(result)
+}
+
+/// Check the ownership and permissions of the files managed by friend,
+/// return the exit code
+pub fn check_permissions(config: &Config) -> Result<i32> {
+    if let Some(t) =
+        fs_type(&config.esp_mountpoint).filter(|t| NO_PERMISSION_FS.contains(&t.as_str()))
+    {
+        println!("{}", fl!("perm_skip_fat", fs = t));
+        return Ok(0);
+    }
+
+    let mut code = 0;
+
+    for dir in [REL_DEST_PATH, REL_ENTRY_PATH] {
+        let dir = config.esp_mountpoint.join(dir);
+
+        if !dir.exists() {
+            continue;
+        }
+
+        for (path, problems) in check_tree(&dir)? {
+            for p in problems {
+                println!(
+                    "{} {}: {}",
+                    style("[!]").red(),
+                    path.display(),
+                    p.describe()
+                );
+            }
+            code = 1;
+        }
+    }
+
+    if code == 0 {
+        println!("{}", fl!("perm_ok"));
+    }
+
+    Ok(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_world_writable() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("vmlinuz");
+
+        fs::write(&file, "").unwrap();
+        fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o755)).unwrap();
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o666)).unwrap();
+
+        let problems = check_tree(dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|(p, problems)| {
+                (
+                    p,
+                    problems
+                        .into_iter()
+                        .filter(|p| *p != Problem::NotOwnedByRoot)
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .filter(|(_, problems)| !problems.is_empty())
+            .collect::<Vec<_>>();
+
+        assert_eq!(problems, vec![(file, vec![Problem::WorldWritable])]);
+    }
+}
@@ -0,0 +1,256 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config::Config,
+    fl,
+    kernel::{sha256_file, KernelOps, REL_ENTRY_PATH},
+    kernel_manager::{print_summary, KernelManager, RetainedKernel, UpdatePlan},
+    println_with_prefix, println_with_prefix_and_fl,
+    prompt::{ColorfulTheme, Confirm},
+    reboot::running_kernel,
+    REL_DEST_PATH,
+};
+
+/// The format of the plan file, raised on incompatible changes
+const PLAN_VERSION: u32 = 1;
+/// Exit code of apply-plan if the system changed since the plan was made
+pub const EXIT_PLAN_STALE: i32 = 3;
+/// Exit code of apply-plan if the plan was made without reading everything,
+/// e.g. by a user who cannot read the ESP, so it can never be checked
+pub const EXIT_PLAN_INCOMPLETE: i32 = 4;
+
+/// The changes of a plan, by the kernel names
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlannedChanges {
+    pub installs: Vec<String>,
+    pub refreshes: Vec<String>,
+    pub removals: Vec<String>,
+    pub entry_writes: Vec<String>,
+    /// The kernel to be set as default, if any
+    pub default: Option<String>,
+    pub retained: Vec<RetainedKernel>,
+    pub excluded: Vec<String>,
+}
+
+/// A file the plan depends on, with its checksum, None if it does not exist
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlannedFile {
+    pub path: PathBuf,
+    pub sha256: Option<String>,
+}
+
+/// An update plan made without touching anything, for `plan --json` and
+/// `apply-plan`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PlanFile {
+    pub version: u32,
+    pub esp_mountpoint: PathBuf,
+    #[serde(flatten)]
+    pub changes: PlannedChanges,
+    /// The source files to be copied and the files in the ESP to be
+    /// replaced or removed, checked again before the plan is applied
+    pub files: Vec<PlannedFile>,
+    /// False if some files could not be read, the plan may then differ
+    /// from the one apply-plan makes, which refuses it
+    pub complete: bool,
+    pub warnings: Vec<String>,
+}
+
+impl PlannedChanges {
+    fn new<K: KernelOps>(plan: &UpdatePlan<K>) -> Self {
+        let names = |kernels: &[&K]| kernels.iter().map(|k| k.name().to_owned()).collect();
+
+        Self {
+            installs: names(&plan.installs),
+            refreshes: names(&plan.refreshes),
+            removals: names(&plan.removals),
+            entry_writes: names(&plan.entry_writes),
+            default: plan
+                .default_change
+                .as_ref()
+                .map(|d| d.new.name().to_owned()),
+            retained: plan
+                .retained
+                .iter()
+                .map(|(k, reason)| RetainedKernel {
+                    kernel: k.name().to_owned(),
+                    reason: *reason,
+                })
+                .collect(),
+            excluded: names(&plan.excluded),
+        }
+    }
+}
+
+/// The checksum of the file, None if it does not exist
+fn file_hash(path: &Path) -> Result<Option<String>> {
+    match fs::metadata(path) {
+        Ok(_) => Ok(Some(sha256_file(path)?)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The files whose changes would make the plan stale
+fn planned_paths<K: KernelOps>(config: &Config, plan: &UpdatePlan<K>) -> Vec<PathBuf> {
+    let mut paths = vec![config.esp_mountpoint.join("loader/loader.conf")];
+
+    for k in plan.installs.iter().chain(plan.refreshes.iter()) {
+        paths.extend(k.source_files().into_iter().map(|(src, _)| src));
+    }
+
+    for k in plan.refreshes.iter().chain(plan.removals.iter()) {
+        paths.extend(k.esp_files());
+    }
+
+    // The microcode image may be shared by the kernels
+    paths.sort();
+    paths.dedup();
+
+    paths
+}
+
+impl PlanFile {
+    /// Describe the plan, the unreadable files are left out with warnings
+    pub fn new<K: KernelOps>(
+        config: &Config,
+        plan: &UpdatePlan<K>,
+        mut warnings: Vec<String>,
+    ) -> Self {
+        // The installed kernels cannot be told without these
+        for dir in [REL_DEST_PATH, REL_ENTRY_PATH] {
+            let path = config.esp_mountpoint.join(dir);
+
+            if let Err(e) = fs::read_dir(&path) {
+                if e.kind() != ErrorKind::NotFound {
+                    warnings.push(fl!(
+                        "plan_unreadable",
+                        path = path.to_string_lossy(),
+                        error = e.to_string()
+                    ));
+                }
+            }
+        }
+
+        let mut files = Vec::new();
+
+        for path in planned_paths(config, plan) {
+            match file_hash(&path) {
+                Ok(sha256) => files.push(PlannedFile { path, sha256 }),
+                Err(e) => warnings.push(fl!(
+                    "plan_unreadable",
+                    path = path.to_string_lossy(),
+                    error = e.to_string()
+                )),
+            }
+        }
+
+        Self {
+            version: PLAN_VERSION,
+            esp_mountpoint: config.esp_mountpoint.to_path_buf(),
+            changes: PlannedChanges::new(plan),
+            files,
+            complete: warnings.is_empty(),
+            warnings,
+        }
+    }
+
+    /// Tell why the plan no longer matches the system, if it does not
+    fn stale_reason<K: KernelOps>(&self, config: &Config, plan: &UpdatePlan<K>) -> Option<String> {
+        if self.version != PLAN_VERSION {
+            return Some(fl!("plan_version", version = self.version));
+        }
+
+        if self.esp_mountpoint != *config.esp_mountpoint {
+            return Some("ESP_MOUNTPOINT".to_owned());
+        }
+
+        if self.changes != PlannedChanges::new(plan) {
+            return Some(fl!("plan_changed"));
+        }
+
+        self.files
+            .iter()
+            .find(|f| file_hash(&f.path).ok().as_ref() != Some(&f.sha256))
+            .map(|f| f.path.to_string_lossy().into_owned())
+    }
+}
+
+/// Print what update would do without touching anything, as JSON if
+/// asked. The warnings of the caller, e.g. for an unreadable loader.conf,
+/// are embedded in the JSON.
+pub fn print_plan<K: KernelOps + PartialEq>(
+    manager: &KernelManager<K>,
+    config: &Config,
+    json: bool,
+    warnings: Vec<String>,
+) -> Result<()> {
+    let plan = manager.plan(config, running_kernel().ok().as_deref())?;
+    let plan_file = PlanFile::new(config, &plan, warnings);
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&plan_file)?);
+        return Ok(());
+    }
+
+    for warning in plan_file.warnings.iter() {
+        println_with_prefix!("{}", warning);
+    }
+
+    let summary = plan.summary()?;
+
+    if summary.is_empty() {
+        println_with_prefix_and_fl!("plan_empty");
+    }
+
+    print_summary(&summary);
+
+    if let Some(d) = plan.default_change {
+        println_with_prefix_and_fl!("plan_default", kernel = d.new.to_string());
+    }
+
+    Ok(())
+}
+
+/// Apply a plan printed by `plan --json` if the system still matches it,
+/// return the exit code
+pub fn apply_plan<K: KernelOps + PartialEq>(
+    manager: &KernelManager<K>,
+    config: &Config,
+    path: &Path,
+    assume_yes: bool,
+) -> Result<i32> {
+    let plan_file: PlanFile = serde_json::from_str(&fs::read_to_string(path)?)?;
+
+    if !plan_file.complete {
+        println_with_prefix_and_fl!("plan_incomplete");
+        return Ok(EXIT_PLAN_INCOMPLETE);
+    }
+
+    let plan = manager.plan(config, running_kernel().ok().as_deref())?;
+
+    if let Some(reason) = plan_file.stale_reason(config, &plan) {
+        println_with_prefix_and_fl!("plan_stale", reason = reason);
+        return Ok(EXIT_PLAN_STALE);
+    }
+
+    if !assume_yes
+        && !Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(fl!("ask_apply_plan"))
+            .default(false)
+            .interact()?
+    {
+        println_with_prefix_and_fl!("plan_not_applied");
+        return Ok(0);
+    }
+
+    manager.execute(config, plan, None, false)?;
+
+    Ok(0)
+}
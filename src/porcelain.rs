@@ -0,0 +1,130 @@
+//! Stable output for scripts, selected with `--porcelain`
+//!
+//! Every line is a record of tab-separated fields, without styling or
+//! localization. The columns are never reordered, new columns may only be
+//! appended to the end.
+//!
+//! - `list-available`: NAME, VERSION, `installed` | `available`, TOTAL
+//! - `list-installed`: NAME, VERSION, `default` | `installed`, SIZE in bytes,
+//!   TOTAL
+//!
+//! TOTAL is the number of kernels before `--newest` and `--since` are
+//! applied, so that the truncation can be noticed.
+//! - `list-entries`: ENTRY, `default` | `entry` | `broken`,
+//!   `friend` | `foreign`, `hidden` | `visible`, TARGET or `-`
+//!
+//! `list-available` and `list-installed` also take `--format`, a template
+//! expanded for every kernel with the placeholders {version}, {display},
+//! {installed}, {default}, {size}, {pkgbase} and {build_id}.
+
+use anyhow::Result;
+use libsdbootconf::SystemdBootConf;
+use std::{cell::RefCell, fs, io::Write, rc::Rc};
+
+use crate::{config::Config, entry_list::collect_entries, kernel::Kernel, util::expand_template};
+
+/// Print the available kernels
+pub fn list_available<K: Kernel, W: Write>(
+    out: &mut W,
+    kernels: &[K],
+    installed_kernels: &[K],
+    total: usize,
+) -> Result<()> {
+    for k in kernels.iter() {
+        let state = if installed_kernels.contains(k) {
+            "installed"
+        } else {
+            "available"
+        };
+
+        writeln!(out, "{}\t{}\t{}\t{}", k.name(), k, state, total)?;
+    }
+
+    Ok(())
+}
+
+/// Print the installed kernels
+pub fn list_installed<K: Kernel, W: Write>(
+    out: &mut W,
+    installed_kernels: &[K],
+    total: usize,
+) -> Result<()> {
+    for k in installed_kernels.iter() {
+        let state = if k.is_default()? {
+            "default"
+        } else {
+            "installed"
+        };
+        let size = k
+            .esp_files()
+            .iter()
+            .map(|f| Ok(fs::metadata(f)?.len()))
+            .sum::<Result<u64>>()?;
+
+        writeln!(out, "{}\t{}\t{}\t{}\t{}", k.name(), k, state, size, total)?;
+    }
+
+    Ok(())
+}
+
+/// Print the kernels with the user-defined template, one per line
+pub fn list_formatted<K: Kernel, W: Write>(
+    out: &mut W,
+    kernels: &[K],
+    installed_kernels: &[K],
+    format: &str,
+) -> Result<()> {
+    for k in kernels.iter() {
+        let installed = installed_kernels.contains(k);
+        // The kernels not installed yet take no space in the ESP
+        let size = k
+            .esp_files()
+            .iter()
+            .filter_map(|f| fs::metadata(f).ok())
+            .map(|m| m.len())
+            .sum::<u64>();
+        let summary = k.summary()?;
+        let fields = [
+            ("version", k.name().to_owned()),
+            ("display", k.to_string()),
+            ("installed", installed.to_string()),
+            ("default", k.is_default()?.to_string()),
+            ("size", size.to_string()),
+            ("pkgbase", summary.pkgbase),
+            ("build_id", summary.build_id),
+        ];
+
+        writeln!(out, "{}", expand_template(format, &fields)?)?;
+    }
+
+    Ok(())
+}
+
+/// Print all the boot entries
+pub fn list_entries<W: Write>(
+    out: &mut W,
+    config: &Config,
+    sbconf: &Rc<RefCell<SystemdBootConf>>,
+) -> Result<()> {
+    for info in collect_entries(config, sbconf)? {
+        let state = if info.error.is_some() {
+            "broken"
+        } else if info.default {
+            "default"
+        } else {
+            "entry"
+        };
+
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{}",
+            info.entry,
+            state,
+            if info.managed { "friend" } else { "foreign" },
+            if info.hidden { "hidden" } else { "visible" },
+            info.target.as_deref().unwrap_or("-")
+        )?;
+    }
+
+    Ok(())
+}
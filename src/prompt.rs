@@ -0,0 +1,137 @@
+//! The prompts, from dialoguer with the `interactive` feature
+//!
+//! Without it, the prompts of the same names answer by themselves with their
+//! defaults. The questions `--yes` covers are not asked at all, as the
+//! callers take `--yes` as given without the feature.
+
+#[cfg(feature = "interactive")]
+pub use dialoguer::{theme::ColorfulTheme, Confirm, Input, MultiSelect, Select};
+
+#[cfg(not(feature = "interactive"))]
+pub use answered::*;
+
+#[cfg(not(feature = "interactive"))]
+mod answered {
+    use std::io;
+
+    /// Stands in for the theme of dialoguer, nothing is drawn
+    #[derive(Default)]
+    pub struct ColorfulTheme {
+        _private: (),
+    }
+
+    /// Takes the default answer
+    pub struct Confirm {
+        default: Option<bool>,
+    }
+
+    impl Confirm {
+        pub fn with_theme(_: &ColorfulTheme) -> Self {
+            Self { default: None }
+        }
+
+        pub fn with_prompt<S: Into<String>>(self, _: S) -> Self {
+            self
+        }
+
+        pub fn default(self, default: bool) -> Self {
+            Self {
+                default: Some(default),
+            }
+        }
+
+        pub fn interact(self) -> io::Result<bool> {
+            self.default
+                .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "no default answer"))
+        }
+    }
+
+    /// Chooses the default item, the first one if not given
+    pub struct Select {
+        default: usize,
+    }
+
+    impl Select {
+        pub fn with_theme(_: &ColorfulTheme) -> Self {
+            Self { default: 0 }
+        }
+
+        pub fn with_prompt<S: Into<String>>(self, _: S) -> Self {
+            self
+        }
+
+        pub fn items<T: ToString>(self, _: &[T]) -> Self {
+            self
+        }
+
+        pub fn default(self, default: usize) -> Self {
+            Self { default }
+        }
+
+        pub fn interact(self) -> io::Result<usize> {
+            Ok(self.default)
+        }
+    }
+
+    /// Chooses the items checked by default
+    pub struct MultiSelect {
+        defaults: Vec<bool>,
+    }
+
+    impl MultiSelect {
+        pub fn with_theme(_: &ColorfulTheme) -> Self {
+            Self {
+                defaults: Vec::new(),
+            }
+        }
+
+        pub fn with_prompt<S: Into<String>>(self, _: S) -> Self {
+            self
+        }
+
+        pub fn items<T: ToString>(self, _: &[T]) -> Self {
+            self
+        }
+
+        pub fn defaults(self, defaults: &[bool]) -> Self {
+            Self {
+                defaults: defaults.to_vec(),
+            }
+        }
+
+        pub fn interact(self) -> io::Result<Vec<usize>> {
+            Ok(self
+                .defaults
+                .iter()
+                .enumerate()
+                .filter_map(|(i, d)| d.then_some(i))
+                .collect())
+        }
+    }
+
+    /// Takes the default value, there is nothing else to take
+    pub struct Input<T> {
+        default: Option<T>,
+    }
+
+    impl<T> Input<T> {
+        pub fn with_theme(_: &ColorfulTheme) -> Self {
+            Self { default: None }
+        }
+
+        pub fn with_prompt<S: Into<String>>(self, _: S) -> Self {
+            self
+        }
+
+        pub fn default(self, default: T) -> Self {
+            Self {
+                default: Some(default),
+            }
+        }
+
+        pub fn interact(self) -> io::Result<T> {
+            self.default
+                .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "no default value"))
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use anyhow::Result;
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config::Config,
+    fl,
+    journal::JOURNAL,
+    kernel::{ostree_esp_files, Kernel},
+    manifest::{Manifest, MANIFEST},
+    println_with_prefix, println_with_prefix_and_fl,
+    prompt::{ColorfulTheme, Confirm},
+    REL_DEST_PATH,
+};
+
+/// List the directories under the friend prefix in the ESP, which friend
+/// never creates
+pub fn unexpected_dirs(esp_mountpoint: &Path) -> Result<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+
+    if let Ok(d) = fs::read_dir(esp_mountpoint.join(REL_DEST_PATH)) {
+        for entry in d {
+            let entry = entry?;
+
+            if entry.file_type()?.is_dir() {
+                dirs.push(entry.path());
+            }
+        }
+    }
+
+    dirs.sort();
+
+    Ok(dirs)
+}
+
+/// What prune removes and whether it asks first
+#[derive(Debug, Default, Clone, Copy)]
+pub struct PruneOptions {
+    /// Also remove the unexpected directories
+    pub include_dirs: bool,
+    /// Only list what would be removed
+    pub dry_run: bool,
+    /// Remove without asking
+    pub assume_yes: bool,
+}
+
+/// Remove the files under the friend prefix in the ESP which belong to no
+/// installed kernel after confirmation, and the unexpected directories with
+/// `include_dirs`. The files of the `unmanaged` kernels, which friend
+/// leaves alone, are kept.
+pub fn prune<K: Kernel>(
+    config: &Config,
+    installed_kernels: &[K],
+    unmanaged: &[K],
+    options: PruneOptions,
+) -> Result<()> {
+    let dest_path = config.esp_mountpoint.join(REL_DEST_PATH);
+    let manifest = Manifest::read(&config.esp_mountpoint)?;
+
+    let mut known = installed_kernels
+        .iter()
+        .chain(unmanaged)
+        .flat_map(|k| k.esp_files())
+        .collect::<HashSet<_>>();
+    known.insert(dest_path.join(MANIFEST));
+    known.insert(dest_path.join(JOURNAL));
+    known.extend(manifest.splash.map(|s| config.esp_mountpoint.join(s)));
+    // Installed by ostree itself
+    known.extend(ostree_esp_files(config)?);
+
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(&dest_path)? {
+        let entry = entry?;
+
+        if !entry.file_type()?.is_dir() && !known.contains(&entry.path()) {
+            files.push(entry.path());
+        }
+    }
+
+    files.sort();
+
+    for file in files.iter() {
+        println_with_prefix_and_fl!("prune_would_remove", path = file.to_string_lossy());
+    }
+
+    if !files.is_empty()
+        && !options.dry_run
+        && (options.assume_yes
+            || Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(fl!("ask_prune_files", count = files.len()))
+                .default(false)
+                .interact()?)
+    {
+        for file in files {
+            println_with_prefix_and_fl!("prune_file", path = file.to_string_lossy());
+            fs::remove_file(file)?;
+        }
+    }
+
+    let dirs = unexpected_dirs(&config.esp_mountpoint)?;
+
+    if dirs.is_empty() {
+        return Ok(());
+    }
+
+    if !options.include_dirs {
+        for dir in dirs {
+            println_with_prefix_and_fl!("prune_skip_dir", path = dir.to_string_lossy());
+        }
+        return Ok(());
+    }
+
+    for dir in dirs {
+        if options.dry_run {
+            println_with_prefix_and_fl!("prune_would_remove", path = dir.to_string_lossy());
+        } else if options.assume_yes
+            || Confirm::with_theme(&ColorfulTheme::default())
+                .with_prompt(fl!("ask_prune_dir", path = dir.to_string_lossy()))
+                .default(false)
+                .interact()?
+        {
+            println_with_prefix_and_fl!("prune_dir", path = dir.to_string_lossy());
+            fs::remove_dir_all(dir)?;
+        }
+    }
+
+    Ok(())
+}
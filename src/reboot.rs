@@ -0,0 +1,98 @@
+use anyhow::Result;
+use std::{
+    cell::RefCell,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+use crate::{fl, println_with_prefix, println_with_prefix_and_fl};
+
+/// Lives in /run so that it is cleared on the next boot
+const STAMP_PATH: &str = "/run/systemd-boot-friend/reboot-required";
+const OSRELEASE: &str = "/proc/sys/kernel/osrelease";
+
+/// Exit code of `needs-reboot` when a reboot is pending
+pub const EXIT_REBOOT_REQUIRED: i32 = 0;
+/// Exit code of `needs-reboot` when the default kernel is running
+pub const EXIT_NO_REBOOT_REQUIRED: i32 = 1;
+
+thread_local! {
+    static STAMP: RefCell<PathBuf> = RefCell::new(PathBuf::from(STAMP_PATH));
+}
+
+/// Keep the reboot stamp at the path in this session instead of /run
+pub fn set_stamp_path(path: PathBuf) {
+    STAMP.with(|s| *s.borrow_mut() = path);
+}
+
+fn stamp_path() -> PathBuf {
+    STAMP.with(|s| s.borrow().clone())
+}
+
+/// Get the release string of the running kernel
+pub(crate) fn running_kernel() -> Result<String> {
+    Ok(fs::read_to_string(OSRELEASE)?.trim().to_owned())
+}
+
+/// Write the stamp if the default kernel is not the running one, remove it
+/// otherwise. The stamp is only a hint, a failure is reported and ignored.
+pub fn refresh_stamp(default: &str) {
+    let stamp = stamp_path();
+
+    if let Err(e) = write_stamp(&stamp, default) {
+        println_with_prefix_and_fl!(
+            "reboot_stamp_failed",
+            path = stamp.to_string_lossy(),
+            error = e.to_string()
+        );
+    }
+}
+
+fn write_stamp(stamp: &Path, default: &str) -> Result<()> {
+    if running_kernel()? != default {
+        println_with_prefix_and_fl!("reboot_required", version = default);
+
+        if let Some(parent) = stamp.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(stamp, format!("{}\n", default))?;
+    } else {
+        match fs::remove_file(stamp) {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
+
+/// Get the kernel version waiting for a reboot, if any
+pub fn pending_reboot() -> Result<Option<String>> {
+    let version = match fs::read_to_string(stamp_path()) {
+        Ok(v) => v.trim().to_owned(),
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    // The stamp is stale if the kernel is already running
+    if version.is_empty() || running_kernel()? == version {
+        return Ok(None);
+    }
+
+    Ok(Some(version))
+}
+
+/// Print whether a reboot is pending, return the exit code
+pub fn needs_reboot() -> Result<i32> {
+    match pending_reboot()? {
+        Some(version) => {
+            println_with_prefix_and_fl!("reboot_required", version = version);
+            Ok(EXIT_REBOOT_REQUIRED)
+        }
+        None => {
+            println_with_prefix_and_fl!("no_reboot_required");
+            Ok(EXIT_NO_REBOOT_REQUIRED)
+        }
+    }
+}
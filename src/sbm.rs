@@ -0,0 +1,179 @@
+use anyhow::{anyhow, Result};
+use similar::TextDiff;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{
+    config::{conf_path, AskDefault, Config},
+    fl, println_with_prefix, println_with_prefix_and_fl,
+    prompt::{ColorfulTheme, Confirm},
+    util::print_colored_diff,
+};
+
+const SBM_CONF_PATH: &str = "/etc/systemd-boot-manager.conf";
+
+/// Read the shell-style `KEY="value"` assignments of the configuration
+fn parse(content: &str) -> Vec<(String, String)> {
+    content
+        .lines()
+        .filter_map(|l| {
+            let l = l.trim();
+            let l = l.strip_prefix("export ").unwrap_or(l);
+
+            if l.starts_with('#') {
+                return None;
+            }
+
+            let (key, value) = l.split_once('=')?;
+            let value = value.trim();
+            let value = ['"', '\'']
+                .iter()
+                .find_map(|q| value.strip_prefix(*q)?.strip_suffix(*q))
+                .unwrap_or_else(|| value.split(" #").next().unwrap_or_default().trim());
+
+            Some((key.trim().to_owned(), value.to_owned()))
+        })
+        .collect()
+}
+
+/// Check if the option should take the value of systemd-boot-manager, ask
+/// if both set it differently
+fn accept(key: &str, current: &str, default: &str, new: &str) -> Result<bool> {
+    if current == new || current == default {
+        return Ok(true);
+    }
+
+    Ok(Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(fl!(
+            "ask_sbm_replace",
+            key = key,
+            current = current,
+            new = new
+        ))
+        .default(false)
+        .interact()?)
+}
+
+/// Map the systemd-boot-manager configuration onto the current one
+fn migrate(config: &mut Config, sbm: Vec<(String, String)>) -> Result<()> {
+    let default = Config::default();
+
+    for (key, value) in sbm {
+        match key.as_str() {
+            "LINUX_OPTIONS" => {
+                let current = config
+                    .bootargs
+                    .borrow()
+                    .get("default")
+                    .cloned()
+                    .unwrap_or_default();
+
+                if accept("BOOTARGS.default", &current, "", &value)? {
+                    config
+                        .bootargs
+                        .borrow_mut()
+                        .insert("default".to_owned(), value);
+                }
+            }
+            "ENTRY_NAME" | "ENTRY_TITLE" => {
+                if accept("DISTRO", &config.distro, &default.distro, &value)? {
+                    config.distro = Rc::new(value);
+                }
+            }
+            "ESP" | "ESP_PATH" => {
+                if accept(
+                    "ESP_MOUNTPOINT",
+                    &config.esp_mountpoint.to_string_lossy(),
+                    &default.esp_mountpoint.to_string_lossy(),
+                    &value,
+                )? {
+                    config.esp_mountpoint = Rc::new(PathBuf::from(value));
+                }
+            }
+            // friend sets the installed kernel as default, if told so
+            "DEFAULT_ENTRY" if value == "latest" => {
+                if Confirm::with_theme(&ColorfulTheme::default())
+                    .with_prompt(fl!("ask_sbm_default_latest"))
+                    .default(true)
+                    .interact()?
+                {
+                    config.ask_default_on_install = AskDefault::AlwaysYes;
+                }
+            }
+            // friend removes the kernels without modules as well
+            "REMOVE_OBSOLETE" if value == "yes" => (),
+            _ => println_with_prefix_and_fl!("sbm_unmapped", key = key, value = value),
+        }
+    }
+
+    Ok(())
+}
+
+/// Import the configuration of systemd-boot-manager into the configuration
+/// file, the boot entries are left as they are
+pub fn migrate_from_sbm(sbm_config: Option<&Path>) -> Result<()> {
+    let path = sbm_config.unwrap_or(Path::new(SBM_CONF_PATH));
+    let content = fs::read_to_string(path).map_err(|e| anyhow!("{}: {}", path.display(), e))?;
+
+    let mut config = Config::read_file()?;
+    migrate(&mut config, parse(&content))?;
+    config.validate()?;
+
+    let conf_path = conf_path();
+    let conf_path = conf_path.to_string_lossy();
+    let current = fs::read_to_string(conf_path.as_ref()).unwrap_or_default();
+    let migrated = config.contents()?;
+
+    if current == migrated {
+        println_with_prefix_and_fl!("sbm_unchanged", path = path.to_string_lossy());
+        return Ok(());
+    }
+
+    print_colored_diff(
+        &TextDiff::from_lines(&current, &migrated)
+            .unified_diff()
+            .header(&conf_path, &conf_path)
+            .to_string(),
+    );
+    println!();
+
+    if Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(fl!("ask_sbm_write", conf_path = conf_path.as_ref()))
+        .default(true)
+        .interact()?
+    {
+        config.write()?;
+        println_with_prefix_and_fl!(
+            "sbm_migrated",
+            path = path.to_string_lossy(),
+            conf_path = conf_path.as_ref()
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let sbm = "# comment\nLINUX_OPTIONS=\"root=/dev/sda1 quiet\"\nexport ENTRY_NAME='Manjaro Linux'\nREMOVE_OBSOLETE=yes # remove\n";
+
+        assert_eq!(
+            parse(sbm),
+            [
+                (
+                    "LINUX_OPTIONS".to_owned(),
+                    "root=/dev/sda1 quiet".to_owned()
+                ),
+                ("ENTRY_NAME".to_owned(), "Manjaro Linux".to_owned()),
+                ("REMOVE_OBSOLETE".to_owned(), "yes".to_owned()),
+            ]
+        );
+    }
+}
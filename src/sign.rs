@@ -0,0 +1,244 @@
+use anyhow::{anyhow, bail, Result};
+use std::{
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use crate::{
+    cli::SigningTool, config::Config, fl, println_with_prefix, println_with_prefix_and_fl,
+    REL_DEST_PATH,
+};
+
+/// Where systemd-boot installs its EFI binaries
+pub(crate) const REL_SYSTEMD_BOOT_PATH: &str = "EFI/systemd/";
+
+/// Check the magic number of PE images, other files can not be signed
+fn is_pe_image(path: &Path) -> Result<bool> {
+    let mut header = Vec::new();
+    fs::File::open(path)?.take(2).read_to_end(&mut header)?;
+
+    Ok(header == b"MZ")
+}
+
+/// Collect the kernel images installed by friend and the systemd-boot EFI binaries
+fn signable_files(config: &Config) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(config.esp_mountpoint.join(REL_DEST_PATH))? {
+        let path = entry?.path();
+
+        if path.is_file() && is_pe_image(&path)? {
+            files.push(path);
+        }
+    }
+
+    if let Ok(dir) = fs::read_dir(config.esp_mountpoint.join(REL_SYSTEMD_BOOT_PATH)) {
+        for entry in dir {
+            let path = entry?.path();
+            let is_systemd_boot = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with("systemd-boot") && n.ends_with(".efi"));
+
+            if is_systemd_boot && path.is_file() {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+
+    Ok(files)
+}
+
+/// Get an attribute of a PKCS#11 URI (RFC 7512), percent-decoded
+fn pkcs11_attr(uri: &str, name: &str) -> Option<String> {
+    let path = uri.strip_prefix("pkcs11:")?.split('?').next()?;
+    let value = path
+        .split(';')
+        .filter_map(|a| a.split_once('='))
+        .find_map(|(k, v)| (k == name).then_some(v))?;
+
+    let mut bytes = Vec::new();
+    let mut chars = value.bytes();
+
+    while let Some(b) = chars.next() {
+        if b == b'%' {
+            let hex = [chars.next()?, chars.next()?];
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(b);
+        }
+    }
+
+    String::from_utf8(bytes).ok()
+}
+
+/// Check if the program is in PATH
+fn program_exists(name: &str) -> bool {
+    std::env::var_os("PATH")
+        .is_some_and(|paths| std::env::split_paths(&paths).any(|p| p.join(name).is_file()))
+}
+
+/// Pick the signing tool, only pesign can use the keys on PKCS#11 tokens
+fn detect_tool(config: &Config) -> SigningTool {
+    if config.pkcs11_uri.is_some() && program_exists("pesign") {
+        SigningTool::Pesign
+    } else {
+        SigningTool::Sbctl
+    }
+}
+
+/// Sign a file in place with the given tool, using the key on the PKCS#11
+/// token if `pkcs11_uri` is given
+fn sign_file(tool: SigningTool, path: &Path, pkcs11_uri: Option<&str>) -> Result<()> {
+    let output = match tool {
+        SigningTool::Sbctl if pkcs11_uri.is_some() => bail!(fl!("sbctl_no_pkcs11")),
+        SigningTool::Sbctl => Command::new("sbctl")
+            .arg("sign")
+            .arg("--save")
+            .arg(path)
+            .stdout(Stdio::null())
+            .output()?,
+        SigningTool::Pesign => {
+            let mut signed = path.as_os_str().to_owned();
+            signed.push(".signed");
+
+            let mut command = Command::new("pesign");
+            command
+                .arg("--sign")
+                .arg("--force")
+                .arg("--in")
+                .arg(path)
+                .arg("--out")
+                .arg(&signed);
+
+            if let Some(uri) = pkcs11_uri {
+                let token = pkcs11_attr(uri, "token")
+                    .ok_or_else(|| anyhow!(fl!("invalid_pkcs11_uri", attr = "token")))?;
+                let certname = pkcs11_attr(uri, "object")
+                    .ok_or_else(|| anyhow!(fl!("invalid_pkcs11_uri", attr = "object")))?;
+
+                command
+                    .arg("--token")
+                    .arg(token)
+                    .arg("--certificate")
+                    .arg(certname);
+            }
+
+            let output = command.stdout(Stdio::null()).output()?;
+
+            if output.status.success() {
+                fs::rename(&signed, path)?;
+            }
+
+            output
+        }
+    };
+
+    if !output.status.success() {
+        bail!(String::from_utf8_lossy(&output.stderr).trim().to_owned());
+    }
+
+    Ok(())
+}
+
+/// Sign all the kernel images installed by friend and the systemd-boot EFI binaries
+pub fn sign_all(config: &Config, tool: Option<SigningTool>, dry_run: bool) -> Result<()> {
+    let tool = tool.unwrap_or_else(|| detect_tool(config));
+    let files = signable_files(config)?;
+
+    if files.is_empty() {
+        println_with_prefix_and_fl!("no_file_to_sign");
+        return Ok(());
+    }
+
+    let mut failed = 0;
+
+    for file in files.iter() {
+        let path = file.to_string_lossy();
+
+        if dry_run {
+            println_with_prefix_and_fl!("sign_dry_run", path = path);
+            continue;
+        }
+
+        match sign_file(tool, file, config.pkcs11_uri.as_deref()) {
+            Ok(()) => println_with_prefix_and_fl!("sign_success", path = path),
+            Err(e) => {
+                failed += 1;
+                println_with_prefix_and_fl!("sign_failed", path = path, error = e.to_string());
+            }
+        }
+    }
+
+    if failed > 0 {
+        bail!(fl!(
+            "sign_summary_failed",
+            failed = failed,
+            total = files.len()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Check that the PKCS#11 token is present and the signing key is
+/// accessible, return the exit code
+pub fn check_token(config: &Config) -> Result<i32> {
+    let uri = match config.pkcs11_uri.as_deref() {
+        Some(u) => u,
+        None => bail!(fl!("no_pkcs11_uri")),
+    };
+    let token = pkcs11_attr(uri, "token")
+        .ok_or_else(|| anyhow!(fl!("invalid_pkcs11_uri", attr = "token")))?;
+
+    let tokens = Command::new("p11tool")
+        .arg("--list-token-urls")
+        .stderr(Stdio::inherit())
+        .output()?;
+    let present = String::from_utf8_lossy(&tokens.stdout)
+        .lines()
+        .any(|l| pkcs11_attr(l.trim(), "token").as_deref() == Some(token.as_str()));
+
+    if !present {
+        println_with_prefix_and_fl!("token_missing", token = token.as_str());
+        return Ok(1);
+    }
+
+    println_with_prefix_and_fl!("token_present", token = token.as_str());
+
+    // May ask for the PIN
+    let keys = Command::new("p11tool")
+        .arg("--login")
+        .arg("--list-privkeys")
+        .arg(uri)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()?;
+
+    if keys.status.success() && String::from_utf8_lossy(&keys.stdout).contains("URL:") {
+        println_with_prefix_and_fl!("token_key_accessible");
+        Ok(0)
+    } else {
+        println_with_prefix_and_fl!("token_key_inaccessible");
+        Ok(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pkcs11_attr() {
+        let uri = "pkcs11:model=YubiKey%20YK5;token=YubiKey%20PIV;object=db%20key;type=private?pin-source=file:/etc/pin";
+
+        assert_eq!(pkcs11_attr(uri, "token").as_deref(), Some("YubiKey PIV"));
+        assert_eq!(pkcs11_attr(uri, "object").as_deref(), Some("db key"));
+        assert_eq!(pkcs11_attr(uri, "pin-source"), None);
+        assert_eq!(pkcs11_attr("file:/key.pem", "token"), None);
+    }
+}
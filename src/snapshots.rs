@@ -0,0 +1,119 @@
+use anyhow::Result;
+use libsdbootconf::SystemdBootConf;
+use std::{
+    fs,
+    time::{Duration, SystemTime},
+};
+
+use crate::{
+    config::Config,
+    default_entry::{default_pattern, match_entry},
+    fl,
+    kernel::{list_entry_ids, REL_ENTRY_PATH},
+    println_with_prefix, println_with_prefix_and_fl,
+};
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Select the snapshot entries outside the retention policy, `entries` are
+/// the ids and modification times of the snapshot entries
+fn outside_policy(
+    mut entries: Vec<(String, SystemTime)>,
+    default: Option<&str>,
+    max_entries: Option<usize>,
+    min_age_days: Option<u64>,
+    now: SystemTime,
+) -> Vec<String> {
+    let max_entries = match max_entries {
+        Some(m) => m,
+        None => return Vec::new(),
+    };
+    let min_age = DAY * min_age_days.unwrap_or(0) as u32;
+
+    // Newest first
+    entries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| b.0.cmp(&a.0)));
+
+    entries
+        .into_iter()
+        .skip(max_entries)
+        .filter(|(id, _)| Some(id.as_str()) != default)
+        .filter(|(_, mtime)| now.duration_since(*mtime).unwrap_or_default() >= min_age)
+        .map(|(id, _)| id)
+        .collect()
+}
+
+/// Remove the snapshot entries outside the retention policy, the snapshots
+/// themselves are left untouched
+pub fn prune_snapshots(config: &Config, sbconf: &SystemdBootConf, dry_run: bool) -> Result<()> {
+    if config.max_snapshot_entries.is_none() {
+        println_with_prefix_and_fl!("snapshots_no_policy");
+        return Ok(());
+    }
+
+    let entries_path = config.esp_mountpoint.join(REL_ENTRY_PATH);
+    let ids = list_entry_ids(&config.esp_mountpoint)?;
+    let default = match default_pattern(sbconf) {
+        Some((pattern, _)) => match_entry(&pattern, ids.iter().map(|id| id.as_str()))?,
+        None => None,
+    };
+
+    let mut snapshots = Vec::new();
+
+    for id in ids
+        .iter()
+        .filter(|id| id.starts_with(&config.snapshot_entry_prefix))
+    {
+        let mtime = fs::metadata(entries_path.join(format!("{}.conf", id)))?.modified()?;
+        snapshots.push((id.to_owned(), mtime));
+    }
+
+    let prunable = outside_policy(
+        snapshots,
+        default,
+        config.max_snapshot_entries,
+        config.snapshot_min_age_days,
+        SystemTime::now(),
+    );
+
+    if prunable.is_empty() {
+        println_with_prefix_and_fl!("snapshots_nothing_to_prune");
+    }
+
+    for id in prunable {
+        let entry = format!("{}.conf", id);
+
+        if dry_run {
+            println_with_prefix_and_fl!("snapshots_would_remove", entry = entry.as_str());
+        } else {
+            println_with_prefix_and_fl!("snapshots_remove", entry = entry.as_str());
+            fs::remove_file(entries_path.join(entry))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outside_policy() {
+        let now = SystemTime::now();
+        let entries = (0..5)
+            .map(|i| (format!("snapshot-{}", i), now - DAY * (5 - i)))
+            .collect::<Vec<_>>();
+
+        // Keep snapshot-4 and snapshot-3, snapshot-0 is the default
+        assert_eq!(
+            outside_policy(entries.clone(), Some("snapshot-0"), Some(2), None, now),
+            vec!["snapshot-2", "snapshot-1"]
+        );
+        // snapshot-2 is only 3 days old
+        assert_eq!(
+            outside_policy(entries.clone(), None, Some(2), Some(4), now),
+            vec!["snapshot-1", "snapshot-0"]
+        );
+        assert!(outside_policy(entries, None, None, None, now).is_empty());
+    }
+}
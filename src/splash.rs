@@ -0,0 +1,59 @@
+use anyhow::Result;
+use std::{fs, path::PathBuf};
+
+use crate::{
+    config::Config, fl, kernel::file_copy, manifest::Manifest, println_with_prefix,
+    println_with_prefix_and_fl,
+};
+
+/// Where the splash image is copied to, relative to the ESP
+pub fn splash_dest(config: &Config) -> Option<PathBuf> {
+    let splash = config.splash.as_ref()?;
+
+    match &config.splash_dest {
+        Some(dest) => Some(dest.strip_prefix("/").unwrap_or(dest).to_owned()),
+        None => splash.file_name().map(PathBuf::from),
+    }
+}
+
+/// Copy the splash image to the ESP, remove the previously installed one
+/// if it is no longer configured
+pub fn update_splash(config: &Config) -> Result<()> {
+    let mut manifest = Manifest::read(&config.esp_mountpoint)?;
+    let dest = splash_dest(config);
+
+    // The destination or the image changed, clean up the old one
+    if let Some(old) = manifest
+        .splash
+        .as_ref()
+        .filter(|old| Some(*old) != dest.as_ref())
+    {
+        println_with_prefix_and_fl!("remove_splash", path = old.to_string_lossy());
+        fs::remove_file(config.esp_mountpoint.join(old)).ok();
+    }
+
+    if let (Some(splash), Some(dest)) = (&config.splash, &dest) {
+        println_with_prefix_and_fl!("install_splash", path = splash.to_string_lossy());
+        let dest = config.esp_mountpoint.join(dest);
+
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        file_copy(splash, dest)?;
+    }
+
+    if manifest.splash != dest {
+        manifest.splash = dest;
+        manifest.write(&config.esp_mountpoint)?;
+    }
+
+    Ok(())
+}
+
+/// Check if the installed splash image is the same as the configured one
+pub fn splash_up_to_date(config: &Config) -> Option<bool> {
+    let splash = config.splash.as_ref()?;
+    let dest = config.esp_mountpoint.join(splash_dest(config)?);
+
+    Some(matches!((fs::read(splash), fs::read(dest)), (Ok(a), Ok(b)) if a == b))
+}
@@ -0,0 +1,67 @@
+use anyhow::Result;
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+    sync::Mutex,
+};
+
+use crate::kernel_manager::UpdateReport;
+
+/// Version of the status event schema, bump on incompatible changes
+const STATUS_VERSION: u32 = 1;
+
+lazy_static! {
+    static ref STATUS_FILE: Mutex<Option<File>> = Mutex::new(None);
+}
+
+/// A machine-readable progress event
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event<'a> {
+    Phase { phase: &'a str },
+    Copy { file: &'a Path, percent: u8 },
+    KernelInstalled { kernel: String },
+    KernelRemoved { kernel: String },
+    Summary { report: &'a UpdateReport },
+}
+
+#[derive(Serialize)]
+struct StatusLine<'a> {
+    version: u32,
+    #[serde(flatten)]
+    event: &'a Event<'a>,
+}
+
+/// Write status events to the given file descriptor
+pub fn init(fd: i32) -> Result<()> {
+    let file = OpenOptions::new()
+        .write(true)
+        .open(format!("/proc/self/fd/{}", fd))?;
+
+    *STATUS_FILE.lock().unwrap() = Some(file);
+
+    Ok(())
+}
+
+/// Check if a status file descriptor is given
+pub fn enabled() -> bool {
+    STATUS_FILE.lock().unwrap().is_some()
+}
+
+/// Emit an event as a line of JSON, does nothing without a status file descriptor
+pub fn emit(event: Event) {
+    if let Some(f) = STATUS_FILE.lock().unwrap().as_mut() {
+        let line = StatusLine {
+            version: STATUS_VERSION,
+            event: &event,
+        };
+
+        // Failing to report progress should not abort the operation
+        if let Ok(json) = serde_json::to_string(&line) {
+            writeln!(f, "{}", json).ok();
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use anyhow::Result;
+use console::style;
+use libsdbootconf::{
+    entry::{Entry, Token},
+    SystemdBootConf,
+};
+use std::{fs, path::Path};
+
+use crate::{
+    config::Config,
+    default_entry::{default_pattern, match_entry},
+    fl,
+    kernel::{list_entry_ids, REL_ENTRY_PATH},
+    sign::REL_SYSTEMD_BOOT_PATH,
+    util::{efi_arch, human_size},
+};
+
+const FW_PLATFORM_SIZE: &str = "/sys/firmware/efi/fw_platform_size";
+const LOADER_INFO_MARKER: &[u8] = b"#### LoaderInfo: ";
+
+/// Extract the version embedded in a systemd-boot binary
+fn loader_version(binary: &[u8]) -> Option<String> {
+    let start = binary
+        .windows(LOADER_INFO_MARKER.len())
+        .position(|w| w == LOADER_INFO_MARKER)?
+        + LOADER_INFO_MARKER.len();
+    let len = binary[start..].windows(5).position(|w| w == b" ####")?;
+
+    Some(String::from_utf8_lossy(&binary[start..start + len]).into_owned())
+}
+
+/// Print a file referenced by an entry with its size and existence
+fn print_file(key: &str, path: &Path, esp_mountpoint: &Path) {
+    let full_path = esp_mountpoint.join(path.strip_prefix("/").unwrap_or(path));
+
+    match fs::metadata(&full_path) {
+        Ok(m) => println!(
+            "        {} {} ({})",
+            key,
+            path.display(),
+            human_size(m.len())
+        ),
+        Err(_) => println!(
+            "        {} {} ({})",
+            key,
+            path.display(),
+            style(fl!("entry_missing")).red()
+        ),
+    }
+}
+
+/// Print the boot chain from the firmware to the default kernel
+pub fn show_topology(config: &Config, sbconf: &SystemdBootConf) -> Result<()> {
+    // Firmware
+    match fs::read_to_string(FW_PLATFORM_SIZE) {
+        Ok(size) => println!(
+            "UEFI ({}, {})",
+            efi_arch(),
+            fl!("topology_fw_bits", bits = size.trim())
+        ),
+        Err(_) => println!("{}", fl!("topology_no_uefi")),
+    }
+
+    // Boot loader
+    let loader = config
+        .esp_mountpoint
+        .join(REL_SYSTEMD_BOOT_PATH)
+        .join(format!("systemd-boot{}.efi", efi_arch()));
+
+    match fs::read(&loader) {
+        Ok(binary) => println!(
+            "└─ {} ({})",
+            loader_version(&binary).unwrap_or_else(|| "systemd-boot".to_owned()),
+            loader.display()
+        ),
+        Err(_) => println!(
+            "└─ systemd-boot ({}: {})",
+            loader.display(),
+            style(fl!("entry_missing")).red()
+        ),
+    }
+
+    // Default entry
+    let (pattern, source) = match default_pattern(sbconf) {
+        Some(p) => p,
+        None => {
+            println!("    └─ {}", fl!("no_default"));
+            return Ok(());
+        }
+    };
+
+    let ids = list_entry_ids(&config.esp_mountpoint)?;
+    let id = match match_entry(&pattern, ids.iter().map(|id| id.as_str()))? {
+        Some(id) => id,
+        None => {
+            println!(
+                "    └─ {}",
+                style(fl!("dangling_default", entry = pattern)).red()
+            );
+            return Ok(());
+        }
+    };
+
+    let entry_path = config
+        .esp_mountpoint
+        .join(REL_ENTRY_PATH)
+        .join(format!("{}.conf", id));
+    println!("    └─ {} ({})", entry_path.display(), source);
+
+    let content = fs::read_to_string(&entry_path)?;
+    for line in content.lines().filter(|l| !l.trim().is_empty()) {
+        println!("        │ {}", line);
+    }
+
+    // Referenced files
+    let entry = content.parse::<Entry>()?;
+    for token in entry.tokens.iter() {
+        match token {
+            Token::Linux(p) => print_file("linux", p, &config.esp_mountpoint),
+            Token::Initrd(p) => print_file("initrd", p, &config.esp_mountpoint),
+            Token::Efi(p) => print_file("efi", p, &config.esp_mountpoint),
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loader_version() {
+        let binary = b"MZ\0\0#### LoaderInfo: systemd-boot 255.4-1 ####\0";
+
+        assert_eq!(
+            loader_version(binary).as_deref(),
+            Some("systemd-boot 255.4-1")
+        );
+        assert_eq!(loader_version(b"MZ\0\0"), None);
+    }
+}
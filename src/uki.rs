@@ -0,0 +1,286 @@
+use anyhow::{anyhow, bail, Result};
+use libsdbootconf::entry::{Entry, Token};
+use std::{
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::{
+    config::Config,
+    fl,
+    kernel::{Kernel, REL_ENTRY_PATH, REL_UKI_PATH},
+    manifest::Manifest,
+    println_with_prefix, println_with_prefix_and_fl,
+    util::efi_arch,
+};
+
+const STUB_PATH: &str = "/usr/lib/systemd/boot/efi/";
+const OS_RELEASE: &str = "/etc/os-release";
+
+/// Magic of the optional header of PE32+ images, PE32 otherwise
+const PE32_PLUS_MAGIC: u16 = 0x20b;
+
+/// The `--add-section` argument of objcopy, the path is kept as is rather
+/// than converted to UTF-8
+fn section(name: &str, path: &Path) -> OsString {
+    let mut arg = OsString::from(name);
+    arg.push("=");
+    arg.push(path);
+    arg
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        data.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+/// Place the sections of the given sizes one after another behind the
+/// sections of the stub, aligned as the stub requires. Return their virtual
+/// addresses, None if the stub is not a PE image.
+fn section_vmas(stub: &[u8], sizes: &[u64]) -> Option<Vec<u64>> {
+    if stub.get(..2)? != b"MZ" {
+        return None;
+    }
+
+    let pe = read_u32(stub, 0x3c)? as usize;
+
+    if stub.get(pe..pe + 4)? != b"PE\0\0" {
+        return None;
+    }
+
+    let sections = read_u16(stub, pe + 6)? as usize;
+    let optional_header = pe + 24;
+    let section_table = optional_header + read_u16(stub, pe + 20)? as usize;
+    let image_base = if read_u16(stub, optional_header)? == PE32_PLUS_MAGIC {
+        read_u64(stub, optional_header + 24)?
+    } else {
+        read_u32(stub, optional_header + 28)? as u64
+    };
+    let align = (read_u32(stub, optional_header + 32)? as u64).max(1);
+
+    let mut end = 0;
+
+    for i in 0..sections {
+        let header = section_table + i * 40;
+        let size = read_u32(stub, header + 8)? as u64;
+        let address = read_u32(stub, header + 12)? as u64;
+        end = end.max(address + size);
+    }
+
+    let mut vma = image_base + end.div_ceil(align) * align;
+
+    Some(
+        sizes
+            .iter()
+            .map(|size| {
+                let this = vma;
+                vma += size.div_ceil(align).max(1) * align;
+                this
+            })
+            .collect(),
+    )
+}
+
+/// Get the EFI stub of systemd for the current architecture
+fn efi_stub() -> Result<PathBuf> {
+    let stub = Path::new(STUB_PATH).join(format!("linux{}.efi.stub", efi_arch()));
+
+    if !stub.exists() {
+        bail!(fl!("err_no_efi_stub", path = stub.to_string_lossy()));
+    }
+
+    Ok(stub)
+}
+
+/// Repack the kernel, initrd and boot arguments of an installed kernel into
+/// a unified kernel image, installed to the ESP unless another output is given
+pub fn convert_to_uki<K: Kernel>(
+    config: &Config,
+    kernel: &K,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let entry_id = kernel.entry_id(&config.default_profile);
+    let uki_path = config.esp_mountpoint.join(REL_UKI_PATH);
+    let entry_path = config
+        .esp_mountpoint
+        .join(REL_ENTRY_PATH)
+        .join(format!("{}.conf", entry_id));
+
+    if !entry_path.exists() {
+        bail!(fl!(
+            "err_path_not_exist",
+            path = entry_path.to_string_lossy()
+        ));
+    }
+
+    let entry = Entry::load(&entry_path)?;
+    let mut linux = None;
+    let mut initrds = Vec::new();
+    let mut cmdline = String::new();
+
+    for token in entry.tokens.iter() {
+        match token {
+            Token::Linux(p) => linux = Some(p.to_owned()),
+            Token::Initrd(p) => initrds.push(p.to_owned()),
+            Token::Options(o) => cmdline = o.to_owned(),
+            _ => (),
+        }
+    }
+
+    // Paths in the entries are relative to the root of the ESP
+    let esp_path = |p: &Path| config.esp_mountpoint.join(p.strip_prefix("/").unwrap_or(p));
+    let linux = esp_path(&linux.ok_or_else(|| {
+        anyhow!(fl!(
+            "err_entry_no_linux",
+            entry = entry_path.to_string_lossy()
+        ))
+    })?);
+
+    println_with_prefix_and_fl!("convert_uki", kernel = kernel.to_string());
+
+    let stub = efi_stub()?;
+    let work_dir = tempfile::Builder::new()
+        .prefix("systemd-boot-friend-")
+        .tempdir()?;
+
+    let mut sections = Vec::new();
+
+    if Path::new(OS_RELEASE).exists() {
+        sections.push((".osrel", PathBuf::from(OS_RELEASE)));
+    }
+
+    let cmdline_path = work_dir.path().join("cmdline");
+    fs::write(&cmdline_path, &cmdline)?;
+    sections.push((".cmdline", cmdline_path));
+    sections.push((".linux", linux));
+
+    // systemd-boot loads multiple initrds one after another, which is the
+    // same as concatenating them
+    if !initrds.is_empty() {
+        let initrd = work_dir.path().join("initrd");
+        let mut initrd_data = Vec::new();
+
+        for p in initrds.iter() {
+            initrd_data.extend(fs::read(esp_path(p))?);
+        }
+        fs::write(&initrd, &initrd_data)?;
+        sections.push((".initrd", initrd));
+    }
+
+    let sizes = sections
+        .iter()
+        .map(|(_, path)| Ok(fs::metadata(path)?.len()))
+        .collect::<Result<Vec<_>>>()?;
+    let vmas = section_vmas(&fs::read(&stub)?, &sizes)
+        .ok_or_else(|| anyhow!(fl!("err_invalid_efi_stub", path = stub.to_string_lossy())))?;
+
+    let output = output.unwrap_or_else(|| uki_path.join(format!("{}.efi", entry_id)));
+
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut command = Command::new("objcopy");
+
+    for ((name, path), vma) in sections.iter().zip(vmas) {
+        command
+            .arg("--add-section")
+            .arg(section(name, path))
+            .arg("--change-section-vma")
+            .arg(format!("{}={:#x}", name, vma));
+    }
+
+    let child_output = command.arg(&stub).arg(&output).output()?;
+
+    if !child_output.status.success() {
+        bail!(String::from_utf8_lossy(&child_output.stderr)
+            .trim()
+            .to_owned());
+    }
+
+    // Not to be taken for a kernel of its own by `scan_uki`
+    if output.parent() == Some(uki_path.as_path()) {
+        let mut manifest = Manifest::read(&config.esp_mountpoint)?;
+        let name = output
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        if !manifest.converted_ukis.contains(&name) {
+            manifest.converted_ukis.push(name);
+            manifest.write(&config.esp_mountpoint)?;
+        }
+    }
+
+    println_with_prefix_and_fl!("uki_installed", path = output.to_string_lossy());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A PE32+ header with the sections given as (address, size)
+    fn stub(image_base: u64, sections: &[(u32, u32)]) -> Vec<u8> {
+        let pe = 0x80;
+        let optional_header_size = 240;
+        let mut data = vec![0; pe + 24 + optional_header_size + sections.len() * 40];
+
+        data[..2].copy_from_slice(b"MZ");
+        data[0x3c..0x40].copy_from_slice(&(pe as u32).to_le_bytes());
+        data[pe..pe + 4].copy_from_slice(b"PE\0\0");
+        data[pe + 6..pe + 8].copy_from_slice(&(sections.len() as u16).to_le_bytes());
+        data[pe + 20..pe + 22].copy_from_slice(&(optional_header_size as u16).to_le_bytes());
+
+        let optional_header = pe + 24;
+        data[optional_header..optional_header + 2].copy_from_slice(&PE32_PLUS_MAGIC.to_le_bytes());
+        data[optional_header + 24..optional_header + 32].copy_from_slice(&image_base.to_le_bytes());
+        data[optional_header + 32..optional_header + 36].copy_from_slice(&0x1000u32.to_le_bytes());
+
+        for (i, (address, size)) in sections.iter().enumerate() {
+            let header = optional_header + optional_header_size + i * 40;
+            data[header + 8..header + 12].copy_from_slice(&size.to_le_bytes());
+            data[header + 12..header + 16].copy_from_slice(&address.to_le_bytes());
+        }
+
+        data
+    }
+
+    #[test]
+    fn test_section_vmas() {
+        // The sections of a large stub end past the addresses once
+        // hardcoded for .osrel and .cmdline
+        let data = stub(0x1_4000_0000, &[(0x1000, 0x2_1000), (0x2_3000, 0x800)]);
+
+        assert_eq!(
+            section_vmas(&data, &[0x100, 0, 0x1800]).unwrap(),
+            [0x1_4002_4000, 0x1_4002_5000, 0x1_4002_6000]
+        );
+    }
+
+    #[test]
+    fn test_section_vmas_invalid() {
+        assert!(section_vmas(b"", &[1]).is_none());
+        assert!(section_vmas(b"#!/bin/sh", &[1]).is_none());
+
+        // Truncated in the section table
+        let data = stub(0, &[(0x1000, 0x1000)]);
+        assert!(section_vmas(&data[..data.len() - 30], &[1]).is_none());
+    }
+}
@@ -0,0 +1,83 @@
+use anyhow::Result;
+use libsdbootconf::SystemdBootConf;
+use std::{cell::RefCell, fs, rc::Rc};
+
+use crate::{
+    config::Config,
+    entry_list::collect_entries,
+    fl,
+    kernel::REL_ENTRY_PATH,
+    manifest::{Manifest, ALIASES},
+    print_block_with_fl, println_with_prefix, println_with_prefix_and_fl,
+    prompt::{ColorfulTheme, Confirm},
+    util::run_bootctl,
+    REL_DEST_PATH,
+};
+
+/// Ask whether to go on with uninstall
+pub fn ask_uninstall() -> Result<bool> {
+    Ok(Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(fl!("ask_uninstall"))
+        .default(false)
+        .interact()?)
+}
+
+/// Remove everything friend put in the ESP: the entries pointing into its
+/// directory, the splash image, the directory itself and the default entry,
+/// and systemd-boot too if `remove_bootloader`. Nothing is touched unless
+/// `confirm` answers yes.
+pub fn uninstall<F: FnOnce() -> Result<bool>>(
+    config: &Config,
+    sbconf: Rc<RefCell<SystemdBootConf>>,
+    remove_bootloader: bool,
+    confirm: F,
+) -> Result<()> {
+    print_block_with_fl!("notice_uninstall");
+
+    if !confirm()? {
+        return Ok(());
+    }
+
+    let entries_path = config.esp_mountpoint.join(REL_ENTRY_PATH);
+
+    // The entries written by hand or by other tools are left alone
+    for info in collect_entries(config, &sbconf)? {
+        if info.managed {
+            println_with_prefix_and_fl!("remove_entry", kernel = info.entry.as_str());
+            fs::remove_file(entries_path.join(&info.entry))?;
+        }
+    }
+
+    // The aliases are only meaningful to friend
+    fs::remove_file(entries_path.join(ALIASES)).ok();
+
+    // The splash image may be outside the directory, and its record goes
+    // with the directory
+    if let Some(splash) = Manifest::read(&config.esp_mountpoint)
+        .ok()
+        .and_then(|m| m.splash)
+    {
+        println_with_prefix_and_fl!("remove_splash", path = splash.to_string_lossy());
+        fs::remove_file(config.esp_mountpoint.join(splash)).ok();
+    }
+
+    let dest_path = config.esp_mountpoint.join(REL_DEST_PATH);
+
+    if dest_path.exists() {
+        println_with_prefix_and_fl!("prune_dir", path = dest_path.to_string_lossy());
+        fs::remove_dir_all(&dest_path)?;
+    }
+
+    // The journal is gone with the directory, so the change is not recorded
+    println_with_prefix_and_fl!("clear_default");
+    sbconf.borrow_mut().config.default = None;
+    sbconf.borrow().write_config()?;
+
+    if remove_bootloader {
+        run_bootctl("remove", &config.esp_mountpoint, config.bootctl_timeout)?;
+    }
+
+    println_with_prefix_and_fl!("uninstalled");
+
+    Ok(())
+}
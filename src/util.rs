@@ -1,10 +1,296 @@
-use crate::{config::Config, fl, kernel::Kernel};
-use anyhow::{bail, Result};
-use dialoguer::{theme::ColorfulTheme, MultiSelect, Select};
+use crate::{
+    config::Config,
+    fl,
+    kernel::{find_kernel, Kernel, KernelOps},
+    prompt::{ColorfulTheme, MultiSelect, Select},
+};
+use anyhow::{anyhow, bail, Result};
+use console::{Style, Term};
 use libsdbootconf::SystemdBootConf;
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::RefCell,
+    fs,
+    io::Read,
+    path::Path,
+    process::{Command, Stdio},
+    rc::Rc,
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use textwrap::{wrap, Options, WordSeparator, WordSplitter};
+use unicode_width::UnicodeWidthStr;
 
-pub fn multiselect_kernel<K: Kernel>(
+/// Format a size in bytes in a human-readable form
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: &[&str] = &["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Width to wrap long lines at, 80 columns if stderr is not a terminal
+pub fn wrap_width() -> usize {
+    let term = Term::stderr();
+
+    if !term.is_term() {
+        return 80;
+    }
+
+    term.size_checked().map_or(80, |(_, cols)| cols as usize)
+}
+
+/// Render the rows as a table aligned by the display width, so that CJK
+/// text lines up, or as `header: value` lines if it is wider than `width`
+pub fn render_table(headers: &[String], rows: &[Vec<String>], width: usize) -> Vec<String> {
+    let mut widths = headers.iter().map(|h| h.width()).collect::<Vec<_>>();
+
+    for row in rows {
+        for (w, cell) in widths.iter_mut().zip(row) {
+            *w = (*w).max(cell.width());
+        }
+    }
+
+    if widths.iter().sum::<usize>() + 2 * widths.len().saturating_sub(1) > width {
+        let mut lines = Vec::new();
+
+        for (n, row) in rows.iter().enumerate() {
+            if n > 0 {
+                lines.push(String::new());
+            }
+            for (header, cell) in headers.iter().zip(row) {
+                lines.push(format!("{}: {}", header, cell));
+            }
+        }
+
+        return lines;
+    }
+
+    let pad = |cells: &[String]| {
+        cells
+            .iter()
+            .zip(widths.iter())
+            .map(|(c, w)| format!("{}{}", c, " ".repeat(w - c.width())))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_owned()
+    };
+
+    std::iter::once(pad(headers))
+        .chain(rows.iter().map(|r| pad(r)))
+        .collect()
+}
+
+/// Wrap a long line of boot options after the label, the continuation lines
+/// are indented to line up with the first option
+pub fn wrap_options(label: &str, options: &str, width: usize) -> Vec<String> {
+    let indent = " ".repeat(console::measure_text_width(label));
+
+    wrap(
+        options,
+        Options::new(width)
+            .initial_indent(label)
+            .subsequent_indent(&indent)
+            .word_separator(WordSeparator::AsciiSpace)
+            .word_splitter(WordSplitter::NoHyphenation),
+    )
+    .into_iter()
+    .map(|l| l.into_owned())
+    .collect()
+}
+
+/// Wrap the `options` line of a boot entry, which may begin with the
+/// marker of a diff line, the other lines are kept as they are
+pub fn wrap_entry_line(line: &str, width: usize) -> Vec<String> {
+    match line.split_once("options ") {
+        Some((marker, options)) if matches!(marker, "" | "+" | "-" | " ") => {
+            wrap_options(&format!("{}options ", marker), options, width)
+        }
+        _ => vec![line.to_owned()],
+    }
+}
+
+/// Expand the `{name}` placeholders in the template with the fields, `{{`
+/// and `}}` are literal braces, `\t` and `\n` are a tab and a newline
+pub fn expand_template(template: &str, fields: &[(&str, String)]) -> Result<String> {
+    let mut out = String::new();
+    let mut chars = template.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.as_str().starts_with('{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.as_str().starts_with('}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let (name, rest) = chars
+                    .as_str()
+                    .split_once('}')
+                    .ok_or_else(|| anyhow!(fl!("unclosed_placeholder", template = template)))?;
+                let value = fields
+                    .iter()
+                    .find_map(|(n, v)| (*n == name).then_some(v))
+                    .ok_or_else(|| {
+                        anyhow!(fl!(
+                            "unknown_placeholder",
+                            placeholder = name,
+                            available = fields
+                                .iter()
+                                .map(|(n, _)| format!("{{{}}}", n))
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ))
+                    })?;
+
+                out.push_str(value);
+                chars = rest.chars();
+            }
+            '\\' if chars.as_str().starts_with('t') => {
+                chars.next();
+                out.push('\t');
+            }
+            '\\' if chars.as_str().starts_with('n') => {
+                chars.next();
+                out.push('\n');
+            }
+            c => out.push(c),
+        }
+    }
+
+    Ok(out)
+}
+
+/// Format a timestamp as `YYYY-MM-DD HH:MM:SS UTC`
+pub fn format_utc(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, rem) = (secs / 86400, secs % 86400);
+
+    // Convert the days since the epoch to a civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z % 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+/// Get the EFI architecture name of the current machine, as used in the
+/// filenames of EFI binaries
+pub fn efi_arch() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "x64",
+        "x86" => "ia32",
+        "aarch64" => "aa64",
+        a => a,
+    }
+}
+
+/// Run a bootctl command on the ESP, the path is passed as a separate
+/// argument so that it may contain spaces or any other characters. bootctl
+/// is killed after `timeout` seconds unless it is 0, as it may hang on slow
+/// firmware or a stale mount.
+pub fn run_bootctl(verb: &str, esp: &Path, timeout: u64) -> Result<()> {
+    wait_bootctl(Command::new("bootctl"), verb, esp, timeout)
+}
+
+fn wait_bootctl(mut command: Command, verb: &str, esp: &Path, timeout: u64) -> Result<()> {
+    let mut child = command
+        .arg(verb)
+        .arg("--esp-path")
+        .arg(esp)
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let deadline = Instant::now() + Duration::from_secs(timeout);
+
+    // Drained meanwhile, bootctl would block on a full pipe otherwise
+    let mut stderr = child.stderr.take().expect("stderr is piped");
+    let reader = thread::spawn(move || {
+        let mut buf = Vec::new();
+        stderr.read_to_end(&mut buf).ok();
+        buf
+    });
+
+    while timeout > 0 && child.try_wait()?.is_none() {
+        if Instant::now() >= deadline {
+            child.kill().ok();
+            child.wait()?;
+            bail!(fl!(
+                "bootctl_timeout",
+                verb = verb,
+                seconds = timeout,
+                esp = esp.to_string_lossy()
+            ));
+        }
+
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    let status = child.wait()?;
+    let stderr = reader.join().unwrap_or_default();
+
+    if !status.success() {
+        bail!(String::from_utf8_lossy(&stderr).trim().to_owned());
+    }
+
+    Ok(())
+}
+
+/// Load loader.conf, point out the offending line if it fails to parse
+pub fn load_loader_conf(path: &Path) -> Result<libsdbootconf::Config> {
+    libsdbootconf::Config::load(path).map_err(|e| {
+        // Parse the lines one by one to locate the error
+        let line = fs::read_to_string(path).ok().and_then(|content| {
+            content
+                .lines()
+                .position(|l| l.parse::<libsdbootconf::Config>().is_err())
+                .map(|n| n + 1)
+        });
+
+        match line {
+            Some(line) => anyhow!(fl!(
+                "loader_conf_parse_error_line",
+                path = path.to_string_lossy(),
+                line = line,
+                error = e.to_string()
+            )),
+            None => anyhow!(fl!(
+                "loader_conf_parse_error",
+                path = path.to_string_lossy(),
+                error = e.to_string()
+            )),
+        }
+    })
+}
+
+pub fn multiselect_kernel<K: KernelOps + Clone + PartialEq>(
     kernels: &[K],
     installed_kernels: &[K],
     prompt: &str,
@@ -30,7 +316,7 @@ pub fn multiselect_kernel<K: Kernel>(
 }
 
 /// Choose a kernel using dialoguer
-pub fn select_kernel<K: Kernel>(kernels: &[K], prompt: &str) -> Result<K> {
+pub fn select_kernel<K: KernelOps + Clone>(kernels: &[K], prompt: &str) -> Result<K> {
     if kernels.is_empty() {
         bail!(fl!("empty_list"));
     }
@@ -43,6 +329,38 @@ pub fn select_kernel<K: Kernel>(kernels: &[K], prompt: &str) -> Result<K> {
     .clone())
 }
 
+/// Choose a kernel using dialoguer, with an item at the top to choose none of them
+pub fn select_kernel_or_none<K: KernelOps + Clone>(
+    kernels: &[K],
+    prompt: &str,
+    none_item: &str,
+) -> Result<Option<K>> {
+    let items = std::iter::once(none_item.to_owned())
+        .chain(kernels.iter().map(|k| k.to_string()))
+        .collect::<Vec<_>>();
+
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .items(&items)
+        .default(0)
+        .interact()?;
+
+    Ok(selection.checked_sub(1).map(|n| kernels[n].clone()))
+}
+
+/// Look up the kernel in the list, parse the name only if it is not found
+fn specify_kernel<K: Kernel>(
+    kernels: &[K],
+    config: &Config,
+    target: &str,
+    sbconf: Rc<RefCell<SystemdBootConf>>,
+) -> Result<K> {
+    match find_kernel(kernels, target) {
+        Some(k) => Ok(k.clone()),
+        None => K::parse(config, target, sbconf),
+    }
+}
+
 pub fn specify_or_multiselect<K: Kernel>(
     kernels: &[K],
     config: &Config,
@@ -54,13 +372,9 @@ pub fn specify_or_multiselect<K: Kernel>(
         // select the kernels when no target is given
         multiselect_kernel(kernels, &[], prompt)
     } else {
-        let mut kernels = Vec::new();
-
-        for target in arg {
-            kernels.push(K::parse(config, target, sbconf.clone())?);
-        }
-
-        Ok(kernels)
+        arg.iter()
+            .map(|target| specify_kernel(kernels, config, target, sbconf.clone()))
+            .collect()
     }
 }
 
@@ -73,8 +387,150 @@ pub fn specify_or_select<K: Kernel>(
 ) -> Result<K> {
     match arg {
         // parse the kernel name when a target is given
-        Some(n) => Ok(K::parse(config, n, sbconf)?),
+        Some(n) => specify_kernel(kernels, config, n, sbconf),
         // select the kernel when no target is given
         None => select_kernel(kernels, prompt),
     }
 }
+
+/// Print a unified diff, colored by the kind of each line
+pub fn print_colored_diff(diff: &str) {
+    let width = wrap_width();
+
+    for line in diff.lines() {
+        let style = if line.starts_with("+++") || line.starts_with("---") {
+            Style::new().bold()
+        } else if line.starts_with('+') {
+            Style::new().green()
+        } else if line.starts_with('-') {
+            Style::new().red()
+        } else if line.starts_with("@@") {
+            Style::new().cyan()
+        } else {
+            Style::new()
+        };
+
+        for wrapped in wrap_entry_line(line, width) {
+            println!("{}", style.apply_to(wrapped));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wait_bootctl() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let bootctl = dir.path().join("bootctl");
+        let run = |script: &str, timeout| {
+            fs::write(&bootctl, format!("#!/bin/sh\n{}", script)).unwrap();
+            fs::set_permissions(&bootctl, fs::Permissions::from_mode(0o755)).unwrap();
+            let mut command = Command::new("bootctl");
+            command.env(
+                "PATH",
+                format!(
+                    "{}:{}",
+                    dir.path().display(),
+                    std::env::var("PATH").unwrap()
+                ),
+            );
+            wait_bootctl(command, "update", Path::new("/efi"), timeout)
+        };
+
+        assert!(run("sleep 1", 5).is_ok());
+        // More than a pipe buffer before exiting
+        let e = run(
+            "head -c 262144 /dev/zero | tr '\\0' x >&2\nsleep 1\nexit 1",
+            5,
+        )
+        .unwrap_err()
+        .to_string();
+        assert_eq!(e.len(), 262144);
+        let e = run("exec sleep 5", 1).unwrap_err().to_string();
+        assert!(e.contains("/efi"), "{}", e);
+    }
+
+    #[test]
+    fn test_render_table() {
+        let headers = ["内核", "操作"].map(String::from);
+        let rows = [vec!["6.8.0-aosc-main".to_owned(), "安装".to_owned()]];
+
+        assert_eq!(
+            render_table(&headers, &rows, 80),
+            ["内核             操作", "6.8.0-aosc-main  安装"]
+        );
+        assert_eq!(
+            render_table(&headers, &rows, 20),
+            ["内核: 6.8.0-aosc-main", "操作: 安装"]
+        );
+    }
+
+    #[test]
+    fn test_expand_template() {
+        let fields = [
+            ("version", "6.8.0-aosc-main".to_owned()),
+            ("default", "true".to_owned()),
+        ];
+
+        assert_eq!(
+            expand_template("{version}\\t{default} {{x}}", &fields).unwrap(),
+            "6.8.0-aosc-main\ttrue {x}"
+        );
+        assert!(expand_template("{size}", &fields).is_err());
+        assert!(expand_template("{version", &fields).is_err());
+    }
+
+    #[test]
+    fn test_human_size() {
+        assert_eq!(human_size(512), "512 B");
+        assert_eq!(human_size(1536), "1.5 KiB");
+        assert_eq!(human_size(45 * 1024 * 1024), "45.0 MiB");
+        assert_eq!(human_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+    }
+
+    #[test]
+    fn test_wrap_options() {
+        assert_eq!(
+            wrap_options("options: ", "root=/dev/sda1 rw quiet splash loglevel=3", 30),
+            vec![
+                "options: root=/dev/sda1",
+                "         rw quiet splash",
+                "         loglevel=3",
+            ]
+        );
+        assert_eq!(
+            wrap_entry_line("+options root=/dev/sda1 rw quiet", 24),
+            vec!["+options root=/dev/sda1", "         rw quiet"]
+        );
+        assert_eq!(
+            wrap_entry_line("title AOSC OS (6.8.0-aosc-main)", 10),
+            vec!["title AOSC OS (6.8.0-aosc-main)"]
+        );
+    }
+
+    #[test]
+    fn test_format_utc() {
+        assert_eq!(format_utc(UNIX_EPOCH), "1970-01-01 00:00:00 UTC");
+        assert_eq!(
+            format_utc(UNIX_EPOCH + std::time::Duration::from_secs(1_709_210_096)),
+            "2024-02-29 12:34:56 UTC"
+        );
+    }
+
+    #[test]
+    fn test_load_loader_conf_unknown_key() {
+        let path = Path::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/loader-unknown-key.conf"
+        ));
+        let err = load_loader_conf(path).unwrap_err().to_string();
+
+        assert!(err.contains("loader-unknown-key.conf"));
+        assert!(err.contains('4'));
+        assert!(err.contains("sbf doctor"));
+    }
+}
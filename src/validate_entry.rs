@@ -0,0 +1,105 @@
+use anyhow::Result;
+use libsdbootconf::entry::{Entry, Token};
+use std::{fs, path::Path};
+
+use crate::{config::Config, doctor::Severity, fl};
+
+/// Check if the quotes in the kernel command line are balanced
+fn quotes_balanced(options: &str) -> bool {
+    options.chars().filter(|c| *c == '"').count() % 2 == 0
+}
+
+/// Find the problems of an entry file, which may be anywhere
+pub(crate) fn entry_issues(config: &Config, path: &Path) -> Vec<(Severity, String)> {
+    let content = match fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(e) => return vec![(Severity::Error, e.to_string())],
+    };
+    let entry = match content.parse::<Entry>() {
+        Ok(e) => e,
+        Err(e) => return vec![(Severity::Error, e.to_string())],
+    };
+
+    let mut issues = Vec::new();
+    let (mut title, mut image, mut options) = (false, false, None);
+
+    for token in entry.tokens.iter() {
+        let file = match token {
+            Token::Title(_) => {
+                title = true;
+                continue;
+            }
+            Token::Options(o) => {
+                options = Some(o.as_str());
+                continue;
+            }
+            Token::Linux(p) | Token::Efi(p) => {
+                image = true;
+                p
+            }
+            Token::Initrd(p) => p,
+            _ => continue,
+        };
+        let esp_path = config
+            .esp_mountpoint
+            .join(file.strip_prefix("/").unwrap_or(file));
+
+        if !esp_path.is_file() {
+            issues.push((
+                Severity::Error,
+                fl!("err_path_not_exist", path = esp_path.to_string_lossy()),
+            ));
+        }
+    }
+
+    if !image {
+        issues.push((Severity::Error, fl!("validate_no_linux")));
+    }
+
+    if !title {
+        issues.push((Severity::Warning, fl!("validate_no_title")));
+    }
+
+    match options {
+        Some(o) if !quotes_balanced(o) => {
+            issues.push((Severity::Error, fl!("validate_unbalanced_quotes")))
+        }
+        Some(o) if o.trim().is_empty() => {
+            issues.push((Severity::Warning, fl!("validate_empty_options")))
+        }
+        _ => (),
+    }
+
+    issues
+}
+
+/// Validate an entry file before rebooting into it, return the exit code
+pub fn validate_entry(config: &Config, path: &Path) -> Result<i32> {
+    let issues = entry_issues(config, path);
+    let status = issues.iter().map(|(s, _)| *s).max().unwrap_or(Severity::Ok);
+
+    for (severity, message) in issues.iter() {
+        println!("{} {}", severity.mark(), message);
+    }
+
+    if issues.is_empty() {
+        println!(
+            "{} {}",
+            Severity::Ok.mark(),
+            fl!("validate_ok", path = path.to_string_lossy())
+        );
+    }
+
+    Ok(status.exit_code())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quotes_balanced() {
+        assert!(quotes_balanced("root=/dev/sda1 dyndbg=\"file x.c +p\""));
+        assert!(!quotes_balanced("root=/dev/sda1 dyndbg=\"file x.c +p"));
+    }
+}
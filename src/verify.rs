@@ -0,0 +1,95 @@
+use anyhow::Result;
+use console::style;
+use std::{collections::HashMap, path::Path};
+
+use crate::{
+    config::Config,
+    fl,
+    kernel::{embedded_hashes, read_entries, sha256_file, Kernel, EMBEDDED_HASH_KEYS},
+    manifest::Manifest,
+    splash::splash_dest,
+};
+
+/// The checksums embedded into the entries of the kernel, see `embed_hashes`
+fn kernel_embedded_hashes<K: Kernel>(
+    config: &Config,
+    kernel: &K,
+) -> Result<HashMap<String, String>> {
+    Ok(read_entries(config, kernel)?
+        .into_values()
+        .flat_map(|(_, content)| embedded_hashes(&content))
+        .collect())
+}
+
+/// Compare the kernel files in the ESP against the sources, or against the
+/// checksums in the manifest if the sources were removed, falling back on
+/// those embedded into the entries, and the splash image against the
+/// configured one. Return the exit code.
+pub fn verify<K: Kernel>(config: &Config, installed_kernels: &[K]) -> Result<i32> {
+    let manifest = Manifest::read(&config.esp_mountpoint)?;
+    let mut code = 0;
+
+    for kernel in installed_kernels {
+        let embedded = kernel_embedded_hashes(config, kernel)?;
+
+        for ((src, dest), key) in kernel.source_files().into_iter().zip(EMBEDDED_HASH_KEYS) {
+            code |= verify_file(&src, &dest, &manifest, embedded.get(key))?;
+        }
+    }
+
+    if let (Some(splash), Some(dest)) = (&config.splash, splash_dest(config)) {
+        code |= verify_file(splash, &config.esp_mountpoint.join(dest), &manifest, None)?;
+    }
+
+    Ok(code)
+}
+
+/// Verify a kernel file in the ESP, return the exit code
+fn verify_file(
+    src: &Path,
+    dest: &Path,
+    manifest: &Manifest,
+    embedded: Option<&String>,
+) -> Result<i32> {
+    let path = dest.to_string_lossy();
+    let mut code = 0;
+    let expected = if src.exists() {
+        Some(sha256_file(src)?)
+    } else {
+        dest.file_name()
+            .and_then(|n| n.to_str())
+            .and_then(|n| manifest.hashes.get(n))
+            .or(embedded)
+            .cloned()
+    };
+
+    if !dest.exists() {
+        // Kernels without an initrd
+        if expected.is_some() {
+            println!(
+                "{} {}",
+                style("[!]").red(),
+                fl!("verify_missing", path = path)
+            );
+            code = 1;
+        }
+        return Ok(code);
+    }
+
+    match expected {
+        Some(hash) if sha256_file(dest)? == hash => {
+            println!("{} {}", style("[*]").green(), fl!("verify_ok", path = path))
+        }
+        Some(_) => {
+            println!(
+                "{} {}",
+                style("[!]").red(),
+                fl!("verify_mismatch", path = path)
+            );
+            code = 1;
+        }
+        None => println!("[?] {}", fl!("verify_unknown", path = path)),
+    }
+
+    Ok(code)
+}
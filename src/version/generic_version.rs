@@ -6,12 +6,14 @@ use nom::{
     sequence::{preceded, tuple},
     IResult,
 };
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
-use super::Version;
+use super::{Version, VersionDisplayFormat};
 use crate::fl;
 
-#[derive(Debug, Default, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Debug, Default, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[serde(into = "SerializedGenericVersion")]
 pub struct GenericVersion {
     pub major: u64,
     pub minor: u64,
@@ -21,22 +23,76 @@ pub struct GenericVersion {
     pub localversion: String,
 }
 
+/// GenericVersion with its Display representation for JSON output
+#[derive(Serialize)]
+struct SerializedGenericVersion {
+    major: u64,
+    minor: u64,
+    patch: u64,
+    rc: Option<u64>,
+    rel: Option<u64>,
+    localversion: String,
+    display: String,
+}
+
+impl From<GenericVersion> for SerializedGenericVersion {
+    fn from(version: GenericVersion) -> Self {
+        Self {
+            display: version.to_string(),
+            major: version.major,
+            minor: version.minor,
+            patch: version.patch,
+            rc: version.rc,
+            rel: version.rel,
+            localversion: version.localversion,
+        }
+    }
+}
+
+/// A GenericVersion displayed in the given format
+struct GenericVersionDisplay<'a> {
+    version: &'a GenericVersion,
+    format: VersionDisplayFormat,
+}
+
+impl fmt::Display for GenericVersionDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let v = self.version;
+
+        write!(f, "{}.{}", v.major, v.minor)?;
+
+        if self.format == VersionDisplayFormat::Short {
+            return Ok(());
+        }
+
+        write!(f, ".{}", v.patch)?;
+
+        // The missing fields are shown as 0 like a missing patch version,
+        // unless they are left out
+        for field in [v.rc, v.rel] {
+            match (field, self.format) {
+                (None, VersionDisplayFormat::Compact) => (),
+                _ => write!(f, "-{}", field.unwrap_or_default())?,
+            }
+        }
+
+        write!(f, "{}", v.localversion)
+    }
+}
+
+impl GenericVersion {
+    /// Display the version in the given format
+    pub fn display_as(&self, format: VersionDisplayFormat) -> impl fmt::Display + '_ {
+        GenericVersionDisplay {
+            version: self,
+            format,
+        }
+    }
+}
+
 impl fmt::Display for GenericVersion {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}.{}.{}{}{}{}",
-            self.major,
-            self.minor,
-            self.patch,
-            self.rc
-                .as_ref()
-                .map_or_else(|| "".to_owned(), |s| format!("-{}", s)),
-            self.rel
-                .as_ref()
-                .map_or_else(|| "".to_owned(), |s| format!("-{}", s)),
-            self.localversion
-        )
+        write!(f, "{}", self.display_as(VersionDisplayFormat::Compact))
     }
 }
 
@@ -128,6 +184,55 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_serde_round_trip() {
+        let version = GenericVersion::parse("5.12.0-rc3-aosc-main").unwrap();
+        let json = serde_json::to_value(&version).unwrap();
+
+        assert_eq!(json["major"], 5);
+        assert_eq!(json["rc"], 3);
+        assert_eq!(json["rel"], serde_json::Value::Null);
+        assert_eq!(json["display"], version.to_string());
+        assert_eq!(
+            serde_json::from_value::<GenericVersion>(json).unwrap(),
+            version
+        );
+    }
+
+    #[test]
+    fn test_display_as() {
+        let version = GenericVersion::parse("6.8.0-11-aosc-main").unwrap();
+
+        assert_eq!(
+            version.display_as(VersionDisplayFormat::Short).to_string(),
+            "6.8"
+        );
+        assert_eq!(
+            version.display_as(VersionDisplayFormat::Full).to_string(),
+            "6.8.0-0-11-aosc-main"
+        );
+        assert_eq!(
+            version
+                .display_as(VersionDisplayFormat::Compact)
+                .to_string(),
+            "6.8.0-11-aosc-main"
+        );
+        assert_eq!(version.to_string(), "6.8.0-11-aosc-main");
+
+        let version = GenericVersion::parse("6.8-aosc-main").unwrap();
+
+        assert_eq!(
+            version.display_as(VersionDisplayFormat::Full).to_string(),
+            "6.8.0-0-0-aosc-main"
+        );
+        assert_eq!(
+            version
+                .display_as(VersionDisplayFormat::Compact)
+                .to_string(),
+            "6.8.0-aosc-main"
+        );
+    }
+
     #[test]
     fn test_debian_version() {
         assert_eq!(
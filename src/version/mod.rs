@@ -1,9 +1,98 @@
-use anyhow::Result;
-use std::fmt::Display;
+use anyhow::{anyhow, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{fmt::Display, fs};
 
-pub trait Version: Display + Sized {
+/// Present on Ubuntu and Debian, e.g. "Ubuntu 6.5.0-27.28~22.04.1-generic 6.5.13"
+const PROC_VERSION_SIGNATURE: &str = "/proc/version_signature";
+/// e.g. "Linux version 6.8.0-aosc-main (gcc ...) #1 SMP ..."
+const PROC_VERSION: &str = "/proc/version";
+const OSRELEASE: &str = "/proc/sys/kernel/osrelease";
+
+/// How a version is displayed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VersionDisplayFormat {
+    /// Only the major and minor versions
+    Short,
+    /// All the fields
+    Full,
+    /// All the fields, leaving out the missing ones
+    #[default]
+    Compact,
+}
+
+pub trait Version: Display + Sized + Serialize + DeserializeOwned {
     fn parse(input: &str) -> Result<Self>;
+
+    /// Detect the version of the running kernel from /proc/version_signature,
+    /// falling back to /proc/version and then /proc/sys/kernel/osrelease
+    fn from_proc_version_signature() -> Result<Self> {
+        let mut error = None;
+
+        for path in [PROC_VERSION_SIGNATURE, PROC_VERSION, OSRELEASE] {
+            let content = match fs::read_to_string(path) {
+                Ok(c) => c,
+                Err(e) => {
+                    error = Some(anyhow!("{}: {}", path, e));
+                    continue;
+                }
+            };
+
+            for candidate in release_candidates(path, &content) {
+                match Self::parse(candidate) {
+                    Ok(v) => return Ok(v),
+                    Err(e) => error = Some(e),
+                }
+            }
+        }
+
+        Err(error.unwrap())
+    }
+}
+
+/// Pick the version strings out of the content of the proc file, the most
+/// complete one first
+fn release_candidates<'a>(path: &str, content: &'a str) -> Vec<&'a str> {
+    let fields = content.split_whitespace().collect::<Vec<_>>();
+
+    match path {
+        // The distribution kernel version and then the upstream one
+        PROC_VERSION_SIGNATURE => fields.into_iter().skip(1).take(2).collect(),
+        PROC_VERSION => fields
+            .windows(3)
+            .find(|w| w[0] == "Linux" && w[1] == "version")
+            .map(|w| vec![w[2]])
+            .unwrap_or_default(),
+        _ => fields.into_iter().take(1).collect(),
+    }
 }
 
 #[cfg(feature = "generic")]
 pub mod generic_version;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_release_candidates() {
+        assert_eq!(
+            release_candidates(
+                PROC_VERSION_SIGNATURE,
+                "Ubuntu 6.5.0-27.28~22.04.1-generic 6.5.13\n"
+            ),
+            ["6.5.0-27.28~22.04.1-generic", "6.5.13"]
+        );
+        assert_eq!(
+            release_candidates(
+                PROC_VERSION,
+                "Linux version 6.8.0-aosc-main (gcc 13.2.0) #1 SMP PREEMPT_DYNAMIC\n"
+            ),
+            ["6.8.0-aosc-main"]
+        );
+        assert_eq!(
+            release_candidates(OSRELEASE, "6.8.0-aosc-main\n"),
+            ["6.8.0-aosc-main"]
+        );
+    }
+}
@@ -0,0 +1,1977 @@
+use libsdbootconf::SystemdBootConf;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    rc::Rc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use systemd_boot_friend_rs::{
+    config::{AskDefault, Profile},
+    default_entry::{ensure_default, repair_default},
+    entry_edit::edit_entry,
+    error::SbfError,
+    journal,
+    kernel::{
+        embedded_hashes,
+        generic_kernel::{set_list_cache, GenericKernel, SkipReason},
+        list_entry_ids, sha256_file, DynKernel, Kernel, KernelOps, KernelSummary,
+    },
+    kernel_manager::{InstallSteps, KernelManager, RetainReason, SummaryAction, UpdatePlan},
+    manifest::Manifest,
+    migrate_esp::populate_esp,
+    plan_file::{apply_plan, PlanFile, EXIT_PLAN_INCOMPLETE, EXIT_PLAN_STALE},
+    porcelain,
+    prune::{prune, unexpected_dirs, PruneOptions},
+    reboot::{pending_reboot, set_stamp_path},
+    uninstall::uninstall,
+    verify::verify,
+    Config, REL_DEST_PATH,
+};
+use tempfile::TempDir;
+
+const VERSION: &str = "6.8.0-test";
+
+/// A mock system with /boot, /usr/lib/modules and an initialized ESP
+struct MockSystem {
+    _root: TempDir,
+    config: Config,
+    sbconf: Rc<RefCell<SystemdBootConf>>,
+}
+
+impl MockSystem {
+    fn new() -> Self {
+        Self::with_config(|_| ())
+    }
+
+    fn with_config<F: FnOnce(&mut Config)>(f: F) -> Self {
+        let root = tempfile::tempdir().unwrap();
+        let boot = root.path().join("boot");
+        let modules = root.path().join("usr/lib/modules");
+        let esp = root.path().join("efi");
+
+        set_stamp_path(root.path().join("run/reboot-required"));
+        fs::create_dir_all(&boot).unwrap();
+        fs::write(boot.join(format!("vmlinuz-{}", VERSION)), "vmlinuz").unwrap();
+        fs::write(
+            boot.join(format!("initramfs-{}.img", VERSION)),
+            b"\x1f\x8binitramfs",
+        )
+        .unwrap();
+
+        fs::create_dir_all(modules.join(VERSION)).unwrap();
+        for f in ["modules.dep", "modules.order", "modules.builtin"] {
+            fs::write(modules.join(VERSION).join(f), "").unwrap();
+        }
+
+        let mut config = Config::default();
+        config.esp_mountpoint = Rc::new(esp);
+        config.src_path = Rc::new(boot);
+        config.modules_path = modules;
+        config.conf_d_path = root.path().join("etc/systemd-boot-friend.d");
+        config
+            .bootargs
+            .borrow_mut()
+            .insert("default".to_owned(), "root=/dev/sda1 rw".to_owned());
+        f(&mut config);
+
+        let esp = config.esp_mountpoint.to_path_buf();
+        fs::create_dir_all(esp.join(REL_DEST_PATH)).unwrap();
+        fs::create_dir_all(esp.join("loader/entries")).unwrap();
+
+        let sbconf = Rc::new(RefCell::new(SystemdBootConf::new(
+            esp.join("loader/"),
+            libsdbootconf::Config::default(),
+            Vec::new(),
+        )));
+        sbconf.borrow().write_config().unwrap();
+
+        Self {
+            _root: root,
+            config,
+            sbconf,
+        }
+    }
+
+    fn esp_path(&self, path: &str) -> PathBuf {
+        self.config.esp_mountpoint.join(path)
+    }
+
+    /// Add another available kernel
+    fn add_kernel(&self, version: &str) {
+        fs::write(
+            self.config.src_path.join(format!("vmlinuz-{}", version)),
+            "vmlinuz",
+        )
+        .unwrap();
+        fs::write(
+            self.config
+                .src_path
+                .join(format!("initramfs-{}.img", version)),
+            b"\x1f\x8binitramfs",
+        )
+        .unwrap();
+
+        let modules = self.config.modules_path.join(version);
+        fs::create_dir_all(&modules).unwrap();
+        for f in ["modules.dep", "modules.order", "modules.builtin"] {
+            fs::write(modules.join(f), "").unwrap();
+        }
+    }
+
+    fn kernels(&self) -> (Vec<GenericKernel>, Vec<GenericKernel>) {
+        (
+            GenericKernel::list(&self.config, self.sbconf.clone()).unwrap(),
+            GenericKernel::list_installed(&self.config, self.sbconf.clone()).unwrap(),
+        )
+    }
+}
+
+fn names(kernels: &[&GenericKernel]) -> Vec<String> {
+    kernels.iter().map(|k| k.to_string()).collect()
+}
+
+#[test]
+fn test_list_available() {
+    let system = MockSystem::new();
+    let kernels = GenericKernel::list(&system.config, system.sbconf.clone()).unwrap();
+
+    assert_eq!(kernels.len(), 1);
+    assert_eq!(kernels[0].to_string(), VERSION);
+}
+
+#[test]
+fn test_install_list_set_default_remove() {
+    let system = MockSystem::new();
+    let kernels = GenericKernel::list(&system.config, system.sbconf.clone()).unwrap();
+    let kernel = &kernels[0];
+
+    // install
+    kernel.install_and_make_config(true).unwrap();
+    assert!(system
+        .esp_path(REL_DEST_PATH)
+        .join(format!("vmlinuz-{}", VERSION))
+        .exists());
+    assert!(system
+        .esp_path(REL_DEST_PATH)
+        .join(format!("initramfs-{}.img", VERSION))
+        .exists());
+    assert!(system
+        .esp_path("loader/entries")
+        .join(format!("{}-default.conf", VERSION))
+        .exists());
+
+    // list_installed
+    let installed = GenericKernel::list_installed(&system.config, system.sbconf.clone()).unwrap();
+    assert_eq!(installed, kernels);
+
+    // set_default
+    assert!(!kernel.is_default().unwrap());
+    kernel.set_default(false).unwrap();
+    assert!(kernel.is_default().unwrap());
+
+    // summary
+    assert_eq!(
+        kernel.summary().unwrap(),
+        KernelSummary {
+            version: VERSION.to_owned(),
+            vmlinux: format!("vmlinuz-{}", VERSION),
+            initrd: format!("initramfs-{}.img", VERSION),
+            entry_files: vec![format!("{}-default.conf", VERSION)],
+            is_installed: true,
+            is_default: true,
+            pkgbase: String::new(),
+            build_id: String::new(),
+        }
+    );
+
+    // the changes are recorded in the journal
+    let events = journal::read(&system.config.esp_mountpoint)
+        .unwrap()
+        .into_iter()
+        .map(|r| r.event)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        events,
+        vec![
+            journal::Event::Install {
+                kernel: VERSION.to_owned()
+            },
+            journal::Event::EntriesWritten {
+                kernel: VERSION.to_owned(),
+                entries: vec![format!("{}-default.conf", VERSION)]
+            },
+            journal::Event::DefaultChanged {
+                old: None,
+                new: Some(format!("{}-default.conf", VERSION))
+            },
+        ]
+    );
+
+    // remove
+    kernel.remove().unwrap();
+    assert!(
+        GenericKernel::list_installed(&system.config, system.sbconf.clone())
+            .unwrap()
+            .is_empty()
+    );
+    assert!(!system
+        .esp_path("loader/entries")
+        .join(format!("{}-default.conf", VERSION))
+        .exists());
+    assert_eq!(system.sbconf.borrow().config.default, None);
+}
+
+#[test]
+fn test_list_cache() {
+    let system = MockSystem::new();
+    set_list_cache(true);
+
+    let (kernels, _) = system.kernels();
+    system.add_kernel("6.9.0-test");
+    assert_eq!(system.kernels().0, kernels);
+
+    // Installing a kernel forgets the lists
+    kernels[0].install_and_make_config(true).unwrap();
+    let (kernels, installed) = system.kernels();
+    assert_eq!(
+        names(&kernels.iter().collect::<Vec<_>>()),
+        ["6.9.0-test", VERSION]
+    );
+    assert_eq!(names(&installed.iter().collect::<Vec<_>>()), [VERSION]);
+
+    set_list_cache(false);
+}
+
+#[test]
+fn test_hidden_profile() {
+    let system = MockSystem::with_config(|config| {
+        config.bootargs.borrow_mut().insert(
+            "debug".to_owned(),
+            "root=/dev/sda1 rw earlyprintk".to_owned(),
+        );
+        config.profiles = Rc::new(
+            [(
+                "debug".to_owned(),
+                Profile {
+                    title_suffix: Some("[debug]".to_owned()),
+                    hidden: true,
+                },
+            )]
+            .into(),
+        );
+    });
+    let kernels = GenericKernel::list(&system.config, system.sbconf.clone()).unwrap();
+
+    kernels[0].install_and_make_config(true).unwrap();
+
+    let entries = system.esp_path("loader/entries");
+    assert!(entries.join(format!("{}-default.conf", VERSION)).exists());
+    assert!(!entries.join(format!("{}-debug.conf", VERSION)).exists());
+
+    let hidden = fs::read_to_string(entries.join(format!(".{}-debug.conf", VERSION))).unwrap();
+    assert!(hidden.contains("[debug]"));
+    assert!(!hidden.contains("(debug)"));
+}
+
+#[test]
+fn test_duplicate_versions() {
+    let system = MockSystem::new();
+
+    // Parses to the same version as VERSION
+    let duplicate = system.config.modules_path.join("6.8-test");
+    fs::create_dir_all(&duplicate).unwrap();
+    for f in ["modules.dep", "modules.order", "modules.builtin"] {
+        fs::write(duplicate.join(f), "").unwrap();
+    }
+
+    let (kernels, skipped) =
+        GenericKernel::scan_modules(&system.config, system.sbconf.clone()).unwrap();
+
+    assert_eq!(kernels.len(), 1);
+    assert_eq!(kernels[0].name(), VERSION);
+    assert_eq!(skipped.len(), 1);
+    assert_eq!(skipped[0].dirname, "6.8-test");
+    assert_eq!(skipped[0].reason, SkipReason::Duplicate(VERSION.to_owned()));
+}
+
+#[test]
+fn test_kernel_override() {
+    let system = MockSystem::new();
+
+    fs::create_dir_all(&system.config.conf_d_path).unwrap();
+    fs::write(
+        system.config.conf_d_path.join(format!("{}.toml", VERSION)),
+        "DISTRO = \"Test OS\"\n[BOOTARGS]\ndefault = \"root=/dev/sda1 rw quiet\"\n",
+    )
+    .unwrap();
+
+    let kernels = GenericKernel::list(&system.config, system.sbconf.clone()).unwrap();
+    kernels[0].install_and_make_config(true).unwrap();
+
+    let entry = fs::read_to_string(
+        system
+            .esp_path("loader/entries")
+            .join(format!("{}-default.conf", VERSION)),
+    )
+    .unwrap();
+    assert!(entry.contains("Test OS"));
+    assert!(entry.contains("root=/dev/sda1 rw quiet"));
+}
+
+#[test]
+fn test_porcelain() {
+    let system = MockSystem::new();
+    let kernels = GenericKernel::list(&system.config, system.sbconf.clone()).unwrap();
+    let mut out = Vec::new();
+
+    porcelain::list_available(&mut out, &kernels, &[], 1).unwrap();
+    assert_eq!(out, b"6.8.0-test\t6.8.0-test\tavailable\t1\n");
+
+    kernels[0].install_and_make_config(true).unwrap();
+    kernels[0].set_default(false).unwrap();
+    fs::write(
+        system.esp_path("loader/entries/windows.conf"),
+        "title Windows\nefi /EFI/Microsoft/Boot/bootmgfw.efi\n",
+    )
+    .unwrap();
+
+    let installed = GenericKernel::list_installed(&system.config, system.sbconf.clone()).unwrap();
+
+    out.clear();
+    porcelain::list_available(&mut out, &kernels, &installed, 1).unwrap();
+    assert_eq!(out, b"6.8.0-test\t6.8.0-test\tinstalled\t1\n");
+
+    out.clear();
+    porcelain::list_installed(&mut out, &installed, 1).unwrap();
+    assert_eq!(out, b"6.8.0-test\t6.8.0-test\tdefault\t18\t1\n");
+
+    out.clear();
+    porcelain::list_entries(&mut out, &system.config, &system.sbconf).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "6.8.0-test-default.conf\tdefault\tfriend\tvisible\tEFI/systemd-boot-friend/vmlinuz-6.8.0-test\n\
+         windows.conf\tentry\tforeign\tvisible\t/EFI/Microsoft/Boot/bootmgfw.efi\n"
+    );
+}
+
+#[test]
+fn test_update_plan_keep() {
+    let system = MockSystem::with_config(|c| c.keep = Some(1));
+    system.add_kernel("6.7.0-test");
+    system.add_kernel("6.9.0-test");
+
+    let (kernels, installed) = system.kernels();
+    let plan = KernelManager::new(&kernels, &installed)
+        .plan(&system.config, None)
+        .unwrap();
+
+    assert_eq!(names(&plan.installs), ["6.9.0-test"]);
+    assert!(plan.refreshes.is_empty());
+    assert!(plan.removals.is_empty());
+    assert_eq!(names(&plan.entry_writes), ["6.9.0-test"]);
+    assert_eq!(plan.default_change.unwrap().new.to_string(), "6.9.0-test");
+
+    // Planning has no side effects
+    assert_eq!(
+        fs::read_dir(system.esp_path(REL_DEST_PATH))
+            .unwrap()
+            .count(),
+        0
+    );
+}
+
+#[test]
+fn test_update_summary() {
+    let system = MockSystem::with_config(|c| c.keep = Some(1));
+    system.add_kernel("6.7.0-test");
+
+    let (kernels, _) = system.kernels();
+    for k in kernels.iter() {
+        k.install_and_make_config(true).unwrap();
+    }
+    system.add_kernel("6.9.0-test");
+
+    let (kernels, installed) = system.kernels();
+    let plan = KernelManager::new(&kernels, &installed)
+        .plan(&system.config, None)
+        .unwrap();
+    let summary = plan.summary().unwrap();
+
+    // Newest first, whatever the action
+    assert_eq!(
+        summary
+            .iter()
+            .map(|r| (r.kernel.as_str(), r.action, r.files))
+            .collect::<Vec<_>>(),
+        [
+            ("6.9.0-test", SummaryAction::Install, 2),
+            (VERSION, SummaryAction::Remove, 2),
+            ("6.7.0-test", SummaryAction::Remove, 2),
+        ]
+    );
+}
+
+#[test]
+fn test_manage_only_and_never_touch() {
+    let system = MockSystem::with_config(|c| {
+        c.keep = Some(1);
+        c.manage_only = vec!["6.8.*".to_owned()];
+        c.never_touch = vec!["*-rt*".to_owned()];
+        c.ucode_overrides = Rc::new(HashMap::from([(
+            "*-rt*".to_owned(),
+            "intel-ucode-rt.img".to_owned(),
+        )]));
+    });
+    system.add_kernel("6.9.0-test");
+    system.add_kernel("6.7.0-rt1");
+
+    // Installed before NEVER_TOUCH was set
+    let rt_files = [
+        &system.config.vmlinux,
+        &system.config.initrd,
+        "intel-ucode-rt.img",
+    ]
+    .map(|t| {
+        system
+            .esp_path(REL_DEST_PATH)
+            .join(t.replace("{VERSION}", "6.7.0-rt1"))
+    });
+    for f in rt_files.iter() {
+        fs::write(f, "rt").unwrap();
+    }
+
+    let (kernels, installed) = system.kernels();
+    assert_eq!(
+        names(&kernels.iter().collect::<Vec<_>>()),
+        ["6.9.0-test", VERSION]
+    );
+    assert!(installed.is_empty());
+
+    let plan = KernelManager::new(&kernels, &installed)
+        .plan(&system.config, None)
+        .unwrap();
+    assert_eq!(names(&plan.excluded), ["6.9.0-test"]);
+    assert_eq!(names(&plan.installs), [VERSION]);
+    assert_eq!(plan.default_change.unwrap().new.name(), VERSION);
+
+    let unmanaged = GenericKernel::list_unmanaged(&system.config, system.sbconf.clone())
+        .unwrap()
+        .into_iter()
+        .map(|(k, _)| k)
+        .collect::<Vec<_>>();
+    assert_eq!(names(&unmanaged.iter().collect::<Vec<_>>()), ["6.7.0-rt1"]);
+
+    let options = PruneOptions {
+        assume_yes: true,
+        ..Default::default()
+    };
+    prune(&system.config, &installed, &unmanaged, options).unwrap();
+    assert!(rt_files.iter().all(|f| f.exists()), "{:?}", rt_files);
+}
+
+#[test]
+fn test_update_plan_pinned_and_running() {
+    let system = MockSystem::with_config(|c| {
+        c.keep = Some(1);
+        c.pinned = vec!["6.7.0-test".to_owned()];
+    });
+    system.add_kernel("6.7.0-test");
+    system.add_kernel("6.9.0-test");
+
+    let (kernels, installed) = system.kernels();
+    let plan = KernelManager::new(&kernels, &installed)
+        .plan(&system.config, Some(VERSION))
+        .unwrap();
+
+    assert_eq!(
+        plan.retained
+            .iter()
+            .map(|(k, r)| (k.to_string(), *r))
+            .collect::<Vec<_>>(),
+        [
+            ("6.9.0-test".to_owned(), RetainReason::WithinKeep),
+            (VERSION.to_owned(), RetainReason::Running),
+            ("6.7.0-test".to_owned(), RetainReason::Pinned),
+        ]
+    );
+    assert_eq!(names(&plan.installs), ["6.9.0-test", VERSION, "6.7.0-test"]);
+}
+
+#[test]
+fn test_update_plan_missing_sources() {
+    let system = MockSystem::new();
+    system.add_kernel("6.7.0-test");
+
+    let (kernels, _) = system.kernels();
+    kernels
+        .iter()
+        .for_each(|k| k.install_and_make_config(true).unwrap());
+    kernels[1].set_default(false).unwrap();
+
+    // Both kernels lose their modules, the default one is kept
+    fs::remove_dir_all(system.config.modules_path.join(VERSION)).unwrap();
+    fs::remove_dir_all(system.config.modules_path.join("6.7.0-test")).unwrap();
+    system.add_kernel("6.9.0-test");
+
+    let (kernels, installed) = system.kernels();
+    let plan = KernelManager::new(&kernels, &installed)
+        .plan(&system.config, None)
+        .unwrap();
+
+    assert_eq!(names(&plan.installs), ["6.9.0-test"]);
+    assert_eq!(names(&plan.removals), [VERSION]);
+    assert!(plan
+        .retained
+        .iter()
+        .any(|(k, r)| k.to_string() == "6.7.0-test" && *r == RetainReason::Default));
+
+    let default_change = plan.default_change.unwrap();
+    assert_eq!(default_change.old.unwrap().to_string(), "6.7.0-test");
+    assert_eq!(default_change.new.to_string(), "6.9.0-test");
+}
+
+#[test]
+fn test_manage_flavors() {
+    let mut system = MockSystem::with_config(|c| c.keep = Some(1));
+    system.add_kernel("6.9.0-aosc-main");
+
+    let (kernels, _) = system.kernels();
+    kernels
+        .iter()
+        .for_each(|k| k.install_and_make_config(true).unwrap());
+
+    // The self-built kernel is neither listed nor pruned
+    system.config.manage_flavors = vec!["aosc-main".to_owned()];
+
+    let (kernels, installed) = system.kernels();
+    assert_eq!(
+        names(&kernels.iter().collect::<Vec<_>>()),
+        ["6.9.0-aosc-main"]
+    );
+    assert_eq!(
+        names(&installed.iter().collect::<Vec<_>>()),
+        ["6.9.0-aosc-main"]
+    );
+
+    let plan = KernelManager::new(&kernels, &installed)
+        .plan(&system.config, None)
+        .unwrap();
+    assert!(plan.removals.is_empty());
+    assert_eq!(names(&plan.entry_writes), ["6.9.0-aosc-main"]);
+
+    let unmanaged = GenericKernel::list_unmanaged(&system.config, system.sbconf.clone()).unwrap();
+    assert_eq!(unmanaged.len(), 1);
+    assert_eq!(unmanaged[0].0.to_string(), VERSION);
+    assert_eq!(unmanaged[0].1, SkipReason::Unmanaged);
+
+    let unmanaged = unmanaged.into_iter().map(|(k, _)| k).collect::<Vec<_>>();
+    let files = unmanaged[0].esp_files();
+    assert!(!files.is_empty());
+
+    prune(
+        &system.config,
+        &installed,
+        &unmanaged,
+        PruneOptions::default(),
+    )
+    .unwrap();
+    assert!(files.iter().all(|f| f.exists()));
+}
+
+#[test]
+fn test_flavor_aliases() {
+    let mut system = MockSystem::new();
+    system.add_kernel("6.7.0-aosc-main");
+
+    let (kernels, _) = system.kernels();
+    let old = kernels
+        .iter()
+        .find(|k| k.name() == "6.7.0-aosc-main")
+        .unwrap();
+    old.install_and_make_config(true).unwrap();
+    old.set_default(false).unwrap();
+
+    // The newest kernel wins by default
+    system.add_kernel("6.9.0-aosc");
+
+    let (kernels, installed) = system.kernels();
+    let plan = KernelManager::new(&kernels, &installed)
+        .plan(&system.config, None)
+        .unwrap();
+    let default_change = plan.default_change.unwrap();
+    assert_eq!(default_change.new.to_string(), "6.9.0-aosc");
+    assert!(default_change.flavor_alias.is_none());
+
+    // The flavor is renamed, the default stays on its own flavor otherwise
+    system.config.default_follows_flavor = true;
+    let plan = KernelManager::new(&kernels, &installed)
+        .plan(&system.config, None)
+        .unwrap();
+    let default_change = plan.default_change.unwrap();
+    assert_eq!(default_change.new.to_string(), "6.7.0-aosc-main");
+    assert!(default_change.flavor_alias.is_none());
+
+    system.config.flavor_aliases =
+        Rc::new(HashMap::from([("aosc-main".to_owned(), "aosc".to_owned())]));
+    let plan = KernelManager::new(&kernels, &installed)
+        .plan(&system.config, None)
+        .unwrap();
+    let default_change = plan.default_change.unwrap();
+    assert_eq!(default_change.new.to_string(), "6.9.0-aosc");
+    assert_eq!(
+        default_change.flavor_alias,
+        Some(("aosc-main".to_owned(), "aosc".to_owned()))
+    );
+}
+
+#[test]
+fn test_skip_profiles() {
+    let system = MockSystem::with_config(|c| {
+        c.bootargs
+            .borrow_mut()
+            .insert("debug".to_owned(), "root=/dev/sda1 rw debug".to_owned());
+        c.skip_profiles = Rc::new(HashMap::from([(
+            "debug".to_owned(),
+            vec!["6.8.*".to_owned()],
+        )]));
+    });
+    system.add_kernel("6.9.0-test");
+
+    let (kernels, _) = system.kernels();
+    kernels
+        .iter()
+        .for_each(|k| k.install_and_make_config(true).unwrap());
+
+    let entries = system.esp_path("loader/entries");
+    assert!(entries.join("6.9.0-test-debug.conf").exists());
+    assert!(entries.join("6.9.0-test-default.conf").exists());
+    assert!(!entries.join(format!("{}-debug.conf", VERSION)).exists());
+    assert!(entries.join(format!("{}-default.conf", VERSION)).exists());
+}
+
+#[test]
+fn test_duplicate_profiles() {
+    let system = MockSystem::with_config(|c| {
+        c.bootargs
+            .borrow_mut()
+            .insert("copy".to_owned(), "root=/dev/sda1  rw".to_owned());
+        c.skip_profiles = Rc::new(HashMap::from([(
+            "copy".to_owned(),
+            vec!["6.8.*".to_owned()],
+        )]));
+    });
+    system.add_kernel("6.9.0-test");
+
+    // The debug profile of the override is the same as the others
+    fs::create_dir_all(&system.config.conf_d_path).unwrap();
+    fs::write(
+        system.config.conf_d_path.join("6.9.0-test.toml"),
+        "[BOOTARGS]\ndebug = \"root=/dev/sda1 rw\"\n",
+    )
+    .unwrap();
+
+    let (kernels, _) = system.kernels();
+    assert_eq!(
+        kernels
+            .iter()
+            .map(|k| k.duplicate_profiles())
+            .collect::<Vec<_>>(),
+        [vec![vec!["copy", "debug", "default"]], vec![]]
+    );
+}
+
+#[test]
+fn test_default_profile() {
+    let system = MockSystem::with_config(|c| {
+        c.bootargs
+            .borrow_mut()
+            .insert("debug".to_owned(), "root=/dev/sda1 rw debug".to_owned());
+        c.default_profile = "debug".to_owned();
+    });
+    let (kernels, _) = system.kernels();
+
+    kernels[0].install_and_make_config(true).unwrap();
+    kernels[0].set_default(false).unwrap();
+    assert_eq!(
+        system.sbconf.borrow().config.default,
+        Some(format!("{}-debug.conf", VERSION))
+    );
+    assert!(kernels[0].is_default().unwrap());
+
+    kernels[0].remove_default().unwrap();
+    assert_eq!(system.sbconf.borrow().config.default, None);
+}
+
+#[test]
+fn test_apply_plan() {
+    let system = MockSystem::new();
+    let (kernels, installed) = system.kernels();
+    let manager = KernelManager::new(&kernels, &installed);
+    let path = system.esp_path("plan.json");
+    let write_plan = || {
+        let plan = manager.plan(&system.config, None).unwrap();
+        let plan_file = PlanFile::new(&system.config, &plan, Vec::new());
+        assert!(plan_file.complete);
+        fs::write(&path, serde_json::to_string(&plan_file).unwrap()).unwrap();
+    };
+
+    // A plan made without reading everything is never applied
+    let plan = manager.plan(&system.config, None).unwrap();
+    let plan_file = PlanFile::new(&system.config, &plan, vec!["unreadable".to_owned()]);
+    assert!(!plan_file.complete);
+    fs::write(&path, serde_json::to_string(&plan_file).unwrap()).unwrap();
+    assert_eq!(
+        apply_plan(&manager, &system.config, &path, true).unwrap(),
+        EXIT_PLAN_INCOMPLETE
+    );
+
+    // The kernel image is rebuilt after the plan is made
+    write_plan();
+    fs::write(
+        system.config.src_path.join(format!("vmlinuz-{}", VERSION)),
+        "rebuilt",
+    )
+    .unwrap();
+    assert_eq!(
+        apply_plan(&manager, &system.config, &path, true).unwrap(),
+        EXIT_PLAN_STALE
+    );
+    assert!(!system
+        .esp_path(REL_DEST_PATH)
+        .join(format!("vmlinuz-{}", VERSION))
+        .exists());
+
+    write_plan();
+    assert_eq!(
+        apply_plan(&manager, &system.config, &path, true).unwrap(),
+        0
+    );
+    assert_eq!(
+        fs::read_to_string(
+            system
+                .esp_path(REL_DEST_PATH)
+                .join(format!("vmlinuz-{}", VERSION))
+        )
+        .unwrap(),
+        "rebuilt"
+    );
+}
+
+#[test]
+fn test_repair_default() {
+    let system = MockSystem::new();
+    let kernels = GenericKernel::list(&system.config, system.sbconf.clone()).unwrap();
+
+    kernels[0].install_and_make_config(true).unwrap();
+    system.sbconf.borrow_mut().config.default = Some("6.1.0-removed-default.conf".to_owned());
+
+    let installed = GenericKernel::list_installed(&system.config, system.sbconf.clone()).unwrap();
+    repair_default(&system.config, &installed, system.sbconf.clone()).unwrap();
+
+    assert!(installed[0].is_default().unwrap());
+}
+
+#[test]
+fn test_ensure_default() {
+    let system = MockSystem::new();
+    system.add_kernel("6.9.0-test");
+    let (kernels, _) = system.kernels();
+
+    kernels
+        .iter()
+        .for_each(|k| k.install_and_make_config(true).unwrap());
+    system.sbconf.borrow_mut().config.default = None;
+
+    let (_, installed) = system.kernels();
+    ensure_default(&system.config, &installed, system.sbconf.clone()).unwrap();
+    assert!(installed[0].is_default().unwrap());
+
+    // An existing default is kept
+    installed[1].set_default(false).unwrap();
+    ensure_default(&system.config, &installed, system.sbconf.clone()).unwrap();
+    assert!(installed[1].is_default().unwrap());
+}
+
+#[test]
+fn test_timestamp_based_entries() {
+    let system = MockSystem::with_config(|config| config.timestamp_based_entries = true);
+    let vmlinuz = system.config.src_path.join(format!("vmlinuz-{}", VERSION));
+
+    fs::File::options()
+        .write(true)
+        .open(&vmlinuz)
+        .unwrap()
+        .set_modified(UNIX_EPOCH + Duration::from_secs(1_700_000_001))
+        .unwrap();
+
+    let kernels = GenericKernel::list(&system.config, system.sbconf.clone()).unwrap();
+    kernels[0].install_and_make_config(true).unwrap();
+
+    let id = format!("{}-1700000000-default", VERSION);
+    assert_eq!(kernels[0].entry_id("default"), id);
+    assert!(system
+        .esp_path("loader/entries")
+        .join(format!("{}.conf", id))
+        .exists());
+
+    // The installed copy keeps the same entry id without the source
+    fs::remove_file(&vmlinuz).unwrap();
+    let installed = GenericKernel::list_installed(&system.config, system.sbconf.clone()).unwrap();
+    assert_eq!(installed[0].entry_id("default"), id);
+
+    // And so does a rebuilt source, the old entries are removed along with
+    // the kernel
+    fs::write(&vmlinuz, "vmlinuz-rebuilt").unwrap();
+    let installed = GenericKernel::list_installed(&system.config, system.sbconf.clone()).unwrap();
+    assert_eq!(installed[0].entry_id("default"), id);
+
+    installed[0].remove().unwrap();
+    assert_eq!(
+        list_entry_ids(&system.config.esp_mountpoint).unwrap().len(),
+        0
+    );
+    assert!(Manifest::read(&system.config.esp_mountpoint)
+        .unwrap()
+        .timestamps
+        .is_empty());
+}
+
+#[test]
+fn test_remove_sources() {
+    let system = MockSystem::with_config(|config| config.remove_sources = true);
+    let manifest = Manifest {
+        remove_sources_confirmed: true,
+        ..Default::default()
+    };
+    manifest.write(&system.config.esp_mountpoint).unwrap();
+
+    let vmlinuz = system.config.src_path.join(format!("vmlinuz-{}", VERSION));
+    let kernels = GenericKernel::list(&system.config, system.sbconf.clone()).unwrap();
+    kernels[0].install_and_make_config(true).unwrap();
+
+    assert!(!vmlinuz.exists());
+    assert!(system.config.modules_path.join(VERSION).exists());
+    assert_eq!(
+        Manifest::read(&system.config.esp_mountpoint)
+            .unwrap()
+            .hashes
+            .len(),
+        2
+    );
+    assert!(kernels[0].changed_files().unwrap().is_empty());
+
+    // Reinstalling the package recreates the same file with a newer mtime
+    fs::write(&vmlinuz, "vmlinuz").unwrap();
+    fs::File::options()
+        .write(true)
+        .open(&vmlinuz)
+        .unwrap()
+        .set_modified(SystemTime::now() + Duration::from_secs(60))
+        .unwrap();
+    assert!(kernels[0].changed_files().unwrap().is_empty());
+
+    fs::write(&vmlinuz, "vmlinuz-rebuilt").unwrap();
+    fs::File::options()
+        .write(true)
+        .open(&vmlinuz)
+        .unwrap()
+        .set_modified(SystemTime::now() + Duration::from_secs(60))
+        .unwrap();
+    assert_eq!(kernels[0].changed_files().unwrap(), vec![vmlinuz]);
+}
+
+#[test]
+fn test_entry_edit() {
+    let system = MockSystem::new();
+    let root = system.config.esp_mountpoint.parent().unwrap().to_owned();
+    let id = format!("{}-default", VERSION);
+    let entry = system.esp_path(&format!("loader/entries/{}.conf", id));
+    let modified = || {
+        Manifest::read(&system.config.esp_mountpoint)
+            .unwrap()
+            .modified_entries
+    };
+    let update = || {
+        let (kernels, installed) = system.kernels();
+        KernelManager::new(&kernels, &installed)
+            .update(&system.config, None, false)
+            .unwrap();
+    };
+    // The editor is a script editing the file given
+    let editor = |name: &str, script: &str| {
+        let path = root.join(name);
+        fs::write(&path, script).unwrap();
+        std::env::set_var("VISUAL", format!("sh {}", path.display()));
+    };
+
+    update();
+    let generated = fs::read_to_string(&entry).unwrap();
+
+    // An edit is kept by update without a terminal to ask on
+    editor("edit.sh", "sed -i 's/ rw/ rw quiet/' \"$1\"\n");
+    let (_, installed) = system.kernels();
+    edit_entry(&system.config, &installed, VERSION, "default", false).unwrap();
+    let edited = fs::read_to_string(&entry).unwrap();
+    assert!(edited.contains("rw quiet"));
+    assert_eq!(modified(), [id.as_str()]);
+
+    update();
+    assert_eq!(fs::read_to_string(&entry).unwrap(), edited);
+
+    // An invalid edit is rejected and the entry is left alone
+    editor(
+        "break.sh",
+        "sed -i 's|^linux .*|linux /EFI/missing|' \"$1\"\n",
+    );
+    let (_, installed) = system.kernels();
+    assert!(edit_entry(&system.config, &installed, VERSION, "default", false).is_err());
+    assert_eq!(fs::read_to_string(&entry).unwrap(), edited);
+    assert_eq!(
+        fs::read_dir(system.esp_path("loader/entries"))
+            .unwrap()
+            .count(),
+        1
+    );
+
+    // Reverting writes the entry again
+    edit_entry(&system.config, &installed, VERSION, "default", true).unwrap();
+    assert_eq!(fs::read_to_string(&entry).unwrap(), generated);
+    assert!(modified().is_empty());
+
+    // The edit is forgotten with the kernel
+    editor("edit.sh", "sed -i 's/ rw/ rw quiet/' \"$1\"\n");
+    edit_entry(&system.config, &installed, VERSION, "default", false).unwrap();
+    assert_eq!(modified(), [id.as_str()]);
+    installed[0].remove().unwrap();
+    assert!(modified().is_empty());
+
+    update();
+    assert_eq!(fs::read_to_string(&entry).unwrap(), generated);
+    std::env::remove_var("VISUAL");
+}
+
+#[test]
+fn test_uninstall() {
+    let mut system = MockSystem::new();
+    let splash = system.config.src_path.join("splash.bmp");
+    fs::write(&splash, "BM").unwrap();
+    system.config.splash = Some(splash);
+
+    let (kernels, installed) = system.kernels();
+    KernelManager::new(&kernels, &installed)
+        .update(&system.config, None, false)
+        .unwrap();
+    let entry = system.esp_path(&format!("loader/entries/{}-default.conf", VERSION));
+    assert!(system.esp_path("splash.bmp").exists());
+    assert!(entry.exists());
+
+    // Declined, nothing is touched
+    uninstall(&system.config, system.sbconf.clone(), false, || Ok(false)).unwrap();
+    assert!(system.esp_path("splash.bmp").exists());
+    assert!(system.esp_path(REL_DEST_PATH).exists());
+    assert!(entry.exists());
+
+    // The splash image is verified along with the kernels
+    let (_, installed) = system.kernels();
+    assert_eq!(verify(&system.config, &installed).unwrap(), 0);
+    fs::write(system.esp_path("splash.bmp"), "BM corrupted").unwrap();
+    assert_eq!(verify(&system.config, &installed).unwrap(), 1);
+
+    uninstall(&system.config, system.sbconf.clone(), false, || Ok(true)).unwrap();
+    assert!(!system.esp_path("splash.bmp").exists());
+    assert!(!system.esp_path(REL_DEST_PATH).exists());
+    assert!(!entry.exists());
+    assert!(system.sbconf.borrow().config.default.is_none());
+}
+
+#[test]
+fn test_journal_failure() {
+    let system = MockSystem::new();
+    // The journal cannot be appended to
+    fs::create_dir(system.esp_path(REL_DEST_PATH).join("journal.jsonl")).unwrap();
+
+    let (kernels, _) = system.kernels();
+    let kernel = &kernels[0];
+    kernel.install_and_make_config(true).unwrap();
+    kernel.set_default(false).unwrap();
+    assert!(kernel.is_default().unwrap());
+    assert!(journal::read(&system.config.esp_mountpoint)
+        .unwrap()
+        .is_empty());
+
+    kernel.remove().unwrap();
+    assert!(
+        GenericKernel::list_installed(&system.config, system.sbconf.clone())
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn test_reboot_stamp() {
+    let system = MockSystem::new();
+    let root = system.config.esp_mountpoint.parent().unwrap().to_owned();
+
+    let (kernels, installed) = system.kernels();
+    KernelManager::new(&kernels, &installed)
+        .update(&system.config, None, false)
+        .unwrap();
+    assert_eq!(
+        fs::read_to_string(root.join("run/reboot-required")).unwrap(),
+        format!("{}\n", VERSION)
+    );
+    assert_eq!(pending_reboot().unwrap().as_deref(), Some(VERSION));
+
+    // The stamp cannot be written under a file, the update goes on
+    fs::write(root.join("blocker"), "").unwrap();
+    set_stamp_path(root.join("blocker/reboot-required"));
+    system.add_kernel("6.9.0-test");
+
+    let (kernels, installed) = system.kernels();
+    KernelManager::new(&kernels, &installed)
+        .update(&system.config, None, false)
+        .unwrap();
+    assert!(
+        GenericKernel::list_installed(&system.config, system.sbconf.clone())
+            .unwrap()
+            .iter()
+            .any(|k| k.name() == "6.9.0-test" && k.is_default().unwrap())
+    );
+}
+
+#[test]
+fn test_update_previous_default_title() {
+    let system = MockSystem::new();
+    let (kernels, installed) = system.kernels();
+    KernelManager::new(&kernels, &installed)
+        .update(&system.config, None, false)
+        .unwrap();
+
+    // The default moves to the new kernel
+    system.add_kernel("6.9.0-test");
+    let (kernels, installed) = system.kernels();
+    KernelManager::new(&kernels, &installed)
+        .update(&system.config, None, false)
+        .unwrap();
+
+    let title = |version: &str| {
+        fs::read_to_string(system.esp_path(&format!("loader/entries/{}-default.conf", version)))
+            .unwrap()
+            .lines()
+            .find_map(|l| l.strip_prefix("title ").map(str::to_owned))
+            .unwrap()
+    };
+    let suffix = systemd_boot_friend_rs::fl!("title_previous_default");
+    assert!(title(VERSION).ends_with(&format!("(default) {}", suffix)));
+    assert!(!title("6.9.0-test").contains(&suffix));
+}
+
+#[test]
+fn test_update_timeout() {
+    let system = MockSystem::new();
+    system.add_kernel("6.9.0-test");
+
+    // Expired before the first kernel, nothing is installed
+    let (kernels, installed) = system.kernels();
+    let e = KernelManager::new(&kernels, &installed)
+        .update(&system.config, Some(Duration::ZERO), false)
+        .unwrap_err();
+    assert!(matches!(
+        e.downcast_ref::<SbfError>(),
+        Some(SbfError::Timeout {
+            completed: 0,
+            remaining: 2
+        })
+    ));
+    assert!(system.kernels().1.is_empty());
+
+    // Completed within the limit
+    let report = KernelManager::new(&kernels, &installed)
+        .update(&system.config, Some(Duration::from_secs(600)), false)
+        .unwrap();
+    assert_eq!(report.installed.len(), 2);
+    assert_eq!(system.kernels().1.len(), 2);
+}
+
+#[test]
+fn test_update_after_remove_sources() {
+    let system = MockSystem::with_config(|config| config.remove_sources = true);
+    let manifest = Manifest {
+        remove_sources_confirmed: true,
+        ..Default::default()
+    };
+    manifest.write(&system.config.esp_mountpoint).unwrap();
+
+    for _ in 0..2 {
+        let (kernels, installed) = system.kernels();
+        KernelManager::new(&kernels, &installed)
+            .update(&system.config, None, false)
+            .unwrap();
+    }
+
+    let dest = system.esp_path(REL_DEST_PATH);
+    assert!(!system
+        .config
+        .src_path
+        .join(format!("vmlinuz-{}", VERSION))
+        .exists());
+    assert_eq!(
+        fs::read(dest.join(format!("vmlinuz-{}", VERSION))).unwrap(),
+        b"vmlinuz"
+    );
+    assert!(dest.join(format!("initramfs-{}.img", VERSION)).exists());
+}
+
+#[test]
+fn test_unexpected_dirs() {
+    let system = MockSystem::new();
+    let kernels = GenericKernel::list(&system.config, system.sbconf.clone()).unwrap();
+    kernels[0].install_and_make_config(true).unwrap();
+
+    // Junk left by a backup tool, named like the kernel files
+    let dest = system.esp_path(REL_DEST_PATH);
+    let junk = dest.join("vmlinuz-6.9.0-backup");
+    fs::create_dir_all(junk.join("nested/deeper")).unwrap();
+    fs::write(junk.join("nested/deeper/vmlinuz-6.9.0-backup"), "junk").unwrap();
+    fs::create_dir_all(dest.join("backup-2023")).unwrap();
+    fs::write(dest.join("stray.img"), "junk").unwrap();
+
+    let installed = GenericKernel::list_installed(&system.config, system.sbconf.clone()).unwrap();
+    assert_eq!(installed, kernels);
+    assert_eq!(
+        unexpected_dirs(&system.config.esp_mountpoint).unwrap(),
+        vec![dest.join("backup-2023"), junk.clone()]
+    );
+
+    // Nothing is removed on a dry run
+    let options = PruneOptions {
+        include_dirs: true,
+        dry_run: true,
+        ..Default::default()
+    };
+    prune(&system.config, &installed, &[], options).unwrap();
+    assert!(dest.join("stray.img").exists());
+    assert!(junk.join("nested/deeper").exists());
+
+    // Directories are left alone without --include-dirs
+    let options = PruneOptions {
+        assume_yes: true,
+        ..Default::default()
+    };
+    prune(&system.config, &installed, &[], options).unwrap();
+    assert!(!dest.join("stray.img").exists());
+    assert!(junk.join("nested/deeper").exists());
+    assert!(dest.join(format!("vmlinuz-{}", VERSION)).exists());
+    assert!(dest.join(format!("initramfs-{}.img", VERSION)).exists());
+
+    let options = PruneOptions {
+        include_dirs: true,
+        assume_yes: true,
+        ..Default::default()
+    };
+    prune(&system.config, &installed, &[], options).unwrap();
+    assert!(unexpected_dirs(&system.config.esp_mountpoint)
+        .unwrap()
+        .is_empty());
+    assert!(dest.join(format!("vmlinuz-{}", VERSION)).exists());
+}
+
+#[test]
+fn test_esp_mounted_at_boot() {
+    let system = MockSystem::with_config(|config| {
+        config.esp_mountpoint = config.src_path.clone();
+        config.remove_sources = true;
+    });
+    let manifest = Manifest {
+        remove_sources_confirmed: true,
+        ..Default::default()
+    };
+    manifest.write(&system.config.esp_mountpoint).unwrap();
+
+    let vmlinuz = format!("vmlinuz-{}", VERSION);
+    let src = system.config.src_path.join(&vmlinuz);
+    let dest = system.esp_path(REL_DEST_PATH).join(&vmlinuz);
+    let kernels = GenericKernel::list(&system.config, system.sbconf.clone()).unwrap();
+
+    kernels[0].install_and_make_config(true).unwrap();
+    assert!(!src.exists());
+    assert_eq!(fs::read(&dest).unwrap(), b"vmlinuz");
+    assert!(kernels[0].changed_files().unwrap().is_empty());
+    assert_eq!(
+        GenericKernel::list_installed(&system.config, system.sbconf.clone()).unwrap(),
+        kernels
+    );
+}
+
+#[test]
+fn test_source_is_destination() {
+    // The kernel files are installed by the package manager right into the ESP
+    let system = MockSystem::with_config(|config| {
+        config.src_path = Rc::new(config.esp_mountpoint.join(REL_DEST_PATH));
+        config.remove_sources = true;
+    });
+    let manifest = Manifest {
+        remove_sources_confirmed: true,
+        ..Default::default()
+    };
+    manifest.write(&system.config.esp_mountpoint).unwrap();
+
+    let dest = system.esp_path(REL_DEST_PATH);
+    let root = system.config.esp_mountpoint.parent().unwrap();
+    for f in [
+        format!("vmlinuz-{}", VERSION),
+        format!("initramfs-{}.img", VERSION),
+    ] {
+        fs::copy(root.join("boot").join(&f), dest.join(&f)).unwrap();
+    }
+
+    let kernels = GenericKernel::list(&system.config, system.sbconf.clone()).unwrap();
+    kernels[0].install_and_make_config(true).unwrap();
+
+    // Neither truncated by copying onto itself nor removed as a source
+    assert_eq!(
+        fs::read(dest.join(format!("vmlinuz-{}", VERSION))).unwrap(),
+        b"vmlinuz"
+    );
+    assert!(dest.join(format!("initramfs-{}.img", VERSION)).exists());
+}
+
+/// A kernel of another type, recording the operations on it
+struct ToyKernel {
+    name: String,
+    log: Rc<RefCell<Vec<String>>>,
+}
+
+impl std::fmt::Display for ToyKernel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl KernelOps for ToyKernel {
+    fn install(&self, _: Option<&std::path::Path>) -> anyhow::Result<()> {
+        self.log.borrow_mut().push(format!("install {}", self.name));
+        Ok(())
+    }
+
+    fn install_from(&self, _: &std::path::Path, _: Option<&std::path::Path>) -> anyhow::Result<()> {
+        self.install(None)
+    }
+
+    fn remove(&self) -> anyhow::Result<()> {
+        self.log.borrow_mut().push(format!("remove {}", self.name));
+        Ok(())
+    }
+
+    fn make_config(&self, _: bool) -> anyhow::Result<()> {
+        self.log.borrow_mut().push(format!("entry {}", self.name));
+        Ok(())
+    }
+
+    fn set_default(&self, _: bool) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn remove_default(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn ask_set_default(&self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn is_default(&self) -> anyhow::Result<bool> {
+        Ok(false)
+    }
+
+    fn install_and_make_config(&self, force_write: bool) -> anyhow::Result<()> {
+        self.install(None)?;
+        self.make_config(force_write)
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn entry_id(&self, profile: &str) -> String {
+        format!("{}-{}", self.name, profile)
+    }
+
+    fn changed_files(&self) -> anyhow::Result<Vec<PathBuf>> {
+        Ok(Vec::new())
+    }
+
+    fn esp_files(&self) -> Vec<PathBuf> {
+        Vec::new()
+    }
+
+    fn source_files(&self) -> Vec<(PathBuf, PathBuf)> {
+        Vec::new()
+    }
+
+    fn summary(&self) -> anyhow::Result<KernelSummary> {
+        Ok(KernelSummary {
+            version: self.name.clone(),
+            vmlinux: String::new(),
+            initrd: String::new(),
+            entry_files: Vec::new(),
+            is_installed: false,
+            is_default: false,
+            pkgbase: String::new(),
+            build_id: String::new(),
+        })
+    }
+}
+
+#[test]
+fn test_mixed_kernel_types() {
+    let system = MockSystem::new();
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let (kernels, _) = system.kernels();
+
+    let mut mixed: Vec<DynKernel> = kernels
+        .into_iter()
+        .map(|k| Rc::new(k) as DynKernel)
+        .collect();
+    mixed.push(Rc::new(ToyKernel {
+        name: "toy".to_owned(),
+        log: log.clone(),
+    }));
+
+    let plan = KernelManager::new(&mixed, &[])
+        .plan(&system.config, None)
+        .unwrap();
+    let installs = plan.installs.iter().map(|k| k.name()).collect::<Vec<_>>();
+    assert_eq!(installs, [VERSION, "toy"]);
+
+    let stats = UpdatePlan::install(&mixed[0], InstallSteps::All)
+        .apply(None, true, None)
+        .unwrap();
+    assert_eq!(stats.kernels_installed, 1);
+    assert_eq!(stats.entries_created, 1);
+    assert_eq!(
+        stats.bytes_copied,
+        ("vmlinuz".len() + b"\x1f\x8binitramfs".len()) as u64
+    );
+    UpdatePlan::install(&mixed[1], InstallSteps::All)
+        .apply(None, true, None)
+        .unwrap();
+    assert!(system
+        .esp_path(REL_DEST_PATH)
+        .join(format!("vmlinuz-{}", VERSION))
+        .exists());
+
+    KernelManager::remove(&mixed).unwrap();
+    assert!(!system
+        .esp_path(REL_DEST_PATH)
+        .join(format!("vmlinuz-{}", VERSION))
+        .exists());
+    assert_eq!(*log.borrow(), ["install toy", "entry toy", "remove toy"]);
+}
+
+#[test]
+fn test_ostree_kernel() {
+    let mut system = MockSystem::new();
+    let (kernels, _) = system.kernels();
+    kernels[0].install_and_make_config(true).unwrap();
+
+    fs::write(
+        system
+            .config
+            .modules_path
+            .join(VERSION)
+            .join(".ostree-commit"),
+        "",
+    )
+    .unwrap();
+
+    // Skipped with the reason, and its files in the ESP are kept
+    let (kernels, skipped) =
+        GenericKernel::scan_modules(&system.config, system.sbconf.clone()).unwrap();
+    assert!(kernels.is_empty());
+    assert_eq!(skipped[0].reason, SkipReason::Ostree);
+
+    let (_, installed) = system.kernels();
+    assert!(installed.is_empty());
+
+    prune(&system.config, &installed, &[], PruneOptions::default()).unwrap();
+    assert!(system
+        .esp_path(REL_DEST_PATH)
+        .join(format!("vmlinuz-{}", VERSION))
+        .exists());
+
+    system.config.manage_ostree = true;
+    let (kernels, installed) = system.kernels();
+    assert_eq!(kernels.len(), 1);
+    assert_eq!(installed.len(), 1);
+}
+
+#[test]
+fn test_embed_hashes() {
+    let system = MockSystem::with_config(|config| {
+        config.remove_sources = true;
+        config.embed_hashes = true;
+    });
+    let manifest = Manifest {
+        remove_sources_confirmed: true,
+        ..Default::default()
+    };
+    manifest.write(&system.config.esp_mountpoint).unwrap();
+
+    let (kernels, _) = system.kernels();
+    kernels[0].install_and_make_config(true).unwrap();
+
+    let entry =
+        fs::read_to_string(system.esp_path(&format!("loader/entries/{}-default.conf", VERSION)))
+            .unwrap();
+    let hashes = embedded_hashes(&entry);
+    assert_eq!(hashes.len(), 2);
+    assert_eq!(
+        hashes["vmlinuz"],
+        sha256_file(system.esp_path(&format!("{}vmlinuz-{}", REL_DEST_PATH, VERSION))).unwrap()
+    );
+    assert!(entry.contains("# generated-by systemd-boot-friend "));
+
+    // The same entry is generated again
+    kernels[0].make_config(true).unwrap();
+    assert_eq!(
+        fs::read_to_string(system.esp_path(&format!("loader/entries/{}-default.conf", VERSION)))
+            .unwrap(),
+        entry
+    );
+
+    // The comments are harmless to the entry parser
+    let (_, installed) = system.kernels();
+    assert_eq!(installed, kernels);
+
+    // Without the checksums in the manifest, verify falls back on the entries
+    manifest.write(&system.config.esp_mountpoint).unwrap();
+    assert_eq!(verify(&system.config, &installed).unwrap(), 0);
+
+    fs::write(
+        system.esp_path(&format!("{}vmlinuz-{}", REL_DEST_PATH, VERSION)),
+        "tampered",
+    )
+    .unwrap();
+    assert_eq!(verify(&system.config, &installed).unwrap(), 1);
+}
+
+#[test]
+fn test_ucode_overrides() {
+    let system = MockSystem::with_config(|config| {
+        config.ucode_overrides = Rc::new(HashMap::from([
+            ("6.*".to_owned(), "intel-ucode-20240115.img".to_owned()),
+            ("6.8.*".to_owned(), "intel-ucode-20231114.img".to_owned()),
+        ]));
+    });
+    system.add_kernel("6.9.0-test");
+    system.add_kernel("6.9.1-test");
+    for ucode in ["intel-ucode-20240115.img", "intel-ucode-20231114.img"] {
+        fs::write(system.config.src_path.join(ucode), b"\x1f\x8bucode").unwrap();
+    }
+
+    let (kernels, _) = system.kernels();
+    for k in kernels.iter() {
+        k.install_and_make_config(true).unwrap();
+    }
+
+    // The longest matching pattern wins
+    let (_, installed) = system.kernels();
+    assert_eq!(
+        installed
+            .iter()
+            .map(|k| k.ucode().unwrap())
+            .collect::<Vec<_>>(),
+        [
+            ("intel-ucode-20240115.img", Some("6.*")),
+            ("intel-ucode-20240115.img", Some("6.*")),
+            ("intel-ucode-20231114.img", Some("6.8.*")),
+        ]
+    );
+    let entry =
+        fs::read_to_string(system.esp_path(&format!("loader/entries/{}-default.conf", VERSION)))
+            .unwrap();
+    assert!(entry.contains("EFI/systemd-boot-friend/intel-ucode-20231114.img"));
+
+    // Shared images are kept until the last kernel loading them is removed
+    let ucode = system
+        .esp_path(REL_DEST_PATH)
+        .join("intel-ucode-20240115.img");
+    installed[0].remove().unwrap();
+    assert!(ucode.exists());
+    installed[1].remove().unwrap();
+    assert!(!ucode.exists());
+}
+
+#[test]
+fn test_scan_uki() {
+    let system = MockSystem::with_config(|c| c.scan_uki = true);
+    let uki_path = system.esp_path("EFI/Linux");
+    fs::create_dir_all(&uki_path).unwrap();
+    fs::write(uki_path.join("6.9.0-uki.efi"), "MZ").unwrap();
+    // Made by convert-to-uki from a kernel of friend
+    fs::write(uki_path.join("6.8.0-test-default.efi"), "MZ").unwrap();
+    let manifest = Manifest {
+        converted_ukis: vec!["6.8.0-test-default.efi".to_owned()],
+        ..Default::default()
+    };
+    manifest.write(&system.config.esp_mountpoint).unwrap();
+
+    // The image is installed where it is
+    let (kernels, installed) = system.kernels();
+    assert_eq!(
+        names(&kernels.iter().collect::<Vec<_>>()),
+        ["6.9.0-uki", VERSION]
+    );
+    assert_eq!(names(&installed.iter().collect::<Vec<_>>()), ["6.9.0-uki"]);
+
+    KernelManager::new(&kernels, &installed)
+        .update(&system.config, None, false)
+        .unwrap();
+
+    // Neither copied nor given an entry, systemd-boot lists it by itself
+    assert!(!system
+        .esp_path(REL_DEST_PATH)
+        .join("6.9.0-uki.efi")
+        .exists());
+    assert!(!system
+        .esp_path("loader/entries/6.9.0-uki-default.conf")
+        .exists());
+    assert_eq!(
+        system.sbconf.borrow().config.default.as_deref(),
+        Some("6.9.0-uki.efi")
+    );
+
+    let (_, installed) = system.kernels();
+    assert_eq!(installed, kernels);
+    assert!(installed[0].is_default().unwrap());
+
+    installed[0].remove().unwrap();
+    assert!(!uki_path.join("6.9.0-uki.efi").exists());
+    assert!(uki_path.join("6.8.0-test-default.efi").exists());
+    assert!(system.sbconf.borrow().config.default.is_none());
+}
+
+#[test]
+fn test_version_alias() {
+    let system = MockSystem::new();
+    let (kernels, _) = system.kernels();
+
+    kernels[0].install_and_make_config(true).unwrap();
+    kernels[0].set_default(false).unwrap();
+
+    // Nothing changes before the kernel is installed under the alias
+    let entries = system.esp_path("loader/entries");
+    let kernel = kernels[0].with_alias("stable").unwrap();
+    assert!(entries.join(format!("{}-default.conf", VERSION)).exists());
+    assert!(!entries.join(".sbf-aliases.toml").exists());
+
+    KernelManager::install(&kernel, None, true, AskDefault::Skip, InstallSteps::All).unwrap();
+    kernel.save_alias(&kernels[0]).unwrap();
+
+    assert!(!entries.join(format!("{}-default.conf", VERSION)).exists());
+    let entry = fs::read_to_string(entries.join("stable-default.conf")).unwrap();
+    assert!(entry.contains("title Linux (stable)"));
+    // The default entry follows the alias
+    assert_eq!(
+        system.sbconf.borrow().config.default.as_deref(),
+        Some("stable-default.conf")
+    );
+    assert!(kernel.is_default().unwrap());
+
+    // The alias is remembered by the kernel found again
+    let (_, installed) = system.kernels();
+    assert_eq!(installed[0].entry_id("default"), "stable-default");
+
+    installed[0].remove().unwrap();
+    assert!(!entries.join("stable-default.conf").exists());
+    assert!(!entries.join(".sbf-aliases.toml").exists());
+}
+
+#[test]
+fn test_build_metadata() {
+    let system = MockSystem::with_config(|c| c.distro = Rc::new("Arch {PKGBASE}".to_owned()));
+    let modules = system.config.modules_path.join(VERSION);
+    fs::write(modules.join("pkgbase"), "linux-lts\n").unwrap();
+    fs::write(modules.join("build-id"), "20260101\n").unwrap();
+
+    let (kernels, _) = system.kernels();
+    let summary = kernels[0].summary().unwrap();
+    assert_eq!(summary.pkgbase, "linux-lts");
+    assert_eq!(summary.build_id, "20260101");
+
+    kernels[0].install_and_make_config(true).unwrap();
+    let entry = fs::read_to_string(
+        system
+            .esp_path("loader/entries")
+            .join(format!("{}-default.conf", VERSION)),
+    )
+    .unwrap();
+    assert!(entry.contains("title Arch linux-lts (6.8.0-test) (default)"));
+}
+
+#[test]
+fn test_install_from() {
+    let system = MockSystem::new();
+    let dir = tempfile::tempdir().unwrap();
+    let (image, initrd) = (dir.path().join("bzImage"), dir.path().join("initrd"));
+    fs::write(&image, "MZ").unwrap();
+    fs::write(&initrd, b"\x1f\x8binitramfs").unwrap();
+
+    let kernel =
+        GenericKernel::parse(&system.config, "6.9.0-custom", system.sbconf.clone()).unwrap();
+    KernelManager::install_from(
+        &system.config,
+        &kernel,
+        &image,
+        Some(&initrd),
+        true,
+        AskDefault::Skip,
+        InstallSteps::All,
+    )
+    .unwrap();
+
+    assert!(system
+        .esp_path(REL_DEST_PATH)
+        .join("vmlinuz-6.9.0-custom")
+        .exists());
+    assert!(system
+        .esp_path("loader/entries/6.9.0-custom-default.conf")
+        .exists());
+    assert_eq!(
+        Manifest::read(&system.config.esp_mountpoint)
+            .unwrap()
+            .out_of_tree,
+        ["6.9.0-custom"]
+    );
+
+    // A missing image is refused before anything is recorded
+    let other =
+        GenericKernel::parse(&system.config, "6.9.1-custom", system.sbconf.clone()).unwrap();
+    assert!(KernelManager::install_from(
+        &system.config,
+        &other,
+        &dir.path().join("missing"),
+        None,
+        true,
+        AskDefault::Skip,
+        InstallSteps::All,
+    )
+    .is_err());
+    assert_eq!(
+        Manifest::read(&system.config.esp_mountpoint)
+            .unwrap()
+            .out_of_tree,
+        ["6.9.0-custom"]
+    );
+
+    kernel.remove().unwrap();
+    assert!(Manifest::read(&system.config.esp_mountpoint)
+        .unwrap()
+        .out_of_tree
+        .is_empty());
+    assert!(!system
+        .esp_path(REL_DEST_PATH)
+        .join("vmlinuz-6.9.0-custom")
+        .exists());
+}
+
+#[test]
+fn test_update_report_after() {
+    let system = MockSystem::with_config(|c| c.keep = Some(1));
+    system.add_kernel("6.7.0-test");
+    let (kernels, _) = system.kernels();
+    for k in kernels.iter() {
+        k.install_and_make_config(true).unwrap();
+    }
+    kernels[0].set_default(false).unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let image = dir.path().join("bzImage");
+    fs::write(&image, "MZ").unwrap();
+    let custom =
+        GenericKernel::parse(&system.config, "6.9.0-custom", system.sbconf.clone()).unwrap();
+    KernelManager::install_from(
+        &system.config,
+        &custom,
+        &image,
+        None,
+        true,
+        AskDefault::Skip,
+        InstallSteps::All,
+    )
+    .unwrap();
+
+    // The obsolete kernel kept by --install-new-only is still installed, and
+    // so is the one installed from an arbitrary path
+    system.add_kernel("6.9.0-test");
+    let (kernels, installed) = system.kernels();
+    let report = KernelManager::new(&kernels, &installed)
+        .update(&system.config, None, true)
+        .unwrap();
+    assert!(report.removed.is_empty());
+    assert_eq!(report.installed, ["6.9.0-test"]);
+    assert_eq!(
+        report.after,
+        ["6.9.0-test", "6.9.0-custom", VERSION, "6.7.0-test"]
+    );
+
+    // Its modules are gone with the package
+    fs::remove_dir_all(system.config.modules_path.join("6.7.0-test")).unwrap();
+    let (kernels, installed) = system.kernels();
+    let report = KernelManager::new(&kernels, &installed)
+        .update(&system.config, None, false)
+        .unwrap();
+    assert_eq!(report.removed, ["6.7.0-test"]);
+    assert_eq!(report.after, ["6.9.0-test", "6.9.0-custom", VERSION]);
+    assert_eq!(
+        names(&system.kernels().1.iter().collect::<Vec<_>>()),
+        report.after
+    );
+}
+
+#[test]
+fn test_update_report_json_install_new_only() {
+    let system = MockSystem::new();
+    let (kernels, _) = system.kernels();
+    kernels[0].install_and_make_config(true).unwrap();
+
+    // The kernel is gone with its package
+    fs::remove_dir_all(system.config.modules_path.join(VERSION)).unwrap();
+    system.add_kernel("6.9.0-test");
+    let (kernels, installed) = system.kernels();
+    let report = KernelManager::new(&kernels, &installed)
+        .update(&system.config, None, true)
+        .unwrap();
+
+    // As printed by update --report-json
+    let json: serde_json::Value =
+        serde_json::from_str(&serde_json::to_string(&report).unwrap()).unwrap();
+    assert_eq!(json["before"], serde_json::json!([VERSION]));
+    assert_eq!(json["installed"], serde_json::json!(["6.9.0-test"]));
+    assert_eq!(json["removed"], serde_json::json!([]));
+    assert_eq!(json["after"], serde_json::json!(["6.9.0-test", VERSION]));
+    assert_eq!(json["default"], "6.9.0-test");
+    assert_eq!(json["stats"]["kernels_removed"], 0);
+    assert_eq!(
+        names(&system.kernels().1.iter().collect::<Vec<_>>()),
+        report.after
+    );
+}
+
+#[test]
+fn test_unparsable_foreign_entry() {
+    let system = MockSystem::new();
+    let (kernels, _) = system.kernels();
+    kernels[0].install_and_make_config(true).unwrap();
+
+    fs::write(
+        system.esp_path("loader/entries/other-os.conf"),
+        "title Other OS\nunknown-key value\n",
+    )
+    .unwrap();
+
+    assert_eq!(
+        names(&system.kernels().1.iter().collect::<Vec<_>>()),
+        [VERSION]
+    );
+    let from_entries =
+        GenericKernel::list_installed_from_entries(&system.config, system.sbconf.clone()).unwrap();
+    assert_eq!(names(&from_entries.iter().collect::<Vec<_>>()), [VERSION]);
+}
+
+#[test]
+fn test_corrupt_manifest() {
+    let system = MockSystem::new();
+    let (kernels, _) = system.kernels();
+    kernels[0].install_and_make_config(true).unwrap();
+
+    let manifest = system.esp_path(REL_DEST_PATH).join("manifest.json");
+    fs::write(&manifest, "{\"out_of_tree\": [").unwrap();
+    assert!(Manifest::read(&system.config.esp_mountpoint)
+        .unwrap()
+        .out_of_tree
+        .is_empty());
+
+    kernels[0].remove().unwrap();
+    assert!(system.kernels().1.is_empty());
+}
+
+#[test]
+fn test_orphan_check() {
+    let mut system = MockSystem::with_config(|c| c.orphan_check = true);
+    let (kernels, _) = system.kernels();
+    kernels[0].install_and_make_config(true).unwrap();
+
+    // The modules are still installed
+    assert!(kernels[0].remove().is_err());
+    assert!(system
+        .esp_path("loader/entries")
+        .join(format!("{}-default.conf", VERSION))
+        .exists());
+
+    // remove-kernel --force
+    system.config.force_remove = true;
+    system.kernels().0[0].remove().unwrap();
+    assert!(system.kernels().1.is_empty());
+}
+
+#[test]
+fn test_esp_path_with_spaces() {
+    let system = MockSystem::with_config(|c| {
+        c.esp_mountpoint = Rc::new(c.esp_mountpoint.join("EFI SYSTEM/ÉSP"))
+    });
+    let (kernels, _) = system.kernels();
+    kernels[0].install_and_make_config(true).unwrap();
+
+    let entry = fs::read_to_string(
+        system
+            .esp_path("loader/entries")
+            .join(format!("{}-default.conf", VERSION)),
+    )
+    .unwrap();
+    assert!(entry.contains(&format!(
+        "linux {}/vmlinuz-{}",
+        REL_DEST_PATH.trim_end_matches('/'),
+        VERSION
+    )));
+
+    // The destination of the images cannot contain spaces
+    let system = MockSystem::with_config(|c| c.vmlinux = "vmlinuz {VERSION}".to_owned());
+    let (kernels, _) = system.kernels();
+    assert!(kernels[0].make_config(true).is_err());
+}
+
+#[test]
+fn test_migrate_esp() {
+    let mut system = MockSystem::new();
+    let (kernels, _) = system.kernels();
+    kernels[0].install_and_make_config(true).unwrap();
+    fs::create_dir_all(system.esp_path("EFI/systemd")).unwrap();
+    fs::write(system.esp_path("EFI/systemd/systemd-bootx64.efi"), "stub").unwrap();
+
+    fs::write(system.esp_path("splash.bmp"), "splash").unwrap();
+
+    // A kernel whose source is gone, like one installed with `--from`
+    system.add_kernel("6.9.0-test");
+    let (kernels, _) = system.kernels();
+    kernels[0].install_and_make_config(true).unwrap();
+    fs::remove_dir_all(system.config.modules_path.join("6.9.0-test")).unwrap();
+
+    let old = system.config.esp_mountpoint.to_path_buf();
+    let mut manifest = Manifest::read(&old).unwrap();
+    manifest.splash = Some("splash.bmp".into());
+    manifest.previous_default = Some(VERSION.to_owned());
+    manifest.modified_entries = vec![format!("{}-default", VERSION)];
+    manifest.out_of_tree = vec!["6.9.0-test".to_owned()];
+    manifest.write(&old).unwrap();
+
+    let target = tempfile::tempdir().unwrap();
+
+    populate_esp::<GenericKernel>(&mut system.config, target.path(), &old, true, false).unwrap();
+    assert!(!target.path().join(REL_DEST_PATH).exists());
+
+    // Not left behind without --force
+    assert!(
+        populate_esp::<GenericKernel>(&mut system.config, target.path(), &old, false, false)
+            .is_err()
+    );
+    assert!(!target.path().join(REL_DEST_PATH).exists());
+
+    populate_esp::<GenericKernel>(&mut system.config, target.path(), &old, false, true).unwrap();
+    for path in [
+        format!("{}vmlinuz-{}", REL_DEST_PATH, VERSION),
+        format!("loader/entries/{}-default.conf", VERSION),
+        "loader/loader.conf".to_owned(),
+        "splash.bmp".to_owned(),
+    ] {
+        assert!(target.path().join(&path).exists(), "{}", path);
+    }
+    assert!(!target
+        .path()
+        .join(format!("{}vmlinuz-6.9.0-test", REL_DEST_PATH))
+        .exists());
+
+    let manifest = Manifest::read(target.path()).unwrap();
+    assert_eq!(manifest.splash, Some("splash.bmp".into()));
+    assert_eq!(manifest.previous_default.as_deref(), Some(VERSION));
+    assert_eq!(manifest.modified_entries, [format!("{}-default", VERSION)]);
+    assert!(manifest.out_of_tree.is_empty());
+}
+
+#[test]
+fn test_generate_entries_only() {
+    let system = MockSystem::new();
+    let (kernels, _) = system.kernels();
+    kernels[0].install_and_make_config(true).unwrap();
+
+    let entry = system
+        .esp_path("loader/entries")
+        .join(format!("{}-default.conf", VERSION));
+    let vmlinuz = system.esp_path(&format!("{}vmlinuz-{}", REL_DEST_PATH, VERSION));
+    fs::remove_file(&entry).unwrap();
+    // Would be replaced if the kernel files were copied again
+    fs::write(&vmlinuz, "kept").unwrap();
+
+    let (kernels, installed) = system.kernels();
+    KernelManager::new(&kernels, &installed)
+        .generate_entries(false)
+        .unwrap();
+
+    assert!(entry.exists());
+    assert_eq!(fs::read_to_string(&vmlinuz).unwrap(), "kept");
+}
+
+/// Measure the copies of update on large initrds, not run by default:
+/// `cargo test --release --test integration -- --ignored bench_update_copy --nocapture`
+#[test]
+#[ignore]
+fn bench_update_copy() {
+    let system = MockSystem::new();
+    let versions = ["6.6.0-test", "6.7.0-test", VERSION, "6.9.0-test"];
+    let initrd = [b"\x1f\x8b".as_slice(), &vec![0u8; 64 << 20]].concat();
+
+    for v in versions {
+        system.add_kernel(v);
+        fs::write(
+            system.config.src_path.join(format!("initramfs-{}.img", v)),
+            &initrd,
+        )
+        .unwrap();
+    }
+
+    let (kernels, installed) = system.kernels();
+
+    // The baseline: kernel by kernel, with the default buffer of `fs::copy`
+    let start = Instant::now();
+    let mut bytes = 0;
+
+    for k in kernels.iter() {
+        for (src, dest) in k.install_copies(None).unwrap().unwrap() {
+            bytes += fs::copy(src, &dest).unwrap();
+            fs::remove_file(dest).unwrap();
+        }
+    }
+    print_throughput("baseline", bytes, start.elapsed());
+
+    let stats = KernelManager::new(&kernels, &installed)
+        .plan(&system.config, None)
+        .unwrap()
+        .apply(None, true, None)
+        .unwrap();
+
+    assert_eq!(stats.kernels_installed, versions.len() as u32);
+    print_throughput("update", stats.bytes_copied, stats.duration);
+}
+
+fn print_throughput(name: &str, bytes: u64, duration: Duration) {
+    println!(
+        "{}: copied {} bytes in {:.3}s, {:.1} MiB/s",
+        name,
+        bytes,
+        duration.as_secs_f64(),
+        bytes as f64 / (1 << 20) as f64 / duration.as_secs_f64()
+    );
+}